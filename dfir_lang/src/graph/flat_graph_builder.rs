@@ -471,11 +471,17 @@ impl FlatGraphBuilder {
                     // Check number of args
                     if op_constraints.num_args != operator.args.len() {
                         self.diagnostics.push(Diagnostic::spanned(
-                            operator.span(),
+                            operator.paren_token.span.join(),
                             Level::Error,
                             format!(
-                                "expected {} argument(s), found {}",
+                                "operator `{}` expects {} argument{}, found {}",
+                                operator.name_string(),
                                 op_constraints.num_args,
+                                if 1 == op_constraints.num_args {
+                                    ""
+                                } else {
+                                    "s"
+                                },
                                 operator.args.len()
                             ),
                         ));
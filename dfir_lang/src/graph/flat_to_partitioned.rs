@@ -1,10 +1,9 @@
 //! Subgraph partioning algorithm
 
-use std::collections::{BTreeMap, BTreeSet};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 
-use proc_macro2::Span;
 use slotmap::{SecondaryMap, SparseSecondaryMap};
-use syn::parse_quote;
+use syn::parse_quote_spanned;
 
 use super::hydroflow_graph::DfirGraph;
 use super::ops::{find_node_op_constraints, DelayType};
@@ -47,6 +46,55 @@ impl BarrierCrossers {
     }
 }
 
+/// For each union-find representative, the representatives of the other sets it shares a barrier
+/// crosser with. This makes checking for a barrier between two sets a lookup, rather than a scan
+/// of all the barrier crossers. Must be kept up to date with [`BarrierPartners::merge`] as sets
+/// are unioned.
+struct BarrierPartners {
+    partners: SparseSecondaryMap<GraphNodeId, HashSet<GraphNodeId>>,
+}
+impl BarrierPartners {
+    /// Starts with every node in its own set.
+    fn new(partitioned_graph: &DfirGraph, barrier_crossers: &BarrierCrossers) -> Self {
+        let mut partners = SparseSecondaryMap::<_, HashSet<_>>::new();
+        for ((src, dst), _delay_type) in barrier_crossers.iter_node_pairs(partitioned_graph) {
+            partners.entry(src).unwrap().or_default().insert(dst);
+            partners.entry(dst).unwrap().or_default().insert(src);
+        }
+        Self { partners }
+    }
+
+    /// Whether there is a barrier crosser between the sets represented by `a` and `b`, in either
+    /// direction.
+    fn has_barrier(&self, a: GraphNodeId, b: GraphNodeId) -> bool {
+        self.partners
+            .get(a)
+            .is_some_and(|partners| partners.contains(&b))
+    }
+
+    /// Updates for the set represented by `absorbed` having been unioned into the set represented
+    /// by `root`.
+    fn merge(&mut self, absorbed: GraphNodeId, root: GraphNodeId) {
+        let Some(absorbed_partners) = self.partners.remove(absorbed) else {
+            return;
+        };
+        let absorbed_partners = absorbed_partners
+            .into_iter()
+            .map(|partner| if partner == absorbed { root } else { partner })
+            .collect::<HashSet<_>>();
+        for &partner in absorbed_partners.iter().filter(|&&partner| partner != root) {
+            let partner_partners = &mut self.partners[partner];
+            partner_partners.remove(&absorbed);
+            partner_partners.insert(root);
+        }
+        let root_partners = self.partners.entry(root).unwrap().or_default();
+        if root_partners.remove(&absorbed) {
+            root_partners.insert(root);
+        }
+        root_partners.extend(absorbed_partners);
+    }
+}
+
 /// Find all the barrier crossers.
 fn find_barrier_crossers(partitioned_graph: &DfirGraph) -> BarrierCrossers {
     let edge_barrier_crossers = partitioned_graph
@@ -76,7 +124,7 @@ fn find_barrier_crossers(partitioned_graph: &DfirGraph) -> BarrierCrossers {
 
 fn find_subgraph_unionfind(
     partitioned_graph: &DfirGraph,
-    barrier_crossers: &BarrierCrossers,
+    barrier_partners: &mut BarrierPartners,
 ) -> (UnionFind<GraphNodeId>, BTreeSet<GraphEdgeId>) {
     // Modality (color) of nodes, push or pull.
     // TODO(mingwei)? This does NOT consider `DelayType` barriers (which generally imply `Pull`),
@@ -109,23 +157,17 @@ fn find_subgraph_unionfind(
         progress = false;
         // TODO(mingwei): Could this iterate `handoff_edges` instead? (Modulo ownership). Then no case (1) below.
         for (edge_id, (src, dst)) in partitioned_graph.edges().collect::<Vec<_>>() {
+            let (src_root, dst_root) = (subgraph_unionfind.find(src), subgraph_unionfind.find(dst));
+
             // Ignore (1) already added edges as well as (2) new self-cycles. (Unless reference edge).
-            if subgraph_unionfind.same_set(src, dst) {
+            if src_root == dst_root {
                 // Note that the _edge_ `edge_id` might not be in the subgraph even when both `src` and `dst` are. This prevents case 2.
                 // Handoffs will be inserted later for this self-loop.
                 continue;
             }
 
             // Do not connect stratum crossers (next edges).
-            if barrier_crossers
-                .iter_node_pairs(partitioned_graph)
-                .any(|((x_src, x_dst), _)| {
-                    (subgraph_unionfind.same_set(x_src, src)
-                        && subgraph_unionfind.same_set(x_dst, dst))
-                        || (subgraph_unionfind.same_set(x_src, dst)
-                            && subgraph_unionfind.same_set(x_dst, src))
-                })
-            {
+            if barrier_partners.has_barrier(src_root, dst_root) {
                 continue;
             }
 
@@ -138,6 +180,9 @@ fn find_subgraph_unionfind(
                 // At this point we have selected this edge and its src & dst to be
                 // within a single subgraph.
                 subgraph_unionfind.union(src, dst);
+                let root = subgraph_unionfind.find(src);
+                let absorbed = if root == src_root { dst_root } else { src_root };
+                barrier_partners.merge(absorbed, root);
                 assert!(handoff_edges.remove(&edge_id));
                 progress = true;
             }
@@ -196,8 +241,9 @@ fn make_subgraphs(partitioned_graph: &mut DfirGraph, barrier_crossers: &mut Barr
     // TODO(mingwei):
     // self.partitioned_graph.assert_valid();
 
+    let mut barrier_partners = BarrierPartners::new(partitioned_graph, barrier_crossers);
     let (subgraph_unionfind, handoff_edges) =
-        find_subgraph_unionfind(partitioned_graph, barrier_crossers);
+        find_subgraph_unionfind(partitioned_graph, &mut barrier_partners);
 
     // Insert handoffs between subgraphs (or on subgraph self-loop edges)
     for edge_id in handoff_edges {
@@ -418,18 +464,19 @@ fn find_subgraph_strata(
                     // We inject a new subgraph between the src/dst which runs as the last stratum
                     // of the tick and therefore delays the data until the next tick.
 
+                    // Point the injected nodes at the original edge, so diagnostics on them
+                    // highlight the `defer_tick()` which caused them.
+                    let src_span = partitioned_graph.node(hoff).span();
+                    let dst_span = partitioned_graph.node(dst).span();
+
                     // Before: A (src) -> H -> B (dst)
                     // Then add intermediate identity:
                     let (new_node_id, new_edge_id) = partitioned_graph.insert_intermediate_node(
                         edge_id,
-                        // TODO(mingwei): Proper span w/ `parse_quote_spanned!`?
-                        GraphNode::Operator(parse_quote! { identity() }),
+                        GraphNode::Operator(parse_quote_spanned! {dst_span=> identity() }),
                     );
                     // Intermediate: A (src) -> H -> ID -> B (dst)
-                    let hoff = GraphNode::Handoff {
-                        src_span: Span::call_site(), // TODO(mingwei): Proper spanning?
-                        dst_span: Span::call_site(),
-                    };
+                    let hoff = GraphNode::Handoff { src_span, dst_span };
                     let (_hoff_node_id, _hoff_edge_id) =
                         partitioned_graph.insert_intermediate_node(new_edge_id, hoff);
                     // After: A (src) -> H -> ID -> H' -> B (dst)
@@ -529,3 +576,104 @@ pub fn partition_graph(flat_graph: DfirGraph) -> Result<DfirGraph, Diagnostic> {
 
     Ok(partitioned_graph)
 }
+
+#[cfg(test)]
+mod test {
+    use super::{find_barrier_crossers, find_subgraph_unionfind, partition_graph, BarrierPartners};
+    use crate::graph::{FlatGraphBuilder, GraphNode};
+    use crate::parse::HfCode;
+
+    #[test]
+    fn test_long_linear_chain() {
+        // Thousands of linear operators, which must all end up in one subgraph without the
+        // partitioning taking quadratic time.
+        // Each operator is its own statement, since parsing one long pipeline recurses deeply.
+        let mut code = String::from("op_0 = source_iter(0..10);");
+        for i in 1..5000 {
+            code += &format!("op_{} = op_{} -> map(|x| x);", i, i - 1);
+        }
+        code += "op_4999 -> for_each(|x| println!(\"{}\", x));";
+        let hf_code = syn::parse_str::<HfCode>(&code).unwrap();
+        let (flat_graph, _uses, diagnostics) = FlatGraphBuilder::from_hfcode(hf_code).build();
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+
+        let partitioned_graph = partition_graph(flat_graph).unwrap();
+        assert_eq!(partitioned_graph.subgraph_ids().count(), 1);
+        assert_eq!(partitioned_graph.subgraphs().next().unwrap().1.len(), 5001);
+    }
+
+    #[test]
+    fn test_many_barrier_crossers() {
+        // Hundreds of tick and stratum boundaries, which used to each be scanned for every edge
+        // considered for joining a subgraph.
+        let mut code = String::from("op_0 = source_iter(0..10);");
+        for i in 1..1000 {
+            let barrier = if i % 2 == 0 {
+                "defer_tick()"
+            } else {
+                "next_stratum()"
+            };
+            code += &format!("op_{} = op_{} -> {} -> map(|x| x);", i, i - 1, barrier);
+        }
+        code += "op_999 -> for_each(|x| println!(\"{}\", x));";
+        let hf_code = syn::parse_str::<HfCode>(&code).unwrap();
+        let (flat_graph, _uses, diagnostics) = FlatGraphBuilder::from_hfcode(hf_code).build();
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+
+        let barrier_crossers = find_barrier_crossers(&flat_graph);
+        assert_eq!(barrier_crossers.edge_barrier_crossers.len(), 999);
+
+        // Checking for a barrier is one lookup into these partner sets, so as long as merging
+        // subgraphs never grows them, no step scans all of the barrier crossers.
+        let mut barrier_partners = BarrierPartners::new(&flat_graph, &barrier_crossers);
+        let partner_count = |barrier_partners: &BarrierPartners| {
+            barrier_partners
+                .partners
+                .values()
+                .map(|partners| partners.len())
+                .sum::<usize>()
+        };
+        assert_eq!(partner_count(&barrier_partners), 2 * 999);
+        let (_subgraph_unionfind, handoff_edges) =
+            find_subgraph_unionfind(&flat_graph, &mut barrier_partners);
+        assert!(partner_count(&barrier_partners) <= 2 * 999);
+
+        // Every barrier crosser must remain a handoff.
+        assert!(barrier_crossers
+            .edge_barrier_crossers
+            .keys()
+            .all(|edge_id| handoff_edges.contains(&edge_id)));
+    }
+
+    #[test]
+    fn test_defer_tick_handoff_spans() {
+        // The leading line keeps every real span off of line 1, where `Span::call_site()` is.
+        let code = "
+            source_iter(0..10) -> defer_tick() -> for_each(|x| println!(\"{}\", x));
+        ";
+        let hf_code = syn::parse_str::<HfCode>(code).unwrap();
+        let (flat_graph, _uses, diagnostics) = FlatGraphBuilder::from_hfcode(hf_code).build();
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+
+        let partitioned_graph = partition_graph(flat_graph).unwrap();
+        let identity_spans = partitioned_graph
+            .nodes()
+            .filter_map(|(_node_id, node)| match node {
+                GraphNode::Operator(op) if op.name_string() == "identity" => Some(node.span()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(identity_spans.len(), 1);
+
+        let handoff_spans = partitioned_graph
+            .nodes()
+            .filter_map(|(_node_id, node)| match *node {
+                GraphNode::Handoff { src_span, dst_span } => Some([src_span, dst_span]),
+                _ => None,
+            })
+            .flatten();
+        for span in identity_spans.into_iter().chain(handoff_spans) {
+            assert_eq!(span.start().line, 2, "{:?}", span);
+        }
+    }
+}
@@ -0,0 +1,259 @@
+//! Structural comparison of two [`DfirGraph`]s.
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+use super::{DfirGraph, GraphNode, GraphNodeId, PortIndexValue};
+
+/// A single difference between two graphs, as reported by [`graph_diff`].
+///
+/// Operators are identified by their pretty-printed source (e.g. `map(|x| x + 1)`), since node
+/// IDs are not stable between separately built graphs.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum GraphDifference {
+    /// An operator present in the second graph but not the first.
+    OperatorAdded(String),
+    /// An operator present in the first graph but not the second.
+    OperatorRemoved(String),
+    /// An operator present in both graphs, but placed in a different stratum.
+    StratumChanged {
+        /// The operator.
+        operator: String,
+        /// The stratum in the first graph, `None` if the graph is not partitioned.
+        before: Option<usize>,
+        /// The stratum in the second graph, `None` if the graph is not partitioned.
+        after: Option<usize>,
+    },
+    /// An edge present in the second graph but not the first.
+    EdgeAdded {
+        /// The source operator, followed by its output port if one is specified.
+        src: String,
+        /// The destination operator, preceded by its input port if one is specified.
+        dst: String,
+    },
+    /// An edge present in the first graph but not the second.
+    EdgeRemoved {
+        /// The source operator, followed by its output port if one is specified.
+        src: String,
+        /// The destination operator, preceded by its input port if one is specified.
+        dst: String,
+    },
+}
+
+impl Display for GraphDifference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GraphDifference::OperatorAdded(op) => write!(f, "+ {}", op),
+            GraphDifference::OperatorRemoved(op) => write!(f, "- {}", op),
+            GraphDifference::StratumChanged {
+                operator,
+                before,
+                after,
+            } => write!(f, "~ {}: stratum {:?} -> {:?}", operator, before, after),
+            GraphDifference::EdgeAdded { src, dst } => write!(f, "+ {} -> {}", src, dst),
+            GraphDifference::EdgeRemoved { src, dst } => write!(f, "- {} -> {}", src, dst),
+        }
+    }
+}
+
+/// Compares two graphs, returning the differences going from `a` to `b`.
+///
+/// Reports operators added or removed, operators whose stratum changed, and edges added or
+/// removed. Returns an empty `Vec` if the graphs have the same structure.
+///
+/// Handoffs are not reported; an edge through a handoff is treated as a direct edge between the
+/// operators on either side, so moving a subgraph boundary only shows up as a stratum change.
+/// Operators that appear several times are matched up by count, and if their strata differ they
+/// are compared in sorted order.
+pub fn graph_diff(a: &DfirGraph, b: &DfirGraph) -> Vec<GraphDifference> {
+    let (ops_a, ops_b) = (operator_strata(a), operator_strata(b));
+    let mut diffs = Vec::new();
+
+    for (op, strata_a) in ops_a.iter() {
+        let strata_b = ops_b.get(op).map(Vec::as_slice).unwrap_or_default();
+        for (before, after) in strata_a.iter().zip(strata_b) {
+            if before != after {
+                diffs.push(GraphDifference::StratumChanged {
+                    operator: op.clone(),
+                    before: *before,
+                    after: *after,
+                });
+            }
+        }
+        for _ in strata_b.len()..strata_a.len() {
+            diffs.push(GraphDifference::OperatorRemoved(op.clone()));
+        }
+    }
+    for (op, strata_b) in ops_b.iter() {
+        let count_a = ops_a.get(op).map_or(0, Vec::len);
+        for _ in count_a..strata_b.len() {
+            diffs.push(GraphDifference::OperatorAdded(op.clone()));
+        }
+    }
+
+    let (edges_a, edges_b) = (operator_edges(a), operator_edges(b));
+    for ((src, dst), &count_a) in edges_a.iter() {
+        let count_b = edges_b
+            .get(&(src.clone(), dst.clone()))
+            .copied()
+            .unwrap_or(0);
+        for _ in count_b..count_a {
+            diffs.push(GraphDifference::EdgeRemoved {
+                src: src.clone(),
+                dst: dst.clone(),
+            });
+        }
+    }
+    for ((src, dst), &count_b) in edges_b.iter() {
+        let count_a = edges_a
+            .get(&(src.clone(), dst.clone()))
+            .copied()
+            .unwrap_or(0);
+        for _ in count_a..count_b {
+            diffs.push(GraphDifference::EdgeAdded {
+                src: src.clone(),
+                dst: dst.clone(),
+            });
+        }
+    }
+
+    diffs
+}
+
+/// Each operator's label, mapped to the (sorted) strata of every operator with that label.
+fn operator_strata(graph: &DfirGraph) -> BTreeMap<String, Vec<Option<usize>>> {
+    let mut out = BTreeMap::<String, Vec<Option<usize>>>::new();
+    for (node_id, node) in graph.nodes() {
+        if let GraphNode::Operator(_) = node {
+            let stratum = graph
+                .node_subgraph(node_id)
+                .and_then(|sg_id| graph.subgraph_stratum(sg_id));
+            out.entry(node.to_pretty_string().into_owned())
+                .or_default()
+                .push(stratum);
+        }
+    }
+    out.values_mut().for_each(|strata| strata.sort());
+    out
+}
+
+/// The operator-to-operator edges of the graph, with handoffs collapsed, mapped to how many times
+/// each appears.
+fn operator_edges(graph: &DfirGraph) -> BTreeMap<(String, String), usize> {
+    let is_operator = |node_id: GraphNodeId| matches!(graph.node(node_id), GraphNode::Operator(_));
+
+    // Follows the edges out of `node_id` through any handoffs, to the operators they reach.
+    fn reachable_inputs(
+        graph: &DfirGraph,
+        node_id: GraphNodeId,
+        out: &mut Vec<(GraphNodeId, String)>,
+    ) {
+        for (edge_id, (_, dst)) in graph.edges().filter(|&(_, (src, _))| src == node_id) {
+            if let GraphNode::Operator(_) = graph.node(dst) {
+                let (_, dst_port) = graph.edge_ports(edge_id);
+                out.push((dst, port_label(dst_port)));
+            } else {
+                reachable_inputs(graph, dst, out);
+            }
+        }
+    }
+
+    let mut out = BTreeMap::new();
+    for (edge_id, (src, dst)) in graph.edges() {
+        if !is_operator(src) {
+            continue;
+        }
+        let (src_port, dst_port) = graph.edge_ports(edge_id);
+        let src_label = format!(
+            "{}{}",
+            graph.node(src).to_pretty_string(),
+            port_label(src_port)
+        );
+
+        let mut dsts = Vec::new();
+        if is_operator(dst) {
+            dsts.push((dst, port_label(dst_port)));
+        } else {
+            reachable_inputs(graph, dst, &mut dsts);
+        }
+        for (dst, dst_port) in dsts {
+            let dst_label = format!("{}{}", dst_port, graph.node(dst).to_pretty_string());
+            *out.entry((src_label.clone(), dst_label)).or_default() += 1;
+        }
+    }
+    out
+}
+
+fn port_label(port: &PortIndexValue) -> String {
+    if port.is_specified() {
+        format!("[{}]", port)
+    } else {
+        String::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::graph::{partition_graph, FlatGraphBuilder};
+    use crate::parse::HfCode;
+
+    fn build(code: &str) -> DfirGraph {
+        let hf_code = syn::parse_str::<HfCode>(code).unwrap();
+        let (flat_graph, _uses, diagnostics) = FlatGraphBuilder::from_hfcode(hf_code).build();
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+        partition_graph(flat_graph).unwrap()
+    }
+
+    #[test]
+    pub fn test_graph_diff_same() {
+        let code = r#"
+            my_join = join();
+            source_iter([(1, 2)]) -> [0]my_join;
+            source_iter([(1, 3)]) -> [1]my_join;
+            my_join -> for_each(|x| println!("{:?}", x));
+        "#;
+        assert_eq!(graph_diff(&build(code), &build(code)), vec![]);
+    }
+
+    #[test]
+    pub fn test_graph_diff_modified() {
+        let a = build(
+            r#"
+            source_iter(0..10) -> map(|x| x + 1) -> for_each(|x| println!("{}", x));
+            "#,
+        );
+        let b = build(
+            r#"
+            source_iter(0..10) -> map(|x| x + 1) -> fold(|| 0, |acc: &mut i32, x| *acc += x)
+                -> for_each(|x| println!("{}", x));
+            "#,
+        );
+
+        let mut diffs = graph_diff(&a, &b);
+        diffs.sort();
+        assert_eq!(
+            diffs,
+            vec![
+                GraphDifference::OperatorAdded("fold(|| 0, |acc: &mut i32, x| *acc += x)".into()),
+                GraphDifference::StratumChanged {
+                    operator: "for_each(|x| println!(\"{}\", x))".into(),
+                    before: Some(0),
+                    after: Some(1),
+                },
+                GraphDifference::EdgeAdded {
+                    src: "fold(|| 0, |acc: &mut i32, x| *acc += x)".into(),
+                    dst: "for_each(|x| println!(\"{}\", x))".into(),
+                },
+                GraphDifference::EdgeAdded {
+                    src: "map(|x| x + 1)".into(),
+                    dst: "fold(|| 0, |acc: &mut i32, x| *acc += x)".into(),
+                },
+                GraphDifference::EdgeRemoved {
+                    src: "map(|x| x + 1)".into(),
+                    dst: "for_each(|x| println!(\"{}\", x))".into(),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,94 @@
+//! Summary statistics of how a [`DfirGraph`] was partitioned, see [`GraphStats`].
+
+use super::{DfirGraph, GraphNode};
+
+/// Counts describing the shape of a [`DfirGraph`], as returned by [`DfirGraph::stats`].
+///
+/// Useful for asserting in tests that a rewrite changed the compiled graph as expected, without
+/// parsing a rendered graph.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct GraphStats {
+    /// The number of operator nodes.
+    pub operators: usize,
+    /// The number of handoff nodes.
+    pub handoffs: usize,
+    /// The number of subgraphs, zero if the graph is not partitioned.
+    pub subgraphs: usize,
+    /// The number of strata, one more than the highest stratum of any subgraph, or zero if the
+    /// graph is not partitioned.
+    pub strata: usize,
+    /// The largest number of incoming edges of any node.
+    pub max_fan_in: usize,
+    /// The largest number of outgoing edges of any node.
+    pub max_fan_out: usize,
+}
+
+impl DfirGraph {
+    /// Computes [`GraphStats`] for this graph, without allocating.
+    pub fn stats(&self) -> GraphStats {
+        let mut stats = GraphStats {
+            subgraphs: self.subgraph_ids().len(),
+            strata: self.max_stratum().map_or(0, |stratum| stratum + 1),
+            ..Default::default()
+        };
+        for (node_id, node) in self.nodes() {
+            match node {
+                GraphNode::Operator(_) => stats.operators += 1,
+                GraphNode::Handoff { .. } => stats.handoffs += 1,
+                GraphNode::ModuleBoundary { .. } => {}
+            }
+            stats.max_fan_in = stats.max_fan_in.max(self.node_degree_in(node_id));
+            stats.max_fan_out = stats.max_fan_out.max(self.node_degree_out(node_id));
+        }
+        stats
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::graph::{partition_graph, FlatGraphBuilder};
+    use crate::parse::HfCode;
+
+    #[test]
+    pub fn test_stats() {
+        let hf_code = syn::parse_str::<HfCode>(
+            r#"
+            numbers = source_iter(0..10) -> tee();
+            numbers -> map(|x| x + 1) -> evens;
+            numbers -> map(|x| x * 2) -> evens;
+            numbers -> next_stratum() -> evens;
+            evens = union() -> filter(|x| x % 2 == 0) -> for_each(|x| println!("{}", x));
+            "#,
+        )
+        .unwrap();
+        let (flat_graph, _uses, diagnostics) = FlatGraphBuilder::from_hfcode(hf_code).build();
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+
+        assert_eq!(
+            flat_graph.stats(),
+            GraphStats {
+                operators: 8,
+                handoffs: 0,
+                subgraphs: 0,
+                strata: 0,
+                max_fan_in: 3,
+                max_fan_out: 3,
+            }
+        );
+
+        // The `union()` starts the second stratum, so each of its inputs crosses a handoff.
+        let partitioned_graph = partition_graph(flat_graph).unwrap();
+        assert_eq!(
+            partitioned_graph.stats(),
+            GraphStats {
+                operators: 8,
+                handoffs: 3,
+                subgraphs: 2,
+                strata: 2,
+                max_fan_in: 3,
+                max_fan_out: 3,
+            }
+        );
+    }
+}
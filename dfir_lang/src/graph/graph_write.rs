@@ -26,7 +26,8 @@ pub(crate) trait GraphWrite {
         node_color: Option<Color>,
     ) -> Result<(), Self::Err>;
 
-    /// Write an edge, with styling.
+    /// Write an edge, with styling. `is_handoff` is set if either end of the edge is a handoff
+    /// node.
     fn write_edge(
         &mut self,
         src_id: GraphNodeId,
@@ -34,6 +35,7 @@ pub(crate) trait GraphWrite {
         delay_type: Option<DelayType>,
         label: Option<&str>,
         is_reference: bool,
+        is_handoff: bool,
     ) -> Result<(), Self::Err>;
 
     /// Begin writing a subgraph.
@@ -83,14 +85,23 @@ pub struct Mermaid<W> {
     // How many links have been written, for styling
     // https://mermaid.js.org/syntax/flowchart.html#styling-links
     link_count: usize,
+    // Draw handoffs as hexagons and stratum-crossing edges as dotted lines.
+    distinct_handoffs: bool,
 }
 impl<W> Mermaid<W> {
     pub fn new(write: W) -> Self {
         Self {
             write,
             link_count: 0,
+            distinct_handoffs: false,
         }
     }
+
+    /// Draw handoffs as hexagons and stratum-crossing edges as dotted `-.->` lines.
+    pub fn with_distinct_handoffs(mut self, distinct_handoffs: bool) -> Self {
+        self.distinct_handoffs = distinct_handoffs;
+        self
+    }
 }
 impl<W> GraphWrite for Mermaid<W>
 where
@@ -144,12 +155,14 @@ where
             lbracket = match node_color {
                 Some(Color::Push) => r"[/",
                 Some(Color::Pull) => r"[\",
+                Some(Color::Hoff) if self.distinct_handoffs => "{{",
                 _ => "[",
             },
             code = escape_mermaid(node),
             rbracket = match node_color {
                 Some(Color::Push) => r"\]",
                 Some(Color::Pull) => r"/]",
+                Some(Color::Hoff) if self.distinct_handoffs => "}}",
                 _ => "]",
             },
         );
@@ -164,17 +177,21 @@ where
         delay_type: Option<DelayType>,
         label: Option<&str>,
         _is_reference: bool,
+        _is_handoff: bool,
     ) -> Result<(), Self::Err> {
         let src_str = format!("{:?}", src_id.data());
         let dest_str = format!("{:?}", dst_id.data());
-        #[expect(clippy::write_literal, reason = "code readability")]
         write!(
             self.write,
             "{src}{arrow_body}{arrow_head}{label}{dst}",
             src = src_str.trim(),
-            arrow_body = "--",
+            arrow_body = match delay_type {
+                Some(DelayType::Stratum) if self.distinct_handoffs => "-.-",
+                _ => "--",
+            },
             arrow_head = match delay_type {
                 None | Some(DelayType::MonotoneAccum) => ">",
+                Some(DelayType::Stratum) if self.distinct_handoffs => ">",
                 Some(DelayType::Stratum) => "x",
                 Some(DelayType::Tick | DelayType::TickLazy) => "o",
             },
@@ -335,6 +352,7 @@ where
         delay_type: Option<DelayType>,
         label: Option<&str>,
         _is_reference: bool,
+        is_handoff: bool,
     ) -> Result<(), Self::Err> {
         let mut properties = Vec::<Cow<'static, str>>::new();
         if let Some(label) = label {
@@ -344,6 +362,10 @@ where
         if delay_type.is_some() {
             properties.push("color=red".into());
         }
+        // Handoff edges cross between subgraphs (or back into one), so draw them dashed.
+        if is_handoff {
+            properties.push("style=dashed".into());
+        }
 
         write!(
             self.write,
@@ -433,3 +455,72 @@ where
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use slotmap::SlotMap;
+
+    use super::{Dot, GraphWrite};
+    use crate::graph::{partition_graph, DfirGraph, FlatGraphBuilder, GraphNodeId, WriteConfig};
+    use crate::parse::HfCode;
+
+    fn build(code: &str) -> DfirGraph {
+        let hf_code = syn::parse_str::<HfCode>(code).unwrap();
+        let (flat_graph, _uses, diagnostics) = FlatGraphBuilder::from_hfcode(hf_code).build();
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+        partition_graph(flat_graph).unwrap()
+    }
+
+    #[test]
+    pub fn test_dot_handoff_edge_before_nodes() {
+        let mut node_ids = SlotMap::<GraphNodeId, ()>::with_key();
+        let (src_id, dst_id) = (node_ids.insert(()), node_ids.insert(()));
+
+        let mut output = String::new();
+        let mut dot = Dot::new(&mut output);
+        dot.write_edge(src_id, dst_id, None, None, false, true)
+            .unwrap();
+        dot.write_edge(dst_id, src_id, None, None, false, false)
+            .unwrap();
+
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(2, lines.len(), "{}", output);
+        assert!(lines[0].contains("style=dashed"), "{}", output);
+        assert!(!lines[1].contains("style=dashed"), "{}", output);
+    }
+
+    #[test]
+    pub fn test_dot_handoff_edges_without_node_colors() {
+        // The `fold` is a stratum barrier, so its input goes through a handoff.
+        let graph = build(
+            r#"
+            source_iter(0..10)
+                -> fold(|| 0, |acc: &mut i32, x| *acc += x)
+                -> for_each(|x| println!("{}", x));
+            "#,
+        );
+        let dot = graph.to_dot(&WriteConfig {
+            no_pull_push: true,
+            ..Default::default()
+        });
+        assert!(dot.contains("style=dashed"), "{}", dot);
+    }
+
+    #[test]
+    pub fn test_mermaid_two_strata() {
+        // The `fold` is a stratum barrier, so the graph is split into two strata joined by a
+        // handoff and a stratum-crossing edge.
+        let graph = build(
+            r#"
+            source_iter(0..10)
+                -> fold(|| 0, |acc: &mut i32, x| *acc += x)
+                -> for_each(|x| println!("{}", x));
+            "#,
+        );
+        assert_eq!(graph.max_stratum(), Some(1));
+        insta::assert_snapshot!(graph.to_mermaid(&WriteConfig {
+            distinct_handoffs: true,
+            ..Default::default()
+        }));
+    }
+}
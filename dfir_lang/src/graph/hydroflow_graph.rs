@@ -36,7 +36,7 @@ use crate::process_singletons;
 /// separate `impl` blocks. You might notice a few particularly specific arbitray-seeming methods
 /// in here--those are just what was needed for the compilation algorithms. If you need another
 /// method then add it.
-#[derive(Default, Debug, Serialize, Deserialize)]
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
 pub struct DfirGraph {
     /// Each node type (operator or handoff).
     nodes: SlotMap<GraphNodeId, GraphNode>,
@@ -298,20 +298,10 @@ impl DfirGraph {
                     .map(Spanned::span)
                     .unwrap_or_else(|| operator.path.span());
 
-                if !op_constraints
-                    .persistence_args
-                    .contains(&generics.persistence_args.len())
+                if let Err(diagnostic) =
+                    op_constraints.check_persistence_args(&generics, operator.path.span())
                 {
-                    diagnostics.push(Diagnostic::spanned(
-                        generics_span,
-                        Level::Error,
-                        format!(
-                            "`{}` should have {} persistence lifetime arguments, actually has {}.",
-                            op_constraints.name,
-                            op_constraints.persistence_args.human_string(),
-                            generics.persistence_args.len()
-                        ),
-                    ));
+                    diagnostics.push(diagnostic);
                 }
                 if !op_constraints.type_args.contains(&generics.type_args.len()) {
                     diagnostics.push(Diagnostic::spanned(
@@ -989,8 +979,14 @@ impl DfirGraph {
                                 arguments_handles,
                             };
 
-                            let write_result =
-                                (op_constraints.write_fn)(&context_args, diagnostics);
+                            // Operators match on the persistence arguments assuming a valid
+                            // count, so only write the operator if the count is checked.
+                            let write_result = op_constraints
+                                .check_persistence_args(&op_inst.generics, op_span)
+                                .map_err(|diagnostic| diagnostics.push(diagnostic))
+                                .and_then(|()| {
+                                    (op_constraints.write_fn)(&context_args, diagnostics)
+                                });
                             let OperatorWriteOutput {
                                 write_prologue,
                                 write_iterator,
@@ -1253,7 +1249,8 @@ impl DfirGraph {
         output: impl std::fmt::Write,
         write_config: &WriteConfig,
     ) -> std::fmt::Result {
-        let mut graph_write = Mermaid::new(output);
+        let mut graph_write =
+            Mermaid::new(output).with_distinct_handoffs(write_config.distinct_handoffs);
         self.write_graph(&mut graph_write, write_config)
     }
 
@@ -1402,7 +1399,17 @@ impl DfirGraph {
             let delay_type = self
                 .node_op_inst(dst_id)
                 .and_then(|op_inst| (op_inst.op_constraints.input_delaytype_fn)(dst_port));
-            graph_write.write_edge(src_id, dst_id, delay_type, label.as_deref(), false)?;
+            let is_handoff = [src_id, dst_id]
+                .into_iter()
+                .any(|node_id| matches!(self.node(node_id), GraphNode::Handoff { .. }));
+            graph_write.write_edge(
+                src_id,
+                dst_id,
+                delay_type,
+                label.as_deref(),
+                false,
+                is_handoff,
+            )?;
         }
 
         // Write reference edges.
@@ -1416,7 +1423,7 @@ impl DfirGraph {
                 {
                     let delay_type = Some(DelayType::Stratum);
                     let label = None;
-                    graph_write.write_edge(src_ref_id, dst_id, delay_type, label, true)?;
+                    graph_write.write_edge(src_ref_id, dst_id, delay_type, label, true, false)?;
                 }
             }
         }
@@ -1602,6 +1609,9 @@ pub struct WriteConfig {
     /// Op text will exclude any line that starts with "use".
     #[cfg_attr(feature = "clap-derive", arg(long))]
     pub op_text_no_imports: bool,
+    /// Mermaid will draw handoffs as hexagons and stratum-crossing edges as dotted lines if set.
+    #[cfg_attr(feature = "clap-derive", arg(long))]
+    pub distinct_handoffs: bool,
 }
 
 /// Enum for choosing between mermaid and dot graph writing.
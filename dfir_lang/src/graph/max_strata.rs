@@ -0,0 +1,200 @@
+//! Guardrail against graphs partitioned into more strata than expected.
+
+use proc_macro2::Span;
+use slotmap::Key;
+
+use super::{DfirGraph, GraphNode, GraphSubgraphId};
+use crate::diagnostic::{Diagnostic, Level};
+
+impl DfirGraph {
+    /// Checks that the highest stratum of a partitioned graph, from [`DfirGraph::max_stratum`],
+    /// is at most `max_strata`. Deeply stratified graphs run many sequential phases per tick,
+    /// which usually indicates a performance problem.
+    ///
+    /// Otherwise returns an error naming the subgraphs along the deepest chain, from stratum 0
+    /// up, along with the operators in each.
+    pub fn check_max_strata(&self, max_strata: usize) -> Result<(), Diagnostic> {
+        match self.max_strata_diagnostic(max_strata, Level::Error) {
+            Some(diagnostic) => Err(diagnostic),
+            None => Ok(()),
+        }
+    }
+
+    /// Like [`DfirGraph::check_max_strata`], but returns a warning instead of an error, which
+    /// also suggests simplifying the graph's tick structure. This lets a build report deep
+    /// schedules without failing.
+    pub fn warn_max_strata(&self, max_strata: usize) -> Option<Diagnostic> {
+        let mut diagnostic = self.max_strata_diagnostic(max_strata, Level::Warning)?;
+        diagnostic.message += ". Consider simplifying the tick structure: each stratum barrier \
+            (such as `fold()` or `difference()`) downstream of another adds a sequential phase \
+            to every tick, and a `defer_tick()` adds one more after the deepest of them.";
+        Some(diagnostic)
+    }
+
+    fn max_strata_diagnostic(&self, max_strata: usize, level: Level) -> Option<Diagnostic> {
+        let max_stratum = self.max_stratum()?;
+        if max_stratum <= max_strata {
+            return None;
+        }
+
+        let chain = self.deepest_stratum_chain();
+        let described = chain
+            .iter()
+            .map(|&sg_id| {
+                let operators = self
+                    .subgraph(sg_id)
+                    .iter()
+                    .filter_map(|&node_id| match self.node(node_id) {
+                        GraphNode::Operator(op) => Some(op.name_string()),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                format!(
+                    "sg_{:?} (stratum {}: {})",
+                    sg_id.data(),
+                    self.subgraph_stratum(sg_id).unwrap_or_default(),
+                    operators.join(", ")
+                )
+            })
+            .collect::<Vec<_>>();
+        let span = chain
+            .last()
+            .and_then(|&sg_id| self.subgraph(sg_id).first())
+            .map_or_else(Span::call_site, |&node_id| self.node(node_id).span());
+
+        Some(Diagnostic::spanned(
+            span,
+            level,
+            format!(
+                "Graph has stratum {}, exceeding the maximum of {}. Deepest chain: {}",
+                max_stratum,
+                max_strata,
+                described.join(" -> ")
+            ),
+        ))
+    }
+
+    /// Walks back from a subgraph in the highest stratum, at each step following the
+    /// upstream subgraph in the highest lower stratum. Returned in stratum order.
+    fn deepest_stratum_chain(&self) -> Vec<GraphSubgraphId> {
+        let stratum = |sg_id| self.subgraph_stratum(sg_id).unwrap_or_default();
+
+        let mut chain = Vec::new();
+        let mut current = self.subgraph_ids().max_by_key(|&sg_id| stratum(sg_id));
+        while let Some(sg_id) = current {
+            chain.push(sg_id);
+            // Subgraphs are connected through handoffs, so look two nodes upstream.
+            current = self
+                .subgraph(sg_id)
+                .iter()
+                .flat_map(|&node_id| self.node_predecessor_nodes(node_id))
+                .flat_map(|hoff_id| self.node_predecessor_nodes(hoff_id))
+                .filter_map(|pred_id| self.node_subgraph(pred_id))
+                .filter(|&pred_sg_id| stratum(pred_sg_id) < stratum(sg_id))
+                .max_by_key(|&pred_sg_id| stratum(pred_sg_id));
+        }
+        chain.reverse();
+        chain
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::diagnostic::Level;
+    use crate::graph::{
+        build_hfcode_with_options, partition_graph, BuildHfCodeOptions, DfirGraph, FlatGraphBuilder,
+    };
+    use crate::parse::HfCode;
+
+    fn build(code: &str) -> DfirGraph {
+        let hf_code = syn::parse_str::<HfCode>(code).unwrap();
+        let (flat_graph, _uses, diagnostics) = FlatGraphBuilder::from_hfcode(hf_code).build();
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+        partition_graph(flat_graph).unwrap()
+    }
+
+    #[test]
+    pub fn test_check_max_strata() {
+        // Each `fold` is a stratum barrier.
+        let graph = build(
+            r#"
+            source_iter(0..10)
+                -> fold(|| 0, |acc: &mut i32, x| *acc += x)
+                -> fold(|| 0, |acc: &mut i32, x| *acc += x)
+                -> fold(|| 0, |acc: &mut i32, x| *acc += x)
+                -> for_each(|x| println!("{}", x));
+            "#,
+        );
+        assert_eq!(graph.max_stratum(), Some(3));
+        assert!(graph.check_max_strata(3).is_ok());
+
+        let diagnostic = graph.check_max_strata(2).unwrap_err();
+        assert!(
+            diagnostic
+                .message
+                .starts_with("Graph has stratum 3, exceeding the maximum of 2."),
+            "{}",
+            diagnostic.message
+        );
+        // The chain runs from the source up through every fold.
+        assert_eq!(diagnostic.message.matches("sg_").count(), 4);
+        assert!(diagnostic.message.contains("stratum 0: source_iter"));
+    }
+
+    #[test]
+    pub fn test_warn_max_strata() {
+        // A chain of barriers whose result is fed back into the next tick: the folds take a
+        // stratum each, and the `defer_tick()` delayer runs in one more after them.
+        let code = r#"
+            input = union() -> fold(|| 0, |acc: &mut i32, x| *acc += x)
+                -> fold(|| 0, |acc: &mut i32, x| *acc += x)
+                -> fold(|| 0, |acc: &mut i32, x| *acc += x)
+                -> fold(|| 0, |acc: &mut i32, x| *acc += x)
+                -> tee();
+            source_iter(0..10) -> input;
+            input -> defer_tick() -> input;
+            input -> for_each(|x| println!("{}", x));
+        "#;
+        let build_with_max_strata = |max_strata| {
+            let hf_code = syn::parse_str::<HfCode>(code).unwrap();
+            let (graph_code, diagnostics) = build_hfcode_with_options(
+                hf_code,
+                &quote::quote! { dfir_rs },
+                &BuildHfCodeOptions { max_strata },
+            );
+            // The warning does not fail the build.
+            let (graph, _code) = graph_code.unwrap();
+            let warnings = diagnostics
+                .into_iter()
+                .filter(|diagnostic| diagnostic.level == Level::Warning)
+                .collect::<Vec<_>>();
+            (graph, warnings)
+        };
+
+        let (graph, warnings) = build_with_max_strata(None);
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        let max_stratum = graph.max_stratum().unwrap();
+        assert!(max_stratum >= 4, "{}", max_stratum);
+
+        let (_graph, warnings) = build_with_max_strata(Some(max_stratum));
+        assert!(warnings.is_empty(), "{:?}", warnings);
+
+        let (_graph, warnings) = build_with_max_strata(Some(2));
+        assert_eq!(warnings.len(), 1, "{:?}", warnings);
+        assert!(
+            warnings[0].message.starts_with(&format!(
+                "Graph has stratum {}, exceeding the maximum of 2.",
+                max_stratum
+            )),
+            "{}",
+            warnings[0].message
+        );
+        assert!(
+            warnings[0]
+                .message
+                .contains("Consider simplifying the tick structure"),
+            "{}",
+            warnings[0].message
+        );
+    }
+}
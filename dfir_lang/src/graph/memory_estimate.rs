@@ -0,0 +1,131 @@
+//! Heuristic estimates of the memory held by stateful operators in a [`DfirGraph`].
+
+use std::collections::BTreeMap;
+
+use super::ops::Persistence;
+use super::{DfirGraph, GraphNode, GraphSubgraphId};
+
+/// Operators that keep one state table per input port, which persists across ticks on the
+/// ports given a `'static` (or `'mutable`) persistence argument.
+const PER_PORT_STATE_OPS: &[&str] = &[
+    "anti_join",
+    "anti_join_multiset",
+    "cross_join",
+    "cross_join_multiset",
+    "difference",
+    "difference_multiset",
+    "join",
+    "join_fused",
+    "join_fused_lhs",
+    "join_fused_rhs",
+    "join_multiset",
+];
+
+/// Operators that keep a single state table, which persists across ticks given a `'static`
+/// (or `'mutable`) persistence argument.
+const SINGLE_STATE_OPS: &[&str] = &[
+    "fold",
+    "fold_keyed",
+    "persist",
+    "persist_mut",
+    "persist_mut_keyed",
+    "reduce",
+    "reduce_keyed",
+    "scan",
+    "state",
+    "state_by",
+    "unique",
+];
+
+impl DfirGraph {
+    /// Estimates the memory held across ticks by the stateful operators in each subgraph, for
+    /// capacity planning.
+    ///
+    /// This is a heuristic: operators are identified by name, and each state table persisted
+    /// across ticks (for example, a `fold::<'static>` or one side of a `join::<'static>`) is
+    /// assumed to hold `state_size` bytes, a user-supplied estimate of the number of keys or
+    /// elements times their size. State cleared every tick is not counted. Every subgraph is
+    /// included, with an estimate of zero if it has no such state.
+    pub fn estimate_memory(&self, state_size: usize) -> BTreeMap<GraphSubgraphId, usize> {
+        let mut estimates = self
+            .subgraph_ids()
+            .map(|sg_id| (sg_id, 0))
+            .collect::<BTreeMap<_, _>>();
+
+        for (node_id, node) in self.nodes() {
+            let GraphNode::Operator(operator) = node else {
+                continue;
+            };
+            let (Some(sg_id), Some(op_inst)) =
+                (self.node_subgraph(node_id), self.node_op_inst(node_id))
+            else {
+                continue;
+            };
+
+            let persistence_args = &op_inst.generics.persistence_args;
+            let is_persisted = |persistence: &Persistence| *persistence != Persistence::Tick;
+            let name = operator.name_string();
+            let persisted_tables = if PER_PORT_STATE_OPS.contains(&&*name) {
+                // A single persistence argument applies to both ports.
+                match persistence_args[..] {
+                    [persistence] if is_persisted(&persistence) => 2,
+                    _ => persistence_args.iter().filter(|p| is_persisted(p)).count(),
+                }
+            } else if SINGLE_STATE_OPS.contains(&&*name) {
+                usize::from(persistence_args.iter().any(is_persisted))
+            } else {
+                0
+            };
+
+            *estimates.entry(sg_id).or_default() += persisted_tables * state_size;
+        }
+
+        estimates
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::graph::{partition_graph, FlatGraphBuilder};
+    use crate::parse::HfCode;
+
+    fn build(code: &str) -> DfirGraph {
+        let hf_code = syn::parse_str::<HfCode>(code).unwrap();
+        let (flat_graph, _uses, diagnostics) = FlatGraphBuilder::from_hfcode(hf_code).build();
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+        partition_graph(flat_graph).unwrap()
+    }
+
+    #[test]
+    pub fn test_estimate_memory_static_fold() {
+        let graph = build(
+            r#"
+            source_iter(0..10) -> fold::<'static>(|| 0, |acc: &mut i32, x| *acc += x)
+                -> for_each(|x| println!("{}", x));
+            source_iter(0..10) -> fold::<'tick>(|| 0, |acc: &mut i32, x| *acc += x)
+                -> for_each(|x| println!("{}", x));
+            "#,
+        );
+
+        let subgraph_of = |op_name: &str, persistence: Persistence| {
+            graph
+                .nodes()
+                .find(|&(node_id, node)| {
+                    matches!(node, GraphNode::Operator(op) if op.name_string() == op_name)
+                        && graph
+                            .node_op_inst(node_id)
+                            .unwrap()
+                            .generics
+                            .persistence_args
+                            == [persistence]
+                })
+                .and_then(|(node_id, _)| graph.node_subgraph(node_id))
+                .unwrap()
+        };
+
+        let estimates = graph.estimate_memory(64);
+        assert_eq!(estimates[&subgraph_of("fold", Persistence::Static)], 64);
+        assert_eq!(estimates[&subgraph_of("fold", Persistence::Tick)], 0);
+    }
+}
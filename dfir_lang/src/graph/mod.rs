@@ -20,9 +20,13 @@ mod di_mul_graph;
 mod eliminate_extra_unions_tees;
 mod flat_graph_builder;
 mod flat_to_partitioned;
+mod graph_diff;
+mod graph_stats;
 mod graph_write;
 mod hydroflow_graph;
 mod hydroflow_graph_debugging;
+mod max_strata;
+mod memory_estimate;
 
 use std::fmt::Display;
 
@@ -30,6 +34,8 @@ pub use di_mul_graph::DiMulGraph;
 pub use eliminate_extra_unions_tees::eliminate_extra_unions_tees;
 pub use flat_graph_builder::FlatGraphBuilder;
 pub use flat_to_partitioned::partition_graph;
+pub use graph_diff::{graph_diff, GraphDifference};
+pub use graph_stats::GraphStats;
 pub use hydroflow_graph::{DfirGraph, WriteConfig, WriteGraphType};
 
 pub mod graph_algorithms;
@@ -375,6 +381,23 @@ impl Display for PortIndexValue {
 pub fn build_hfcode(
     hf_code: HfCode,
     root: &TokenStream,
+) -> (Option<(DfirGraph, TokenStream)>, Vec<Diagnostic>) {
+    build_hfcode_with_options(hf_code, root, &BuildHfCodeOptions::default())
+}
+
+/// Options for [`build_hfcode_with_options`].
+#[derive(Clone, Debug, Default)]
+pub struct BuildHfCodeOptions {
+    /// If set, a partitioned graph with a higher stratum gets a warning from
+    /// [`DfirGraph::warn_max_strata`].
+    pub max_strata: Option<usize>,
+}
+
+/// Like [`build_hfcode`], but with additional checks configured by `options`.
+pub fn build_hfcode_with_options(
+    hf_code: HfCode,
+    root: &TokenStream,
+    options: &BuildHfCodeOptions,
 ) -> (Option<(DfirGraph, TokenStream)>, Vec<Diagnostic>) {
     let flat_graph_builder = FlatGraphBuilder::from_hfcode(hf_code);
     let (mut flat_graph, uses, mut diagnostics) = flat_graph_builder.build();
@@ -387,6 +410,9 @@ pub fn build_hfcode(
         eliminate_extra_unions_tees(&mut flat_graph);
         match partition_graph(flat_graph) {
             Ok(partitioned_graph) => {
+                if let Some(max_strata) = options.max_strata {
+                    diagnostics.extend(partitioned_graph.warn_max_strata(max_strata));
+                }
                 let code = partitioned_graph.as_code(
                     root,
                     true,
@@ -25,7 +25,7 @@ use super::{
 /// ```
 pub const DEST_SINK_SERDE: OperatorConstraints = OperatorConstraints {
     name: "dest_sink_serde",
-    categories: &[OperatorCategory::Sink],
+    categories: &[OperatorCategory::Sink, OperatorCategory::Network],
     hard_range_inn: RANGE_1,
     soft_range_inn: RANGE_1,
     hard_range_out: RANGE_0,
@@ -0,0 +1,131 @@
+use quote::quote_spanned;
+
+use super::{
+    OpInstGenerics, OperatorCategory, OperatorConstraints, OperatorInstance,
+    OperatorWriteOutput, Persistence, WriteContextArgs, RANGE_0, RANGE_1,
+};
+use crate::diagnostic::{Diagnostic, Level};
+
+/// > Arguments: A single closure `FnMut(usize, &Item)`.
+///
+/// Like [`inspect`](#inspect), but the closure is also passed the index of each item, as
+/// [`enumerate`](#enumerate) would assign it. Items are passed through unmodified.
+///
+/// `inspect_enumerate` can also be provided with one generic lifetime persistence argument,
+/// either `'tick` or `'static`, to specify if indexing resets. If `'tick` (the default) is
+/// specified, indexing will restart at zero at the start of each tick. Otherwise `'static`
+/// will never reset and count monotonically upwards.
+///
+/// > Note: The closure has access to the [`context` object](surface_flows.mdx#the-context-object).
+///
+/// ```dfir
+/// source_iter(["hello", "world"])
+///     -> inspect_enumerate(|i, x| println!("{}: {}", i, x))
+///     -> assert_eq(["hello", "world"]);
+/// ```
+pub const INSPECT_ENUMERATE: OperatorConstraints = OperatorConstraints {
+    name: "inspect_enumerate",
+    categories: &[OperatorCategory::Map],
+    hard_range_inn: RANGE_1,
+    soft_range_inn: RANGE_1,
+    hard_range_out: RANGE_1,
+    soft_range_out: RANGE_1,
+    num_args: 1,
+    persistence_args: &(0..=1),
+    type_args: RANGE_0,
+    is_external_input: false,
+    has_singleton_output: false,
+    flo_type: None,
+    ports_inn: None,
+    ports_out: None,
+    input_delaytype_fn: |_| None,
+    write_fn: |wc @ &WriteContextArgs {
+                   root,
+                   op_span,
+                   context,
+                   hydroflow,
+                   ident,
+                   inputs,
+                   outputs,
+                   is_pull,
+                   arguments,
+                   op_inst:
+                       OperatorInstance {
+                           generics:
+                               OpInstGenerics {
+                                   persistence_args, ..
+                               },
+                           ..
+                       },
+                   ..
+               },
+               diagnostics| {
+        let persistence = match persistence_args[..] {
+            [] => Persistence::Tick,
+            [Persistence::Mutable] => {
+                diagnostics.push(Diagnostic::spanned(
+                    op_span,
+                    Level::Error,
+                    "An implementation of 'mutable does not exist",
+                ));
+                Persistence::Tick
+            },
+            [a] => a,
+            _ => unreachable!(),
+        };
+
+        let counter_ident = wc.make_ident("counterdata");
+
+        let mut write_prologue = quote_spanned! {op_span=>
+            let #counter_ident = #hydroflow.add_state(::std::cell::RefCell::new(0..));
+        };
+        if Persistence::Tick == persistence {
+            write_prologue.extend(quote_spanned! {op_span=>
+                #hydroflow.set_state_tick_hook(#counter_ident, |rcell| { rcell.replace(0..); });
+            });
+        }
+
+        let next_index = quote_spanned! {op_span=>
+            || #context.state_ref(#counter_ident).borrow_mut().next().unwrap()
+        };
+        // The helper fns tie the closure's argument type to the item type, so that the
+        // closure's parameters do not need annotations.
+        let write_iterator = if is_pull {
+            let input = &inputs[0];
+            quote_spanned! {op_span=>
+                let #ident = {
+                    #[inline(always)]
+                    fn inspect_enumerate<Item>(
+                        input: impl ::std::iter::Iterator<Item = Item>,
+                        mut next_index: impl ::std::ops::FnMut() -> usize,
+                        mut func: impl ::std::ops::FnMut(usize, &Item),
+                    ) -> impl ::std::iter::Iterator<Item = Item> {
+                        input.inspect(move |item| func(next_index(), item))
+                    }
+                    inspect_enumerate(#input, #next_index, #arguments)
+                };
+            }
+        } else {
+            let output = &outputs[0];
+            quote_spanned! {op_span=>
+                let #ident = {
+                    #[inline(always)]
+                    fn inspect_enumerate<Item>(
+                        mut next_index: impl ::std::ops::FnMut() -> usize,
+                        mut func: impl ::std::ops::FnMut(usize, &Item),
+                        output: impl #root::pusherator::Pusherator<Item = Item>,
+                    ) -> impl #root::pusherator::Pusherator<Item = Item> {
+                        #root::pusherator::inspect::Inspect::new(move |item: &Item| func(next_index(), item), output)
+                    }
+                    inspect_enumerate(#next_index, #arguments, #output)
+                };
+            }
+        };
+
+        Ok(OperatorWriteOutput {
+            write_prologue,
+            write_iterator,
+            ..Default::default()
+        })
+    },
+};
@@ -29,6 +29,16 @@ use crate::diagnostic::{Diagnostic, Level};
 /// When a single persistence argument is supplied, it is applied to both input ports.
 /// When no persistence arguments are applied it defaults to `'tick` for both.
 ///
+/// A port may also be `'mutable`, in which case its input is a stream of
+/// [`PersistenceKeyed`](https://hydro.run/rustdoc/dfir_rs/util/enum.PersistenceKeyed.html) records
+/// rather than pairs. `Persist(k, v)` inserts a pair, which is remembered across ticks like with
+/// `'static`, and `Delete(k)` is a tombstone removing every pair with key `k`. The other port
+/// must be `'mutable` or `'static`. Instead of the joined pairs, a `'mutable` join outputs the
+/// net change to them each tick, as
+/// [`Persistence`](https://hydro.run/rustdoc/dfir_rs/util/enum.Persistence.html) records:
+/// `Persist((k, (v1, v2)))` for a pair that starts matching and `Delete((k, (v1, v2)))` for a
+/// pair that stops matching. Feeding the output into `persist_mut()` recovers the current join.
+///
 /// The syntax is as follows:
 /// ```dfir,ignore
 /// join(); // Or
@@ -39,6 +49,8 @@ use crate::diagnostic::{Diagnostic, Level};
 /// join::<'static, 'tick>();
 ///
 /// join::<'tick, 'static>();
+///
+/// join::<'mutable, 'static>();
 /// // etc.
 /// ```
 ///
@@ -79,6 +91,25 @@ use crate::diagnostic::{Diagnostic, Level};
 /// ```
 /// Prints out `"(hello, (world, oakland))"` and `"(hello, (world, san francisco))"` since the
 /// inputs are peristed across ticks.
+///
+/// ---
+///
+/// ```rustbook
+/// use dfir_rs::util::PersistenceKeyed;
+///
+/// let (input_send, input_recv) = dfir_rs::util::unbounded_channel::<PersistenceKeyed<&str, &str>>();
+/// let mut flow = dfir_rs::dfir_syntax! {
+///     source_stream(input_recv) -> [0]my_join;
+///     source_iter([("hello", "world")]) -> [1]my_join;
+///     my_join = join::<'mutable, 'static>() -> for_each(|change| println!("{:?}", change));
+/// };
+/// input_send.send(PersistenceKeyed::Persist("hello", "oakland")).unwrap();
+/// flow.run_tick();
+/// input_send.send(PersistenceKeyed::Delete("hello")).unwrap();
+/// flow.run_tick();
+/// ```
+/// Prints out `Persist(("hello", ("oakland", "world")))` in the first tick and
+/// `Delete(("hello", ("oakland", "world")))` in the second, and nothing in later ticks.
 pub const JOIN: OperatorConstraints = OperatorConstraints {
     name: "join",
     categories: &[OperatorCategory::MultiIn],
@@ -134,87 +165,123 @@ pub const JOIN: OperatorConstraints = OperatorConstraints {
             quote_spanned!(op_span=>)
         };
 
-        let mut make_joindata = |persistence, side| {
+        let persistences = match persistence_args[..] {
+            [] => [Persistence::Tick, Persistence::Tick],
+            [a] => [a, a],
+            [a, b] => [a, b],
+            _ => unreachable!(),
+        };
+        let is_mutable = persistences.contains(&Persistence::Mutable);
+        if is_mutable && persistences.contains(&Persistence::Tick) {
+            diagnostics.push(Diagnostic::spanned(
+                op_span,
+                Level::Error,
+                "A 'mutable join input can only be combined with a 'mutable or 'static input",
+            ));
+            return Err(());
+        }
+
+        let make_joindata = |persistence, side| {
             let joindata_ident = wc.make_ident(format!("joindata_{}", side));
             let borrow_ident = wc.make_ident(format!("joindata_{}_borrow", side));
+            // Both sides of a `'mutable` join keep their values as sets, so that retracted pairs
+            // can be found.
+            let (state_type, value_bounds) = if is_mutable {
+                (
+                    quote_spanned!(op_span=> #root::compiled::pull::MutableJoinState),
+                    quote_spanned!(op_span=> + ::std::cmp::Eq),
+                )
+            } else {
+                (join_type.clone(), additional_trait_bounds.clone())
+            };
             let reset = match persistence {
                 Persistence::Tick => quote_spanned! {op_span=>
                     #hydroflow.set_state_tick_hook(#joindata_ident, |rcell| #root::util::clear::Clear::clear(rcell.get_mut()));
                 },
-                Persistence::Static => Default::default(),
-                Persistence::Mutable => {
-                    diagnostics.push(Diagnostic::spanned(
-                        op_span,
-                        Level::Error,
-                        "An implementation of 'mutable does not exist",
-                    ));
-                    return Err(());
-                }
+                Persistence::Static | Persistence::Mutable => Default::default(),
             };
             let init = quote_spanned! {op_span=>
                 let #joindata_ident = #hydroflow.add_state(::std::cell::RefCell::new(
-                    #join_type::default()
+                    #state_type::default()
                 ));
                 #reset
             };
-            Ok((joindata_ident, borrow_ident, init))
-        };
-
-        let persistences = match persistence_args[..] {
-            [] => [Persistence::Tick, Persistence::Tick],
-            [a] => [a, a],
-            [a, b] => [a, b],
-            _ => panic!(),
+            (joindata_ident, borrow_ident, state_type, value_bounds, init)
         };
 
-        let (lhs_joindata_ident, lhs_borrow_ident, lhs_init) =
-            make_joindata(persistences[0], "lhs")?;
-        let (rhs_joindata_ident, rhs_borrow_ident, rhs_init) =
-            make_joindata(persistences[1], "rhs")?;
+        let (lhs_joindata_ident, lhs_borrow_ident, lhs_type, lhs_bounds, lhs_init) =
+            make_joindata(persistences[0], "lhs");
+        let (rhs_joindata_ident, rhs_borrow_ident, rhs_type, rhs_bounds, rhs_init) =
+            make_joindata(persistences[1], "rhs");
 
         let write_prologue = quote_spanned! {op_span=>
             #lhs_init
             #rhs_init
         };
 
-        let lhs = &inputs[0];
-        let rhs = &inputs[1];
-        let write_iterator = quote_spanned! {op_span=>
-            let mut #lhs_borrow_ident = #context.state_ref(#lhs_joindata_ident).borrow_mut();
-            let mut #rhs_borrow_ident = #context.state_ref(#rhs_joindata_ident).borrow_mut();
-            let #ident = {
-                // Limit error propagation by bounding locally, erasing output iterator type.
-                #[inline(always)]
-                fn check_inputs<'a, K, I1, V1, I2, V2>(
-                    lhs: I1,
-                    rhs: I2,
-                    lhs_state: &'a mut #join_type<K, V1, V2>,
-                    rhs_state: &'a mut #join_type<K, V2, V1>,
-                    is_new_tick: bool,
-                ) -> impl 'a + Iterator<Item = (K, (V1, V2))>
-                where
-                    K: Eq + std::hash::Hash + Clone,
-                    V1: Clone #additional_trait_bounds,
-                    V2: Clone #additional_trait_bounds,
-                    I1: 'a + Iterator<Item = (K, V1)>,
-                    I2: 'a + Iterator<Item = (K, V2)>,
-                {
-                    #root::compiled::pull::symmetric_hash_join_into_iter(lhs, rhs, lhs_state, rhs_state, is_new_tick)
+        let write_iterator = if is_mutable {
+            // A `'static` input of a `'mutable` join only ever inserts pairs.
+            let make_input = |input, persistence| {
+                if persistence == Persistence::Mutable {
+                    quote_spanned!(op_span=> #input)
+                } else {
+                    quote_spanned! {op_span=>
+                        #input.map(|(k, v)| #root::util::PersistenceKeyed::Persist(k, v))
+                    }
                 }
-
-                check_inputs(#lhs, #rhs, &mut *#lhs_borrow_ident, &mut *#rhs_borrow_ident, #context.is_first_run_this_tick())
             };
+            let lhs = make_input(&inputs[0], persistences[0]);
+            let rhs = make_input(&inputs[1], persistences[1]);
+            quote_spanned! {op_span=>
+                let mut #lhs_borrow_ident = #context.state_ref(#lhs_joindata_ident).borrow_mut();
+                let mut #rhs_borrow_ident = #context.state_ref(#rhs_joindata_ident).borrow_mut();
+                let #ident = #root::compiled::pull::mutable_join_into_iter(
+                    #lhs,
+                    #rhs,
+                    &mut *#lhs_borrow_ident,
+                    &mut *#rhs_borrow_ident,
+                );
+            }
+        } else {
+            let lhs = &inputs[0];
+            let rhs = &inputs[1];
+            quote_spanned! {op_span=>
+                let mut #lhs_borrow_ident = #context.state_ref(#lhs_joindata_ident).borrow_mut();
+                let mut #rhs_borrow_ident = #context.state_ref(#rhs_joindata_ident).borrow_mut();
+                let #ident = {
+                    // Limit error propagation by bounding locally, erasing output iterator type.
+                    #[inline(always)]
+                    fn check_inputs<'a, K, I1, V1, I2, V2>(
+                        lhs: I1,
+                        rhs: I2,
+                        lhs_state: &'a mut #lhs_type<K, V1, V2>,
+                        rhs_state: &'a mut #rhs_type<K, V2, V1>,
+                        is_new_tick: bool,
+                    ) -> impl 'a + Iterator<Item = (K, (V1, V2))>
+                    where
+                        K: Eq + std::hash::Hash + Clone,
+                        V1: Clone #lhs_bounds,
+                        V2: Clone #rhs_bounds,
+                        I1: 'a + Iterator<Item = (K, V1)>,
+                        I2: 'a + Iterator<Item = (K, V2)>,
+                    {
+                        #root::compiled::pull::symmetric_hash_join_into_iter(lhs, rhs, lhs_state, rhs_state, is_new_tick)
+                    }
+
+                    check_inputs(#lhs, #rhs, &mut *#lhs_borrow_ident, &mut *#rhs_borrow_ident, #context.is_first_run_this_tick())
+                };
+            }
         };
 
-        let write_iterator_after =
-            if persistences[0] == Persistence::Static || persistences[1] == Persistence::Static {
-                quote_spanned! {op_span=>
-                    // TODO: Probably only need to schedule if #*_borrow.len() > 0?
-                    #context.schedule_subgraph(#context.current_subgraph(), false);
-                }
-            } else {
-                quote_spanned! {op_span=>}
-            };
+        // A `'mutable` join only emits changes, so it has nothing to replay in later ticks.
+        let write_iterator_after = if !is_mutable && persistences.contains(&Persistence::Static) {
+            quote_spanned! {op_span=>
+                // TODO: Probably only need to schedule if #*_borrow.len() > 0?
+                #context.schedule_subgraph(#context.current_subgraph(), false);
+            }
+        } else {
+            quote_spanned! {op_span=>}
+        };
 
         Ok(OperatorWriteOutput {
             write_prologue,
@@ -10,12 +10,13 @@ use quote::quote_spanned;
 use serde::{Deserialize, Serialize};
 use slotmap::Key;
 use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
 use syn::{parse_quote_spanned, Expr, Token};
 
 use super::{
     GraphNode, GraphNodeId, GraphSubgraphId, OpInstGenerics, OperatorInstance, PortIndexValue,
 };
-use crate::diagnostic::Diagnostic;
+use crate::diagnostic::{Diagnostic, Level};
 use crate::parse::{Operator, PortIndex};
 
 /// The delay (soft barrier) type, for each input to an operator if needed.
@@ -88,6 +89,39 @@ pub struct OperatorConstraints {
 pub type WriteFn =
     fn(&WriteContextArgs<'_>, &mut Vec<Diagnostic>) -> Result<OperatorWriteOutput, ()>;
 
+impl OperatorConstraints {
+    /// Checks that `generics` has a number of persistence lifetime arguments within
+    /// [`Self::persistence_args`], so that `write_fn` can assume a valid count.
+    ///
+    /// The error is spanned at the generic arguments, or `fallback_span` if there are none.
+    pub fn check_persistence_args(
+        &self,
+        generics: &OpInstGenerics,
+        fallback_span: Span,
+    ) -> Result<(), Diagnostic> {
+        if self
+            .persistence_args
+            .contains(&generics.persistence_args.len())
+        {
+            return Ok(());
+        }
+        let span = generics
+            .generic_args
+            .as_ref()
+            .map_or(fallback_span, Spanned::span);
+        Err(Diagnostic::spanned(
+            span,
+            Level::Error,
+            format!(
+                "`{}` should have {} persistence lifetime arguments, actually has {}.",
+                self.name,
+                self.persistence_args.human_string(),
+                generics.persistence_args.len()
+            ),
+        ))
+    }
+}
+
 impl Debug for OperatorConstraints {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("OperatorConstraints")
@@ -269,6 +303,7 @@ declare_ops![
     identity::IDENTITY,
     initialize::INITIALIZE,
     inspect::INSPECT,
+    inspect_enumerate::INSPECT_ENUMERATE,
     join::JOIN,
     join_fused::JOIN_FUSED,
     join_fused_lhs::JOIN_FUSED_LHS,
@@ -295,6 +330,7 @@ declare_ops![
     persist_mut_keyed::PERSIST_MUT_KEYED,
     py_udf::PY_UDF,
     reduce::REDUCE,
+    scan::SCAN,
     spin::SPIN,
     sort::SORT,
     sort_by_key::SORT_BY_KEY,
@@ -320,6 +356,14 @@ pub fn operator_lookup() -> &'static HashMap<&'static str, &'static OperatorCons
         OnceLock::new();
     OPERATOR_LOOKUP.get_or_init(|| OPERATORS.iter().map(|op| (op.name, op)).collect())
 }
+/// Iterate the operators tagged with the given category.
+pub fn operators_in_category(
+    category: OperatorCategory,
+) -> impl Iterator<Item = &'static OperatorConstraints> {
+    OPERATORS
+        .iter()
+        .filter(move |op| op.categories.contains(&category))
+}
 /// Find an operator by [`GraphNode`].
 pub fn find_node_op_constraints(node: &GraphNode) -> Option<&'static OperatorConstraints> {
     if let GraphNode::Operator(operator) = node {
@@ -498,6 +542,7 @@ pub enum OperatorCategory {
     MultiOut,
     Source,
     Sink,
+    Network,
     Control,
     CompilerFusionOperator,
     Windowing,
@@ -518,6 +563,7 @@ impl OperatorCategory {
             OperatorCategory::MultiOut => "Multi-Output Operators",
             OperatorCategory::Source => "Sources",
             OperatorCategory::Sink => "Sinks",
+            OperatorCategory::Network => "Network Operators",
             OperatorCategory::Control => "Control Flow Operators",
             OperatorCategory::CompilerFusionOperator => "Compiler Fusion Operators",
             OperatorCategory::Windowing => "Windowing Operator",
@@ -542,6 +588,9 @@ impl OperatorCategory {
             OperatorCategory::Sink => {
                 "Operators which consume input elements (and produce no outputs)."
             }
+            OperatorCategory::Network => {
+                "Operators which receive from or send to external streams and sinks, such as the network."
+            }
             OperatorCategory::Control => "Operators which affect control flow/scheduling.",
             OperatorCategory::CompilerFusionOperator => {
                 "Operators which are necessary to implement certain optimizations and rewrite rules"
@@ -562,3 +611,49 @@ pub enum FloType {
     /// An un-windowing operator, for moving data out of a loop context.
     Unwindowing,
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn test_dest_sink_is_network() {
+        assert!(operator_lookup()["dest_sink"]
+            .categories
+            .contains(&OperatorCategory::Network));
+
+        let network = operators_in_category(OperatorCategory::Network)
+            .map(|op| op.name)
+            .collect::<Vec<_>>();
+        assert!(network.contains(&"dest_sink"));
+        assert!(network.contains(&"source_stream"));
+        assert!(!network.contains(&"source_iter"));
+    }
+
+    #[test]
+    pub fn test_join_extra_persistence_args() {
+        let hf_code = syn::parse_str::<crate::parse::HfCode>(
+            r#"
+            j = join::<'static, 'static, 'static>() -> for_each(std::mem::drop);
+            source_iter(0..10) -> map(|x| (x, x)) -> [0]j;
+            source_iter(0..10) -> map(|x| (x, x)) -> [1]j;
+            "#,
+        )
+        .unwrap();
+        let (flat_graph, _uses, _diagnostics) =
+            crate::graph::FlatGraphBuilder::from_hfcode(hf_code).build();
+        let partitioned_graph = crate::graph::partition_graph(flat_graph).unwrap();
+
+        // Generates code despite the graph-level validation error, which must be reported again
+        // instead of reaching the `unreachable!()` in `join`'s `write_fn`.
+        let mut diagnostics = Vec::new();
+        partitioned_graph.as_code(
+            &quote::quote! { dfir_rs },
+            true,
+            quote::quote!(),
+            &mut diagnostics,
+        );
+        assert!(diagnostics.iter().any(|diagnostic| diagnostic.message
+            == "`join` should have at least 0 and at most 2 persistence lifetime arguments, actually has 3."));
+    }
+}
@@ -0,0 +1,145 @@
+use quote::quote_spanned;
+
+use super::{
+    OpInstGenerics, OperatorCategory, OperatorConstraints, OperatorInstance,
+    OperatorWriteOutput, Persistence, WriteContextArgs, RANGE_0, RANGE_1,
+};
+use crate::diagnostic::{Diagnostic, Level};
+
+/// > 1 input stream, 1 output stream
+///
+/// > Arguments: two arguments, both closures. The first closure is used to create the initial
+/// > value for the state, and the second is called with an `&mut State` and each item, returning
+/// > an `Option` of the output item.
+///
+/// Akin to Rust's built-in [`scan`](https://doc.rust-lang.org/std/iter/trait.Iterator.html#method.scan)
+/// operator, this maps each item while threading a mutable state through the calls. Unlike the
+/// iterator version, returning `None` does not end the stream; the item is simply dropped.
+///
+/// > Note: The closures have access to the [`context` object](surface_flows.mdx#the-context-object).
+///
+/// `scan` can also be provided with one generic lifetime persistence argument, either `'tick` or
+/// `'static`, to specify how the state persists. With `'tick` (the default), the state is reset to
+/// the initial value at the start of each tick. With `'static`, the state is kept across ticks.
+///
+/// ```dfir
+/// source_iter([1, 2, 3, 4])
+///     -> scan(|| 0, |sum: &mut i32, x| {
+///         *sum += x;
+///         Some(*sum)
+///     })
+///     -> assert_eq([1, 3, 6, 10]);
+/// ```
+pub const SCAN: OperatorConstraints = OperatorConstraints {
+    name: "scan",
+    categories: &[OperatorCategory::Map],
+    hard_range_inn: RANGE_1,
+    soft_range_inn: RANGE_1,
+    hard_range_out: RANGE_1,
+    soft_range_out: RANGE_1,
+    num_args: 2,
+    persistence_args: &(0..=1),
+    type_args: RANGE_0,
+    is_external_input: false,
+    has_singleton_output: false,
+    flo_type: None,
+    ports_inn: None,
+    ports_out: None,
+    input_delaytype_fn: |_| None,
+    write_fn: |wc @ &WriteContextArgs {
+                   root,
+                   op_span,
+                   context,
+                   hydroflow,
+                   ident,
+                   inputs,
+                   outputs,
+                   is_pull,
+                   arguments,
+                   op_inst:
+                       OperatorInstance {
+                           generics:
+                               OpInstGenerics {
+                                   persistence_args, ..
+                               },
+                           ..
+                       },
+                   ..
+               },
+               diagnostics| {
+        let persistence = match persistence_args[..] {
+            [] => Persistence::Tick,
+            [Persistence::Mutable] => {
+                diagnostics.push(Diagnostic::spanned(
+                    op_span,
+                    Level::Error,
+                    "An implementation of 'mutable does not exist",
+                ));
+                Persistence::Tick
+            },
+            [a] => a,
+            _ => unreachable!(),
+        };
+
+        let init = &arguments[0];
+        let func = &arguments[1];
+        let initializer_func_ident = wc.make_ident("initializer_func");
+        let state_ident = wc.make_ident("scanstate");
+
+        let mut write_prologue = quote_spanned! {op_span=>
+            #[allow(unused_mut)]
+            let mut #initializer_func_ident = #init;
+
+            #[allow(clippy::redundant_closure_call)]
+            let #state_ident = #hydroflow.add_state(
+                ::std::cell::RefCell::new((#initializer_func_ident)())
+            );
+        };
+        if Persistence::Tick == persistence {
+            write_prologue.extend(quote_spanned! {op_span=>
+                // Reset the state to the initializer fn if it is a new tick.
+                #hydroflow.set_state_tick_hook(#state_ident, move |rcell| { rcell.replace((#initializer_func_ident)()); });
+            });
+        }
+
+        // The helper fns tie the closure's argument types to the state and item types, so that
+        // the closure's parameters do not need annotations.
+        let write_iterator = if is_pull {
+            let input = &inputs[0];
+            quote_spanned! {op_span=>
+                let #ident = {
+                    #[inline(always)]
+                    fn scan<'a, Item, State, Out>(
+                        input: impl 'a + ::std::iter::Iterator<Item = Item>,
+                        state: &'a ::std::cell::RefCell<State>,
+                        mut func: impl 'a + ::std::ops::FnMut(&mut State, Item) -> ::std::option::Option<Out>,
+                    ) -> impl 'a + ::std::iter::Iterator<Item = Out> {
+                        input.filter_map(move |item| func(&mut *state.borrow_mut(), item))
+                    }
+                    scan(#input, #context.state_ref(#state_ident), #func)
+                };
+            }
+        } else {
+            let output = &outputs[0];
+            quote_spanned! {op_span=>
+                let #ident = {
+                    #[inline(always)]
+                    fn scan<'a, Item, State, Out>(
+                        state: &'a ::std::cell::RefCell<State>,
+                        mut func: impl 'a + ::std::ops::FnMut(&mut State, Item) -> ::std::option::Option<Out>,
+                        output: impl 'a + #root::pusherator::Pusherator<Item = Out>,
+                    ) -> impl 'a + #root::pusherator::Pusherator<Item = Item> {
+                        #root::pusherator::filter_map::FilterMap::new(move |item| func(&mut *state.borrow_mut(), item), output)
+                    }
+                    scan(#context.state_ref(#state_ident), #func, #output)
+                };
+            }
+        };
+
+        Ok(OperatorWriteOutput {
+            write_prologue,
+            write_iterator,
+            ..Default::default()
+        })
+    },
+};
@@ -26,7 +26,7 @@ use super::{
 /// ```
 pub const SOURCE_STREAM: OperatorConstraints = OperatorConstraints {
     name: "source_stream",
-    categories: &[OperatorCategory::Source],
+    categories: &[OperatorCategory::Source, OperatorCategory::Network],
     hard_range_inn: RANGE_0,
     soft_range_inn: RANGE_0,
     hard_range_out: RANGE_1,
@@ -26,7 +26,7 @@ use super::{
 /// ```
 pub const SOURCE_STREAM_SERDE: OperatorConstraints = OperatorConstraints {
     name: "source_stream_serde",
-    categories: &[OperatorCategory::Source],
+    categories: &[OperatorCategory::Source, OperatorCategory::Network],
     hard_range_inn: RANGE_0,
     soft_range_inn: RANGE_0,
     hard_range_out: RANGE_1,
@@ -612,4 +612,32 @@ demux(|(msg, addr), var_args!(clients, msgs, errs)| match msg {
             op.to_pretty_string()
         );
     }
+
+    #[test]
+    fn test_named_pipeline_round_trip() {
+        let code: HfCode = parse_quote! {
+            my_join = join();
+            source_iter(0..10) -> [0]my_join[1] -> for_each(|x| println!("{:?}", x));
+        };
+        let tokens = code.to_token_stream();
+        assert!(tokens.to_string().contains("[0] my_join [1]"));
+
+        let reparsed: HfCode = syn::parse2(tokens.clone()).unwrap();
+        assert_eq!(tokens.to_string(), reparsed.to_token_stream().to_string());
+    }
+
+    #[test]
+    fn test_named_port_round_trip() {
+        let code: HfCode = parse_quote! {
+            my_demux = source_iter(0..10) -> demux(|v, var_args!(pos, neg)| if v > 0 { pos.give(v) } else { neg.give(v) });
+            my_demux[pos] -> for_each(|x| println!("{:?}", x));
+            my_demux[neg] -> null();
+        };
+        let tokens = code.to_token_stream();
+        assert!(tokens.to_string().contains("my_demux [pos]"));
+        assert!(tokens.to_string().contains("my_demux [neg]"));
+
+        let reparsed: HfCode = syn::parse2(tokens.clone()).unwrap();
+        assert_eq!(tokens.to_string(), reparsed.to_token_stream().to_string());
+    }
 }
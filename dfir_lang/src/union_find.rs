@@ -4,7 +4,8 @@ use slotmap::{Key, SecondaryMap};
 
 /// Union-find data structure.
 ///
-/// Used to efficiently track sets of equivalent items.
+/// Used to efficiently track sets of equivalent items. Uses union-by-size and path compression,
+/// so operations take nearly constant amortized time.
 ///
 /// <https://en.wikipedia.org/wiki/Disjoint-set_data_structure>
 #[derive(Default, Clone)]
@@ -13,6 +14,10 @@ where
     K: Key,
 {
     links: SecondaryMap<K, K>,
+    /// The representative item and the number of items of each set, keyed by the root of its
+    /// tree. The representative is tracked separately from the root so that it does not depend
+    /// on how the trees are balanced.
+    sets: SecondaryMap<K, (K, usize)>,
 }
 impl<K> UnionFind<K>
 where
@@ -26,27 +31,43 @@ where
     pub fn with_capacity(capacity: usize) -> Self {
         Self {
             links: SecondaryMap::with_capacity(capacity),
+            sets: SecondaryMap::with_capacity(capacity),
         }
     }
 
-    /// Combines two items `a` and `b` as equivalent, in the same set.
+    /// Combines two items `a` and `b` as equivalent, in the same set. The representative of `b`
+    /// becomes the representative of the combined set.
     pub fn union(&mut self, a: K, b: K) {
-        let i = self.find(a);
-        let j = self.find(b);
+        let i = self.root(a);
+        let j = self.root(b);
         if i == j {
             return;
         }
-        self.links[i] = j;
+        let (_, i_size) = self.sets[i];
+        let (representative, j_size) = self.sets[j];
+        // Attach the smaller tree under the larger one, keeping the trees shallow.
+        let (small, large) = if i_size < j_size { (i, j) } else { (j, i) };
+        self.links[small] = large;
+        self.sets.remove(small);
+        self.sets[large] = (representative, i_size + j_size);
     }
 
     /// Finds the "representative" item for `k`. Each set of equivalent items is represented by one
     /// of its member items.
     pub fn find(&mut self, k: K) -> K {
+        let root = self.root(k);
+        self.sets[root].0
+    }
+
+    /// Finds the root of the tree containing `k`, compressing the path to it.
+    fn root(&mut self, k: K) -> K {
         if let Some(next) = self.links.insert(k, k) {
             if k == next {
                 return k;
             }
-            self.links[k] = self.find(next);
+            self.links[k] = self.root(next);
+        } else {
+            self.sets.insert(k, (k, 1));
         }
         self.links[k]
     }
@@ -82,4 +103,31 @@ mod test {
         assert!(!uf.same_set(b, d));
         assert!(!uf.same_set(d, c));
     }
+
+    #[test]
+    fn test_representative() {
+        let mut sm = SlotMap::new();
+        let keys = (0..4).map(|_| sm.insert(())).collect::<Vec<_>>();
+
+        let mut uf = UnionFind::new();
+        uf.union(keys[0], keys[1]);
+        uf.union(keys[1], keys[2]);
+        // The larger set's tree root is kept, but the representative is still taken from `b`.
+        uf.union(keys[0], keys[3]);
+        assert!(keys.iter().all(|&k| uf.find(k) == keys[3]));
+    }
+
+    #[test]
+    fn test_long_chain() {
+        let mut sm = SlotMap::new();
+        let keys = (0..100_000).map(|_| sm.insert(())).collect::<Vec<_>>();
+
+        // Linking each item to the next would build a single deep path without union-by-size,
+        // overflowing the stack in the recursive `find`.
+        let mut uf = UnionFind::with_capacity(keys.len());
+        for pair in keys.windows(2) {
+            uf.union(pair[0], pair[1]);
+        }
+        assert!(keys.iter().all(|&k| uf.same_set(keys[0], k)));
+    }
 }
@@ -1,12 +1,14 @@
 mod fold;
 mod fold_from;
 mod multiset;
+mod mutable;
 mod reduce;
 mod set;
 
 pub use fold::HalfJoinStateFold;
 pub use fold_from::HalfJoinStateFoldFrom;
 pub use multiset::HalfMultisetJoinState;
+pub use mutable::{mutable_join_into_iter, MutableJoinState};
 pub use reduce::HalfJoinStateReduce;
 pub use set::HalfSetJoinState;
 use smallvec::SmallVec;
@@ -0,0 +1,277 @@
+use std::collections::hash_map::Entry;
+use std::collections::VecDeque;
+
+use super::HalfJoinState;
+use crate::util::clear::Clear;
+use crate::util::{Persistence, PersistenceKeyed};
+
+type HashMap<K, V> = rustc_hash::FxHashMap<K, V>;
+type HashSet<K> = rustc_hash::FxHashSet<K>;
+
+use smallvec::{smallvec, SmallVec};
+
+/// Join state for a `'mutable` join input, which persists across ticks but also accepts
+/// tombstones ([`PersistenceKeyed::Delete`]) retracting every value of a key.
+///
+/// Like [`super::HalfSetJoinState`], each distinct value is only stored once, so a tombstone
+/// retracts a value no matter how many times it was inserted.
+#[derive(Debug)]
+pub struct MutableJoinState<Key, ValBuild, ValProbe> {
+    /// Table to probe, vec val contains all matches.
+    table: HashMap<Key, SmallVec<[ValBuild; 1]>>,
+    /// Not-yet emitted matches.
+    current_matches: VecDeque<(Key, ValProbe, ValBuild)>,
+    len: usize,
+}
+impl<Key, ValBuild, ValProbe> Default for MutableJoinState<Key, ValBuild, ValProbe> {
+    fn default() -> Self {
+        Self {
+            table: HashMap::default(),
+            current_matches: VecDeque::default(),
+            len: 0,
+        }
+    }
+}
+impl<Key, ValBuild, ValProbe> Clear for MutableJoinState<Key, ValBuild, ValProbe> {
+    fn clear(&mut self) {
+        self.table.clear();
+        self.current_matches.clear();
+        self.len = 0;
+    }
+}
+impl<Key, ValBuild, ValProbe> MutableJoinState<Key, ValBuild, ValProbe>
+where
+    Key: Clone + Eq + std::hash::Hash,
+    ValBuild: Clone + Eq,
+    ValProbe: Clone,
+{
+    /// Applies an insertion or a tombstone to the state.
+    pub fn apply(&mut self, record: PersistenceKeyed<Key, ValBuild>) {
+        match record {
+            PersistenceKeyed::Persist(k, v) => {
+                self.build(k, &v);
+            }
+            PersistenceKeyed::Delete(k) => {
+                self.delete(&k);
+            }
+        }
+    }
+
+    /// Removes every value of `k`, returning how many distinct values were removed.
+    pub fn delete(&mut self, k: &Key) -> usize {
+        let removed = self.table.remove(k).map_or(0, |vals| vals.len());
+        self.len -= removed;
+        removed
+    }
+}
+impl<Key, ValBuild, ValProbe> HalfJoinState<Key, ValBuild, ValProbe>
+    for MutableJoinState<Key, ValBuild, ValProbe>
+where
+    Key: Clone + Eq + std::hash::Hash,
+    ValBuild: Clone + Eq,
+    ValProbe: Clone,
+{
+    fn build(&mut self, k: Key, v: &ValBuild) -> bool {
+        let entry = self.table.entry(k);
+
+        match entry {
+            Entry::Occupied(mut e) => {
+                let vec = e.get_mut();
+
+                if !vec.contains(v) {
+                    vec.push(v.clone());
+                    self.len += 1;
+                    return true;
+                }
+            }
+            Entry::Vacant(e) => {
+                e.insert(smallvec![v.clone()]);
+                self.len += 1;
+                return true;
+            }
+        };
+
+        false
+    }
+
+    fn probe(&mut self, k: &Key, v: &ValProbe) -> Option<(Key, ValProbe, ValBuild)> {
+        let mut iter = self
+            .table
+            .get(k)?
+            .iter()
+            .map(|valbuild| (k.clone(), v.clone(), valbuild.clone()));
+
+        let first = iter.next();
+
+        self.current_matches.extend(iter);
+
+        first
+    }
+
+    fn full_probe(&self, k: &Key) -> std::slice::Iter<'_, ValBuild> {
+        let Some(sv) = self.table.get(k) else {
+            return [].iter();
+        };
+
+        sv.iter()
+    }
+
+    fn pop_match(&mut self) -> Option<(Key, ValProbe, ValBuild)> {
+        self.current_matches.pop_front()
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+    fn iter(&self) -> std::collections::hash_map::Iter<'_, Key, SmallVec<[ValBuild; 1]>> {
+        self.table.iter()
+    }
+}
+
+/// Applies a tick's records to both sides of a `'mutable` join.
+///
+/// Returns the net change to the joined pairs: [`Persistence::Persist`] for each pair that
+/// starts matching and [`Persistence::Delete`] for each pair that stops matching.
+///
+/// Only keys with a record in `lhs` or `rhs` can change, so only their pairs are compared.
+pub fn mutable_join_into_iter<Key, V1, V2, I1, I2>(
+    lhs: I1,
+    rhs: I2,
+    lhs_state: &mut MutableJoinState<Key, V1, V2>,
+    rhs_state: &mut MutableJoinState<Key, V2, V1>,
+) -> std::vec::IntoIter<Persistence<(Key, (V1, V2))>>
+where
+    Key: Clone + Eq + std::hash::Hash,
+    V1: Clone + Eq,
+    V2: Clone + Eq,
+    I1: Iterator<Item = PersistenceKeyed<Key, V1>>,
+    I2: Iterator<Item = PersistenceKeyed<Key, V2>>,
+{
+    fn joined<Key, V1, V2>(
+        k: &Key,
+        lhs_state: &MutableJoinState<Key, V1, V2>,
+        rhs_state: &MutableJoinState<Key, V2, V1>,
+    ) -> Vec<(V1, V2)>
+    where
+        Key: Clone + Eq + std::hash::Hash,
+        V1: Clone + Eq,
+        V2: Clone + Eq,
+    {
+        lhs_state
+            .full_probe(k)
+            .flat_map(|v1| rhs_state.full_probe(k).map(|v2| (v1.clone(), v2.clone())))
+            .collect()
+    }
+
+    let lhs = lhs.collect::<Vec<_>>();
+    let rhs = rhs.collect::<Vec<_>>();
+
+    // The pairs of each touched key before this tick's records are applied, in record order.
+    let mut seen = HashSet::default();
+    let before = lhs
+        .iter()
+        .map(|record| match record {
+            PersistenceKeyed::Persist(k, _) | PersistenceKeyed::Delete(k) => k,
+        })
+        .chain(rhs.iter().map(|record| match record {
+            PersistenceKeyed::Persist(k, _) | PersistenceKeyed::Delete(k) => k,
+        }))
+        .filter(|k| seen.insert((*k).clone()))
+        .map(|k| (k.clone(), joined(k, lhs_state, rhs_state)))
+        .collect::<Vec<_>>();
+
+    for record in lhs {
+        lhs_state.apply(record);
+    }
+    for record in rhs {
+        rhs_state.apply(record);
+    }
+
+    let mut changes = Vec::new();
+    for (k, before) in before {
+        let after = joined(&k, lhs_state, rhs_state);
+        changes.extend(
+            before
+                .iter()
+                .filter(|pair| !after.contains(pair))
+                .map(|pair| Persistence::Delete((k.clone(), pair.clone()))),
+        );
+        changes.extend(
+            after
+                .into_iter()
+                .filter(|pair| !before.contains(pair))
+                .map(|pair| Persistence::Persist((k.clone(), pair))),
+        );
+    }
+    changes.into_iter()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{mutable_join_into_iter, MutableJoinState};
+    use crate::compiled::pull::HalfJoinState;
+    use crate::util::Persistence;
+    use crate::util::PersistenceKeyed::{Delete, Persist};
+
+    #[test]
+    fn keeps_sets_and_applies_tombstones() {
+        let mut state = MutableJoinState::<&str, u32, ()>::default();
+
+        state.apply(Persist("a", 1));
+        state.apply(Persist("a", 1));
+        state.apply(Persist("a", 2));
+        state.apply(Persist("b", 3));
+        assert_eq!(state.len(), 3);
+        assert_eq!(
+            state.full_probe(&"a").copied().collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+
+        // A tombstone removes a value regardless of how many times it was inserted.
+        state.apply(Delete("a"));
+        assert_eq!(state.len(), 1);
+        assert_eq!(state.full_probe(&"a").count(), 0);
+        assert_eq!(state.full_probe(&"b").copied().collect::<Vec<_>>(), vec![3]);
+    }
+
+    #[test]
+    fn emits_net_changes() {
+        let mut lhs_state = MutableJoinState::<&str, u32, char>::default();
+        let mut rhs_state = MutableJoinState::<&str, char, u32>::default();
+
+        let changes = mutable_join_into_iter(
+            [Persist("a", 1), Persist("b", 2)].into_iter(),
+            [Persist("a", 'x')].into_iter(),
+            &mut lhs_state,
+            &mut rhs_state,
+        );
+        assert_eq!(
+            changes.collect::<Vec<_>>(),
+            vec![Persistence::Persist(("a", (1, 'x')))]
+        );
+
+        // Inserting an existing value changes nothing.
+        let changes = mutable_join_into_iter(
+            [Persist("a", 1)].into_iter(),
+            [].into_iter(),
+            &mut lhs_state,
+            &mut rhs_state,
+        );
+        assert_eq!(changes.count(), 0);
+
+        // Pairs inserted and deleted within a tick cancel out.
+        let changes = mutable_join_into_iter(
+            [Delete("a"), Persist("a", 3), Persist("b", 4), Delete("b")].into_iter(),
+            [Persist("b", 'y')].into_iter(),
+            &mut lhs_state,
+            &mut rhs_state,
+        );
+        assert_eq!(
+            changes.collect::<Vec<_>>(),
+            vec![
+                Persistence::Delete(("a", (1, 'x'))),
+                Persistence::Persist(("a", (3, 'x'))),
+            ]
+        );
+    }
+}
@@ -42,6 +42,7 @@ use serde::de::DeserializeOwned;
 use serde::ser::Serialize;
 
 /// Persit or delete tuples
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Persistence<T> {
     /// Persist T values
     Persist(T),
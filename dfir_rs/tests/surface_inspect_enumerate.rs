@@ -0,0 +1,57 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use dfir_rs::dfir_syntax;
+use dfir_rs::util::collect_ready;
+use multiplatform_test::multiplatform_test;
+
+#[multiplatform_test]
+pub fn test_inspect_enumerate_tick() {
+    let (items_send, items_recv) = dfir_rs::util::unbounded_channel::<&str>();
+    let (out_send, mut out_recv) = dfir_rs::util::unbounded_channel::<&str>();
+    let seen = Rc::new(RefCell::new(Vec::new()));
+    let seen_inner = seen.clone();
+
+    let mut df = dfir_syntax! {
+        out = source_stream(items_recv)
+            -> inspect_enumerate(|i, x| seen_inner.borrow_mut().push((i, *x)))
+            -> tee(); // Tee to force `inspect_enumerate()` to be push.
+        out -> for_each(|x| out_send.send(x).unwrap());
+        out -> null();
+    };
+
+    items_send.send("a").unwrap();
+    items_send.send("b").unwrap();
+    df.run_tick();
+    items_send.send("c").unwrap();
+    df.run_tick();
+
+    assert_eq!(
+        &["a", "b", "c"],
+        &*collect_ready::<Vec<_>, _>(&mut out_recv)
+    );
+    assert_eq!(&[(0, "a"), (1, "b"), (0, "c")], &**seen.borrow());
+}
+
+#[multiplatform_test]
+pub fn test_inspect_enumerate_static() {
+    let (items_send, items_recv) = dfir_rs::util::unbounded_channel::<&str>();
+    let seen = Rc::new(RefCell::new(Vec::new()));
+    let seen_inner = seen.clone();
+
+    let mut df = dfir_syntax! {
+        source_stream(items_recv)
+            -> inspect_enumerate::<'static>(|i, x| seen_inner.borrow_mut().push((i, *x)))
+            -> m;
+        source_iter(None) -> m; // Extra union to force `inspect_enumerate()` to be pull.
+        m = union() -> null();
+    };
+
+    items_send.send("a").unwrap();
+    items_send.send("b").unwrap();
+    df.run_tick();
+    items_send.send("c").unwrap();
+    df.run_tick();
+
+    assert_eq!(&[(0, "a"), (1, "b"), (2, "c")], &**seen.borrow());
+}
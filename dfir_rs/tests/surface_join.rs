@@ -151,3 +151,108 @@ pub fn replay_static() {
         assert_contains_each_by_tick!(results, TickInstant::new(2), &[(7, (1, 3)), (7, (1, 4)), (7, (2, 3)), (7, (2, 4))]);
     };
 }
+
+#[multiplatform_test]
+pub fn mutable_static() {
+    use dfir_rs::util::Persistence;
+    use dfir_rs::util::PersistenceKeyed::*;
+
+    let results = Rc::new(RefCell::new(HashMap::<TickInstant, Vec<_>>::new()));
+    let results_inner = Rc::clone(&results);
+
+    let (input_send, input_recv) = dfir_rs::util::unbounded_channel();
+
+    let mut df = dfir_syntax! {
+        source_stream(input_recv) -> [0]my_join;
+        source_iter([(7, 3), (8, 4)]) -> [1]my_join;
+        my_join = join::<'mutable, 'static>()
+            -> for_each(|x| results_inner.borrow_mut().entry(context.current_tick()).or_default().push(x));
+    };
+
+    input_send.send(Persist(7, 1)).unwrap();
+    input_send.send(Persist(8, 2)).unwrap();
+    df.run_tick();
+
+    input_send.send(Delete(7)).unwrap();
+    df.run_tick();
+
+    // Deleting and re-inserting within a tick only reports the net change.
+    input_send.send(Delete(8)).unwrap();
+    input_send.send(Persist(8, 5)).unwrap();
+    df.run_tick();
+
+    // Nothing changes, so nothing is emitted.
+    df.run_tick();
+
+    let mut results = results.take();
+    for joined in results.values_mut() {
+        joined.sort_by_key(|change| match change {
+            Persistence::Persist(pair) | Persistence::Delete(pair) => *pair,
+        });
+    }
+    assert_eq!(
+        results[&TickInstant::new(0)],
+        &[
+            Persistence::Persist((7, (1, 3))),
+            Persistence::Persist((8, (2, 4)))
+        ]
+    );
+    assert_eq!(
+        results[&TickInstant::new(1)],
+        &[Persistence::Delete((7, (1, 3)))]
+    );
+    assert_eq!(
+        results[&TickInstant::new(2)],
+        &[
+            Persistence::Delete((8, (2, 4))),
+            Persistence::Persist((8, (5, 4)))
+        ]
+    );
+    assert_eq!(results.get(&TickInstant::new(3)), None);
+}
+
+#[multiplatform_test]
+pub fn mutable_mutable() {
+    use dfir_rs::util::Persistence;
+    use dfir_rs::util::PersistenceKeyed::*;
+
+    let results = Rc::new(RefCell::new(HashMap::<TickInstant, Vec<_>>::new()));
+    let results_inner = Rc::clone(&results);
+
+    let (lhs_send, lhs_recv) = dfir_rs::util::unbounded_channel();
+    let (rhs_send, rhs_recv) = dfir_rs::util::unbounded_channel();
+
+    let mut df = dfir_syntax! {
+        source_stream(lhs_recv) -> [0]my_join;
+        source_stream(rhs_recv) -> [1]my_join;
+        my_join = join::<'mutable>()
+            -> for_each(|x| results_inner.borrow_mut().entry(context.current_tick()).or_default().push(x));
+    };
+
+    lhs_send.send(Persist(7, 1)).unwrap();
+    rhs_send.send(Persist(7, 3)).unwrap();
+    df.run_tick();
+
+    // Inserting the same value again does not change the join.
+    lhs_send.send(Persist(7, 1)).unwrap();
+    df.run_tick();
+
+    rhs_send.send(Delete(7)).unwrap();
+    df.run_tick();
+
+    // The deleted key stops producing pairs.
+    lhs_send.send(Persist(7, 2)).unwrap();
+    df.run_tick();
+
+    let results = results.take();
+    assert_eq!(
+        results[&TickInstant::new(0)],
+        &[Persistence::Persist((7, (1, 3)))]
+    );
+    assert_eq!(results.get(&TickInstant::new(1)), None);
+    assert_eq!(
+        results[&TickInstant::new(2)],
+        &[Persistence::Delete((7, (1, 3)))]
+    );
+    assert_eq!(results.get(&TickInstant::new(3)), None);
+}
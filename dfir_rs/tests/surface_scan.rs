@@ -0,0 +1,71 @@
+use dfir_rs::dfir_syntax;
+use dfir_rs::util::collect_ready;
+use multiplatform_test::multiplatform_test;
+
+#[multiplatform_test]
+pub fn test_scan_tick() {
+    let (items_send, items_recv) = dfir_rs::util::unbounded_channel::<u32>();
+    let (out_send, mut out_recv) = dfir_rs::util::unbounded_channel::<u32>();
+
+    let mut df = dfir_syntax! {
+        out = source_stream(items_recv)
+            -> scan(|| 0, |sum: &mut u32, x| {
+                *sum += x;
+                Some(*sum)
+            })
+            -> tee(); // Tee to force `scan()` to be push.
+        out -> for_each(|x| out_send.send(x).unwrap());
+        out -> null();
+    };
+
+    items_send.send(1).unwrap();
+    items_send.send(2).unwrap();
+    df.run_tick();
+    items_send.send(3).unwrap();
+    df.run_tick();
+
+    assert_eq!(&[1, 3, 3], &*collect_ready::<Vec<_>, _>(&mut out_recv));
+}
+
+#[multiplatform_test]
+pub fn test_scan_static() {
+    let (items_send, items_recv) = dfir_rs::util::unbounded_channel::<u32>();
+    let (out_send, mut out_recv) = dfir_rs::util::unbounded_channel::<u32>();
+
+    let mut df = dfir_syntax! {
+        source_stream(items_recv)
+            -> scan::<'static>(|| 0, |sum: &mut u32, x| {
+                *sum += x;
+                Some(*sum)
+            })
+            -> m;
+        source_iter(None) -> m; // Extra union to force `scan()` to be pull.
+        m = union() -> for_each(|x| out_send.send(x).unwrap());
+    };
+
+    items_send.send(1).unwrap();
+    items_send.send(2).unwrap();
+    df.run_tick();
+    items_send.send(3).unwrap();
+    df.run_tick();
+
+    assert_eq!(&[1, 3, 6], &*collect_ready::<Vec<_>, _>(&mut out_recv));
+}
+
+#[multiplatform_test]
+pub fn test_scan_drops_none() {
+    let (out_send, mut out_recv) = dfir_rs::util::unbounded_channel::<u32>();
+
+    let mut df = dfir_syntax! {
+        source_iter([1, 2, 3, 4, 5])
+            -> scan(|| 0, |count: &mut usize, x| {
+                *count += 1;
+                (*count % 2 == 0).then_some(x)
+            })
+            -> for_each(|x| out_send.send(x).unwrap());
+    };
+
+    df.run_available();
+
+    assert_eq!(&[2, 4], &*collect_ready::<Vec<_>, _>(&mut out_recv));
+}
@@ -60,6 +60,11 @@ impl HydroflowCrate {
         }
     }
 
+    /// The host that the crate will be deployed on.
+    pub fn host(&self) -> &Arc<dyn Host> {
+        &self.on
+    }
+
     /// Sets the target to be a binary with the given name,
     /// equivalent to `cargo run --bin <name>`.
     pub fn bin(mut self, bin: impl Into<String>) -> Self {
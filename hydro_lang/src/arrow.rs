@@ -0,0 +1,113 @@
+//! Conversion of per-tick batches into Apache Arrow [`RecordBatch`]es, for feeding Hydro
+//! output into Arrow-based tools. See [`crate::Stream::to_arrow`].
+
+pub use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{ArrowError, SchemaRef};
+pub use {arrow_array, arrow_schema};
+
+/// A type whose values can be laid out as the rows of an Arrow [`RecordBatch`], with one
+/// column per field.
+///
+/// # Example
+/// ```rust
+/// use std::sync::Arc;
+///
+/// use hydro_lang::arrow::arrow_array::{ArrayRef, Int64Array, StringArray};
+/// use hydro_lang::arrow::arrow_schema::{DataType, Field, Schema, SchemaRef};
+/// use hydro_lang::arrow::ArrowSerialize;
+///
+/// struct Reading {
+///     sensor: String,
+///     value: i64,
+/// }
+///
+/// impl ArrowSerialize for Reading {
+///     fn schema() -> SchemaRef {
+///         Arc::new(Schema::new(vec![
+///             Field::new("sensor", DataType::Utf8, false),
+///             Field::new("value", DataType::Int64, false),
+///         ]))
+///     }
+///
+///     fn columns(rows: &[Self]) -> Vec<ArrayRef> {
+///         vec![
+///             Arc::new(StringArray::from_iter_values(
+///                 rows.iter().map(|r| r.sensor.as_str()),
+///             )),
+///             Arc::new(Int64Array::from_iter_values(rows.iter().map(|r| r.value))),
+///         ]
+///     }
+/// }
+/// ```
+pub trait ArrowSerialize: Sized {
+    /// The schema of the record batches, with one field per column returned by
+    /// [`ArrowSerialize::columns`].
+    fn schema() -> SchemaRef;
+
+    /// Lays out `rows` as columns, in the order of the fields in [`ArrowSerialize::schema`].
+    fn columns(rows: &[Self]) -> Vec<ArrayRef>;
+
+    /// Converts `rows` into a [`RecordBatch`], which fails if the columns do not match the
+    /// schema.
+    fn to_record_batch(rows: &[Self]) -> Result<RecordBatch, ArrowError> {
+        RecordBatch::try_new(Self::schema(), Self::columns(rows))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{ArrayRef, Int64Array, StringArray};
+    use arrow_schema::{DataType, Field, Schema, SchemaRef};
+
+    use super::ArrowSerialize;
+
+    struct Reading {
+        sensor: String,
+        value: i64,
+    }
+
+    impl ArrowSerialize for Reading {
+        fn schema() -> SchemaRef {
+            Arc::new(Schema::new(vec![
+                Field::new("sensor", DataType::Utf8, false),
+                Field::new("value", DataType::Int64, false),
+            ]))
+        }
+
+        fn columns(rows: &[Self]) -> Vec<ArrayRef> {
+            vec![
+                Arc::new(StringArray::from_iter_values(
+                    rows.iter().map(|r| r.sensor.as_str()),
+                )),
+                Arc::new(Int64Array::from_iter_values(rows.iter().map(|r| r.value))),
+            ]
+        }
+    }
+
+    #[test]
+    fn to_record_batch_has_schema() {
+        let rows = vec![
+            Reading {
+                sensor: "a".to_string(),
+                value: 1,
+            },
+            Reading {
+                sensor: "b".to_string(),
+                value: 2,
+            },
+        ];
+
+        let batch = Reading::to_record_batch(&rows).unwrap();
+        assert_eq!(batch.schema(), Reading::schema());
+        assert_eq!(batch.num_rows(), 2);
+
+        let values = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(values.values(), &[1, 2]);
+    }
+}
@@ -1,12 +1,14 @@
-use std::collections::{BTreeMap, HashMap};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::marker::PhantomData;
+use std::rc::Rc;
 
-use dfir_lang::graph::{eliminate_extra_unions_tees, DfirGraph};
+use dfir_lang::graph::{eliminate_extra_unions_tees, partition_graph, DfirGraph};
 
 use super::compiled::CompiledFlow;
 use super::deploy::{DeployFlow, DeployResult};
 use crate::deploy::{ClusterSpec, Deploy, ExternalSpec, IntoProcessSpec, LocalDeploy};
-use crate::ir::HydroLeaf;
+use crate::ir::{check_unpersist_eliminated, HydroLeaf, HydroNode, SeenTees, SurvivingUnpersist};
 use crate::location::{Cluster, ExternalProcess, Process};
 use crate::staging_util::Invariant;
 
@@ -14,6 +16,11 @@ pub struct BuiltFlow<'a> {
     pub(super) ir: Vec<HydroLeaf>,
     pub(super) processes: Vec<usize>,
     pub(super) clusters: Vec<usize>,
+    pub(super) cluster_placements: HashMap<usize, HashMap<u32, usize>>,
+    /// Errors found while building the flow, which are reported when it is compiled.
+    pub(super) build_errors: Vec<String>,
+    pub(super) max_strata: Option<usize>,
+    pub(super) operator_names: bool,
     pub(super) used: bool,
 
     pub(super) _phantom: Invariant<'a>,
@@ -27,7 +34,60 @@ impl Drop for BuiltFlow<'_> {
     }
 }
 
-pub(crate) fn build_inner(ir: &mut Vec<HydroLeaf>) -> BTreeMap<usize, DfirGraph> {
+/// An error that prevents a flow from being compiled, see [`DeployFlow::try_compile`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CompileError {
+    /// Errors found while the flow was built, such as a statically exceeded
+    /// [`crate::Stream::cross_product_capped`] cap.
+    Build(Vec<String>),
+    /// An [`HydroNode::Unpersist`] marker was not optimized away.
+    SurvivingUnpersist(SurvivingUnpersist),
+    /// A location has more strata than allowed by [`crate::FlowBuilder::with_max_strata`].
+    MaxStrata {
+        /// The id of the location.
+        location: usize,
+        /// Describes the deepest chain of strata.
+        message: String,
+    },
+}
+
+impl std::fmt::Display for CompileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompileError::Build(errors) => write!(f, "{}", errors.join("\n")),
+            CompileError::SurvivingUnpersist(surviving) => write!(f, "{}", surviving),
+            CompileError::MaxStrata { location, message } => {
+                write!(f, "Location {}: {}", location, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CompileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CompileError::SurvivingUnpersist(surviving) => Some(surviving),
+            _ => None,
+        }
+    }
+}
+
+pub(crate) fn build_inner(
+    ir: &mut Vec<HydroLeaf>,
+    build_errors: &[String],
+    max_strata: Option<usize>,
+    operator_names: bool,
+) -> Result<BTreeMap<usize, DfirGraph>, CompileError> {
+    if !build_errors.is_empty() {
+        return Err(CompileError::Build(build_errors.to_vec()));
+    }
+
+    check_unpersist_eliminated(ir).map_err(CompileError::SurvivingUnpersist)?;
+
+    if operator_names {
+        *ir = crate::rewrites::operator_names::operator_names(std::mem::take(ir));
+    }
+
     let mut builders = BTreeMap::new();
     let mut built_tees = HashMap::new();
     let mut next_stmt_id = 0;
@@ -40,7 +100,18 @@ pub(crate) fn build_inner(ir: &mut Vec<HydroLeaf>) -> BTreeMap<usize, DfirGraph>
         .map(|(k, v)| {
             let (mut flat_graph, _, _) = v.build();
             eliminate_extra_unions_tees(&mut flat_graph);
-            (k, flat_graph)
+            if let Some(max_strata) = max_strata {
+                // Partitioning consumes the graph, so check a copy.
+                let partitioned_graph = partition_graph(flat_graph.clone())
+                    .expect("Failed to partition (cycle detected).");
+                if let Err(diagnostic) = partitioned_graph.check_max_strata(max_strata) {
+                    return Err(CompileError::MaxStrata {
+                        location: k,
+                        message: diagnostic.message,
+                    });
+                }
+            }
+            Ok((k, flat_graph))
         })
         .collect()
 }
@@ -50,17 +121,69 @@ impl<'a> BuiltFlow<'a> {
         &self.ir
     }
 
+    /// Counts the leaves and nodes of each kind in the IR, keyed by [`HydroLeaf::kind_name`]
+    /// and [`HydroNode::kind_name`], such as to confirm that a rewrite fired. A shared
+    /// [`HydroNode::Tee`] is counted once, however many times it is referenced.
+    pub fn ir_histogram(&mut self) -> HashMap<&'static str, usize> {
+        type Counts = (
+            HashMap<&'static str, usize>,
+            HashSet<*const RefCell<HydroNode>>,
+        );
+        fn count_node(node: &mut HydroNode, (histogram, seen_tees): &mut Counts) {
+            if let HydroNode::Tee { inner } = node {
+                if !seen_tees.insert(Rc::as_ptr(&inner.0)) {
+                    return;
+                }
+            }
+            *histogram.entry(node.kind_name()).or_default() += 1;
+        }
+
+        let mut counts = Counts::default();
+        let mut seen_tees = SeenTees::default();
+        self.ir = std::mem::take(&mut self.ir)
+            .into_iter()
+            .map(|leaf| {
+                *counts.0.entry(leaf.kind_name()).or_default() += 1;
+                leaf.transform_children(
+                    |n, s| n.transform_bottom_up(count_node, s, &mut counts),
+                    &mut seen_tees,
+                )
+            })
+            .collect();
+        counts.0
+    }
+
     pub fn optimize_with(mut self, f: impl FnOnce(Vec<HydroLeaf>) -> Vec<HydroLeaf>) -> Self {
         self.used = true;
         BuiltFlow {
             ir: f(std::mem::take(&mut self.ir)),
             processes: std::mem::take(&mut self.processes),
             clusters: std::mem::take(&mut self.clusters),
+            cluster_placements: std::mem::take(&mut self.cluster_placements),
+            build_errors: std::mem::take(&mut self.build_errors),
+            max_strata: self.max_strata,
+            operator_names: self.operator_names,
             used: false,
             _phantom: PhantomData,
         }
     }
 
+    /// Fuses each chain of `map`, `filter_map`, and `flat_map` operators into a single
+    /// operator, see [`crate::rewrites::map_fusion`]. This is not part of the default
+    /// optimizations, and should run after [`crate::rewrites::persist_pullup`] so that chains
+    /// are not split by `Persist` markers.
+    pub fn with_map_fusion(self) -> Self {
+        self.optimize_with(crate::rewrites::map_fusion::map_fusion)
+    }
+
+    /// Moves filters ahead of the maps created with [`crate::Stream::map_preserving_filters`],
+    /// see [`crate::rewrites::filter_pushdown`]. This is not part of the default optimizations,
+    /// and should run after [`crate::rewrites::persist_pullup`] so that filters are not blocked
+    /// by `Persist` markers.
+    pub fn with_filter_pushdown(self) -> Self {
+        self.optimize_with(crate::rewrites::filter_pushdown::filter_pushdown)
+    }
+
     pub fn with_default_optimize<D: LocalDeploy<'a>>(self) -> DeployFlow<'a, D> {
         self.optimize_with(crate::rewrites::persist_pullup::persist_pullup)
             .into_deploy()
@@ -91,6 +214,10 @@ impl<'a> BuiltFlow<'a> {
             nodes: processes,
             clusters,
             externals: HashMap::new(),
+            cluster_placements: std::mem::take(&mut self.cluster_placements),
+            build_errors: std::mem::take(&mut self.build_errors),
+            max_strata: self.max_strata,
+            operator_names: self.operator_names,
             used: false,
             _phantom: PhantomData,
         }
@@ -128,6 +255,19 @@ impl<'a> BuiltFlow<'a> {
         self.into_deploy::<D>().compile_no_network()
     }
 
+    pub fn try_compile<D: Deploy<'a>>(
+        self,
+        env: &D::CompileEnv,
+    ) -> Result<CompiledFlow<'a, D::GraphId>, CompileError> {
+        self.into_deploy::<D>().try_compile(env)
+    }
+
+    pub fn try_compile_no_network<D: LocalDeploy<'a>>(
+        self,
+    ) -> Result<CompiledFlow<'a, D::GraphId>, CompileError> {
+        self.into_deploy::<D>().try_compile_no_network()
+    }
+
     pub fn deploy<D: Deploy<'a, CompileEnv = ()>>(
         self,
         env: &mut D::InstantiateEnv,
@@ -1,7 +1,8 @@
 use std::collections::BTreeMap;
 use std::marker::PhantomData;
 
-use dfir_lang::graph::{partition_graph, DfirGraph};
+use dfir_lang::diagnostic::Diagnostic;
+use dfir_lang::graph::{partition_graph, DfirGraph, GraphNode};
 use dfir_rs::scheduled::graph::Dfir;
 use proc_macro2::TokenStream;
 use quote::quote;
@@ -24,6 +25,39 @@ impl<ID> CompiledFlow<'_, ID> {
     pub fn take_ir(self) -> BTreeMap<usize, DfirGraph> {
         self.hydroflow_ir
     }
+
+    /// Lists the names of the operators generated for each location (e.g. `map`, `dest_sink`),
+    /// keyed by the location's ID. This is useful for checking that a location was assigned
+    /// the operators you expect.
+    pub fn operators_by_location(&self) -> BTreeMap<usize, Vec<String>> {
+        self.hydroflow_ir
+            .iter()
+            .map(|(location_id, graph)| {
+                let operators = graph
+                    .nodes()
+                    .filter_map(|(_node_id, node)| match node {
+                        GraphNode::Operator(op) => Some(op.name_string()),
+                        _ => None,
+                    })
+                    .collect();
+                (*location_id, operators)
+            })
+            .collect()
+    }
+
+    /// Counts the subgraphs that the DFIR graph of each location is partitioned into, keyed by
+    /// the location's ID. Returns the partitioning error if any location's graph cannot be
+    /// partitioned (e.g. because of a cycle).
+    pub fn subgraph_count(&self) -> Result<BTreeMap<usize, usize>, Diagnostic> {
+        self.hydroflow_ir
+            .iter()
+            .map(|(location_id, flat_graph)| {
+                // Partitioning consumes the graph, so count on a copy.
+                let partitioned_graph = partition_graph(flat_graph.clone())?;
+                Ok((*location_id, partitioned_graph.subgraph_ids().count()))
+            })
+            .collect()
+    }
 }
 
 impl<'a> CompiledFlow<'a, usize> {
@@ -4,13 +4,13 @@ use std::marker::PhantomData;
 use std::pin::Pin;
 
 use dfir_rs::bytes::Bytes;
-use dfir_rs::futures::{Sink, Stream};
+use dfir_rs::futures::{Sink, SinkExt, Stream, StreamExt};
 use proc_macro2::Span;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 use stageleft::QuotedWithContext;
 
-use super::built::build_inner;
+use super::built::{build_inner, CompileError};
 use super::compiled::CompiledFlow;
 use crate::deploy::{
     ClusterSpec, Deploy, ExternalSpec, IntoProcessSpec, LocalDeploy, Node, ProcessSpec,
@@ -20,7 +20,7 @@ use crate::ir::HydroLeaf;
 use crate::location::external_process::{
     ExternalBincodeSink, ExternalBincodeStream, ExternalBytesPort,
 };
-use crate::location::{Cluster, ExternalProcess, Location, LocationId, Process};
+use crate::location::{Cluster, ClusterId, ExternalProcess, Location, LocationId, Process};
 use crate::staging_util::Invariant;
 
 pub struct DeployFlow<'a, D: LocalDeploy<'a>> {
@@ -28,6 +28,16 @@ pub struct DeployFlow<'a, D: LocalDeploy<'a>> {
     pub(super) nodes: HashMap<usize, D::Process>,
     pub(super) externals: HashMap<usize, D::ExternalProcess>,
     pub(super) clusters: HashMap<usize, D::Cluster>,
+    /// Pinned host IDs for cluster members, keyed by cluster ID and then member ID.
+    pub(super) cluster_placements: HashMap<usize, HashMap<u32, usize>>,
+    /// Errors found while building the flow, which are reported when it is compiled.
+    pub(super) build_errors: Vec<String>,
+    /// The highest stratum allowed in each location's graph, see
+    /// [`crate::FlowBuilder::with_max_strata`].
+    pub(super) max_strata: Option<usize>,
+    /// Whether operators are named after their kind, see
+    /// [`crate::FlowBuilder::with_operator_names`].
+    pub(super) operator_names: bool,
     pub(super) used: bool,
 
     pub(super) _phantom: Invariant<'a, D>,
@@ -77,19 +87,41 @@ impl<'a, D: LocalDeploy<'a>> DeployFlow<'a, D> {
         self
     }
 
-    pub fn compile_no_network(mut self) -> CompiledFlow<'a, D::GraphId> {
+    pub fn compile_no_network(self) -> CompiledFlow<'a, D::GraphId> {
+        self.try_compile_no_network()
+            .unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Like [`DeployFlow::compile_no_network`], but returns an error instead of panicking if
+    /// the flow cannot be compiled.
+    pub fn try_compile_no_network(mut self) -> Result<CompiledFlow<'a, D::GraphId>, CompileError> {
         self.used = true;
 
-        CompiledFlow {
-            hydroflow_ir: build_inner(&mut self.ir),
+        Ok(CompiledFlow {
+            hydroflow_ir: build_inner(
+                &mut self.ir,
+                &self.build_errors,
+                self.max_strata,
+                self.operator_names,
+            )?,
             extra_stmts: BTreeMap::new(),
             _phantom: PhantomData,
-        }
+        })
     }
 }
 
 impl<'a, D: Deploy<'a>> DeployFlow<'a, D> {
-    pub fn compile(mut self, env: &D::CompileEnv) -> CompiledFlow<'a, D::GraphId> {
+    pub fn compile(self, env: &D::CompileEnv) -> CompiledFlow<'a, D::GraphId> {
+        self.try_compile(env)
+            .unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Like [`DeployFlow::compile`], but returns an error instead of panicking if the flow
+    /// cannot be compiled.
+    pub fn try_compile(
+        mut self,
+        env: &D::CompileEnv,
+    ) -> Result<CompiledFlow<'a, D::GraphId>, CompileError> {
         self.used = true;
 
         let mut seen_tees: HashMap<_, _> = HashMap::new();
@@ -108,11 +140,16 @@ impl<'a, D: Deploy<'a>> DeployFlow<'a, D> {
 
         let extra_stmts = self.extra_stmts(env);
 
-        CompiledFlow {
-            hydroflow_ir: build_inner(&mut flow_state_networked),
+        Ok(CompiledFlow {
+            hydroflow_ir: build_inner(
+                &mut flow_state_networked,
+                &self.build_errors,
+                self.max_strata,
+                self.operator_names,
+            )?,
             extra_stmts,
             _phantom: PhantomData,
-        }
+        })
     }
 
     fn extra_stmts(&self, env: &<D as Deploy<'a>>::CompileEnv) -> BTreeMap<usize, Vec<syn::Stmt>> {
@@ -167,7 +204,13 @@ impl<'a, D: Deploy<'a, CompileEnv = ()>> DeployFlow<'a, D> {
             })
             .collect();
 
-        let mut compiled = build_inner(&mut flow_state_networked);
+        let mut compiled = build_inner(
+            &mut flow_state_networked,
+            &self.build_errors,
+            self.max_strata,
+            self.operator_names,
+        )
+        .unwrap_or_else(|error| panic!("{}", error));
         let mut extra_stmts = self.extra_stmts(&());
         let mut meta = D::Meta::default();
 
@@ -192,6 +235,9 @@ impl<'a, D: Deploy<'a, CompileEnv = ()>> DeployFlow<'a, D> {
                 .into_iter()
                 .filter_map(|(cluster_id, cluster)| {
                     if let Some(ir) = compiled.remove(&cluster_id) {
+                        if let Some(placement) = self.cluster_placements.get(&cluster_id) {
+                            D::pin_cluster_placement(&cluster, placement);
+                        }
                         cluster.instantiate(
                             env,
                             &mut meta,
@@ -301,6 +347,28 @@ impl<'a, D: Deploy<'a>> DeployResult<'a, D> {
             .await
     }
 
+    /// Connects to a port created by [`ExternalProcess::source_external_bincode`] targeting a
+    /// cluster, where each message is sent to the member with the paired [`ClusterId`].
+    pub async fn connect_sink_bincode_demux<
+        C: 'static,
+        T: Serialize + DeserializeOwned + 'static,
+    >(
+        &self,
+        port: ExternalBincodeSink<(ClusterId<C>, T)>,
+    ) -> Pin<Box<dyn Sink<(ClusterId<C>, T), Error = Error>>> {
+        let sink = self
+            .externals
+            .get(&port.process_id)
+            .unwrap()
+            .as_bincode_demux_sink(port.port_id)
+            .await;
+        Box::pin(
+            sink.with(
+                |(id, item): (ClusterId<C>, T)| async move { Ok::<_, Error>((id.raw_id, item)) },
+            ),
+        )
+    }
+
     pub async fn connect_source_bytes(
         &self,
         port: ExternalBytesPort,
@@ -322,4 +390,22 @@ impl<'a, D: Deploy<'a>> DeployResult<'a, D> {
             .as_bincode_source(port.port_id)
             .await
     }
+
+    /// Connects to a port created by [`crate::Stream::send_bincode_external`] from a cluster,
+    /// where each message is tagged with the [`ClusterId`] of the member that sent it.
+    pub async fn connect_source_bincode_tagged<
+        C: 'static,
+        T: Serialize + DeserializeOwned + 'static,
+    >(
+        &self,
+        port: ExternalBincodeStream<(ClusterId<C>, T)>,
+    ) -> Pin<Box<dyn Stream<Item = (ClusterId<C>, T)>>> {
+        let source = self
+            .externals
+            .get(&port.process_id)
+            .unwrap()
+            .as_bincode_tagged_source(port.port_id)
+            .await;
+        Box::pin(source.map(|(id, item)| (ClusterId::from_raw(id), item)))
+    }
 }
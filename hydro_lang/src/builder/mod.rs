@@ -12,7 +12,7 @@ use stageleft::*;
 #[cfg(feature = "build")]
 use crate::deploy::{ClusterSpec, Deploy, ExternalSpec, IntoProcessSpec, LocalDeploy};
 use crate::ir::HydroLeaf;
-use crate::location::{Cluster, ExternalProcess, Process};
+use crate::location::{Cluster, ClusterId, ExternalProcess, Process};
 use crate::staging_util::Invariant;
 
 #[cfg(feature = "build")]
@@ -36,6 +36,10 @@ pub struct FlowStateInner {
 
     /// Counters for clock IDs.
     pub(crate) next_clock_id: usize,
+    /// Errors found while building the flow, such as a
+    /// [`crate::Stream::cross_product_capped`] that statically exceeds its cap. These are
+    /// reported when the flow is compiled.
+    pub(crate) build_errors: Vec<String>,
 }
 
 pub type FlowState = Rc<RefCell<FlowStateInner>>;
@@ -46,6 +50,9 @@ pub struct FlowBuilder<'a> {
     flow_state: FlowState,
     nodes: RefCell<Vec<usize>>,
     clusters: RefCell<Vec<usize>>,
+    cluster_placements: RefCell<HashMap<usize, HashMap<u32, usize>>>,
+    max_strata: Option<usize>,
+    operator_names: bool,
 
     next_node_id: RefCell<usize>,
 
@@ -86,9 +93,13 @@ impl<'a> FlowBuilder<'a> {
                 next_external_out: 0,
                 cycle_counts: HashMap::new(),
                 next_clock_id: 0,
+                build_errors: vec![],
             })),
             nodes: RefCell::new(vec![]),
             clusters: RefCell::new(vec![]),
+            cluster_placements: RefCell::new(HashMap::new()),
+            max_strata: None,
+            operator_names: false,
             next_node_id: RefCell::new(0),
             finalized: false,
             _phantom: PhantomData,
@@ -98,11 +109,16 @@ impl<'a> FlowBuilder<'a> {
     #[cfg(feature = "build")]
     pub fn finalize(mut self) -> built::BuiltFlow<'a> {
         self.finalized = true;
+        let mut flow_state = self.flow_state.borrow_mut();
 
         built::BuiltFlow {
-            ir: self.flow_state.borrow_mut().leaves.take().unwrap(),
+            ir: flow_state.leaves.take().unwrap(),
             processes: self.nodes.replace(vec![]),
             clusters: self.clusters.replace(vec![]),
+            cluster_placements: self.cluster_placements.take(),
+            build_errors: std::mem::take(&mut flow_state.build_errors),
+            max_strata: self.max_strata,
+            operator_names: self.operator_names,
             used: false,
             _phantom: PhantomData,
         }
@@ -113,6 +129,28 @@ impl<'a> FlowBuilder<'a> {
         self.finalize().with_default_optimize()
     }
 
+    /// Guards against accidentally deep pipelines by checking, when the flow is compiled, that
+    /// the DFIR graph of every location is partitioned into strata numbered at most
+    /// `max_strata`. Deeply stratified graphs run many sequential phases in each tick, which
+    /// usually indicates a performance problem.
+    ///
+    /// # Panics
+    /// Compiling or deploying the flow panics with a diagnostic naming the subgraphs in the
+    /// deepest chain if the limit is exceeded.
+    pub fn with_max_strata(mut self, max_strata: usize) -> Self {
+        self.max_strata = Some(max_strata);
+        self
+    }
+
+    /// Names the idents generated for operators after their kind when the flow is compiled,
+    /// such as `map_42` instead of `stream_42`, so that the generated DFIR and its diagnostics
+    /// are easier to read. Operators labeled with [`crate::Stream::named`] keep their label.
+    /// See [`crate::rewrites::operator_names`].
+    pub fn with_operator_names(mut self) -> Self {
+        self.operator_names = true;
+        self
+    }
+
     #[cfg(feature = "build")]
     pub fn optimize_with(
         self,
@@ -121,6 +159,41 @@ impl<'a> FlowBuilder<'a> {
         self.finalize().optimize_with(f)
     }
 
+    /// Finalizes the flow with the default optimizations, and then fuses each chain of `map`,
+    /// `filter_map`, and `flat_map` operators into a single operator. See
+    /// [`built::BuiltFlow::with_map_fusion`].
+    #[cfg(feature = "build")]
+    pub fn with_map_fusion(self) -> built::BuiltFlow<'a> {
+        self.optimize_with(crate::rewrites::persist_pullup::persist_pullup)
+            .with_map_fusion()
+    }
+
+    /// Finalizes the flow with the default optimizations, and then moves filters ahead of the
+    /// maps created with [`crate::Stream::map_preserving_filters`]. See
+    /// [`built::BuiltFlow::with_filter_pushdown`].
+    #[cfg(feature = "build")]
+    pub fn with_filter_pushdown(self) -> built::BuiltFlow<'a> {
+        self.optimize_with(crate::rewrites::persist_pullup::persist_pullup)
+            .with_filter_pushdown()
+    }
+
+    /// Finalizes the flow with the default optimizations, and instruments it to capture the IR
+    /// and the first `max_samples` elements of each operator's output to the file at `path`.
+    ///
+    /// The IR is written immediately, and samples are appended by the deployed processes as
+    /// they run. Elements that do not implement [`std::fmt::Debug`] are recorded by type name.
+    #[cfg(feature = "build")]
+    pub fn with_debug_capture(
+        self,
+        path: impl AsRef<std::path::Path>,
+        max_samples: usize,
+    ) -> built::BuiltFlow<'a> {
+        self.optimize_with(crate::rewrites::persist_pullup::persist_pullup)
+            .optimize_with(|ir| {
+                crate::rewrites::debug_capture::debug_capture(ir, path.as_ref(), max_samples)
+            })
+    }
+
     pub fn flow_state(&self) -> &FlowState {
         &self.flow_state
     }
@@ -167,6 +240,29 @@ impl<'a> FlowBuilder<'a> {
         }
     }
 
+    /// Pins members of `cluster` to specific hosts, given as pairs of a member ID and the ID of
+    /// the host it should run on. This makes the member-to-host mapping reproducible, for
+    /// example across benchmark runs.
+    ///
+    /// Each pinned member is created from the first unused cluster spec on its host, and
+    /// unpinned members take the remaining specs in order. The resolved mapping is available at
+    /// runtime in [`crate::deploy_runtime::HydroflowPlusMeta::cluster_hosts`].
+    ///
+    /// # Panics
+    /// Deploying panics if a pinned member ID is out of range or no unused spec is on the
+    /// requested host, or if the deployment backend does not support pinning.
+    pub fn pin_cluster_placement<C>(
+        &self,
+        cluster: &Cluster<C>,
+        placement: impl IntoIterator<Item = (ClusterId<C>, usize)>,
+    ) {
+        self.cluster_placements
+            .borrow_mut()
+            .entry(cluster.id)
+            .or_default()
+            .extend(placement.into_iter().map(|(id, host)| (id.raw_id, host)));
+    }
+
     #[cfg(feature = "build")]
     pub fn with_process<P, D: LocalDeploy<'a>>(
         self,
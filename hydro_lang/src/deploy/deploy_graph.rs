@@ -8,8 +8,11 @@ use std::sync::Arc;
 
 use dfir_lang::graph::DfirGraph;
 use dfir_rs::bytes::Bytes;
+use dfir_rs::futures::stream::select_all;
 use dfir_rs::futures::{Sink, SinkExt, Stream, StreamExt};
-use dfir_rs::util::deploy::{ConnectedSink, ConnectedSource};
+use dfir_rs::util::deploy::{
+    ConnectedDemux, ConnectedDirect, ConnectedSink, ConnectedSource, ConnectedTagged,
+};
 use hydro_deploy::custom_service::CustomClientPort;
 use hydro_deploy::hydroflow_crate::ports::{
     DemuxSink, HydroflowSink, HydroflowSource, TaggedSource,
@@ -35,7 +38,7 @@ impl<'a> Deploy<'a> for HydroDeploy {
     type Process = DeployNode;
     type Cluster = DeployCluster;
     type ExternalProcess = DeployExternal;
-    type Meta = HashMap<usize, Vec<u32>>;
+    type Meta = HydroflowPlusMeta;
     type GraphId = ();
     type Port = String;
     type ExternalRawPort = CustomClientPort;
@@ -317,6 +320,66 @@ impl<'a> Deploy<'a> for HydroDeploy {
         })
     }
 
+    fn e2m_source(
+        _compile_env: &Self::CompileEnv,
+        _p1: &Self::ExternalProcess,
+        p1_port: &Self::Port,
+        _c2: &Self::Cluster,
+        c2_port: &Self::Port,
+    ) -> syn::Expr {
+        let p1_port = p1_port.as_str();
+        let c2_port = c2_port.as_str();
+        deploy_e2m(
+            RuntimeData::new("__hydro_lang_trybuild_cli"),
+            p1_port,
+            c2_port,
+        )
+    }
+
+    fn e2m_connect(
+        p1: &Self::ExternalProcess,
+        p1_port: &Self::Port,
+        c2: &Self::Cluster,
+        c2_port: &Self::Port,
+    ) -> Box<dyn FnOnce()> {
+        let p1 = p1.clone();
+        let p1_port = p1_port.clone();
+        let c2 = c2.clone();
+        let c2_port = c2_port.clone();
+
+        Box::new(move || {
+            let self_underlying_borrow = p1.underlying.borrow();
+            let self_underlying = self_underlying_borrow.as_ref().unwrap();
+            let source_port = self_underlying
+                .try_read()
+                .unwrap()
+                .declare_client(self_underlying);
+
+            let recipient_port = DemuxSink {
+                demux: c2
+                    .members
+                    .borrow()
+                    .iter()
+                    .enumerate()
+                    .map(|(id, c)| {
+                        let n = c.underlying.try_read().unwrap();
+                        (
+                            id as u32,
+                            Arc::new(n.get_port(c2_port.clone(), &c.underlying))
+                                as Arc<dyn HydroflowSink + 'static>,
+                        )
+                    })
+                    .collect(),
+            };
+
+            source_port.send_to(&recipient_port);
+
+            p1.client_ports
+                .borrow_mut()
+                .insert(p1_port.clone(), source_port);
+        })
+    }
+
     fn o2e_sink(
         _compile_env: &Self::CompileEnv,
         _p1: &Self::Process,
@@ -367,6 +430,65 @@ impl<'a> Deploy<'a> for HydroDeploy {
         })
     }
 
+    fn m2e_sink(
+        _compile_env: &Self::CompileEnv,
+        _c1: &Self::Cluster,
+        c1_port: &Self::Port,
+        _p2: &Self::ExternalProcess,
+        p2_port: &Self::Port,
+    ) -> syn::Expr {
+        let c1_port = c1_port.as_str();
+        let p2_port = p2_port.as_str();
+        deploy_m2e(
+            RuntimeData::new("__hydro_lang_trybuild_cli"),
+            c1_port,
+            p2_port,
+        )
+    }
+
+    fn m2e_connect(
+        c1: &Self::Cluster,
+        c1_port: &Self::Port,
+        p2: &Self::ExternalProcess,
+        p2_port: &Self::Port,
+    ) -> Box<dyn FnOnce()> {
+        let c1 = c1.clone();
+        let c1_port = c1_port.clone();
+        let p2 = p2.clone();
+        let p2_port = p2_port.clone();
+
+        Box::new(move || {
+            let other_underlying_borrow = p2.underlying.borrow();
+            let other_underlying = other_underlying_borrow.as_ref().unwrap();
+
+            let mut recipient_ports = Vec::new();
+            for (i, node) in c1.members.borrow().iter().enumerate() {
+                let source_port = node
+                    .underlying
+                    .try_read()
+                    .unwrap()
+                    .get_port(c1_port.clone(), &node.underlying);
+
+                let recipient_port = other_underlying
+                    .try_read()
+                    .unwrap()
+                    .declare_client(other_underlying);
+
+                TaggedSource {
+                    source: Arc::new(source_port),
+                    tag: i as u32,
+                }
+                .send_to(&recipient_port);
+
+                recipient_ports.push(recipient_port);
+            }
+
+            p2.member_client_ports
+                .borrow_mut()
+                .insert(p2_port.clone(), recipient_ports);
+        })
+    }
+
     fn cluster_ids(
         _env: &Self::CompileEnv,
         of_cluster: usize,
@@ -377,6 +499,10 @@ impl<'a> Deploy<'a> for HydroDeploy {
     fn cluster_self_id(_env: &Self::CompileEnv) -> impl QuotedWithContext<'a, u32, ()> + Copy + 'a {
         cluster_self_id(RuntimeData::new("__hydro_lang_trybuild_cli"))
     }
+
+    fn pin_cluster_placement(cluster: &Self::Cluster, placement: &HashMap<u32, usize>) {
+        cluster.placement.borrow_mut().clone_from(placement);
+    }
 }
 
 pub trait DeployCrateWrapper {
@@ -509,6 +635,8 @@ pub struct DeployExternal {
     host: Arc<dyn Host>,
     underlying: Rc<RefCell<Option<Arc<RwLock<CustomService>>>>>,
     client_ports: Rc<RefCell<HashMap<String, CustomClientPort>>>,
+    /// Client ports receiving from each member of a cluster, indexed by member ID.
+    member_client_ports: Rc<RefCell<HashMap<String, Vec<CustomClientPort>>>>,
     allocated_ports: Rc<RefCell<HashMap<usize, String>>>,
 }
 
@@ -519,6 +647,13 @@ impl DeployExternal {
             .remove(self.allocated_ports.borrow().get(&key).unwrap())
             .unwrap()
     }
+
+    fn take_member_ports(&self, key: usize) -> Vec<CustomClientPort> {
+        self.member_client_ports
+            .borrow_mut()
+            .remove(self.allocated_ports.borrow().get(&key).unwrap())
+            .unwrap()
+    }
 }
 
 impl<'a> RegisterPort<'a, HydroDeploy> for DeployExternal {
@@ -556,6 +691,25 @@ impl<'a> RegisterPort<'a, HydroDeploy> for DeployExternal {
         }
     }
 
+    fn as_bincode_demux_sink<T: Serialize + 'static>(
+        &self,
+        key: usize,
+    ) -> impl Future<Output = Pin<Box<dyn Sink<(u32, T), Error = Error>>>> + 'a {
+        let port = self.raw_port(key);
+        async move {
+            let sink = port
+                .server_port()
+                .await
+                .instantiate()
+                .connect::<ConnectedDemux<ConnectedDirect>>()
+                .await
+                .into_sink();
+            Box::pin(sink.with(|(id, item)| async move {
+                Ok((id, bincode::serialize(&item).unwrap().into()))
+            })) as Pin<Box<dyn Sink<(u32, T), Error = Error>>>
+        }
+    }
+
     fn as_bytes_source(
         &self,
         key: usize,
@@ -578,11 +732,36 @@ impl<'a> RegisterPort<'a, HydroDeploy> for DeployExternal {
                 as Pin<Box<dyn Stream<Item = T>>>
         }
     }
+
+    fn as_bincode_tagged_source<T: DeserializeOwned + 'static>(
+        &self,
+        key: usize,
+    ) -> impl Future<Output = Pin<Box<dyn Stream<Item = (u32, T)>>>> + 'a {
+        let ports = self.take_member_ports(key);
+        async move {
+            let mut sources = Vec::new();
+            for port in ports {
+                sources.push(
+                    port.server_port()
+                        .await
+                        .instantiate()
+                        .connect::<ConnectedTagged<ConnectedDirect>>()
+                        .await
+                        .into_source(),
+                );
+            }
+
+            Box::pin(select_all(sources).map(|item| {
+                let (id, b) = item.unwrap();
+                (id, bincode::deserialize(&b).unwrap())
+            })) as Pin<Box<dyn Stream<Item = (u32, T)>>>
+        }
+    }
 }
 
 impl Node for DeployExternal {
     type Port = String;
-    type Meta = HashMap<usize, Vec<u32>>;
+    type Meta = HydroflowPlusMeta;
     type InstantiateEnv = Deployment;
 
     fn next_port(&self) -> Self::Port {
@@ -614,6 +793,7 @@ impl ExternalSpec<'_, HydroDeploy> for Arc<dyn Host> {
             underlying: Rc::new(RefCell::new(None)),
             allocated_ports: Rc::new(RefCell::new(HashMap::new())),
             client_ports: Rc::new(RefCell::new(HashMap::new())),
+            member_client_ports: Rc::new(RefCell::new(HashMap::new())),
         }
     }
 }
@@ -626,6 +806,7 @@ impl<H: Host + 'static> ExternalSpec<'_, HydroDeploy> for Arc<H> {
             underlying: Rc::new(RefCell::new(None)),
             allocated_ports: Rc::new(RefCell::new(HashMap::new())),
             client_ports: Rc::new(RefCell::new(HashMap::new())),
+            member_client_ports: Rc::new(RefCell::new(HashMap::new())),
         }
     }
 }
@@ -651,7 +832,7 @@ impl DeployCrateWrapper for DeployNode {
 
 impl Node for DeployNode {
     type Port = String;
-    type Meta = HashMap<usize, Vec<u32>>;
+    type Meta = HydroflowPlusMeta;
     type InstantiateEnv = Deployment;
 
     fn next_port(&self) -> String {
@@ -665,9 +846,9 @@ impl Node for DeployNode {
         let underlying_node = self.underlying.borrow();
         let mut n = underlying_node.as_ref().unwrap().try_write().unwrap();
         n.update_meta(HydroflowPlusMeta {
-            clusters: meta.clone(),
             cluster_id: None,
             subgraph_id: self.id,
+            ..meta.clone()
         });
     }
 
@@ -694,6 +875,16 @@ impl Node for DeployNode {
 #[derive(Clone)]
 pub struct DeployClusterNode {
     underlying: Arc<RwLock<HydroflowCrateService>>,
+    meta: Option<HydroflowPlusMeta>,
+}
+
+impl DeployClusterNode {
+    /// The metadata this member is started with, available once the flow has been deployed.
+    pub fn meta(&self) -> &HydroflowPlusMeta {
+        self.meta
+            .as_ref()
+            .expect("Cluster member metadata is only available after deploying the flow")
+    }
 }
 
 impl DeployCrateWrapper for DeployClusterNode {
@@ -709,6 +900,8 @@ pub struct DeployCluster {
     cluster_spec: Rc<RefCell<Option<Vec<CrateOrTrybuild>>>>,
     members: Rc<RefCell<Vec<DeployClusterNode>>>,
     name_hint: Option<String>,
+    /// Pinned host IDs, keyed by member ID.
+    placement: Rc<RefCell<HashMap<u32, usize>>>,
 }
 
 impl DeployCluster {
@@ -719,7 +912,7 @@ impl DeployCluster {
 
 impl Node for DeployCluster {
     type Port = String;
-    type Meta = HashMap<usize, Vec<u32>>;
+    type Meta = HydroflowPlusMeta;
     type InstantiateEnv = Deployment;
 
     fn next_port(&self) -> String {
@@ -750,13 +943,32 @@ impl Node for DeployCluster {
             None
         };
 
-        let cluster_nodes = self
+        let mut specs = self
             .cluster_spec
             .borrow_mut()
             .take()
             .unwrap()
             .into_iter()
-            .map(|spec| {
+            .map(Some)
+            .collect::<Vec<_>>();
+        let host_ids = specs
+            .iter()
+            .map(|spec| match spec.as_ref().unwrap() {
+                CrateOrTrybuild::Crate(c) => c.host().id(),
+                CrateOrTrybuild::Trybuild(t) => t.host.id(),
+            })
+            .collect::<Vec<_>>();
+        let order = resolve_placement(&host_ids, &self.placement.borrow());
+
+        let cluster_nodes = order
+            .iter()
+            .enumerate()
+            .map(|(member_id, &spec_idx)| {
+                let mut spec = specs[spec_idx].take().unwrap();
+                if let CrateOrTrybuild::Trybuild(trybuild) = &mut spec {
+                    trybuild.cluster_idx = Some(member_id);
+                }
+
                 let service = match spec {
                     CrateOrTrybuild::Crate(c) => c,
                     CrateOrTrybuild::Trybuild(trybuild) => {
@@ -769,25 +981,73 @@ impl Node for DeployCluster {
                 env.add_service(service)
             })
             .collect::<Vec<_>>();
-        meta.insert(self.id, (0..(cluster_nodes.len() as u32)).collect());
+        meta.clusters
+            .insert(self.id, (0..(cluster_nodes.len() as u32)).collect());
+        meta.cluster_hosts
+            .insert(self.id, order.iter().map(|&i| host_ids[i]).collect());
         *self.members.borrow_mut() = cluster_nodes
             .into_iter()
-            .map(|n| DeployClusterNode { underlying: n })
+            .map(|n| DeployClusterNode {
+                underlying: n,
+                meta: None,
+            })
             .collect();
     }
 
     fn update_meta(&mut self, meta: &Self::Meta) {
-        for (cluster_id, node) in self.members.borrow().iter().enumerate() {
-            let mut n = node.underlying.try_write().unwrap();
-            n.update_meta(HydroflowPlusMeta {
-                clusters: meta.clone(),
+        for (cluster_id, node) in self.members.borrow_mut().iter_mut().enumerate() {
+            let member_meta = HydroflowPlusMeta {
                 cluster_id: Some(cluster_id as u32),
                 subgraph_id: self.id,
-            });
+                ..meta.clone()
+            };
+            node.underlying
+                .try_write()
+                .unwrap()
+                .update_meta(member_meta.clone());
+            node.meta = Some(member_meta);
         }
     }
 }
 
+/// Decides which spec (by index into `host_ids`) each cluster member is created from.
+///
+/// Members pinned to a host take the first unused spec on that host, and the remaining members
+/// take the remaining specs in order.
+fn resolve_placement(host_ids: &[usize], placement: &HashMap<u32, usize>) -> Vec<usize> {
+    let mut used = vec![false; host_ids.len()];
+    let mut order = vec![None; host_ids.len()];
+
+    let mut pinned = placement.iter().collect::<Vec<_>>();
+    pinned.sort();
+    for (&member_id, &host_id) in pinned {
+        let member_id = member_id as usize;
+        assert!(
+            member_id < host_ids.len(),
+            "Pinned placement for cluster member {} but the cluster only has {} members",
+            member_id,
+            host_ids.len()
+        );
+
+        let spec_idx = (0..host_ids.len())
+            .find(|&i| !used[i] && host_ids[i] == host_id)
+            .unwrap_or_else(|| {
+                panic!(
+                    "Cluster member {} is pinned to host {}, but no unused member spec is on that host",
+                    member_id, host_id
+                )
+            });
+        used[spec_idx] = true;
+        order[member_id] = Some(spec_idx);
+    }
+
+    let mut unused = (0..host_ids.len()).filter(|&i| !used[i]);
+    order
+        .into_iter()
+        .map(|spec_idx| spec_idx.unwrap_or_else(|| unused.next().unwrap()))
+        .collect()
+}
+
 #[derive(Clone)]
 pub struct DeployProcessSpec(HydroflowCrate);
 
@@ -839,6 +1099,7 @@ impl ClusterSpec<'_, HydroDeploy> for DeployClusterSpec {
             ))),
             members: Rc::new(RefCell::new(vec![])),
             name_hint: None,
+            placement: Rc::new(RefCell::new(HashMap::new())),
         }
     }
 }
@@ -862,6 +1123,7 @@ impl<T: Into<TrybuildHost>, I: IntoIterator<Item = T>> ClusterSpec<'_, HydroDepl
             ))),
             members: Rc::new(RefCell::new(vec![])),
             name_hint: Some(name_hint),
+            placement: Rc::new(RefCell::new(HashMap::new())),
         }
     }
 }
@@ -902,3 +1164,69 @@ fn create_trybuild_service(
 
     ret
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use hydro_deploy::{Deployment, Host, LocalhostHost};
+    use stageleft::q;
+
+    use super::resolve_placement;
+    use crate::location::Location;
+    use crate::{ClusterId, FlowBuilder};
+
+    #[test]
+    fn resolve_placement_honors_pins() {
+        let host_ids = [10, 10, 20, 30];
+        let placement = HashMap::from([(0, 30), (2, 10)]);
+        assert_eq!(resolve_placement(&host_ids, &placement), vec![3, 1, 0, 2]);
+        assert_eq!(
+            resolve_placement(&host_ids, &HashMap::new()),
+            vec![0, 1, 2, 3]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "no unused member spec is on that host")]
+    fn resolve_placement_panics_on_missing_host() {
+        resolve_placement(&[10, 20], &HashMap::from([(0, 20), (1, 20)]));
+    }
+
+    struct C1 {}
+
+    #[test]
+    fn pinned_cluster_placement_in_meta() {
+        let mut deployment = Deployment::new();
+        let host_a = deployment.add_host(LocalhostHost::new);
+        let host_b = deployment.add_host(LocalhostHost::new);
+
+        let flow = FlowBuilder::new();
+        let cluster = flow.cluster::<C1>();
+        cluster.source_iter(q!(0..1)).for_each(q!(|_| {}));
+
+        flow.pin_cluster_placement(
+            &cluster,
+            [
+                (ClusterId::from_raw(0), host_b.id()),
+                (ClusterId::from_raw(1), host_a.id()),
+            ],
+        );
+
+        let nodes = flow
+            .with_cluster(
+                &cluster,
+                vec![host_a.clone(), host_b.clone(), host_a.clone()],
+            )
+            .deploy(&mut deployment);
+
+        for (member_id, member) in nodes.get_cluster(&cluster).members().iter().enumerate() {
+            let meta = member.meta();
+            assert_eq!(meta.cluster_id, Some(member_id as u32));
+            assert_eq!(
+                meta.cluster_hosts[&cluster.id],
+                vec![host_b.id(), host_a.id(), host_a.id()]
+            );
+        }
+    }
+}
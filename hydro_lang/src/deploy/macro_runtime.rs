@@ -148,6 +148,25 @@ impl<'a> Deploy<'a> for DeployRuntime {
         panic!()
     }
 
+    fn e2m_source(
+        _compile_env: &Self::CompileEnv,
+        _p1: &Self::ExternalProcess,
+        _p1_port: &Self::Port,
+        _c2: &Self::Cluster,
+        _c2_port: &Self::Port,
+    ) -> syn::Expr {
+        panic!()
+    }
+
+    fn e2m_connect(
+        _p1: &Self::ExternalProcess,
+        _p1_port: &Self::Port,
+        _c2: &Self::Cluster,
+        _c2_port: &Self::Port,
+    ) -> Box<dyn FnOnce()> {
+        panic!()
+    }
+
     fn o2e_sink(
         _compile_env: &Self::CompileEnv,
         _p1: &Self::Process,
@@ -167,6 +186,25 @@ impl<'a> Deploy<'a> for DeployRuntime {
         panic!()
     }
 
+    fn m2e_sink(
+        _compile_env: &Self::CompileEnv,
+        _c1: &Self::Cluster,
+        _c1_port: &Self::Port,
+        _p2: &Self::ExternalProcess,
+        _p2_port: &Self::Port,
+    ) -> syn::Expr {
+        panic!()
+    }
+
+    fn m2e_connect(
+        _c1: &Self::Cluster,
+        _c1_port: &Self::Port,
+        _p2: &Self::ExternalProcess,
+        _p2_port: &Self::Port,
+    ) -> Box<dyn FnOnce()> {
+        panic!()
+    }
+
     fn cluster_ids(
         env: &Self::CompileEnv,
         of_cluster: usize,
@@ -215,6 +253,17 @@ impl<'a> RegisterPort<'a, DeployRuntime> for DeployRuntimeNode {
         async { panic!() }
     }
 
+    #[expect(
+        clippy::manual_async_fn,
+        reason = "buggy Clippy lint for lifetime bounds"
+    )]
+    fn as_bincode_demux_sink<T: serde::Serialize + 'static>(
+        &self,
+        _key: usize,
+    ) -> impl Future<Output = Pin<Box<dyn Sink<(u32, T), Error = std::io::Error>>>> + 'a {
+        async { panic!() }
+    }
+
     #[expect(
         clippy::manual_async_fn,
         reason = "buggy Clippy lint for lifetime bounds"
@@ -236,6 +285,17 @@ impl<'a> RegisterPort<'a, DeployRuntime> for DeployRuntimeNode {
     ) -> impl Future<Output = Pin<Box<dyn Stream<Item = T>>>> + 'a {
         async { panic!() }
     }
+
+    #[expect(
+        clippy::manual_async_fn,
+        reason = "buggy Clippy lint for lifetime bounds"
+    )]
+    fn as_bincode_tagged_source<T: serde::de::DeserializeOwned + 'static>(
+        &self,
+        _key: usize,
+    ) -> impl Future<Output = Pin<Box<dyn Stream<Item = (u32, T)>>>> + 'a {
+        async { panic!() }
+    }
 }
 
 impl Node for DeployRuntimeNode {
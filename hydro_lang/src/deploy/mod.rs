@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::future::Future;
 use std::io::Error;
 use std::pin::Pin;
@@ -153,6 +154,20 @@ pub trait Deploy<'a> {
         p2_port: &Self::Port,
     ) -> Box<dyn FnOnce()>;
 
+    fn e2m_source(
+        compile_env: &Self::CompileEnv,
+        p1: &Self::ExternalProcess,
+        p1_port: &Self::Port,
+        c2: &Self::Cluster,
+        c2_port: &Self::Port,
+    ) -> syn::Expr;
+    fn e2m_connect(
+        p1: &Self::ExternalProcess,
+        p1_port: &Self::Port,
+        c2: &Self::Cluster,
+        c2_port: &Self::Port,
+    ) -> Box<dyn FnOnce()>;
+
     fn o2e_sink(
         compile_env: &Self::CompileEnv,
         p1: &Self::Process,
@@ -167,11 +182,32 @@ pub trait Deploy<'a> {
         p2_port: &Self::Port,
     ) -> Box<dyn FnOnce()>;
 
+    fn m2e_sink(
+        compile_env: &Self::CompileEnv,
+        c1: &Self::Cluster,
+        c1_port: &Self::Port,
+        p2: &Self::ExternalProcess,
+        p2_port: &Self::Port,
+    ) -> syn::Expr;
+    fn m2e_connect(
+        c1: &Self::Cluster,
+        c1_port: &Self::Port,
+        p2: &Self::ExternalProcess,
+        p2_port: &Self::Port,
+    ) -> Box<dyn FnOnce()>;
+
     fn cluster_ids(
         env: &Self::CompileEnv,
         of_cluster: usize,
     ) -> impl QuotedWithContext<'a, &'a [u32], ()> + Copy + 'a;
     fn cluster_self_id(env: &Self::CompileEnv) -> impl QuotedWithContext<'a, u32, ()> + Copy + 'a;
+
+    /// Pins members of `cluster` to hosts, mapping member IDs to host IDs, before the cluster
+    /// is instantiated. Called for placements set with
+    /// [`crate::FlowBuilder::pin_cluster_placement`].
+    fn pin_cluster_placement(_cluster: &Self::Cluster, _placement: &HashMap<u32, usize>) {
+        panic!("This deployment backend does not support pinning cluster placement")
+    }
 }
 
 impl<
@@ -259,6 +295,13 @@ pub trait RegisterPort<'a, D: Deploy<'a> + ?Sized>: Clone {
         key: usize,
     ) -> impl Future<Output = Pin<Box<dyn Sink<T, Error = Error>>>> + 'a;
 
+    /// Like [`RegisterPort::as_bincode_sink`], for a port connected to every member of a
+    /// cluster, where each message is sent to the member with the given ID.
+    fn as_bincode_demux_sink<T: Serialize + 'static>(
+        &self,
+        key: usize,
+    ) -> impl Future<Output = Pin<Box<dyn Sink<(u32, T), Error = Error>>>> + 'a;
+
     fn as_bytes_source(
         &self,
         key: usize,
@@ -268,4 +311,11 @@ pub trait RegisterPort<'a, D: Deploy<'a> + ?Sized>: Clone {
         &self,
         key: usize,
     ) -> impl Future<Output = Pin<Box<dyn Stream<Item = T>>>> + 'a;
+
+    /// Like [`RegisterPort::as_bincode_source`], for a port receiving from every member of a
+    /// cluster, where each message is tagged with the ID of the member that sent it.
+    fn as_bincode_tagged_source<T: DeserializeOwned + 'static>(
+        &self,
+        key: usize,
+    ) -> impl Future<Output = Pin<Box<dyn Stream<Item = (u32, T)>>>> + 'a;
 }
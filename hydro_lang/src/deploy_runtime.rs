@@ -6,9 +6,11 @@ use dfir_rs::util::deploy::{
 use serde::{Deserialize, Serialize};
 use stageleft::{q, QuotedWithContext, RuntimeData};
 
-#[derive(Default, Serialize, Deserialize)]
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub struct HydroflowPlusMeta {
     pub clusters: HashMap<usize, Vec<u32>>,
+    /// For each cluster, the ID of the host each member was placed on, indexed by member ID.
+    pub cluster_hosts: HashMap<usize, Vec<usize>>,
     pub cluster_id: Option<u32>,
     pub subgraph_id: usize,
 }
@@ -147,6 +149,19 @@ pub fn deploy_e2o(
     .splice_untyped_ctx(&())
 }
 
+pub fn deploy_e2m(
+    env: RuntimeData<&DeployPorts<HydroflowPlusMeta>>,
+    _e1_port: &str,
+    c2_port: &str,
+) -> syn::Expr {
+    q!({
+        env.port(c2_port)
+            .connect_local_blocking::<ConnectedDirect>()
+            .into_source()
+    })
+    .splice_untyped_ctx(&())
+}
+
 pub fn deploy_o2e(
     env: RuntimeData<&DeployPorts<HydroflowPlusMeta>>,
     p1_port: &str,
@@ -159,3 +174,16 @@ pub fn deploy_o2e(
     })
     .splice_untyped_ctx(&())
 }
+
+pub fn deploy_m2e(
+    env: RuntimeData<&DeployPorts<HydroflowPlusMeta>>,
+    c1_port: &str,
+    _e2_port: &str,
+) -> syn::Expr {
+    q!({
+        env.port(c1_port)
+            .connect_local_blocking::<ConnectedDirect>()
+            .into_sink()
+    })
+    .splice_untyped_ctx(&())
+}
@@ -2,10 +2,11 @@ use core::panic;
 use std::cell::RefCell;
 #[cfg(feature = "build")]
 use std::collections::BTreeMap;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
 use std::ops::Deref;
 use std::rc::Rc;
+use std::time::Duration;
 
 #[cfg(feature = "build")]
 use dfir_lang::graph::FlatGraphBuilder;
@@ -116,6 +117,15 @@ impl HydroLeaf {
         )
     }
 
+    /// The name of this leaf's variant, such as `"ForEach"`.
+    pub fn kind_name(&self) -> &'static str {
+        match self {
+            HydroLeaf::ForEach { .. } => "ForEach",
+            HydroLeaf::DestSink { .. } => "DestSink",
+            HydroLeaf::CycleSink { .. } => "CycleSink",
+        }
+    }
+
     pub fn transform_children(
         self,
         mut transform: impl FnMut(&mut HydroNode, &mut SeenTees),
@@ -281,11 +291,27 @@ pub enum HydroNode {
         inner: TeeNode,
     },
 
+    /// Routes each element to the output with index `f(x) % num_outputs`, evaluating `f` once
+    /// per element. Its outputs are consumed through [`HydroNode::PartitionOutput`] nodes which
+    /// share this node, so every consumer reads from the same single pass.
+    Partition {
+        f: DebugExpr,
+        num_outputs: usize,
+        input: Box<HydroNode>,
+    },
+    PartitionOutput {
+        index: usize,
+        inner: TeeNode,
+    },
+
     Persist(Box<HydroNode>),
     Unpersist(Box<HydroNode>),
     Delta(Box<HydroNode>),
 
     Chain(Box<HydroNode>, Box<HydroNode>),
+    /// Pairs up the elements of two ordered inputs by position. If both inputs are
+    /// [`HydroNode::Persist`], unmatched elements are kept across ticks.
+    Zip(Box<HydroNode>, Box<HydroNode>),
     CrossProduct(Box<HydroNode>, Box<HydroNode>),
     CrossSingleton(Box<HydroNode>, Box<HydroNode>),
     Join(Box<HydroNode>, Box<HydroNode>),
@@ -308,16 +334,70 @@ pub enum HydroNode {
         f: DebugExpr,
         input: Box<HydroNode>,
     },
+    /// Marks a [`HydroNode::Map`] whose closure preserves everything that downstream filters
+    /// inspect, so that [`crate::rewrites::filter_pushdown`] may run those filters before it.
+    /// Emits its input unchanged.
+    FilterTransparent(Box<HydroNode>),
 
     DeferTick(Box<HydroNode>),
     Enumerate {
         is_static: bool,
         input: Box<HydroNode>,
     },
+    /// Like [`HydroNode::Enumerate`] with `is_static`, but the index wraps around to 0 after
+    /// reaching `modulus - 1`.
+    EnumerateMod {
+        modulus: u64,
+        input: Box<HydroNode>,
+    },
+    /// Passes through every `stride`-th element, starting with the first. The count is kept
+    /// across ticks.
+    SampleEvery {
+        stride: usize,
+        input: Box<HydroNode>,
+    },
+    /// Groups each tick's elements into consecutive `Vec`s of at most `size` elements.
+    Batch {
+        size: usize,
+        input: Box<HydroNode>,
+    },
+    /// Applies `f` to each tick's elements on up to `parallelism` threads. Unless `ordered` is
+    /// set, the output order is unspecified.
+    MapParallel {
+        f: DebugExpr,
+        parallelism: usize,
+        ordered: bool,
+        input: Box<HydroNode>,
+    },
+    Scan {
+        init: DebugExpr,
+        acc: DebugExpr,
+        input: Box<HydroNode>,
+    },
+    /// Threads a state of type `S`, created by `init`, through `f: Fn(&mut S, T) -> Vec<U>`,
+    /// emitting every element of the returned `Vec`s in order.
+    StateMachine {
+        init: DebugExpr,
+        f: DebugExpr,
+        input: Box<HydroNode>,
+    },
+    /// Reduces each window of the last `window` elements, keeping the elements of the current
+    /// window in a ring buffer so that the aggregate can be updated with `add` as an element
+    /// enters and `sub` as one leaves.
+    SlidingReduce {
+        window: usize,
+        add: DebugExpr,
+        sub: DebugExpr,
+        input: Box<HydroNode>,
+    },
     Inspect {
         f: DebugExpr,
         input: Box<HydroNode>,
     },
+    Named {
+        name: String,
+        input: Box<HydroNode>,
+    },
 
     Unique(Box<HydroNode>),
 
@@ -350,6 +430,11 @@ pub enum HydroNode {
         serialize_fn: Option<DebugExpr>,
         instantiate_fn: DebugInstantiate,
         deserialize_fn: Option<DebugExpr>,
+        /// If set, elements are coalesced into frames of at most `.0` elements, each sent
+        /// no later than `.1` after its first element was buffered. The `serialize_fn`
+        /// then frames a `Vec` of elements, and the `deserialize_fn` unpacks a frame into
+        /// an iterator of elements.
+        batching: Option<(usize, Duration)>,
         input: Box<HydroNode>,
     },
 }
@@ -412,6 +497,51 @@ impl<'a> HydroNode {
         }
     }
 
+    /// The name of this node's variant, such as `"Map"` or `"Network"`.
+    pub fn kind_name(&self) -> &'static str {
+        match self {
+            HydroNode::Placeholder => "Placeholder",
+            HydroNode::Source { .. } => "Source",
+            HydroNode::CycleSource { .. } => "CycleSource",
+            HydroNode::Tee { .. } => "Tee",
+            HydroNode::Partition { .. } => "Partition",
+            HydroNode::PartitionOutput { .. } => "PartitionOutput",
+            HydroNode::Persist(..) => "Persist",
+            HydroNode::Unpersist(..) => "Unpersist",
+            HydroNode::Delta(..) => "Delta",
+            HydroNode::Chain(..) => "Chain",
+            HydroNode::Zip(..) => "Zip",
+            HydroNode::CrossProduct(..) => "CrossProduct",
+            HydroNode::CrossSingleton(..) => "CrossSingleton",
+            HydroNode::Join(..) => "Join",
+            HydroNode::Difference(..) => "Difference",
+            HydroNode::AntiJoin(..) => "AntiJoin",
+            HydroNode::Map { .. } => "Map",
+            HydroNode::FlatMap { .. } => "FlatMap",
+            HydroNode::Filter { .. } => "Filter",
+            HydroNode::FilterMap { .. } => "FilterMap",
+            HydroNode::FilterTransparent(..) => "FilterTransparent",
+            HydroNode::DeferTick(..) => "DeferTick",
+            HydroNode::Enumerate { .. } => "Enumerate",
+            HydroNode::EnumerateMod { .. } => "EnumerateMod",
+            HydroNode::SampleEvery { .. } => "SampleEvery",
+            HydroNode::Batch { .. } => "Batch",
+            HydroNode::MapParallel { .. } => "MapParallel",
+            HydroNode::Scan { .. } => "Scan",
+            HydroNode::StateMachine { .. } => "StateMachine",
+            HydroNode::SlidingReduce { .. } => "SlidingReduce",
+            HydroNode::Inspect { .. } => "Inspect",
+            HydroNode::Named { .. } => "Named",
+            HydroNode::Unique(..) => "Unique",
+            HydroNode::Sort(..) => "Sort",
+            HydroNode::Fold { .. } => "Fold",
+            HydroNode::FoldKeyed { .. } => "FoldKeyed",
+            HydroNode::Reduce { .. } => "Reduce",
+            HydroNode::ReduceKeyed { .. } => "ReduceKeyed",
+            HydroNode::Network { .. } => "Network",
+        }
+    }
+
     pub fn transform_bottom_up<C>(
         &mut self,
         mut transform: impl FnMut(&mut HydroNode, &mut C) + Copy,
@@ -438,7 +568,7 @@ impl<'a> HydroNode {
 
             HydroNode::CycleSource { .. } => {}
 
-            HydroNode::Tee { inner } => {
+            HydroNode::Tee { inner } | HydroNode::PartitionOutput { inner, .. } => {
                 if let Some(transformed) =
                     seen_tees.get(&(inner.0.as_ref() as *const RefCell<HydroNode>))
                 {
@@ -456,6 +586,10 @@ impl<'a> HydroNode {
                 }
             }
 
+            HydroNode::Partition { input, .. } => {
+                transform(input.as_mut(), seen_tees);
+            }
+
             HydroNode::Persist(inner) => transform(inner.as_mut(), seen_tees),
             HydroNode::Unpersist(inner) => transform(inner.as_mut(), seen_tees),
             HydroNode::Delta(inner) => transform(inner.as_mut(), seen_tees),
@@ -464,6 +598,10 @@ impl<'a> HydroNode {
                 transform(left.as_mut(), seen_tees);
                 transform(right.as_mut(), seen_tees);
             }
+            HydroNode::Zip(left, right) => {
+                transform(left.as_mut(), seen_tees);
+                transform(right.as_mut(), seen_tees);
+            }
             HydroNode::CrossProduct(left, right) => {
                 transform(left.as_mut(), seen_tees);
                 transform(right.as_mut(), seen_tees);
@@ -497,6 +635,9 @@ impl<'a> HydroNode {
             HydroNode::FilterMap { input, .. } => {
                 transform(input.as_mut(), seen_tees);
             }
+            HydroNode::FilterTransparent(input) => {
+                transform(input.as_mut(), seen_tees);
+            }
             HydroNode::Sort(input) => {
                 transform(input.as_mut(), seen_tees);
             }
@@ -506,9 +647,33 @@ impl<'a> HydroNode {
             HydroNode::Enumerate { input, .. } => {
                 transform(input.as_mut(), seen_tees);
             }
+            HydroNode::EnumerateMod { input, .. } => {
+                transform(input.as_mut(), seen_tees);
+            }
+            HydroNode::SampleEvery { input, .. } => {
+                transform(input.as_mut(), seen_tees);
+            }
+            HydroNode::Batch { input, .. } => {
+                transform(input.as_mut(), seen_tees);
+            }
+            HydroNode::MapParallel { input, .. } => {
+                transform(input.as_mut(), seen_tees);
+            }
+            HydroNode::Scan { input, .. } => {
+                transform(input.as_mut(), seen_tees);
+            }
+            HydroNode::StateMachine { input, .. } => {
+                transform(input.as_mut(), seen_tees);
+            }
+            HydroNode::SlidingReduce { input, .. } => {
+                transform(input.as_mut(), seen_tees);
+            }
             HydroNode::Inspect { input, .. } => {
                 transform(input.as_mut(), seen_tees);
             }
+            HydroNode::Named { input, .. } => {
+                transform(input.as_mut(), seen_tees);
+            }
 
             HydroNode::Unique(input) => {
                 transform(input.as_mut(), seen_tees);
@@ -534,12 +699,81 @@ impl<'a> HydroNode {
         }
     }
 
+    /// Calls `visit` on each input of this node, without modifying the IR. The input of a
+    /// [`HydroNode::Tee`] is visited every time it is referenced.
+    pub fn visit_children(&self, mut visit: impl FnMut(&HydroNode)) {
+        match self {
+            HydroNode::Placeholder => {
+                panic!();
+            }
+
+            HydroNode::Source { .. } | HydroNode::CycleSource { .. } => {}
+
+            HydroNode::Tee { inner } | HydroNode::PartitionOutput { inner, .. } => {
+                visit(&inner.0.borrow());
+            }
+
+            HydroNode::Persist(input)
+            | HydroNode::Unpersist(input)
+            | HydroNode::Delta(input)
+            | HydroNode::FilterTransparent(input)
+            | HydroNode::Sort(input)
+            | HydroNode::DeferTick(input)
+            | HydroNode::Unique(input) => visit(input),
+
+            HydroNode::Chain(left, right)
+            | HydroNode::Zip(left, right)
+            | HydroNode::CrossProduct(left, right)
+            | HydroNode::CrossSingleton(left, right)
+            | HydroNode::Join(left, right)
+            | HydroNode::Difference(left, right)
+            | HydroNode::AntiJoin(left, right) => {
+                visit(left);
+                visit(right);
+            }
+
+            HydroNode::Partition { input, .. }
+            | HydroNode::Map { input, .. }
+            | HydroNode::FlatMap { input, .. }
+            | HydroNode::Filter { input, .. }
+            | HydroNode::FilterMap { input, .. }
+            | HydroNode::Enumerate { input, .. }
+            | HydroNode::EnumerateMod { input, .. }
+            | HydroNode::SampleEvery { input, .. }
+            | HydroNode::Batch { input, .. }
+            | HydroNode::MapParallel { input, .. }
+            | HydroNode::Scan { input, .. }
+            | HydroNode::StateMachine { input, .. }
+            | HydroNode::SlidingReduce { input, .. }
+            | HydroNode::Inspect { input, .. }
+            | HydroNode::Named { input, .. }
+            | HydroNode::Fold { input, .. }
+            | HydroNode::FoldKeyed { input, .. }
+            | HydroNode::Reduce { input, .. }
+            | HydroNode::ReduceKeyed { input, .. }
+            | HydroNode::Network { input, .. } => visit(input),
+        }
+    }
+
     #[cfg(feature = "build")]
     pub fn emit(
         &self,
         graph_builders: &mut BTreeMap<usize, FlatGraphBuilder>,
         built_tees: &mut HashMap<*const RefCell<HydroNode>, (syn::Ident, usize)>,
         next_stmt_id: &mut usize,
+    ) -> (syn::Ident, usize) {
+        self.emit_labeled(graph_builders, built_tees, next_stmt_id, None)
+    }
+
+    /// Emits this node, using `label` (if any) instead of `stream` as the
+    /// prefix of the ident generated for its statement.
+    #[cfg(feature = "build")]
+    fn emit_labeled(
+        &self,
+        graph_builders: &mut BTreeMap<usize, FlatGraphBuilder>,
+        built_tees: &mut HashMap<*const RefCell<HydroNode>, (syn::Ident, usize)>,
+        next_stmt_id: &mut usize,
+        label: Option<&str>,
     ) -> (syn::Ident, usize) {
         match self {
             HydroNode::Placeholder => {
@@ -552,8 +786,7 @@ impl<'a> HydroNode {
                 let persist_id = *next_stmt_id;
                 *next_stmt_id += 1;
 
-                let persist_ident =
-                    syn::Ident::new(&format!("stream_{}", persist_id), Span::call_site());
+                let persist_ident = stmt_ident(label, persist_id);
 
                 let builder = graph_builders.entry(location).or_default();
                 builder.add_statement(parse_quote! {
@@ -573,8 +806,7 @@ impl<'a> HydroNode {
                 let delta_id = *next_stmt_id;
                 *next_stmt_id += 1;
 
-                let delta_ident =
-                    syn::Ident::new(&format!("stream_{}", delta_id), Span::call_site());
+                let delta_ident = stmt_ident(label, delta_id);
 
                 let builder = graph_builders.entry(location).or_default();
                 builder.add_statement(parse_quote! {
@@ -601,8 +833,7 @@ impl<'a> HydroNode {
                     let source_id = *next_stmt_id;
                     *next_stmt_id += 1;
 
-                    let source_ident =
-                        syn::Ident::new(&format!("stream_{}", source_id), Span::call_site());
+                    let source_ident = stmt_ident(label, source_id);
 
                     let source_stmt = match source {
                         HydroSource::Stream(expr) => {
@@ -665,8 +896,7 @@ impl<'a> HydroNode {
                     let tee_id = *next_stmt_id;
                     *next_stmt_id += 1;
 
-                    let tee_ident =
-                        syn::Ident::new(&format!("stream_{}", tee_id), Span::call_site());
+                    let tee_ident = stmt_ident(label, tee_id);
 
                     let builder = graph_builders.entry(inner_location_id).or_default();
                     builder.add_statement(parse_quote! {
@@ -682,6 +912,63 @@ impl<'a> HydroNode {
                 }
             }
 
+            HydroNode::Partition {
+                f,
+                num_outputs,
+                input,
+            } => {
+                let (input_ident, input_location_id) =
+                    input.emit(graph_builders, built_tees, next_stmt_id);
+
+                let partition_id = *next_stmt_id;
+                *next_stmt_id += 1;
+
+                let partition_ident = stmt_ident(label, partition_id);
+
+                let builder = graph_builders.entry(input_location_id).or_default();
+                // `partition` re-evaluates its closure expression for every item, so the route
+                // is computed in a `map` first, which keeps any state in `f` across items.
+                builder.add_statement(parse_quote! {
+                    #partition_ident = #input_ident -> map({
+                        let f = #f;
+                        move |item| (f(&item) % #num_outputs, item)
+                    }) -> partition(|(index, _): &(usize, _), _| *index);
+                });
+
+                // Every port must be connected, even for outputs that are never consumed.
+                for index in 0..*num_outputs {
+                    let output_ident = partition_output_ident(&partition_ident, index);
+                    let port = syn::Index::from(index);
+                    builder.add_statement(parse_quote! {
+                        #output_ident = #partition_ident[#port] -> map(|(_, item): (usize, _)| item) -> tee();
+                    });
+                }
+
+                (partition_ident, input_location_id)
+            }
+
+            HydroNode::PartitionOutput { index, inner } => {
+                let (partition_ident, partition_location_id) = if let Some(ret) =
+                    built_tees.get(&(inner.0.as_ref() as *const RefCell<HydroNode>))
+                {
+                    ret.clone()
+                } else {
+                    let ret = inner
+                        .0
+                        .borrow()
+                        .emit(graph_builders, built_tees, next_stmt_id);
+
+                    built_tees.insert(inner.0.as_ref() as *const RefCell<HydroNode>, ret.clone());
+
+                    ret
+                };
+
+                (
+                    partition_output_ident(&partition_ident, *index),
+                    partition_location_id,
+                )
+            }
+
             HydroNode::Chain(left, right) => {
                 let (left_ident, left_location_id) =
                     left.emit(graph_builders, built_tees, next_stmt_id);
@@ -696,8 +983,7 @@ impl<'a> HydroNode {
                 let union_id = *next_stmt_id;
                 *next_stmt_id += 1;
 
-                let chain_ident =
-                    syn::Ident::new(&format!("stream_{}", union_id), Span::call_site());
+                let chain_ident = stmt_ident(label, union_id);
 
                 let builder = graph_builders.entry(left_location_id).or_default();
                 builder.add_statement(parse_quote! {
@@ -715,6 +1001,49 @@ impl<'a> HydroNode {
                 (chain_ident, left_location_id)
             }
 
+            HydroNode::Zip(left, right) => {
+                let (left, right, is_static) = match (left.as_ref(), right.as_ref()) {
+                    (HydroNode::Persist(left), HydroNode::Persist(right)) => (left, right, true),
+                    _ => (left, right, false),
+                };
+
+                let (left_ident, left_location_id) =
+                    left.emit(graph_builders, built_tees, next_stmt_id);
+                let (right_ident, right_location_id) =
+                    right.emit(graph_builders, built_tees, next_stmt_id);
+
+                assert_eq!(
+                    left_location_id, right_location_id,
+                    "zip inputs must be in the same location"
+                );
+
+                let zip_id = *next_stmt_id;
+                *next_stmt_id += 1;
+
+                let zip_ident = stmt_ident(label, zip_id);
+
+                let builder = graph_builders.entry(left_location_id).or_default();
+                if is_static {
+                    builder.add_statement(parse_quote! {
+                        #zip_ident = zip::<'static>();
+                    });
+                } else {
+                    builder.add_statement(parse_quote! {
+                        #zip_ident = zip::<'tick>();
+                    });
+                }
+
+                builder.add_statement(parse_quote! {
+                    #left_ident -> [0]#zip_ident;
+                });
+
+                builder.add_statement(parse_quote! {
+                    #right_ident -> [1]#zip_ident;
+                });
+
+                (zip_ident, left_location_id)
+            }
+
             HydroNode::CrossSingleton(left, right) => {
                 let (left_ident, left_location_id) =
                     left.emit(graph_builders, built_tees, next_stmt_id);
@@ -729,8 +1058,7 @@ impl<'a> HydroNode {
                 let union_id = *next_stmt_id;
                 *next_stmt_id += 1;
 
-                let cross_ident =
-                    syn::Ident::new(&format!("stream_{}", union_id), Span::call_site());
+                let cross_ident = stmt_ident(label, union_id);
 
                 let builder = graph_builders.entry(left_location_id).or_default();
                 builder.add_statement(parse_quote! {
@@ -787,8 +1115,7 @@ impl<'a> HydroNode {
                 let stream_id = *next_stmt_id;
                 *next_stmt_id += 1;
 
-                let stream_ident =
-                    syn::Ident::new(&format!("stream_{}", stream_id), Span::call_site());
+                let stream_ident = stmt_ident(label, stream_id);
 
                 let builder = graph_builders.entry(left_location_id).or_default();
 
@@ -857,8 +1184,7 @@ impl<'a> HydroNode {
                 let stream_id = *next_stmt_id;
                 *next_stmt_id += 1;
 
-                let stream_ident =
-                    syn::Ident::new(&format!("stream_{}", stream_id), Span::call_site());
+                let stream_ident = stmt_ident(label, stream_id);
 
                 let builder = graph_builders.entry(left_location_id).or_default();
 
@@ -890,7 +1216,7 @@ impl<'a> HydroNode {
                 let map_id = *next_stmt_id;
                 *next_stmt_id += 1;
 
-                let map_ident = syn::Ident::new(&format!("stream_{}", map_id), Span::call_site());
+                let map_ident = stmt_ident(label, map_id);
 
                 let builder = graph_builders.entry(input_location_id).or_default();
                 builder.add_statement(parse_quote! {
@@ -907,8 +1233,7 @@ impl<'a> HydroNode {
                 let flat_map_id = *next_stmt_id;
                 *next_stmt_id += 1;
 
-                let flat_map_ident =
-                    syn::Ident::new(&format!("stream_{}", flat_map_id), Span::call_site());
+                let flat_map_ident = stmt_ident(label, flat_map_id);
 
                 let builder = graph_builders.entry(input_location_id).or_default();
                 builder.add_statement(parse_quote! {
@@ -925,8 +1250,7 @@ impl<'a> HydroNode {
                 let filter_id = *next_stmt_id;
                 *next_stmt_id += 1;
 
-                let filter_ident =
-                    syn::Ident::new(&format!("stream_{}", filter_id), Span::call_site());
+                let filter_ident = stmt_ident(label, filter_id);
 
                 let builder = graph_builders.entry(input_location_id).or_default();
                 builder.add_statement(parse_quote! {
@@ -943,8 +1267,7 @@ impl<'a> HydroNode {
                 let filter_map_id = *next_stmt_id;
                 *next_stmt_id += 1;
 
-                let filter_map_ident =
-                    syn::Ident::new(&format!("stream_{}", filter_map_id), Span::call_site());
+                let filter_map_ident = stmt_ident(label, filter_map_id);
 
                 let builder = graph_builders.entry(input_location_id).or_default();
                 builder.add_statement(parse_quote! {
@@ -954,6 +1277,10 @@ impl<'a> HydroNode {
                 (filter_map_ident, input_location_id)
             }
 
+            HydroNode::FilterTransparent(input) => {
+                input.emit_labeled(graph_builders, built_tees, next_stmt_id, label)
+            }
+
             HydroNode::Sort(input) => {
                 let (input_ident, input_location_id) =
                     input.emit(graph_builders, built_tees, next_stmt_id);
@@ -961,7 +1288,7 @@ impl<'a> HydroNode {
                 let sort_id = *next_stmt_id;
                 *next_stmt_id += 1;
 
-                let sort_ident = syn::Ident::new(&format!("stream_{}", sort_id), Span::call_site());
+                let sort_ident = stmt_ident(label, sort_id);
 
                 let builder = graph_builders.entry(input_location_id).or_default();
                 builder.add_statement(parse_quote! {
@@ -978,8 +1305,7 @@ impl<'a> HydroNode {
                 let defer_tick_id = *next_stmt_id;
                 *next_stmt_id += 1;
 
-                let defer_tick_ident =
-                    syn::Ident::new(&format!("stream_{}", defer_tick_id), Span::call_site());
+                let defer_tick_ident = stmt_ident(label, defer_tick_id);
 
                 let builder = graph_builders.entry(input_location_id).or_default();
                 builder.add_statement(parse_quote! {
@@ -996,8 +1322,7 @@ impl<'a> HydroNode {
                 let enumerate_id = *next_stmt_id;
                 *next_stmt_id += 1;
 
-                let enumerate_ident =
-                    syn::Ident::new(&format!("stream_{}", enumerate_id), Span::call_site());
+                let enumerate_ident = stmt_ident(label, enumerate_id);
 
                 let builder = graph_builders.entry(input_location_id).or_default();
 
@@ -1014,6 +1339,232 @@ impl<'a> HydroNode {
                 (enumerate_ident, input_location_id)
             }
 
+            HydroNode::EnumerateMod { modulus, input } => {
+                let (input_ident, input_location_id) =
+                    input.emit(graph_builders, built_tees, next_stmt_id);
+
+                let enumerate_id = *next_stmt_id;
+                *next_stmt_id += 1;
+
+                let enumerate_ident = stmt_ident(label, enumerate_id);
+
+                let builder = graph_builders.entry(input_location_id).or_default();
+                builder.add_statement(parse_quote! {
+                    #enumerate_ident = #input_ident -> scan::<'static>(|| 0u64, |next: &mut u64, item| {
+                        let index = *next;
+                        *next = (index + 1) % #modulus;
+                        ::std::option::Option::Some((index, item))
+                    });
+                });
+
+                (enumerate_ident, input_location_id)
+            }
+
+            HydroNode::SampleEvery { stride, input } => {
+                let (input_ident, input_location_id) =
+                    input.emit(graph_builders, built_tees, next_stmt_id);
+
+                let sample_id = *next_stmt_id;
+                *next_stmt_id += 1;
+
+                let sample_ident = stmt_ident(label, sample_id);
+
+                let builder = graph_builders.entry(input_location_id).or_default();
+                builder.add_statement(parse_quote! {
+                    #sample_ident = #input_ident -> enumerate::<'static>() -> filter_map(|(index, item)| {
+                        if index % #stride == 0 {
+                            ::std::option::Option::Some(item)
+                        } else {
+                            ::std::option::Option::None
+                        }
+                    });
+                });
+
+                (sample_ident, input_location_id)
+            }
+
+            HydroNode::Batch { size, input } => {
+                let (input_ident, input_location_id) =
+                    input.emit(graph_builders, built_tees, next_stmt_id);
+
+                let batch_id = *next_stmt_id;
+                *next_stmt_id += 1;
+
+                let batch_ident = stmt_ident(label, batch_id);
+
+                let builder = graph_builders.entry(input_location_id).or_default();
+                builder.add_statement(parse_quote! {
+                    #batch_ident = #input_ident
+                        -> fold::<'tick>(::std::vec::Vec::new, |batches: &mut ::std::vec::Vec<::std::vec::Vec<_>>, item| {
+                            if batches.last().is_none_or(|batch| batch.len() >= #size) {
+                                batches.push(::std::vec::Vec::with_capacity(#size));
+                            }
+                            batches.last_mut().unwrap().push(item);
+                        })
+                        -> flatten();
+                });
+
+                (batch_ident, input_location_id)
+            }
+
+            HydroNode::MapParallel {
+                f,
+                parallelism,
+                ordered,
+                input,
+            } => {
+                let (input_ident, input_location_id) =
+                    input.emit(graph_builders, built_tees, next_stmt_id);
+
+                let map_parallel_id = *next_stmt_id;
+                *next_stmt_id += 1;
+
+                let map_parallel_ident = stmt_ident(label, map_parallel_id);
+
+                let root = crate::staging_util::get_this_crate();
+                let builder = graph_builders.entry(input_location_id).or_default();
+                // Operator arguments are evaluated every tick, so the pool is kept in `'static`
+                // scan state for its threads to be reused across ticks.
+                builder.add_statement(parse_quote! {
+                    #map_parallel_ident = #input_ident
+                        -> fold::<'tick>(::std::vec::Vec::new, |items: &mut ::std::vec::Vec<_>, item| {
+                            items.push(item);
+                        })
+                        -> scan::<'static>(
+                            move || #root::runtime_support::parallel::ParallelMap::new(#f, #parallelism, #ordered),
+                            |pool: &mut #root::runtime_support::parallel::ParallelMap<_, _>, items| Some(pool.map(items))
+                        )
+                        -> flatten();
+                });
+
+                (map_parallel_ident, input_location_id)
+            }
+
+            HydroNode::Scan { init, acc, input } => {
+                let (input, input_was_persist) = if let HydroNode::Persist(input) = input.as_ref() {
+                    (input, true)
+                } else {
+                    (input, false)
+                };
+
+                let (input_ident, input_location_id) =
+                    input.emit(graph_builders, built_tees, next_stmt_id);
+
+                let scan_id = *next_stmt_id;
+                *next_stmt_id += 1;
+
+                let scan_ident = stmt_ident(label, scan_id);
+
+                let builder = graph_builders.entry(input_location_id).or_default();
+                if input_was_persist {
+                    builder.add_statement(parse_quote! {
+                        #scan_ident = #input_ident -> scan::<'static>(#init, {
+                            let acc = #acc;
+                            move |state, item| Some(acc(state, item))
+                        });
+                    });
+                } else {
+                    builder.add_statement(parse_quote! {
+                        #scan_ident = #input_ident -> scan::<'tick>(#init, {
+                            let acc = #acc;
+                            move |state, item| Some(acc(state, item))
+                        });
+                    });
+                }
+
+                (scan_ident, input_location_id)
+            }
+
+            HydroNode::StateMachine { init, f, input } => {
+                let (input, input_was_persist) = if let HydroNode::Persist(input) = input.as_ref() {
+                    (input, true)
+                } else {
+                    (input, false)
+                };
+
+                let (input_ident, input_location_id) =
+                    input.emit(graph_builders, built_tees, next_stmt_id);
+
+                let state_machine_id = *next_stmt_id;
+                *next_stmt_id += 1;
+
+                let state_machine_ident = stmt_ident(label, state_machine_id);
+
+                let lifetime: syn::Lifetime = if input_was_persist {
+                    parse_quote!('static)
+                } else {
+                    parse_quote!('tick)
+                };
+
+                let builder = graph_builders.entry(input_location_id).or_default();
+                builder.add_statement(parse_quote! {
+                    #state_machine_ident = #input_ident -> scan::<#lifetime>(#init, {
+                        let f = #f;
+                        move |state, item| ::std::option::Option::Some(f(state, item))
+                    }) -> flatten();
+                });
+
+                (state_machine_ident, input_location_id)
+            }
+
+            HydroNode::SlidingReduce {
+                window,
+                add,
+                sub,
+                input,
+            } => {
+                let (input, input_was_persist) = if let HydroNode::Persist(input) = input.as_ref() {
+                    (input, true)
+                } else {
+                    (input, false)
+                };
+
+                let (input_ident, input_location_id) =
+                    input.emit(graph_builders, built_tees, next_stmt_id);
+
+                let sliding_reduce_id = *next_stmt_id;
+                *next_stmt_id += 1;
+
+                let sliding_reduce_ident = stmt_ident(label, sliding_reduce_id);
+
+                let lifetime: syn::Lifetime = if input_was_persist {
+                    parse_quote!('static)
+                } else {
+                    parse_quote!('tick)
+                };
+
+                let builder = graph_builders.entry(input_location_id).or_default();
+                builder.add_statement(parse_quote! {
+                    #sliding_reduce_ident = #input_ident -> scan::<#lifetime>(
+                        || (::std::collections::VecDeque::with_capacity(#window), ::std::option::Option::None),
+                        {
+                            let add = #add;
+                            let sub = #sub;
+                            move |(buffer, aggregate): &mut (::std::collections::VecDeque<_>, ::std::option::Option<_>), item| {
+                                buffer.push_back(::std::clone::Clone::clone(&item));
+                                match aggregate {
+                                    ::std::option::Option::Some(current) => add(current, item),
+                                    ::std::option::Option::None => *aggregate = ::std::option::Option::Some(item),
+                                }
+
+                                if buffer.len() > #window {
+                                    let leaving = buffer.pop_front().unwrap();
+                                    sub(aggregate.as_mut().unwrap(), leaving);
+                                }
+
+                                if buffer.len() == #window {
+                                    ::std::clone::Clone::clone(aggregate)
+                                } else {
+                                    ::std::option::Option::None
+                                }
+                            }
+                        }
+                    );
+                });
+
+                (sliding_reduce_ident, input_location_id)
+            }
+
             HydroNode::Inspect { f, input } => {
                 let (input_ident, input_location_id) =
                     input.emit(graph_builders, built_tees, next_stmt_id);
@@ -1021,8 +1572,7 @@ impl<'a> HydroNode {
                 let inspect_id = *next_stmt_id;
                 *next_stmt_id += 1;
 
-                let inspect_ident =
-                    syn::Ident::new(&format!("stream_{}", inspect_id), Span::call_site());
+                let inspect_ident = stmt_ident(label, inspect_id);
 
                 let builder = graph_builders.entry(input_location_id).or_default();
                 builder.add_statement(parse_quote! {
@@ -1032,6 +1582,10 @@ impl<'a> HydroNode {
                 (inspect_ident, input_location_id)
             }
 
+            HydroNode::Named { name, input } => {
+                input.emit_labeled(graph_builders, built_tees, next_stmt_id, Some(name))
+            }
+
             HydroNode::Unique(input) => {
                 let (input_ident, input_location_id) =
                     input.emit(graph_builders, built_tees, next_stmt_id);
@@ -1039,8 +1593,7 @@ impl<'a> HydroNode {
                 let unique_id = *next_stmt_id;
                 *next_stmt_id += 1;
 
-                let unique_ident =
-                    syn::Ident::new(&format!("stream_{}", unique_id), Span::call_site());
+                let unique_ident = stmt_ident(label, unique_id);
 
                 let builder = graph_builders.entry(input_location_id).or_default();
                 builder.add_statement(parse_quote! {
@@ -1075,8 +1628,7 @@ impl<'a> HydroNode {
                 let reduce_id = *next_stmt_id;
                 *next_stmt_id += 1;
 
-                let fold_ident =
-                    syn::Ident::new(&format!("stream_{}", reduce_id), Span::call_site());
+                let fold_ident = stmt_ident(label, reduce_id);
 
                 let builder = graph_builders.entry(input_location_id).or_default();
                 if input_was_persist {
@@ -1116,8 +1668,7 @@ impl<'a> HydroNode {
                 let reduce_id = *next_stmt_id;
                 *next_stmt_id += 1;
 
-                let reduce_ident =
-                    syn::Ident::new(&format!("stream_{}", reduce_id), Span::call_site());
+                let reduce_ident = stmt_ident(label, reduce_id);
 
                 let builder = graph_builders.entry(input_location_id).or_default();
                 if input_was_persist {
@@ -1141,6 +1692,7 @@ impl<'a> HydroNode {
                 serialize_fn: serialize_pipeline,
                 instantiate_fn,
                 deserialize_fn: deserialize_pipeline,
+                batching,
                 input,
             } => {
                 let (sink_expr, source_expr, _connect_fn) = match instantiate_fn {
@@ -1158,7 +1710,22 @@ impl<'a> HydroNode {
 
                 let sender_builder = graph_builders.entry(input_location_id).or_default();
 
-                if let Some(serialize_pipeline) = serialize_pipeline {
+                if let Some((max_batch, max_delay)) = batching {
+                    let root = crate::staging_util::get_this_crate();
+                    let delay_secs = max_delay.as_secs();
+                    let delay_nanos = max_delay.subsec_nanos();
+                    let serialize_pipeline = serialize_pipeline
+                        .as_ref()
+                        .expect("batched networks must frame their batches");
+                    sender_builder.add_statement(parse_quote! {
+                        #input_ident -> dest_sink(#root::runtime_support::batching::batch_sink(
+                            #sink_expr,
+                            #max_batch,
+                            ::std::time::Duration::new(#delay_secs, #delay_nanos),
+                            #serialize_pipeline,
+                        ));
+                    });
+                } else if let Some(serialize_pipeline) = serialize_pipeline {
                     sender_builder.add_statement(parse_quote! {
                         #input_ident -> map(#serialize_pipeline) -> dest_sink(#sink_expr);
                     });
@@ -1179,13 +1746,18 @@ impl<'a> HydroNode {
                 let receiver_stream_id = *next_stmt_id;
                 *next_stmt_id += 1;
 
-                let receiver_stream_ident =
-                    syn::Ident::new(&format!("stream_{}", receiver_stream_id), Span::call_site());
+                let receiver_stream_ident = stmt_ident(label, receiver_stream_id);
 
                 if let Some(deserialize_pipeline) = deserialize_pipeline {
-                    receiver_builder.add_statement(parse_quote! {
-                        #receiver_stream_ident = source_stream(#source_expr) -> map(#deserialize_pipeline);
-                    });
+                    if batching.is_some() {
+                        receiver_builder.add_statement(parse_quote! {
+                            #receiver_stream_ident = source_stream(#source_expr) -> flat_map(#deserialize_pipeline);
+                        });
+                    } else {
+                        receiver_builder.add_statement(parse_quote! {
+                            #receiver_stream_ident = source_stream(#source_expr) -> map(#deserialize_pipeline);
+                        });
+                    }
                 } else {
                     receiver_builder.add_statement(parse_quote! {
                         #receiver_stream_ident = source_stream(#source_expr);
@@ -1198,6 +1770,125 @@ impl<'a> HydroNode {
     }
 }
 
+/// An [`HydroNode::Unpersist`] that was not optimized away, see [`check_unpersist_eliminated`].
+///
+/// `Unpersist` is only a marker for rewrites such as [`crate::rewrites::persist_pullup`], so it
+/// cannot be compiled.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SurvivingUnpersist {
+    /// The location whose elements the `Unpersist` consumes, if it could be determined.
+    pub location: Option<LocationId>,
+    /// The kind of the node or leaf that consumes the `Unpersist`, such as `"ForEach"`.
+    pub consumer_kind: &'static str,
+    /// The kind of the node that the `Unpersist` wraps, such as `"Map"`.
+    pub input_kind: &'static str,
+}
+
+impl std::fmt::Display for SurvivingUnpersist {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "An `Unpersist` marker node ")?;
+        if let Some(location) = &self.location {
+            write!(f, "at {:?} ", location)?;
+        }
+        write!(
+            f,
+            "between `{}` and its input `{}` was not optimized away. Rewrites that remove \
+             `Persist` nodes must also remove the `Unpersist` nodes that depend on them.",
+            self.consumer_kind, self.input_kind
+        )
+    }
+}
+
+impl std::error::Error for SurvivingUnpersist {}
+
+/// Checks that no [`HydroNode::Unpersist`] remains in `ir`, which would otherwise fail to
+/// compile. Useful at the end of a custom rewrite.
+pub fn check_unpersist_eliminated(ir: &[HydroLeaf]) -> Result<(), SurvivingUnpersist> {
+    fn find_unpersist(
+        node: &HydroNode,
+        consumer_kind: &'static str,
+        seen_tees: &mut HashSet<*const RefCell<HydroNode>>,
+    ) -> Result<(), SurvivingUnpersist> {
+        match node {
+            HydroNode::Unpersist(input) => {
+                return Err(SurvivingUnpersist {
+                    location: location_of(input),
+                    consumer_kind,
+                    input_kind: input.kind_name(),
+                });
+            }
+            HydroNode::Tee { inner } | HydroNode::PartitionOutput { inner, .. } => {
+                if !seen_tees.insert(Rc::as_ptr(&inner.0)) {
+                    return Ok(());
+                }
+            }
+            _ => {}
+        }
+
+        let kind = node.kind_name();
+        let mut result = Ok(());
+        node.visit_children(|child| {
+            if result.is_ok() {
+                result = find_unpersist(child, kind, seen_tees);
+            }
+        });
+        result
+    }
+
+    let mut seen_tees = HashSet::new();
+    for leaf in ir {
+        let (HydroLeaf::ForEach { input, .. }
+        | HydroLeaf::DestSink { input, .. }
+        | HydroLeaf::CycleSink { input, .. }) = leaf;
+        find_unpersist(input, leaf.kind_name(), &mut seen_tees)?;
+    }
+    Ok(())
+}
+
+/// The location whose elements `node` produces, found by following its inputs back to a
+/// source or network receiver.
+fn location_of(node: &HydroNode) -> Option<LocationId> {
+    match node {
+        HydroNode::Source { location_kind, .. } | HydroNode::CycleSource { location_kind, .. } => {
+            Some(location_kind.clone())
+        }
+        HydroNode::Network { to_location, .. } => Some(to_location.clone()),
+        _ => {
+            let mut location = None;
+            node.visit_children(|child| {
+                if location.is_none() {
+                    location = location_of(child);
+                }
+            });
+            location
+        }
+    }
+}
+
+/// Panics if `name` cannot be used as the label passed to `stmt_ident`, which is the case if it is
+/// not a valid Rust identifier.
+pub(crate) fn assert_valid_label(name: &str) {
+    if syn::parse_str::<syn::Ident>(name).is_err() {
+        panic!("Operator name `{}` is not a valid identifier", name);
+    }
+}
+
+#[cfg(feature = "build")]
+fn stmt_ident(label: Option<&str>, id: usize) -> syn::Ident {
+    syn::Ident::new(
+        &format!("{}_{}", label.unwrap_or("stream"), id),
+        Span::call_site(),
+    )
+}
+
+#[cfg(feature = "build")]
+fn partition_output_ident(partition_ident: &syn::Ident, index: usize) -> syn::Ident {
+    syn::Ident::new(
+        &format!("{}_out_{}", partition_ident, index),
+        Span::call_site(),
+    )
+}
+
 #[cfg(feature = "build")]
 #[expect(clippy::too_many_arguments, reason = "networking internals")]
 fn instantiate_network<'a, D: Deploy<'a>>(
@@ -1330,8 +2021,36 @@ fn instantiate_network<'a, D: Deploy<'a>>(
                 D::e2o_connect(&from_node, &sink_port, &to_node, &source_port),
             )
         }
-        (LocationId::ExternalProcess(_from), LocationId::Cluster(_to)) => {
-            todo!("NYI")
+        (LocationId::ExternalProcess(from), LocationId::Cluster(to)) => {
+            let from_node = externals
+                .get(from)
+                .unwrap_or_else(|| {
+                    panic!(
+                        "A external used in the graph was not instantiated: {}",
+                        from
+                    )
+                })
+                .clone();
+
+            let to_node = clusters
+                .get(to)
+                .unwrap_or_else(|| {
+                    panic!("A cluster used in the graph was not instantiated: {}", to)
+                })
+                .clone();
+
+            let sink_port = D::allocate_external_port(&from_node);
+            let source_port = D::allocate_cluster_port(&to_node);
+
+            from_node.register(from_key.unwrap(), sink_port.clone());
+
+            (
+                (
+                    parse_quote!(DUMMY),
+                    D::e2m_source(compile_env, &from_node, &sink_port, &to_node, &source_port),
+                ),
+                D::e2m_connect(&from_node, &sink_port, &to_node, &source_port),
+            )
         }
         (LocationId::ExternalProcess(_), LocationId::ExternalProcess(_)) => {
             panic!("Cannot send from external to external")
@@ -1364,11 +2083,101 @@ fn instantiate_network<'a, D: Deploy<'a>>(
                 D::o2e_connect(&from_node, &sink_port, &to_node, &source_port),
             )
         }
-        (LocationId::Cluster(_from), LocationId::ExternalProcess(_to)) => {
-            todo!("NYI")
+        (LocationId::Cluster(from), LocationId::ExternalProcess(to)) => {
+            let from_node = clusters
+                .get(from)
+                .unwrap_or_else(|| {
+                    panic!("A cluster used in the graph was not instantiated: {}", from)
+                })
+                .clone();
+
+            let to_node = externals
+                .get(to)
+                .unwrap_or_else(|| {
+                    panic!("A external used in the graph was not instantiated: {}", to)
+                })
+                .clone();
+
+            let sink_port = D::allocate_cluster_port(&from_node);
+            let source_port = D::allocate_external_port(&to_node);
+
+            to_node.register(to_key.unwrap(), source_port.clone());
+
+            (
+                (
+                    D::m2e_sink(compile_env, &from_node, &sink_port, &to_node, &source_port),
+                    parse_quote!(DUMMY),
+                ),
+                D::m2e_connect(&from_node, &sink_port, &to_node, &source_port),
+            )
         }
         (LocationId::Tick(_, _), _) => panic!(),
         (_, LocationId::Tick(_, _)) => panic!(),
     };
     (sink, source, connect_fn)
 }
+
+#[cfg(test)]
+mod tests {
+    use stageleft::q;
+
+    use super::{check_unpersist_eliminated, SurvivingUnpersist};
+    use crate::builder::built::CompileError;
+    use crate::deploy::MultiGraph;
+    use crate::location::Location;
+    use crate::FlowBuilder;
+
+    struct P1 {}
+
+    #[test]
+    fn check_unpersist_eliminated_reports_surviving_marker() {
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        node.source_iter(q!(0..10))
+            .map(q!(|n| n + 1))
+            .for_each(q!(|n| println!("{}", n)));
+
+        let built = flow.finalize();
+        assert_eq!(
+            check_unpersist_eliminated(built.ir()),
+            Err(SurvivingUnpersist {
+                location: Some(node.id()),
+                consumer_kind: "ForEach",
+                input_kind: "Map",
+            })
+        );
+
+        let optimized = built.optimize_with(crate::rewrites::persist_pullup::persist_pullup);
+        assert_eq!(check_unpersist_eliminated(optimized.ir()), Ok(()));
+        let _ = optimized.compile_no_network::<MultiGraph>();
+    }
+
+    #[test]
+    fn compile_rejects_surviving_unpersist() {
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        node.source_iter(q!(0..10))
+            .map(q!(|n| n + 1))
+            .for_each(q!(|n| println!("{}", n)));
+
+        let error = flow
+            .finalize()
+            .try_compile_no_network::<MultiGraph>()
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "An `Unpersist` marker node at Process(0) between `ForEach` and its input `Map` was \
+             not optimized away. Rewrites that remove `Persist` nodes must also remove the \
+             `Unpersist` nodes that depend on them."
+        );
+        assert_eq!(
+            error,
+            CompileError::SurvivingUnpersist(SurvivingUnpersist {
+                location: Some(node.id()),
+                consumer_kind: "ForEach",
+                input_kind: "Map",
+            })
+        );
+    }
+}
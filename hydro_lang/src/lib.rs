@@ -6,6 +6,37 @@ pub use stageleft::q;
 #[doc(hidden)]
 pub mod runtime_support {
     pub use bincode;
+    use dfir_rs::bytes::Bytes;
+
+    pub mod batching;
+    pub mod bloom;
+    pub mod buffer;
+    pub mod changelog;
+    pub mod checksum;
+    pub mod compression;
+    pub mod debug_capture;
+    pub mod dedup;
+    pub mod durable;
+    pub mod gaps;
+    pub mod heartbeat;
+    pub mod hyperloglog;
+    pub mod parallel;
+    pub mod partition;
+    pub mod processing_window;
+    pub mod ratio;
+    pub mod reliable;
+    pub mod spill;
+    pub mod stall;
+    pub mod token_bucket;
+    pub mod trace;
+    pub mod versioned;
+    pub mod windowing;
+
+    /// Converts a payload for [`crate::Stream::send_bytes_into`] into the [`Bytes`] sent over the
+    /// network.
+    pub fn into_bytes<T: Into<Bytes>>(payload: T) -> Bytes {
+        payload.into()
+    }
 }
 
 pub mod runtime_context;
@@ -23,6 +54,11 @@ pub use singleton::Singleton;
 pub mod optional;
 pub use optional::Optional;
 
+pub mod windowing;
+
+#[cfg(feature = "arrow")]
+pub mod arrow;
+
 pub mod location;
 pub use location::cluster::CLUSTER_SELF_ID;
 pub use location::{Cluster, ClusterId, ExternalProcess, Location, Process, Tick, Timestamped};
@@ -50,6 +86,8 @@ pub mod test_util;
 fn add_private_reexports() {
     stageleft::add_private_reexport(vec!["tokio", "time", "instant"], vec!["tokio", "time"]);
     stageleft::add_private_reexport(vec!["bytes", "bytes"], vec!["bytes"]);
+    #[cfg(feature = "arrow")]
+    stageleft::add_private_reexport(vec!["arrow_array", "record_batch"], vec!["arrow_array"]);
 }
 
 #[stageleft::runtime]
@@ -4,7 +4,7 @@ use dfir_rs::bytes::Bytes;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 
-use super::{Location, LocationId, NoTick};
+use super::{CanSend, Location, LocationId, NoTick};
 use crate::builder::FlowState;
 use crate::ir::{HydroNode, HydroSource};
 use crate::staging_util::Invariant;
@@ -118,6 +118,7 @@ impl<'a, P> ExternalProcess<'a, P> {
                     serialize_fn: None,
                     instantiate_fn: crate::ir::DebugInstantiate::Building(),
                     deserialize_fn: Some(deser_expr.into()),
+                    batching: None,
                     input: Box::new(HydroNode::Source {
                         source: HydroSource::ExternalNetwork(),
                         location_kind: LocationId::ExternalProcess(self.id),
@@ -127,10 +128,23 @@ impl<'a, P> ExternalProcess<'a, P> {
         )
     }
 
+    /// Creates a port the external process can send bincode-serialized messages through, and
+    /// the stream receiving them at `to`.
+    ///
+    /// When `to` is a [`crate::Cluster`], the sink takes `(ClusterId, T)` pairs and routes each
+    /// message to the member it is addressed to, like [`Stream::send_bincode`] does.
+    #[expect(clippy::type_complexity, reason = "sink and stream pair")]
     pub fn source_external_bincode<L: Location<'a> + NoTick, T: Serialize + DeserializeOwned>(
         &self,
         to: &L,
-    ) -> (ExternalBincodeSink<T>, Stream<T, L, Unbounded>) {
+    ) -> (
+        ExternalBincodeSink<<Self as CanSend<'a, L>>::In<T>>,
+        Stream<T, L, Unbounded>,
+    )
+    where
+        Self: CanSend<'a, L>,
+        <Self as CanSend<'a, L>>::In<T>: Serialize,
+    {
         let next_external_port_id = {
             let mut flow_state = self.flow_state.borrow_mut();
             let id = flow_state.next_external_out;
@@ -154,6 +168,7 @@ impl<'a, P> ExternalProcess<'a, P> {
                     serialize_fn: None,
                     instantiate_fn: crate::ir::DebugInstantiate::Building(),
                     deserialize_fn: Some(crate::stream::deserialize_bincode::<T>(None).into()),
+                    batching: None,
                     input: Box::new(HydroNode::Source {
                         source: HydroSource::ExternalNetwork(),
                         location_kind: LocationId::ExternalProcess(self.id),
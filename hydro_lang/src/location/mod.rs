@@ -5,11 +5,15 @@ use std::time::Duration;
 use dfir_rs::futures::stream::Stream as FuturesStream;
 use dfir_rs::{tokio, tokio_stream};
 use proc_macro2::Span;
+use serde::de::DeserializeOwned;
 use stageleft::{q, QuotedWithContext};
+use syn::parse_quote;
 
 use super::builder::FlowState;
 use crate::cycle::{CycleCollection, ForwardRef, ForwardRefMarker};
 use crate::ir::{HydroNode, HydroSource};
+use crate::runtime_support::durable::DurableOffset;
+use crate::staging_util::get_this_crate;
 use crate::{Singleton, Stream, Unbounded};
 
 pub mod external_process;
@@ -133,6 +137,38 @@ pub trait Location<'a>: Clone {
         )
     }
 
+    /// Reads the records of the log at `log_path` (written with
+    /// [`crate::runtime_support::durable::append_record`]), starting after the offset committed
+    /// at `offset_path`.
+    ///
+    /// Each record is paired with the [`DurableOffset`] just after it. Committing that offset
+    /// once the record has been processed, with [`Stream::commit_offset`], means that after a
+    /// restart the log is replayed from the first record that was not yet committed. Records
+    /// processed after the last commit are replayed, so processing must be idempotent for
+    /// exactly-once results.
+    fn source_durable<T: DeserializeOwned>(
+        &self,
+        log_path: &str,
+        offset_path: &str,
+    ) -> Stream<(DurableOffset, T), Self, Unbounded>
+    where
+        Self: Sized + NoTick,
+    {
+        let root = get_this_crate();
+        let t_type: syn::Type = stageleft::quote_type::<T>();
+        let e: syn::Expr = parse_quote!(
+            #root::runtime_support::durable::replay::<#t_type>(#log_path, #offset_path)
+        );
+
+        Stream::new(
+            self.clone(),
+            HydroNode::Persist(Box::new(HydroNode::Source {
+                source: HydroSource::Stream(e.into()),
+                location_kind: self.id(),
+            })),
+        )
+    }
+
     fn singleton<T: Clone>(
         &self,
         e: impl QuotedWithContext<'a, T, Self>,
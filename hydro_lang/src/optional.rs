@@ -8,7 +8,7 @@ use syn::parse_quote;
 
 use crate::builder::FLOW_USED_MESSAGE;
 use crate::cycle::{CycleCollection, CycleComplete, DeferTick, ForwardRefMarker, TickCycleMarker};
-use crate::ir::{HydroLeaf, HydroNode, HydroSource, TeeNode};
+use crate::ir::{assert_valid_label, HydroLeaf, HydroNode, HydroSource, TeeNode};
 use crate::location::tick::{NoTimestamp, Timestamped};
 use crate::location::{check_matching_location, LocationId, NoTick};
 use crate::singleton::ZipResult;
@@ -208,6 +208,23 @@ impl<'a, T, L: Location<'a>, B> Optional<T, L, B> {
         )
     }
 
+    /// Labels the operator that produces this optional, so that the generated DFIR uses
+    /// `name` in its ident, see [`Stream::named`].
+    ///
+    /// # Panics
+    /// Panics if `name` is not a valid Rust identifier.
+    pub fn named(self, name: &str) -> Optional<T, L, B> {
+        assert_valid_label(name);
+
+        Optional::new(
+            self.location,
+            HydroNode::Named {
+                name: name.to_string(),
+                input: Box::new(self.ir_node.into_inner()),
+            },
+        )
+    }
+
     pub fn flat_map_ordered<U, I: IntoIterator<Item = U>, F: Fn(T) -> I + 'a>(
         self,
         f: impl IntoQuotedMut<'a, F, L>,
@@ -326,28 +343,45 @@ impl<'a, T, L: Location<'a>, B> Optional<T, L, B> {
         }
     }
 
-    pub fn unwrap_or(self, other: Singleton<T, L, B>) -> Singleton<T, L, B> {
+    /// Yields the value of `self` if it is present, and otherwise the value of `other`.
+    pub fn or_else(self, other: Optional<T, L, B>) -> Optional<T, L, B> {
         check_matching_location(&self.location, &other.location);
 
+        // Chaining emits the value of `self` first, so keeping the first value prefers it.
+        let keep_first: syn::Expr = parse_quote!(|_first, _fallback| {});
+
         if L::is_top_level() {
-            Singleton::new(
+            Optional::new(
                 self.location,
-                HydroNode::Persist(Box::new(HydroNode::Chain(
-                    Box::new(HydroNode::Unpersist(Box::new(self.ir_node.into_inner()))),
-                    Box::new(HydroNode::Unpersist(Box::new(other.ir_node.into_inner()))),
-                ))),
+                HydroNode::Persist(Box::new(HydroNode::Reduce {
+                    f: keep_first.into(),
+                    input: Box::new(HydroNode::Chain(
+                        Box::new(HydroNode::Unpersist(Box::new(self.ir_node.into_inner()))),
+                        Box::new(HydroNode::Unpersist(Box::new(other.ir_node.into_inner()))),
+                    )),
+                })),
             )
         } else {
-            Singleton::new(
+            Optional::new(
                 self.location,
-                HydroNode::Chain(
-                    Box::new(self.ir_node.into_inner()),
-                    Box::new(other.ir_node.into_inner()),
-                ),
+                HydroNode::Reduce {
+                    f: keep_first.into(),
+                    input: Box::new(HydroNode::Chain(
+                        Box::new(self.ir_node.into_inner()),
+                        Box::new(other.ir_node.into_inner()),
+                    )),
+                },
             )
         }
     }
 
+    /// Yields the value of `self` if it is present, and otherwise the value of `other`, which
+    /// is always present.
+    pub fn unwrap_or(self, other: Singleton<T, L, B>) -> Singleton<T, L, B> {
+        let selected = self.or_else(other.into());
+        Singleton::new(selected.location, selected.ir_node.into_inner())
+    }
+
     pub fn into_singleton(self) -> Singleton<Option<T>, L, B>
     where
         T: Clone,
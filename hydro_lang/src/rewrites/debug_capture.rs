@@ -0,0 +1,139 @@
+use std::path::Path;
+
+use syn::parse_quote;
+
+use crate::ir::*;
+use crate::location::LocationId;
+use crate::staging_util::get_this_crate;
+
+/// Add a tap before each node which records the first `max_samples` elements it produces
+fn add_capture_node(
+    node: &mut HydroNode,
+    path: &str,
+    max_samples: usize,
+    id: &mut usize,
+    seen_tees: &mut SeenTees,
+) {
+    node.transform_children(
+        |node, seen_tees| add_capture_node(node, path, max_samples, id, seen_tees),
+        seen_tees,
+    );
+
+    // Operators that match on a `Persist` input must stay adjacent to it, partitions are only
+    // consumed through their outputs, and external processes do not run any operators.
+    if matches!(
+        node,
+        HydroNode::Placeholder
+            | HydroNode::Persist(_)
+            | HydroNode::Unpersist(_)
+            | HydroNode::Partition { .. }
+            | HydroNode::Network {
+                to_location: LocationId::ExternalProcess(_),
+                ..
+            }
+    ) {
+        return;
+    }
+
+    let tap = *id;
+    *id += 1;
+
+    let label = node.kind_name();
+    let root = get_this_crate();
+    let f: syn::Expr = parse_quote! {
+        {
+            #[allow(unused_imports)]
+            use #root::runtime_support::debug_capture::{SampleDebug as _, SampleOpaque as _};
+            // Operator closures are recreated every tick, so the count is kept in a static,
+            // which is separate for each tap.
+            static SAMPLE_COUNT: ::std::sync::atomic::AtomicUsize = ::std::sync::atomic::AtomicUsize::new(0);
+            |v| #root::runtime_support::debug_capture::record_sample(#path, #tap, #label, #max_samples, &SAMPLE_COUNT, || {
+                (&#root::runtime_support::debug_capture::Sample(v)).sample()
+            })
+        }
+    };
+
+    let orig_node = std::mem::replace(node, HydroNode::Placeholder);
+    *node = HydroNode::Inspect {
+        f: f.into(),
+        input: Box::new(orig_node),
+    }
+}
+
+/// Write the IR to `path`, and sample the first `max_samples` elements of each node into it at runtime
+pub fn debug_capture(ir: Vec<HydroLeaf>, path: &Path, max_samples: usize) -> Vec<HydroLeaf> {
+    // The samples are written by the deployed binaries, which run in a different directory.
+    let path = std::path::absolute(path).expect("Failed to resolve debug capture path.");
+    std::fs::write(
+        &path,
+        format!(
+            "=== IR ===\n{}\n=== SAMPLES ===\n",
+            dbg_dedup_tee(|| format!("{:#?}", ir))
+        ),
+    )
+    .expect("Failed to write debug capture file.");
+
+    let path = path.to_str().expect("Debug capture path must be UTF-8.");
+    let mut id = 0;
+    let mut seen_tees = Default::default();
+    ir.into_iter()
+        .map(|l| {
+            l.transform_children(
+                |node, seen_tees| add_capture_node(node, path, max_samples, &mut id, seen_tees),
+                &mut seen_tees,
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use dfir_rs::futures::StreamExt;
+    use hydro_deploy::Deployment;
+    use stageleft::*;
+
+    use crate::location::Location;
+
+    #[tokio::test]
+    async fn debug_capture_writes_ir_and_samples() {
+        let mut deployment = Deployment::new();
+
+        let flow = crate::builder::FlowBuilder::new();
+        let process = flow.process::<()>();
+        let external = flow.external_process::<()>();
+
+        let out_port = process
+            .source_iter(q!(0..10))
+            .map(q!(|v| v * 10))
+            .send_bincode_external(&external);
+
+        let path =
+            std::env::temp_dir().join(format!("hydro_debug_capture_{}.txt", std::process::id()));
+        let nodes = flow
+            .with_debug_capture(&path, 3)
+            .with_process(&process, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        for i in 0..10 {
+            assert_eq!(out.next().await.unwrap(), i * 10);
+        }
+
+        let capture = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(capture.starts_with("=== IR ===\n"));
+        assert!(capture.contains("Map {"));
+
+        let samples = capture.split("=== SAMPLES ===\n").nth(1).unwrap();
+        assert!(samples.contains("Source] 2\n"));
+        assert!(samples.contains("Map] 20\n"));
+        assert!(!samples.contains("Map] 30\n"));
+    }
+}
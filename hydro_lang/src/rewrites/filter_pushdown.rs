@@ -0,0 +1,156 @@
+use crate::ir::*;
+
+/// Applies the filter `f` to `input`, placing it behind every map that `input` starts with
+/// which is marked as [`HydroNode::FilterTransparent`], whether or not the map is labeled.
+fn push_filter(f: DebugExpr, input: HydroNode) -> HydroNode {
+    match_box::match_box! {
+        match input {
+            HydroNode::FilterTransparent(mb!(* HydroNode::Map { f: map_f, input })) => {
+                HydroNode::FilterTransparent(Box::new(HydroNode::Map {
+                    f: map_f,
+                    input: Box::new(push_filter(f, *input)),
+                }))
+            }
+
+            // The label stays on the map, which is still the operator that produces its output.
+            HydroNode::Named {
+                name,
+                input: mb!(* HydroNode::FilterTransparent(map)),
+            } => {
+                if let HydroNode::Map { .. } = map.as_ref() {
+                    HydroNode::Named {
+                        name,
+                        input: Box::new(push_filter(f, HydroNode::FilterTransparent(map))),
+                    }
+                } else {
+                    HydroNode::Filter {
+                        f,
+                        input: Box::new(HydroNode::Named {
+                            name,
+                            input: Box::new(HydroNode::FilterTransparent(map)),
+                        }),
+                    }
+                }
+            }
+
+            input => HydroNode::Filter {
+                f,
+                input: Box::new(input),
+            },
+        }
+    }
+}
+
+fn filter_pushdown_node(node: &mut HydroNode, _: &mut ()) {
+    if let HydroNode::Filter { .. } = node {
+        if let HydroNode::Filter { f, input } = std::mem::replace(node, HydroNode::Placeholder) {
+            *node = push_filter(f, *input);
+        }
+    }
+}
+
+/// Moves each filter ahead of the maps before it that were created with
+/// [`crate::Stream::map_preserving_filters`], so that those maps only process the elements
+/// that are kept.
+///
+/// Closures are opaque to the rewrite, so it only relies on that annotation, and never moves a
+/// filter across any other node. In particular, a filter is never moved across a `Network`,
+/// whose elements are observed on another location, nor across a stateful node such as a fold
+/// or `enumerate`, whose output depends on every element it has seen. A filter is also never
+/// moved behind a `Tee`, because the nodes behind it are shared with other consumers that
+/// must still see every element.
+pub fn filter_pushdown(ir: Vec<HydroLeaf>) -> Vec<HydroLeaf> {
+    let mut seen_tees = Default::default();
+    ir.into_iter()
+        .map(|l| {
+            l.transform_children(
+                |n, s| n.transform_bottom_up(filter_pushdown_node, s, &mut ()),
+                &mut seen_tees,
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use stageleft::*;
+
+    use crate::deploy::MultiGraph;
+    use crate::location::Location;
+
+    #[test]
+    fn filter_pushdown_through_annotated_maps() {
+        let flow = crate::builder::FlowBuilder::new();
+        let process = flow.process::<()>();
+
+        process
+            .source_iter(q!(vec![(1, 100), (2, 250), (3, 400)]))
+            .map_preserving_filters(q!(|(id, cents)| (id, cents * 2)))
+            .map_preserving_filters(q!(|(id, cents)| (id, cents + 1)))
+            .filter(q!(|(id, _)| *id != 2))
+            .for_each(q!(|n| println!("{:?}", n)));
+
+        let built = flow.optimize_with(crate::rewrites::persist_pullup::persist_pullup);
+
+        insta::assert_debug_snapshot!(built.ir());
+
+        let optimized = built.with_filter_pushdown();
+
+        insta::assert_debug_snapshot!(optimized.ir());
+        for (id, graph) in optimized.compile_no_network::<MultiGraph>().hydroflow_ir() {
+            insta::with_settings!({snapshot_suffix => format!("surface_graph_{id}")}, {
+                insta::assert_snapshot!(graph.surface_syntax_string());
+            });
+        }
+    }
+
+    #[test]
+    fn filter_pushdown_stops_at_unannotated_map_and_tee() {
+        let flow = crate::builder::FlowBuilder::new();
+        let process = flow.process::<()>();
+
+        let shared = process
+            .source_iter(q!(vec![(1, 100), (2, 250)]))
+            .map_preserving_filters(q!(|(id, cents)| (id, cents * 2)));
+        shared
+            .clone()
+            .filter(q!(|(id, _)| *id == 1))
+            .for_each(q!(|n| println!("{:?}", n)));
+        shared
+            .map(q!(|(id, cents)| (id, cents + 1)))
+            .filter(q!(|(id, _)| *id == 2))
+            .for_each(q!(|n| println!("{:?}", n)));
+
+        let built = flow.optimize_with(crate::rewrites::persist_pullup::persist_pullup);
+        let before = format!("{:?}", built.ir());
+
+        let optimized = built.with_filter_pushdown();
+        assert_eq!(format!("{:?}", optimized.ir()), before);
+        let _ = optimized.compile_no_network::<MultiGraph>();
+    }
+
+    #[test]
+    fn filter_pushdown_through_labeled_map() {
+        let flow = crate::builder::FlowBuilder::new();
+        let process = flow.process::<()>();
+
+        process
+            .source_iter(q!(vec![(1, 100), (2, 250)]))
+            .map_preserving_filters(q!(|(id, cents)| (id, cents * 2)))
+            .named("double")
+            .filter(q!(|(id, _)| *id != 2))
+            .for_each(q!(|n| println!("{:?}", n)));
+
+        let optimized = flow
+            .optimize_with(crate::rewrites::persist_pullup::persist_pullup)
+            .with_filter_pushdown();
+
+        // The filter is now the input of the labeled map, rather than its consumer.
+        let ir = format!("{:?}", optimized.ir());
+        let named = ir.find("Named").unwrap();
+        let map = ir.find("Map {").unwrap();
+        let filter = ir.find("Filter {").unwrap();
+        assert!(named < map && map < filter, "{}", ir);
+        let _ = optimized.compile_no_network::<MultiGraph>();
+    }
+}
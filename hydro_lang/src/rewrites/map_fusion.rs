@@ -0,0 +1,219 @@
+use syn::parse_quote;
+
+use crate::ir::*;
+
+/// Fuses `node` with its input if both apply a closure to each element independently. Children
+/// are transformed first, so a chain of maps collapses into a single node.
+///
+/// A [`HydroNode::Tee`] is never fused into its consumers, because the nodes behind it are
+/// shared and must still produce their output for every consumer.
+fn map_fusion_node(node: &mut HydroNode, _: &mut ()) {
+    let label = take_fusable_label(node);
+
+    *node = match_box::match_box! {
+        match std::mem::replace(node, HydroNode::Placeholder) {
+            HydroNode::Map {
+                f: outer,
+                input: mb!(* HydroNode::Map { f: inner, input }),
+            } => HydroNode::Map {
+                f: compose(inner, outer, parse_quote!(outer_fn(inner_fn(x)))),
+                input,
+            },
+
+            HydroNode::FilterMap {
+                f: outer,
+                input: mb!(* HydroNode::Map { f: inner, input }),
+            } => HydroNode::FilterMap {
+                f: compose(inner, outer, parse_quote!(outer_fn(inner_fn(x)))),
+                input,
+            },
+
+            HydroNode::FlatMap {
+                f: outer,
+                input: mb!(* HydroNode::Map { f: inner, input }),
+            } => HydroNode::FlatMap {
+                f: compose(inner, outer, parse_quote!(outer_fn(inner_fn(x)))),
+                input,
+            },
+
+            HydroNode::Map {
+                f: outer,
+                input: mb!(* HydroNode::FilterMap { f: inner, input }),
+            } => HydroNode::FilterMap {
+                f: compose(inner, outer, parse_quote!(inner_fn(x).map(|y| outer_fn(y)))),
+                input,
+            },
+
+            HydroNode::FilterMap {
+                f: outer,
+                input: mb!(* HydroNode::FilterMap { f: inner, input }),
+            } => HydroNode::FilterMap {
+                f: compose(inner, outer, parse_quote!(inner_fn(x).and_then(|y| outer_fn(y)))),
+                input,
+            },
+
+            node => node,
+        }
+    };
+
+    if let Some(name) = label {
+        let fused = std::mem::replace(node, HydroNode::Placeholder);
+        *node = HydroNode::Named {
+            name,
+            input: Box::new(fused),
+        };
+    }
+}
+
+/// If `node` applies a closure to each element of a [`HydroNode::Named`] input that could be
+/// fused with it, removes the label from the input and returns it, so that it can be put back on
+/// the fused node.
+fn take_fusable_label(node: &mut HydroNode) -> Option<String> {
+    let (HydroNode::Map { input, .. }
+    | HydroNode::FilterMap { input, .. }
+    | HydroNode::FlatMap { input, .. }) = node
+    else {
+        return None;
+    };
+
+    let HydroNode::Named {
+        input: labeled_input,
+        ..
+    } = input.as_ref()
+    else {
+        return None;
+    };
+    if !matches!(
+        labeled_input.as_ref(),
+        HydroNode::Map { .. } | HydroNode::FilterMap { .. }
+    ) {
+        return None;
+    }
+
+    let HydroNode::Named {
+        name,
+        input: labeled_input,
+    } = std::mem::replace(input.as_mut(), HydroNode::Placeholder)
+    else {
+        unreachable!()
+    };
+    *input = labeled_input;
+    Some(name)
+}
+
+/// Builds a closure that binds the `inner` and `outer` closures once, as `inner_fn` and
+/// `outer_fn`, and evaluates `body` for each element `x`.
+fn compose(inner: DebugExpr, outer: DebugExpr, body: syn::Expr) -> DebugExpr {
+    let fused: syn::Expr = parse_quote! {
+        {
+            let inner_fn = #inner;
+            let outer_fn = #outer;
+            move |x| #body
+        }
+    };
+    fused.into()
+}
+
+/// Collapses chains of `map`, `filter_map`, and `flat_map` into a single operator, so that each
+/// element is passed through one closure instead of one per operator.
+pub fn map_fusion(ir: Vec<HydroLeaf>) -> Vec<HydroLeaf> {
+    let mut seen_tees = Default::default();
+    ir.into_iter()
+        .map(|l| {
+            l.transform_children(
+                |n, s| n.transform_bottom_up(map_fusion_node, s, &mut ()),
+                &mut seen_tees,
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use stageleft::*;
+
+    use crate::deploy::MultiGraph;
+    use crate::location::Location;
+
+    #[test]
+    fn map_fusion_three_maps() {
+        let flow = crate::builder::FlowBuilder::new();
+        let process = flow.process::<()>();
+
+        process
+            .source_iter(q!(0..10))
+            .map(q!(|v| v + 1))
+            .map(q!(|v| v * 2))
+            .map(q!(|v| v.to_string()))
+            .for_each(q!(|n| println!("{}", n)));
+
+        let built = flow.optimize_with(crate::rewrites::persist_pullup::persist_pullup);
+
+        insta::assert_debug_snapshot!(built.ir());
+
+        let optimized = built.with_map_fusion();
+
+        insta::assert_debug_snapshot!(optimized.ir());
+        for (id, graph) in optimized.compile_no_network::<MultiGraph>().hydroflow_ir() {
+            insta::with_settings!({snapshot_suffix => format!("surface_graph_{id}")}, {
+                insta::assert_snapshot!(graph.surface_syntax_string());
+            });
+        }
+    }
+
+    #[test]
+    fn map_fusion_stops_at_tee() {
+        let flow = crate::builder::FlowBuilder::new();
+        let process = flow.process::<()>();
+
+        let shared = process.source_iter(q!(0..10)).map(q!(|v| v + 1));
+        shared
+            .clone()
+            .map(q!(|v| v * 2))
+            .for_each(q!(|n| println!("{}", n)));
+        shared
+            .filter_map(q!(|v| if v % 2 == 0 { Some(v) } else { None }))
+            .for_each(q!(|n| println!("{}", n)));
+
+        let mut optimized = flow.with_map_fusion();
+        assert_eq!(
+            optimized.ir_histogram(),
+            HashMap::from([
+                ("ForEach", 2),
+                ("Source", 1),
+                ("Map", 2),
+                ("FilterMap", 1),
+                ("Tee", 1),
+            ])
+        );
+        let _ = optimized.compile_no_network::<MultiGraph>();
+    }
+
+    #[test]
+    fn map_fusion_keeps_label() {
+        let flow = crate::builder::FlowBuilder::new();
+        let process = flow.process::<()>();
+
+        process
+            .source_iter(q!(vec!["1", "2", "3"]))
+            .map(q!(|s| s.parse::<u32>().unwrap()))
+            .named("parse")
+            .map(q!(|v| v * 2))
+            .for_each(q!(|n| println!("{}", n)));
+
+        let mut optimized = flow.with_map_fusion();
+        assert_eq!(
+            optimized.ir_histogram(),
+            HashMap::from([("ForEach", 1), ("Source", 1), ("Named", 1), ("Map", 1)])
+        );
+
+        let compiled = optimized.compile_no_network::<MultiGraph>();
+        let graph = compiled.hydroflow_ir().values().next().unwrap();
+        assert!(graph
+            .node_ids()
+            .filter_map(|id| graph.node_varname(id))
+            .any(|ident| ident.to_string().starts_with("parse_")));
+    }
+}
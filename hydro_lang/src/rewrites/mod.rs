@@ -1,3 +1,7 @@
+pub mod debug_capture;
+pub mod filter_pushdown;
+pub mod map_fusion;
+pub mod operator_names;
 pub mod persist_pullup;
 pub mod profiler;
 pub mod properties;
@@ -0,0 +1,105 @@
+use crate::ir::*;
+
+/// The label given to nodes of the given kind, such as `flat_map` for `FlatMap`.
+fn kind_label(kind_name: &str) -> String {
+    let mut label = String::new();
+    for (i, c) in kind_name.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 {
+                label.push('_');
+            }
+            label.push(c.to_ascii_lowercase());
+        } else {
+            label.push(c);
+        }
+    }
+    label
+}
+
+fn operator_names_node(node: &mut HydroNode, _: &mut ()) {
+    match node {
+        // Consumers check whether their input is a `Persist` when they are emitted, so it must
+        // not be hidden behind a label.
+        HydroNode::Persist(_) | HydroNode::Placeholder => {}
+
+        HydroNode::Named { input, .. } => {
+            // The input was labeled with its kind when it was visited, which would take
+            // precedence over this label.
+            if let HydroNode::Named {
+                name,
+                input: labeled,
+            } = input.as_mut()
+            {
+                if *name == kind_label(labeled.kind_name()) {
+                    let labeled = std::mem::replace(labeled.as_mut(), HydroNode::Placeholder);
+                    **input = labeled;
+                }
+            }
+        }
+
+        _ => {
+            let name = kind_label(node.kind_name());
+            let input = std::mem::replace(node, HydroNode::Placeholder);
+            *node = HydroNode::Named {
+                name,
+                input: Box::new(input),
+            };
+        }
+    }
+}
+
+/// Labels every operator with the name of its kind, unless it has a label already.
+///
+/// The generated idents then read like `map_42` instead of `stream_42`. This runs just before the
+/// IR is emitted, as the labels would otherwise get in the way of other rewrites.
+pub fn operator_names(ir: Vec<HydroLeaf>) -> Vec<HydroLeaf> {
+    let mut seen_tees = Default::default();
+    ir.into_iter()
+        .map(|l| {
+            l.transform_children(
+                |n, s| n.transform_bottom_up(operator_names_node, s, &mut ()),
+                &mut seen_tees,
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use stageleft::*;
+
+    use crate::deploy::MultiGraph;
+    use crate::location::Location;
+
+    #[test]
+    fn operator_names_label_idents() {
+        let flow = crate::builder::FlowBuilder::new().with_operator_names();
+        let process = flow.process::<()>();
+
+        let numbers = process
+            .source_iter(q!(vec!["1", "2", "3"]))
+            .map(q!(|s| s.parse::<u32>().unwrap()))
+            .named("parse");
+        numbers
+            .filter(q!(|n| *n > 1))
+            .for_each(q!(|n| println!("{}", n)));
+
+        let compiled = flow
+            .with_default_optimize::<MultiGraph>()
+            .compile_no_network();
+
+        let graph = compiled.hydroflow_ir().values().next().unwrap();
+        let mut varnames = graph
+            .node_ids()
+            .filter_map(|id| graph.node_varname(id))
+            .map(|ident| ident.to_string())
+            .collect::<Vec<_>>();
+        varnames.sort();
+        varnames.dedup();
+        let prefixes = varnames
+            .iter()
+            .map(|name| name.rsplit_once('_').unwrap().0)
+            .collect::<Vec<_>>();
+        assert_eq!(prefixes, vec!["filter", "parse", "source"]);
+    }
+}
@@ -36,6 +36,36 @@ fn persist_pullup_node(
                 }
             }
 
+            HydroNode::PartitionOutput { index, inner } => {
+                if persist_pulled_tees.contains(&(inner.0.as_ref() as *const RefCell<HydroNode>)) {
+                    HydroNode::Persist(Box::new(HydroNode::PartitionOutput {
+                        index,
+                        inner: TeeNode(inner.0.clone()),
+                    }))
+                } else if matches!(
+                    &*inner.0.borrow(),
+                    HydroNode::Partition { input, .. } if matches!(**input, HydroNode::Persist(_))
+                ) {
+                    persist_pulled_tees.insert(inner.0.as_ref() as *const RefCell<HydroNode>);
+                    if let HydroNode::Partition { input, .. } = &mut *inner.0.borrow_mut() {
+                        if let HydroNode::Persist(behind_persist) =
+                            std::mem::replace(input.as_mut(), HydroNode::Placeholder)
+                        {
+                            *input = behind_persist;
+                        } else {
+                            unreachable!()
+                        }
+                    }
+
+                    HydroNode::Persist(Box::new(HydroNode::PartitionOutput {
+                        index,
+                        inner: TeeNode(inner.0.clone()),
+                    }))
+                } else {
+                    HydroNode::PartitionOutput { index, inner }
+                }
+            }
+
             HydroNode::Map {
                 f,
                 input: mb!(* HydroNode::Persist(behind_persist)),
@@ -68,6 +98,18 @@ fn persist_pullup_node(
                 input: behind_persist,
             })),
 
+            HydroNode::FilterTransparent(mb!(* HydroNode::Persist(behind_persist))) => {
+                HydroNode::Persist(Box::new(HydroNode::FilterTransparent(behind_persist)))
+            }
+
+            HydroNode::Named {
+                name,
+                input: mb!(* HydroNode::Persist(behind_persist)),
+            } => HydroNode::Persist(Box::new(HydroNode::Named {
+                name,
+                input: behind_persist,
+            })),
+
             HydroNode::Network {
                 from_location,
                 from_key,
@@ -76,6 +118,7 @@ fn persist_pullup_node(
                 serialize_fn,
                 instantiate_fn,
                 deserialize_fn,
+                batching,
                 input: mb!(* HydroNode::Persist(behind_persist)),
                 ..
             } => HydroNode::Persist(Box::new(HydroNode::Network {
@@ -86,6 +129,7 @@ fn persist_pullup_node(
                 serialize_fn,
                 instantiate_fn,
                 deserialize_fn,
+                batching,
                 input: behind_persist,
             })),
 
@@ -0,0 +1,145 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+
+use dfir_rs::futures::{Sink, SinkExt};
+use tokio::sync::mpsc::{channel, Receiver};
+use tokio::time::Instant;
+
+/// Wraps `sink` so that items are coalesced into batches before being sent, for
+/// [`crate::Stream::send_bincode_batched`]. Each batch is turned into the messages for `sink`
+/// with `frame_fn`.
+///
+/// Items are handed off to a background task, which frames a batch as soon as it reaches
+/// `max_batch` items, or once `max_delay` has elapsed since the first item of the batch was
+/// received. Because of this, flushing the returned sink does not wait for buffered items to
+/// reach `sink`. At most `max_batch` items wait for the task, after which sending waits for it
+/// to catch up. If `sink` fails, the error is returned by the next send.
+pub fn batch_sink<S, I, O, F>(
+    sink: S,
+    max_batch: usize,
+    max_delay: Duration,
+    frame_fn: F,
+) -> impl Sink<I, Error = S::Error> + Unpin
+where
+    S: Sink<O> + Unpin + 'static,
+    I: 'static,
+    O: 'static,
+    F: FnMut(Vec<I>) -> Vec<O> + 'static,
+{
+    assert!(max_batch > 0, "max_batch must be at least 1");
+
+    let (items_send, items_recv) = channel(max_batch);
+    let error = Rc::new(RefCell::new(None));
+
+    // The sink may be created before the runtime starts, so the task is spawned on first use.
+    let mut driver = Some(drive_batches(
+        items_recv,
+        sink,
+        max_batch,
+        max_delay,
+        frame_fn,
+        error.clone(),
+    ));
+
+    Box::pin(dfir_rs::futures::sink::unfold(
+        items_send,
+        move |items_send, item| {
+            if let Some(driver) = driver.take() {
+                tokio::task::spawn_local(driver);
+            }
+
+            let error = error.clone();
+            async move {
+                if items_send.send(item).await.is_err() {
+                    let error = error.borrow_mut().take();
+                    return Err(error.expect("Batching task stopped without an error."));
+                }
+                Ok(items_send)
+            }
+        },
+    ))
+}
+
+async fn drive_batches<S, I, O, F>(
+    mut items: Receiver<I>,
+    mut sink: S,
+    max_batch: usize,
+    max_delay: Duration,
+    mut frame_fn: F,
+    error: Rc<RefCell<Option<S::Error>>>,
+) where
+    S: Sink<O> + Unpin,
+    F: FnMut(Vec<I>) -> Vec<O>,
+{
+    while let Some(first) = items.recv().await {
+        let deadline = Instant::now() + max_delay;
+        let mut batch = vec![first];
+        while batch.len() < max_batch {
+            match tokio::time::timeout_at(deadline, items.recv()).await {
+                Ok(Some(item)) => batch.push(item),
+                Ok(None) | Err(_) => break,
+            }
+        }
+
+        let mut frames = dfir_rs::futures::stream::iter(frame_fn(batch).into_iter().map(Ok));
+        if let Err(e) = sink.send_all(&mut frames).await {
+            // Dropping `items` makes the next send fail, which reports this error.
+            *error.borrow_mut() = Some(e);
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use dfir_rs::futures::channel::mpsc;
+    use dfir_rs::futures::{SinkExt, StreamExt};
+    use tokio::task::LocalSet;
+
+    use super::batch_sink;
+
+    #[tokio::test]
+    async fn frames_full_and_partial_batches() {
+        LocalSet::new()
+            .run_until(async {
+                let (send, recv) = mpsc::unbounded();
+                let mut sink = batch_sink(send, 3, Duration::from_millis(10), |batch: Vec<u32>| {
+                    vec![batch]
+                });
+
+                for item in 0..7 {
+                    sink.send(item).await.unwrap();
+                }
+
+                let frames = recv.take(3).collect::<Vec<_>>().await;
+                assert_eq!(frames, vec![vec![0, 1, 2], vec![3, 4, 5], vec![6]]);
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn returns_sink_errors() {
+        LocalSet::new()
+            .run_until(async {
+                let (send, recv) = mpsc::unbounded();
+                drop(recv);
+                let mut sink = batch_sink(send, 1, Duration::from_millis(10), |batch: Vec<u32>| {
+                    vec![batch]
+                });
+
+                let mut result = Ok(());
+                for item in 0..10 {
+                    result = sink.send(item).await;
+                    if result.is_err() {
+                        break;
+                    }
+                    tokio::task::yield_now().await;
+                }
+                assert!(result.unwrap_err().is_disconnected());
+            })
+            .await;
+    }
+}
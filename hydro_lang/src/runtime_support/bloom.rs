@@ -0,0 +1,121 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// A Bloom filter, built by [`crate::Stream::build_bloom`] and probed by
+/// [`crate::Stream::filter_in_bloom`].
+///
+/// Membership checks may report false positives but never false negatives. The bits are
+/// shared between clones until one of them is modified, so the filter is cheap to pair with
+/// every element of a stream.
+///
+/// Items are hashed with the standard library's default hasher, so a filter should only be
+/// probed by programs built with the same toolchain as the one that built it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BloomFilter {
+    #[serde(
+        serialize_with = "serialize_bits",
+        deserialize_with = "deserialize_bits"
+    )]
+    bits: Arc<Vec<u64>>,
+    num_hashes: u32,
+}
+
+fn serialize_bits<S: Serializer>(bits: &Arc<Vec<u64>>, serializer: S) -> Result<S::Ok, S::Error> {
+    bits.as_slice().serialize(serializer)
+}
+
+fn deserialize_bits<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Arc<Vec<u64>>, D::Error> {
+    Vec::deserialize(deserializer).map(Arc::new)
+}
+
+impl Default for BloomFilter {
+    /// A filter with 2^20 bits (128 KiB) and 7 hash functions, which has a false positive
+    /// rate of about 1% with 100,000 items.
+    fn default() -> Self {
+        BloomFilter::new(1 << 20, 7)
+    }
+}
+
+impl BloomFilter {
+    /// Creates an empty filter with at least `num_bits` bits (rounded up to a multiple of 64),
+    /// setting `num_hashes` bits per item.
+    pub fn new(num_bits: usize, num_hashes: u32) -> Self {
+        assert!(num_bits > 0, "a Bloom filter needs at least one bit");
+        assert!(
+            num_hashes > 0,
+            "a Bloom filter needs at least one hash function"
+        );
+        BloomFilter {
+            bits: Arc::new(vec![0; num_bits.div_ceil(64)]),
+            num_hashes,
+        }
+    }
+
+    /// Adds `item` to the filter.
+    pub fn insert<T: Hash + ?Sized>(&mut self, item: &T) {
+        let num_bits = self.bits.len() as u64 * 64;
+        let (h1, h2) = hash_pair(item);
+        let bits = Arc::make_mut(&mut self.bits);
+        for i in 0..self.num_hashes as u64 {
+            let bit = h1.wrapping_add(i.wrapping_mul(h2)) % num_bits;
+            bits[(bit / 64) as usize] |= 1 << (bit % 64);
+        }
+    }
+
+    /// Returns `false` if `item` was definitely never added, and `true` if it may have been.
+    pub fn contains<T: Hash + ?Sized>(&self, item: &T) -> bool {
+        let num_bits = self.bits.len() as u64 * 64;
+        let (h1, h2) = hash_pair(item);
+        (0..self.num_hashes as u64).all(|i| {
+            let bit = h1.wrapping_add(i.wrapping_mul(h2)) % num_bits;
+            self.bits[(bit / 64) as usize] & (1 << (bit % 64)) != 0
+        })
+    }
+}
+
+/// Two independent hashes of `item`, combined to derive each of the filter's bit positions.
+fn hash_pair<T: Hash + ?Sized>(item: &T) -> (u64, u64) {
+    let mut hasher = DefaultHasher::new();
+    item.hash(&mut hasher);
+    let h1 = hasher.finish();
+
+    let mut hasher = DefaultHasher::new();
+    h1.hash(&mut hasher);
+    item.hash(&mut hasher);
+    // An odd step is never a multiple of the (even) number of bits, so the hash functions
+    // do not all land on the same bit.
+    (h1, hasher.finish() | 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BloomFilter;
+
+    #[test]
+    fn no_false_negatives_and_few_false_positives() {
+        let mut filter = BloomFilter::new(1 << 14, 5);
+        for i in (0..2000).step_by(2) {
+            filter.insert(&i);
+        }
+
+        assert!((0..2000).step_by(2).all(|i| filter.contains(&i)));
+
+        let false_positives = (1..2000).step_by(2).filter(|i| filter.contains(i)).count();
+        assert!(false_positives < 50, "{} false positives", false_positives);
+    }
+
+    #[test]
+    fn clones_share_bits_until_modified() {
+        let mut filter = BloomFilter::new(64, 2);
+        filter.insert("a");
+        let snapshot = filter.clone();
+        filter.insert("b");
+
+        assert!(snapshot.contains("a"));
+        assert!(filter.contains("a") && filter.contains("b"));
+        assert_ne!(snapshot, filter);
+    }
+}
@@ -0,0 +1,94 @@
+use std::collections::VecDeque;
+
+/// What [`crate::Stream::bounded_buffer`] does with an element that arrives when the buffer is
+/// full.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum OverflowPolicy {
+    /// Drops the arriving element, keeping the elements already in the buffer.
+    DropNewest,
+    /// Drops the oldest element in the buffer to make room for the arriving one.
+    DropOldest,
+    /// Holds the arriving element back, admitting it into the buffer once there is room.
+    /// Nothing is dropped, but elements may be released in a later tick than they arrived.
+    Block,
+}
+
+/// The state of a [`crate::Stream::bounded_buffer`], which admits up to `capacity` elements and
+/// releases them at the end of each tick.
+pub struct BoundedBuffer<T> {
+    capacity: usize,
+    policy: OverflowPolicy,
+    buffer: VecDeque<T>,
+    /// Elements held back by [`OverflowPolicy::Block`], in arrival order.
+    blocked: VecDeque<T>,
+}
+
+impl<T> BoundedBuffer<T> {
+    pub fn new(capacity: usize, policy: OverflowPolicy) -> Self {
+        assert!(capacity > 0, "bounded_buffer capacity must be at least 1");
+        BoundedBuffer {
+            capacity,
+            policy,
+            buffer: VecDeque::new(),
+            blocked: VecDeque::new(),
+        }
+    }
+
+    /// Offers `item` to the buffer, applying the overflow policy if it is full.
+    pub fn push(&mut self, item: T) {
+        if self.buffer.len() < self.capacity && self.blocked.is_empty() {
+            self.buffer.push_back(item);
+            return;
+        }
+
+        match self.policy {
+            OverflowPolicy::DropNewest => {}
+            OverflowPolicy::DropOldest => {
+                self.buffer.pop_front();
+                self.buffer.push_back(item);
+            }
+            OverflowPolicy::Block => self.blocked.push_back(item),
+        }
+    }
+
+    /// Called at the end of each tick, releasing the buffered elements in arrival order and
+    /// then admitting as many blocked elements as fit for the next tick.
+    pub fn drain(&mut self) -> Vec<T> {
+        let released = self.buffer.drain(..).collect();
+        let admitted = self.blocked.len().min(self.capacity);
+        self.buffer.extend(self.blocked.drain(..admitted));
+        released
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BoundedBuffer, OverflowPolicy};
+
+    fn fill(policy: OverflowPolicy) -> BoundedBuffer<i32> {
+        let mut buffer = BoundedBuffer::new(2, policy);
+        for x in 1..=5 {
+            buffer.push(x);
+        }
+        buffer
+    }
+
+    #[test]
+    fn bounded_buffer_applies_overflow_policy() {
+        let mut drop_newest = fill(OverflowPolicy::DropNewest);
+        assert_eq!(drop_newest.drain(), vec![1, 2]);
+        assert_eq!(drop_newest.drain(), Vec::<i32>::new());
+
+        let mut drop_oldest = fill(OverflowPolicy::DropOldest);
+        assert_eq!(drop_oldest.drain(), vec![4, 5]);
+        assert_eq!(drop_oldest.drain(), Vec::<i32>::new());
+
+        let mut block = fill(OverflowPolicy::Block);
+        assert_eq!(block.drain(), vec![1, 2]);
+        // Blocked elements keep their place ahead of newer arrivals.
+        block.push(6);
+        assert_eq!(block.drain(), vec![3, 4]);
+        assert_eq!(block.drain(), vec![5, 6]);
+        assert_eq!(block.drain(), Vec::<i32>::new());
+    }
+}
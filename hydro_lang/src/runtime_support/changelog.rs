@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// The state of [`crate::Stream::changelog`]: the snapshot of the previous tick, and the one
+/// being collected in the current tick.
+pub struct SnapshotDiff<K, V> {
+    previous: HashMap<K, V>,
+    current: HashMap<K, V>,
+}
+
+impl<K, V> Default for SnapshotDiff<K, V> {
+    fn default() -> Self {
+        SnapshotDiff {
+            previous: HashMap::new(),
+            current: HashMap::new(),
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone, V: PartialEq + Clone> SnapshotDiff<K, V> {
+    /// Adds an entry to the current tick's snapshot.
+    pub fn insert(&mut self, key: K, value: V) {
+        self.current.insert(key, value);
+    }
+
+    /// Called at the end of each tick, returning `(key, Some(value))` for every key that was
+    /// inserted or updated since the previous snapshot and `(key, None)` for every key that
+    /// was removed. The current snapshot then becomes the previous one.
+    pub fn end_tick(&mut self) -> Vec<(K, Option<V>)> {
+        let mut changes = Vec::new();
+        for (key, value) in &self.current {
+            if self.previous.get(key) != Some(value) {
+                changes.push((key.clone(), Some(value.clone())));
+            }
+        }
+        for key in self.previous.keys() {
+            if !self.current.contains_key(key) {
+                changes.push((key.clone(), None));
+            }
+        }
+
+        self.previous = std::mem::take(&mut self.current);
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SnapshotDiff;
+
+    #[test]
+    fn reports_inserts_updates_and_deletes() {
+        let mut diff = SnapshotDiff::default();
+        diff.insert('a', 1);
+        diff.insert('b', 2);
+        let mut changes = diff.end_tick();
+        changes.sort();
+        assert_eq!(changes, vec![('a', Some(1)), ('b', Some(2))]);
+
+        diff.insert('a', 1);
+        diff.insert('b', 3);
+        diff.insert('c', 4);
+        let mut changes = diff.end_tick();
+        changes.sort();
+        assert_eq!(changes, vec![('b', Some(3)), ('c', Some(4))]);
+
+        diff.insert('b', 3);
+        let mut changes = diff.end_tick();
+        changes.sort();
+        assert_eq!(changes, vec![('a', None), ('c', None)]);
+
+        diff.insert('b', 3);
+        assert_eq!(diff.end_tick(), vec![]);
+    }
+}
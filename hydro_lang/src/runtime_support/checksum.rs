@@ -0,0 +1,46 @@
+use serde::Serialize;
+
+/// Computes a checksum over the bincode serialization of `value`, for
+/// [`crate::Stream::with_checksum`].
+///
+/// This uses 64-bit FNV-1a, which (unlike [`std::hash::DefaultHasher`]) gives the same result
+/// in every process and build, so checksums can be verified after crossing the network.
+pub fn checksum<T: Serialize>(value: &T) -> u64 {
+    let bytes = bincode::serialize(value).unwrap();
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Returns `value` if its checksum matches `expected`, for [`crate::Stream::verify_checksum`].
+/// Otherwise, reports the mismatch on stderr and returns `None`.
+pub fn verify<T: Serialize>(expected: u64, value: T) -> Option<T> {
+    let actual = checksum(&value);
+    if actual == expected {
+        Some(value)
+    } else {
+        eprintln!(
+            "verify_checksum: dropping element with checksum {:#x}, expected {:#x}",
+            actual, expected
+        );
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{checksum, verify};
+
+    #[test]
+    fn checksum_detects_changes() {
+        let sum = checksum(&(1, "hello".to_string()));
+        assert_eq!(sum, checksum(&(1, "hello".to_string())));
+        assert_ne!(sum, checksum(&(1, "hellp".to_string())));
+
+        assert_eq!(
+            verify(sum, (1, "hello".to_string())),
+            Some((1, "hello".to_string()))
+        );
+        assert_eq!(verify(sum ^ 1, (1, "hello".to_string())), None);
+    }
+}
@@ -0,0 +1,114 @@
+use std::borrow::Cow;
+use std::io::{Read, Write};
+
+use dfir_rs::bytes::Bytes;
+use lz4_flex::frame::{BlockMode, BlockSize, FrameDecoder, FrameEncoder, FrameInfo};
+
+/// How hard [`crate::Stream::send_bincode_compressed`] works to shrink each message.
+///
+/// LZ4 has a single compression speed, so levels instead choose the layout of the LZ4
+/// frame: larger, linked blocks let repeated data match further back in the message, at the
+/// cost of more memory on both ends.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Lz4Level {
+    /// Independent 64 KB blocks, which is the fastest and uses the least memory.
+    Fast,
+    /// Linked 4 MB blocks, which compresses large messages better.
+    High,
+}
+
+/// Compresses a serialized payload into an LZ4 frame for
+/// [`crate::Stream::send_bincode_compressed`].
+pub fn compress_frame(level: Lz4Level, payload: &[u8]) -> Bytes {
+    write_frame(level, payload, Vec::new()).into()
+}
+
+/// Appends the LZ4 frame for `payload` to `out`.
+fn write_frame(level: Lz4Level, payload: &[u8], out: Vec<u8>) -> Vec<u8> {
+    let frame_info = match level {
+        Lz4Level::Fast => FrameInfo::new()
+            .block_size(BlockSize::Max64KB)
+            .block_mode(BlockMode::Independent),
+        Lz4Level::High => FrameInfo::new()
+            .block_size(BlockSize::Max4MB)
+            .block_mode(BlockMode::Linked),
+    };
+    let mut encoder = FrameEncoder::with_frame_info(frame_info, out);
+    encoder
+        .write_all(payload)
+        .expect("failed to compress network payload");
+    encoder
+        .finish()
+        .expect("failed to compress network payload")
+}
+
+/// Reverses [`compress_frame`], returning the original serialized payload. The frame header
+/// records its own layout, so the level is not needed.
+pub fn decompress_frame(frame: &[u8]) -> Vec<u8> {
+    let mut payload = Vec::new();
+    FrameDecoder::new(frame)
+        .read_to_end(&mut payload)
+        .expect("failed to decompress network payload");
+    payload
+}
+
+/// Frames a serialized payload for [`crate::Stream::send_bincode_adaptive`].
+///
+/// Payloads of at least `min_bytes` are compressed into a [`Lz4Level::Fast`] frame, and a
+/// leading flag byte records whether they were.
+pub fn compress_above(payload: Vec<u8>, min_bytes: usize) -> Bytes {
+    if payload.len() >= min_bytes {
+        write_frame(Lz4Level::Fast, &payload, vec![1]).into()
+    } else {
+        let mut framed = Vec::with_capacity(payload.len() + 1);
+        framed.push(0);
+        framed.extend(payload);
+        framed.into()
+    }
+}
+
+/// Reverses [`compress_above`], returning the original serialized payload.
+pub fn decompress_above(framed: &[u8]) -> Cow<'_, [u8]> {
+    match framed.split_first() {
+        Some((0, payload)) => Cow::Borrowed(payload),
+        Some((1, frame)) => Cow::Owned(decompress_frame(frame)),
+        _ => panic!("unexpected framing for network payload"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compress_above, compress_frame, decompress_above, decompress_frame, Lz4Level};
+
+    #[test]
+    fn shrinks_repetitive_payloads() {
+        let values = (0..10_000).map(|i| i % 16).collect::<Vec<u64>>();
+        let payload = bincode::serialize(&values).unwrap();
+
+        for level in [Lz4Level::Fast, Lz4Level::High] {
+            let frame = compress_frame(level, &payload);
+            assert!(
+                frame.len() * 10 < payload.len(),
+                "{:?} compressed {} bytes to {}",
+                level,
+                payload.len(),
+                frame.len()
+            );
+            assert_eq!(decompress_frame(&frame), payload);
+        }
+    }
+
+    #[test]
+    fn compresses_only_above_threshold() {
+        let small = vec![7; 16];
+        let framed = compress_above(small.clone(), 64);
+        assert_eq!(framed[0], 0);
+        assert_eq!(decompress_above(&framed), small);
+
+        let large = vec![7; 4096];
+        let framed = compress_above(large.clone(), 64);
+        assert_eq!(framed[0], 1);
+        assert!(framed.len() < large.len());
+        assert_eq!(decompress_above(&framed), large);
+    }
+}
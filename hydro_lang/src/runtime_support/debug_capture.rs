@@ -0,0 +1,60 @@
+use std::fmt::Debug;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Appends a sample for the tap `tap` to the capture file at `path`.
+///
+/// Used by [`crate::FlowBuilder::with_debug_capture`]. `count` is the number of samples the tap
+/// has recorded so far, so only the first `max_samples` samples of each tap are written; `sample`
+/// is not called for the rest.
+pub fn record_sample(
+    path: &str,
+    tap: usize,
+    label: &str,
+    max_samples: usize,
+    count: &AtomicUsize,
+    sample: impl FnOnce() -> String,
+) {
+    if count
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |count| {
+            (count < max_samples).then_some(count + 1)
+        })
+        .is_err()
+    {
+        return;
+    }
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .expect("Failed to open debug capture file.");
+    writeln!(file, "[{} {}] {}", tap, label, sample()).expect("Failed to write debug sample.");
+}
+
+/// Wraps an element being sampled.
+///
+/// Calling `(&Sample(v)).sample()` formats `v` with [`Debug`] if it implements it, and otherwise
+/// falls back to its type name, so that taps can be inserted on streams of any type.
+pub struct Sample<'a, T>(pub &'a T);
+
+pub trait SampleDebug {
+    fn sample(&self) -> String;
+}
+
+impl<T: Debug> SampleDebug for Sample<'_, T> {
+    fn sample(&self) -> String {
+        format!("{:?}", self.0)
+    }
+}
+
+pub trait SampleOpaque {
+    fn sample(&self) -> String;
+}
+
+impl<T> SampleOpaque for &Sample<'_, T> {
+    fn sample(&self) -> String {
+        format!("<{}>", std::any::type_name::<T>())
+    }
+}
@@ -0,0 +1,54 @@
+use std::collections::{HashSet, VecDeque};
+use std::hash::Hash;
+
+/// The most recently seen idempotency keys, for [`crate::Stream::send_bincode_exactly_once`].
+pub struct DedupWindow<K> {
+    window: usize,
+    seen: HashSet<K>,
+    /// The keys in `seen`, oldest first.
+    order: VecDeque<K>,
+}
+
+impl<K: Eq + Hash + Clone> DedupWindow<K> {
+    pub fn new(window: usize) -> Self {
+        assert!(window > 0, "exactly-once dedup window must be at least 1");
+        DedupWindow {
+            window,
+            seen: HashSet::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// Records `key`, returning `true` if it is not among the last `window` distinct keys
+    /// seen. Once more than `window` distinct keys have been seen, the oldest is forgotten.
+    pub fn insert(&mut self, key: K) -> bool {
+        if !self.seen.insert(key.clone()) {
+            return false;
+        }
+
+        self.order.push_back(key);
+        if self.order.len() > self.window {
+            let evicted = self.order.pop_front().unwrap();
+            self.seen.remove(&evicted);
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DedupWindow;
+
+    #[test]
+    fn dedup_window_forgets_oldest() {
+        let mut window = DedupWindow::new(2);
+        assert!(window.insert(1));
+        assert!(!window.insert(1));
+        assert!(window.insert(2));
+        assert!(window.insert(3));
+
+        // 1 has been evicted, so a duplicate of it is no longer caught.
+        assert!(!window.insert(2));
+        assert!(window.insert(1));
+    }
+}
@@ -0,0 +1,199 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use dfir_rs::futures::stream::{self, Iter};
+use dfir_rs::futures::{sink, Sink};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// The position just after a record read by [`crate::Location::source_durable`].
+///
+/// Committing it (usually with [`crate::Stream::commit_offset`]) marks the record and all
+/// records before it as processed, so they are skipped when the log is replayed on restart.
+#[derive(Clone, Debug)]
+pub struct DurableOffset {
+    offset_path: Arc<PathBuf>,
+    offset: u64,
+}
+
+impl DurableOffset {
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Persists this offset, replacing the previously committed one. This blocks on disk I/O,
+    /// see [`commit_sink`] for committing from the runtime.
+    ///
+    /// The offset is written and synced to a temporary file which is then renamed over the
+    /// offset file, so a crash never leaves a partially written offset behind. The directory is
+    /// synced after the rename, so the commit itself survives a crash once this returns.
+    pub fn commit(&self) -> io::Result<()> {
+        let mut tmp_path = self.offset_path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+
+        let mut tmp_file = File::create(&tmp_path)?;
+        tmp_file.write_all(&self.offset.to_le_bytes())?;
+        tmp_file.sync_all()?;
+        drop(tmp_file);
+
+        std::fs::rename(&tmp_path, self.offset_path.as_path())?;
+
+        let dir = match self.offset_path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        File::open(dir)?.sync_all()
+    }
+}
+
+/// The sink used by [`crate::Stream::commit_offset`].
+///
+/// Each offset is committed on a blocking thread, so the runtime keeps processing while the
+/// offset is synced to disk, and commits are applied in the order they are sent.
+pub fn commit_sink() -> impl Sink<DurableOffset, Error = io::Error> + Unpin {
+    Box::pin(sink::unfold((), |(), offset: DurableOffset| async move {
+        tokio::task::spawn_blocking(move || offset.commit())
+            .await
+            .expect("Failed to commit durable offset.")
+    }))
+}
+
+/// Reads the committed offset at `offset_path`, or 0 if nothing has been committed yet.
+pub fn committed_offset(offset_path: impl AsRef<Path>) -> u64 {
+    match std::fs::read(offset_path) {
+        Ok(bytes) => {
+            u64::from_le_bytes(bytes.try_into().expect("Durable offset file is corrupted."))
+        }
+        Err(e) if e.kind() == ErrorKind::NotFound => 0,
+        Err(e) => panic!("Failed to read durable offset: {}", e),
+    }
+}
+
+/// Appends a record to the log at `log_path`, in the framing read by [`DurableLog`].
+///
+/// Each record is a little-endian `u32` length followed by the bincode-serialized value.
+pub fn append_record<T: Serialize>(log_path: impl AsRef<Path>, value: &T) {
+    let bytes = bincode::serialize(value).unwrap();
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_path)
+        .expect("Failed to open durable log.");
+    file.write_all(&(bytes.len() as u32).to_le_bytes())
+        .and_then(|_| file.write_all(&bytes))
+        .expect("Failed to append to durable log.");
+}
+
+/// Iterates over the records of a log written with [`append_record`], starting after the
+/// committed offset.
+///
+/// Iteration stops at the end of the log. A truncated record at the end (from a writer that
+/// crashed mid-append) is treated as the end of the log.
+pub struct DurableLog<T> {
+    reader: BufReader<File>,
+    offset_path: Arc<PathBuf>,
+    offset: u64,
+    _phantom: PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned> DurableLog<T> {
+    pub fn open(log_path: impl AsRef<Path>, offset_path: impl AsRef<Path>) -> Self {
+        let offset = committed_offset(&offset_path);
+        let mut file = File::open(log_path).expect("Failed to open durable log.");
+        file.seek(SeekFrom::Start(offset))
+            .expect("Failed to seek to committed offset.");
+
+        DurableLog {
+            reader: BufReader::new(file),
+            offset_path: Arc::new(offset_path.as_ref().to_path_buf()),
+            offset,
+            _phantom: PhantomData,
+        }
+    }
+
+    fn read_frame(&mut self) -> Option<Vec<u8>> {
+        let mut len = [0; 4];
+        if let Err(e) = self.reader.read_exact(&mut len) {
+            assert_eq!(
+                e.kind(),
+                ErrorKind::UnexpectedEof,
+                "Failed to read durable log: {}",
+                e
+            );
+            return None;
+        }
+
+        let mut bytes = vec![0; u32::from_le_bytes(len) as usize];
+        if let Err(e) = self.reader.read_exact(&mut bytes) {
+            assert_eq!(
+                e.kind(),
+                ErrorKind::UnexpectedEof,
+                "Failed to read durable log: {}",
+                e
+            );
+            return None;
+        }
+
+        self.offset += (len.len() + bytes.len()) as u64;
+        Some(bytes)
+    }
+}
+
+impl<T: DeserializeOwned> Iterator for DurableLog<T> {
+    type Item = (DurableOffset, T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let bytes = self.read_frame()?;
+        let offset = DurableOffset {
+            offset_path: self.offset_path.clone(),
+            offset: self.offset,
+        };
+        Some((offset, bincode::deserialize(&bytes).unwrap()))
+    }
+}
+
+/// The source stream used by [`crate::Location::source_durable`].
+pub fn replay<T: DeserializeOwned>(
+    log_path: impl AsRef<Path>,
+    offset_path: impl AsRef<Path>,
+) -> Iter<DurableLog<T>> {
+    stream::iter(DurableLog::open(log_path, offset_path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{append_record, committed_offset, DurableLog};
+
+    #[test]
+    fn durable_log_resumes_from_committed_offset() {
+        let dir = std::env::temp_dir().join(format!("hydro_durable_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let log_path = dir.join("log.bin");
+        let offset_path = dir.join("offset");
+        let _ = std::fs::remove_file(&log_path);
+        let _ = std::fs::remove_file(&offset_path);
+
+        for i in 0..5u32 {
+            append_record(&log_path, &format!("record{}", i));
+        }
+
+        // Process the first two records, then "crash".
+        let mut log = DurableLog::<String>::open(&log_path, &offset_path);
+        let (_, first) = log.next().unwrap();
+        let (offset, second) = log.next().unwrap();
+        assert_eq!((first.as_str(), second.as_str()), ("record0", "record1"));
+        offset.commit().unwrap();
+        drop(log);
+        assert_eq!(committed_offset(&offset_path), offset.offset());
+
+        let resumed = DurableLog::<String>::open(&log_path, &offset_path)
+            .map(|(_, record)| record)
+            .collect::<Vec<_>>();
+        assert_eq!(resumed, vec!["record2", "record3", "record4"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
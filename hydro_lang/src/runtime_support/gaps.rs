@@ -0,0 +1,60 @@
+use serde::{Deserialize, Serialize};
+
+/// A run of sequence numbers that were skipped, reported by [`crate::Stream::detect_gaps`].
+///
+/// Both ends are inclusive, so a single missing message `n` is reported as `from: n, to: n`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct GapReport {
+    /// The first missing sequence number.
+    pub from: u64,
+    /// The last missing sequence number.
+    pub to: u64,
+}
+
+/// The next sequence number expected from a source, for [`crate::Stream::detect_gaps`].
+#[derive(Default)]
+pub struct SequenceTracker {
+    next: Option<u64>,
+}
+
+impl SequenceTracker {
+    /// Records that `seq` was received, returning the sequence numbers skipped since the
+    /// highest one received so far, if any.
+    ///
+    /// The first sequence number received sets the expectation without reporting a gap.
+    /// Sequence numbers below the expected one (duplicates, or messages that arrived late and
+    /// were already reported missing) are ignored.
+    pub fn observe(&mut self, seq: u64) -> Option<GapReport> {
+        let gap = match self.next {
+            Some(next) if seq > next => Some(GapReport {
+                from: next,
+                to: seq - 1,
+            }),
+            _ => None,
+        };
+
+        if self.next.is_none_or(|next| seq >= next) {
+            self.next = Some(seq + 1);
+        }
+
+        gap
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GapReport, SequenceTracker};
+
+    #[test]
+    fn sequence_tracker_reports_skipped_ranges() {
+        let mut tracker = SequenceTracker::default();
+
+        assert_eq!(tracker.observe(5), None);
+        assert_eq!(tracker.observe(6), None);
+        assert_eq!(tracker.observe(9), Some(GapReport { from: 7, to: 8 }));
+        // A late message does not move the expectation backwards.
+        assert_eq!(tracker.observe(7), None);
+        assert_eq!(tracker.observe(10), None);
+        assert_eq!(tracker.observe(12), Some(GapReport { from: 11, to: 11 }));
+    }
+}
@@ -0,0 +1,6 @@
+pub use either::Either;
+use serde::{Deserialize, Serialize};
+
+/// A liveness marker interleaved into a stream by [`crate::Stream::with_heartbeats`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Heartbeat;
@@ -0,0 +1,171 @@
+use std::iter::Enumerate;
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Arc, Mutex};
+use std::vec::IntoIter;
+
+/// The results computed by one thread for a [`Batch`], tagged with the index of each element.
+type Results<U> = std::thread::Result<Vec<(usize, U)>>;
+
+/// The elements of one tick, shared by the threads that work on them.
+struct Batch<T, U> {
+    queue: Mutex<Enumerate<IntoIter<T>>>,
+    results: Sender<Results<U>>,
+}
+
+/// The state of a [`crate::Stream::map_parallel`] or [`crate::Stream::map_parallel_ordered`],
+/// which applies `f` to the elements of each tick on a pool of up to `parallelism` threads.
+///
+/// The threads are started when they are first needed and kept for later ticks, until this is
+/// dropped. They take the next element from a shared queue whenever they finish one, so a few
+/// slow elements do not hold up the rest.
+pub struct ParallelMap<T, U> {
+    f: Arc<dyn Fn(T) -> U + Send + Sync>,
+    parallelism: usize,
+    ordered: bool,
+    workers: Vec<Sender<Arc<Batch<T, U>>>>,
+}
+
+impl<T: Send + 'static, U: Send + 'static> ParallelMap<T, U> {
+    pub fn new(
+        f: impl Fn(T) -> U + Send + Sync + 'static,
+        parallelism: usize,
+        ordered: bool,
+    ) -> Self {
+        assert!(
+            parallelism > 0,
+            "map_parallel parallelism must be at least 1"
+        );
+        ParallelMap {
+            f: Arc::new(f),
+            parallelism,
+            ordered,
+            workers: Vec::new(),
+        }
+    }
+
+    /// Applies `f` to each of `items`, waiting for all of the results. If `ordered` is set, the
+    /// results are returned in the order of `items`. Otherwise they are grouped by the thread
+    /// that produced them.
+    pub fn map(&mut self, items: Vec<T>) -> Vec<U> {
+        let threads = self.parallelism.min(items.len());
+        if threads <= 1 {
+            return items.into_iter().map(|item| (self.f)(item)).collect();
+        }
+
+        while self.workers.len() < threads {
+            self.workers.push(spawn_worker(self.f.clone()));
+        }
+
+        let len = items.len();
+        let (results_send, results_recv) = channel();
+        let batch = Arc::new(Batch {
+            queue: Mutex::new(items.into_iter().enumerate()),
+            results: results_send,
+        });
+        for worker in &self.workers[..threads] {
+            worker
+                .send(batch.clone())
+                .expect("map_parallel worker thread stopped");
+        }
+        drop(batch);
+
+        let per_thread = (0..threads)
+            .map(|_| {
+                results_recv
+                    .recv()
+                    .expect("map_parallel worker thread stopped")
+                    .unwrap_or_else(|e| resume_unwind(e))
+            })
+            .collect::<Vec<_>>();
+
+        if self.ordered {
+            let mut slots = (0..len).map(|_| None).collect::<Vec<_>>();
+            for (index, result) in per_thread.into_iter().flatten() {
+                slots[index] = Some(result);
+            }
+            slots.into_iter().map(Option::unwrap).collect()
+        } else {
+            per_thread
+                .into_iter()
+                .flatten()
+                .map(|(_, result)| result)
+                .collect()
+        }
+    }
+}
+
+/// Starts a thread that applies `f` to the elements of each batch sent to it, until the
+/// returned sender is dropped.
+fn spawn_worker<T: Send + 'static, U: Send + 'static>(
+    f: Arc<dyn Fn(T) -> U + Send + Sync>,
+) -> Sender<Arc<Batch<T, U>>> {
+    let (batch_send, batch_recv) = channel::<Arc<Batch<T, U>>>();
+    std::thread::Builder::new()
+        .name("map_parallel".to_string())
+        .spawn(move || {
+            for batch in batch_recv {
+                // A panic in `f` is passed on to the caller, and the thread stays in the pool.
+                let results = catch_unwind(AssertUnwindSafe(|| {
+                    let mut results = Vec::new();
+                    loop {
+                        // The lock is released before `f` runs.
+                        let next = batch.queue.lock().unwrap().next();
+                        let Some((index, item)) = next else {
+                            break;
+                        };
+                        results.push((index, f(item)));
+                    }
+                    results
+                }));
+                // The caller stops listening if another thread panicked.
+                let _ = batch.results.send(results);
+            }
+        })
+        .expect("failed to start map_parallel worker thread");
+    batch_send
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::ParallelMap;
+
+    #[test]
+    fn ordered_results_follow_input() {
+        let mut pool = ParallelMap::new(|x: u64| x * x, 4, true);
+        let results = pool.map((0..1000).collect());
+        assert_eq!(results, (0..1000).map(|x| x * x).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn unordered_results_are_complete() {
+        let mut pool = ParallelMap::new(|x: u64| x + 1, 4, false);
+        let mut results = pool.map((0..1000).collect());
+        results.sort();
+        assert_eq!(results, (1..1001).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn reuses_threads_across_ticks() {
+        let mut pool = ParallelMap::new(
+            |_: u64| {
+                std::thread::sleep(std::time::Duration::from_millis(1));
+                std::thread::current().id()
+            },
+            4,
+            false,
+        );
+
+        let first_tick = pool.map((0..64).collect());
+        assert!(first_tick.iter().collect::<HashSet<_>>().len() > 1);
+
+        let second_tick = pool.map((0..64).collect());
+        let all_threads = first_tick
+            .into_iter()
+            .chain(second_tick)
+            .collect::<HashSet<_>>();
+        assert!(all_threads.len() <= 4);
+    }
+}
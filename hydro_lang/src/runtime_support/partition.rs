@@ -0,0 +1,53 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+/// Picks which of `partitions` outputs `key` is routed to, for
+/// [`crate::Stream::stable_partition`] and [`crate::Stream::send_partitioned`].
+///
+/// This uses jump consistent hashing over a stable hash of the key, so a key is always routed
+/// to the same partition, and increasing the number of partitions by one only moves keys into
+/// the new partition.
+pub fn stable_partition<K: Hash + ?Sized>(key: &K, partitions: usize) -> usize {
+    // Unlike a `RandomState` hasher, `DefaultHasher::new` uses fixed keys, so every process
+    // built with the same toolchain agrees on the hash of a key.
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    let mut hash = hasher.finish();
+
+    // Jump consistent hash, from "A Fast, Minimal Memory, Consistent Hash Algorithm" (Lamping
+    // and Veach, 2014).
+    let (mut bucket, mut next) = (-1i64, 0i64);
+    while next < partitions as i64 {
+        bucket = next;
+        hash = hash.wrapping_mul(2862933555777941757).wrapping_add(1);
+        next = ((bucket + 1) as f64 * ((1u64 << 31) as f64 / ((hash >> 33) + 1) as f64)) as i64;
+    }
+    bucket as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::stable_partition;
+
+    #[test]
+    fn stable_partition_is_consistent() {
+        for key in 0..1000 {
+            let partition = stable_partition(&key, 4);
+            assert!(partition < 4);
+            assert_eq!(partition, stable_partition(&key, 4));
+
+            // Adding a partition only moves keys into the new one.
+            let grown = stable_partition(&key, 5);
+            assert!(grown == partition || grown == 4);
+        }
+
+        let counts = (0..1000).fold([0; 4], |mut counts, key| {
+            counts[stable_partition(&key, 4)] += 1;
+            counts
+        });
+        assert!(
+            counts.iter().all(|count| (200..300).contains(count)),
+            "{:?}",
+            counts
+        );
+    }
+}
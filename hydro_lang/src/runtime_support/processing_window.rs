@@ -0,0 +1,124 @@
+use std::time::{Duration, Instant};
+
+/// The state of a processing-time window, for [`crate::Stream::processing_window`].
+///
+/// Windows are consecutive spans of `duration` wall-clock time, starting when the state is
+/// created. Only the aggregate of the currently open window is kept.
+pub struct ProcessingWindow<A> {
+    duration: Duration,
+    emit_empty: bool,
+    window_end: Instant,
+    current: Option<A>,
+}
+
+impl<A> ProcessingWindow<A> {
+    /// Opens the first window, ending `duration` from now.
+    pub fn new(duration: Duration, emit_empty: bool) -> Self {
+        assert!(
+            !duration.is_zero(),
+            "processing_window duration must be positive"
+        );
+        ProcessingWindow {
+            duration,
+            emit_empty,
+            window_end: Instant::now() + duration,
+            current: None,
+        }
+    }
+
+    /// Closes every window that ended at or before `now`, returning their aggregates in
+    /// order. Windows without elements produce `init()` if `emit_empty` is set, and are
+    /// skipped otherwise.
+    pub fn close_at(&mut self, now: Instant, init: impl Fn() -> A) -> Vec<A> {
+        let mut closed = Vec::new();
+        while now >= self.window_end {
+            if let Some(aggregate) = self.current.take() {
+                closed.push(aggregate);
+            } else if self.emit_empty {
+                closed.push(init());
+            } else {
+                // Skip straight past the run of empty windows.
+                let behind =
+                    now.duration_since(self.window_end).as_nanos() / self.duration.as_nanos();
+                self.window_end += self.duration * behind as u32;
+            }
+            self.window_end += self.duration;
+        }
+        closed
+    }
+
+    /// Handles an element (`Some`) or a timer firing (`None`) at time `now`, first closing
+    /// any windows that have ended and then folding the element into the open window.
+    pub fn observe_at<T>(
+        &mut self,
+        now: Instant,
+        item: Option<T>,
+        init: impl Fn() -> A,
+        acc: impl Fn(&mut A, T),
+    ) -> Vec<A> {
+        let closed = self.close_at(now, &init);
+        if let Some(item) = item {
+            acc(self.current.get_or_insert_with(init), item);
+        }
+        closed
+    }
+
+    /// Like [`ProcessingWindow::observe_at`], at the current time.
+    pub fn observe<T>(
+        &mut self,
+        item: Option<T>,
+        init: impl Fn() -> A,
+        acc: impl Fn(&mut A, T),
+    ) -> Vec<A> {
+        self.observe_at(Instant::now(), item, init, acc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::ProcessingWindow;
+
+    fn sum(window: &mut ProcessingWindow<i32>, now: Instant, item: Option<i32>) -> Vec<i32> {
+        window.observe_at(now, item, || 0, |acc, x| *acc += x)
+    }
+
+    #[test]
+    fn closes_windows_at_boundaries() {
+        let mut window = ProcessingWindow::new(Duration::from_secs(1), true);
+        let start = window.window_end - Duration::from_secs(1);
+
+        assert!(sum(&mut window, start, Some(1)).is_empty());
+        assert!(sum(&mut window, start + Duration::from_millis(500), Some(2)).is_empty());
+
+        // The element at 1.5s lands in the second window, after the first one closes.
+        assert_eq!(
+            sum(&mut window, start + Duration::from_millis(1500), Some(4)),
+            vec![3]
+        );
+
+        // The windows ending at 2s and 3s both close, and the latter had no elements.
+        assert_eq!(
+            sum(&mut window, start + Duration::from_millis(3100), None),
+            vec![4, 0]
+        );
+    }
+
+    #[test]
+    fn skips_empty_windows() {
+        let mut window = ProcessingWindow::new(Duration::from_secs(1), false);
+        let start = window.window_end - Duration::from_secs(1);
+
+        assert!(sum(&mut window, start, Some(1)).is_empty());
+        assert_eq!(
+            sum(&mut window, start + Duration::from_secs(10), None),
+            vec![1]
+        );
+        assert!(sum(&mut window, start + Duration::from_millis(10_500), Some(2)).is_empty());
+        assert_eq!(
+            sum(&mut window, start + Duration::from_millis(11_000), None),
+            vec![2]
+        );
+    }
+}
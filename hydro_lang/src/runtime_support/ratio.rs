@@ -0,0 +1,38 @@
+/// Decides whether the element at `index` goes to the first output of
+/// [`crate::Stream::split_ratio`], which happens with probability `p`.
+///
+/// The decision is a pure function of `seed` and `index` (a SplitMix64 hash), so the same seed
+/// always splits the same stream the same way.
+pub fn assigns_first(seed: u64, index: u64, p: f64) -> bool {
+    let mut z = seed.wrapping_add(index.wrapping_add(1).wrapping_mul(0x9e3779b97f4a7c15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^= z >> 31;
+
+    // The top 53 bits, as a float uniformly distributed in [0, 1).
+    let sample = (z >> 11) as f64 / (1u64 << 53) as f64;
+    sample < p
+}
+
+#[cfg(test)]
+mod tests {
+    use super::assigns_first;
+
+    #[test]
+    fn assigns_first_is_seeded() {
+        let split = |seed| {
+            (0..10000)
+                .map(|i| assigns_first(seed, i, 0.25))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(split(1), split(1));
+        assert_ne!(split(1), split(2));
+
+        let first = split(1).into_iter().filter(|b| *b).count();
+        assert!((2300..2700).contains(&first), "{}", first);
+
+        assert!((0..100).all(|i| !assigns_first(1, i, 0.0)));
+        assert!((0..100).all(|i| assigns_first(1, i, 1.0)));
+    }
+}
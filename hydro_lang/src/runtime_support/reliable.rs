@@ -0,0 +1,132 @@
+use std::collections::BTreeMap;
+
+/// The sender's buffer of unacknowledged elements for [`crate::Stream::reliable_send`].
+pub struct RetransmitBuffer<T> {
+    /// Each unacknowledged element by sequence number, along with how many times it was sent.
+    unacked: BTreeMap<u64, (usize, T)>,
+}
+
+impl<T> Default for RetransmitBuffer<T> {
+    fn default() -> Self {
+        RetransmitBuffer {
+            unacked: BTreeMap::new(),
+        }
+    }
+}
+
+impl<T: Clone> RetransmitBuffer<T> {
+    /// Buffers `item` until it is acknowledged, returning its first transmission as
+    /// `(seq, attempt, item)`. Attempts are numbered from zero.
+    pub fn send(&mut self, seq: u64, item: T) -> (u64, usize, T) {
+        self.unacked.insert(seq, (1, item.clone()));
+        (seq, 0, item)
+    }
+
+    /// Drops every element before `next_expected`, which the receiver has acknowledged.
+    pub fn ack(&mut self, next_expected: u64) {
+        self.unacked = self.unacked.split_off(&next_expected);
+    }
+
+    /// Returns a retransmission of every unacknowledged element, in sequence order.
+    pub fn retransmit(&mut self) -> Vec<(u64, usize, T)> {
+        self.unacked
+            .iter_mut()
+            .map(|(seq, (attempts, item))| {
+                *attempts += 1;
+                (*seq, *attempts - 1, item.clone())
+            })
+            .collect()
+    }
+
+    /// The number of unacknowledged elements.
+    pub fn len(&self) -> usize {
+        self.unacked.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.unacked.is_empty()
+    }
+}
+
+/// The receiver's state for [`crate::Stream::reliable_send`] and [`crate::Stream::reorder`].
+///
+/// Releases elements exactly once and in sequence order, however they were duplicated or
+/// reordered in transit.
+#[derive(Default)]
+pub struct ReorderBuffer<T> {
+    /// The sequence number of the next element to release.
+    next: u64,
+    /// Elements received ahead of `next`.
+    pending: BTreeMap<u64, T>,
+    received_this_tick: bool,
+}
+
+impl<T> ReorderBuffer<T> {
+    /// Creates an empty buffer that releases elements starting at sequence number `next`.
+    pub fn starting_at(next: u64) -> Self {
+        ReorderBuffer {
+            next,
+            pending: BTreeMap::new(),
+            received_this_tick: false,
+        }
+    }
+
+    /// Records that `item` was received, returning the elements that are now ready to be
+    /// released in order.
+    pub fn receive(&mut self, seq: u64, item: T) -> Vec<T> {
+        self.received_this_tick = true;
+        if seq >= self.next {
+            self.pending.insert(seq, item);
+        }
+
+        let mut released = Vec::new();
+        while let Some(item) = self.pending.remove(&self.next) {
+            released.push(item);
+            self.next += 1;
+        }
+        released
+    }
+
+    /// Called at the end of each tick, returning the cumulative acknowledgement (the next
+    /// sequence number expected) if anything was received during the tick.
+    pub fn end_tick(&mut self) -> Option<u64> {
+        std::mem::take(&mut self.received_this_tick).then_some(self.next)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ReorderBuffer, RetransmitBuffer};
+
+    #[test]
+    fn retransmit_buffer_trims_on_ack() {
+        let mut buffer = RetransmitBuffer::default();
+        assert_eq!(buffer.send(0, 'a'), (0, 0, 'a'));
+        assert_eq!(buffer.send(1, 'b'), (1, 0, 'b'));
+        assert_eq!(buffer.send(2, 'c'), (2, 0, 'c'));
+
+        buffer.ack(1);
+        assert_eq!(buffer.len(), 2);
+        assert_eq!(buffer.retransmit(), vec![(1, 1, 'b'), (2, 1, 'c')]);
+        assert_eq!(buffer.retransmit(), vec![(1, 2, 'b'), (2, 2, 'c')]);
+
+        buffer.ack(3);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn reorder_buffer_releases_in_order_once() {
+        let mut buffer = ReorderBuffer::default();
+        assert_eq!(buffer.end_tick(), None);
+
+        assert_eq!(buffer.receive(1, 'b'), Vec::<char>::new());
+        assert_eq!(buffer.receive(0, 'a'), vec!['a', 'b']);
+        assert_eq!(buffer.end_tick(), Some(2));
+
+        // Duplicates are acknowledged again, but not released.
+        assert_eq!(buffer.receive(1, 'b'), Vec::<char>::new());
+        assert_eq!(buffer.end_tick(), Some(2));
+        assert_eq!(buffer.receive(2, 'c'), vec!['c']);
+        assert_eq!(buffer.end_tick(), Some(3));
+    }
+}
@@ -0,0 +1,237 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{File, OpenOptions};
+use std::hash::Hash;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Storage for the values evicted from memory by a [`SpillingMap`].
+pub trait SpillStore<K, A> {
+    /// Stores the value for `key`, which is not currently in the store.
+    fn put(&mut self, key: K, value: A);
+
+    /// Removes and returns the value for `key`, if it was spilled.
+    fn take(&mut self, key: &K) -> Option<A>;
+
+    /// The number of values currently in the store.
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes and returns all values in the store.
+    fn drain(&mut self) -> Vec<(K, A)>;
+}
+
+static NEXT_SPILL_FILE: AtomicUsize = AtomicUsize::new(0);
+
+/// A [`SpillStore`] which appends bincode-serialized values to a temporary file.
+///
+/// The keys and the location of each value are kept in memory. The file is deleted when the
+/// store is dropped.
+pub struct FileSpillStore<K> {
+    path: PathBuf,
+    /// Created on the first spill.
+    file: Option<File>,
+    end: u64,
+    index: HashMap<K, (u64, usize)>,
+}
+
+impl<K> FileSpillStore<K> {
+    pub fn new() -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "hydro_spill_{}_{}.bin",
+            std::process::id(),
+            NEXT_SPILL_FILE.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        FileSpillStore {
+            path,
+            file: None,
+            end: 0,
+            index: HashMap::new(),
+        }
+    }
+
+    fn file(&mut self) -> &mut File {
+        self.file.get_or_insert_with(|| {
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(&self.path)
+                .expect("Failed to create spill file.")
+        })
+    }
+
+    fn read_at(&mut self, offset: u64, len: usize) -> Vec<u8> {
+        let mut bytes = vec![0; len];
+        let file = self.file();
+        file.seek(SeekFrom::Start(offset))
+            .and_then(|_| file.read_exact(&mut bytes))
+            .expect("Failed to read from spill file.");
+        bytes
+    }
+}
+
+impl<K> Default for FileSpillStore<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K> Drop for FileSpillStore<K> {
+    fn drop(&mut self) {
+        if self.file.is_some() {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+impl<K: Eq + Hash, A: Serialize + DeserializeOwned> SpillStore<K, A> for FileSpillStore<K> {
+    fn put(&mut self, key: K, value: A) {
+        let bytes = bincode::serialize(&value).unwrap();
+        let end = self.end;
+        let file = self.file();
+        file.seek(SeekFrom::Start(end))
+            .and_then(|_| file.write_all(&bytes))
+            .expect("Failed to write to spill file.");
+
+        self.index.insert(key, (self.end, bytes.len()));
+        self.end += bytes.len() as u64;
+    }
+
+    fn take(&mut self, key: &K) -> Option<A> {
+        let (offset, len) = self.index.remove(key)?;
+        let bytes = self.read_at(offset, len);
+
+        if self.index.is_empty() {
+            // Nothing left is live, so the space can be reused.
+            self.end = 0;
+        }
+
+        Some(bincode::deserialize(&bytes).unwrap())
+    }
+
+    fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    fn drain(&mut self) -> Vec<(K, A)> {
+        let mut entries = std::mem::take(&mut self.index)
+            .into_iter()
+            .collect::<Vec<_>>();
+        // Read sequentially through the file.
+        entries.sort_by_key(|(_, (offset, _))| *offset);
+
+        let values = entries
+            .into_iter()
+            .map(|(key, (offset, len))| {
+                let bytes = self.read_at(offset, len);
+                (key, bincode::deserialize(&bytes).unwrap())
+            })
+            .collect();
+
+        self.end = 0;
+        values
+    }
+}
+
+/// A map which keeps at most `mem_limit` values in memory, for [`crate::Stream::fold_keyed_spilling`].
+///
+/// The least recently used values are spilled to a [`SpillStore`], and transparently brought
+/// back when they are updated.
+pub struct SpillingMap<K, A, S = FileSpillStore<K>> {
+    mem_limit: usize,
+    in_memory: HashMap<K, (A, u64)>,
+    recency: BTreeMap<u64, K>,
+    next_use: u64,
+    spilled: S,
+}
+
+impl<K: Eq + Hash + Clone, A: Serialize + DeserializeOwned> SpillingMap<K, A> {
+    pub fn new(mem_limit: usize) -> Self {
+        Self::with_store(mem_limit, FileSpillStore::new())
+    }
+}
+
+impl<K: Eq + Hash + Clone, A, S: SpillStore<K, A>> SpillingMap<K, A, S> {
+    pub fn with_store(mem_limit: usize, store: S) -> Self {
+        assert!(mem_limit > 0, "mem_limit must be at least 1");
+        SpillingMap {
+            mem_limit,
+            in_memory: HashMap::new(),
+            recency: BTreeMap::new(),
+            next_use: 0,
+            spilled: store,
+        }
+    }
+
+    /// Applies `f` to the value for `key`, which is created with `init` if the key is new.
+    pub fn update(&mut self, key: K, init: impl FnOnce() -> A, f: impl FnOnce(&mut A)) {
+        let use_id = self.next_use;
+        self.next_use += 1;
+
+        if let Some((value, last_use)) = self.in_memory.get_mut(&key) {
+            self.recency.remove(last_use);
+            *last_use = use_id;
+            f(value);
+        } else {
+            let mut value = self.spilled.take(&key).unwrap_or_else(init);
+            f(&mut value);
+            self.in_memory.insert(key.clone(), (value, use_id));
+        }
+        self.recency.insert(use_id, key);
+
+        while self.in_memory.len() > self.mem_limit {
+            let (_, coldest) = self.recency.pop_first().unwrap();
+            let (value, _) = self.in_memory.remove(&coldest).unwrap();
+            self.spilled.put(coldest, value);
+        }
+    }
+
+    /// The number of values currently spilled out of memory.
+    pub fn spilled_len(&self) -> usize {
+        self.spilled.len()
+    }
+
+    /// Removes and returns every key and value, including spilled ones, leaving the map empty.
+    pub fn drain(&mut self) -> Vec<(K, A)> {
+        self.recency.clear();
+        let mut entries = self
+            .in_memory
+            .drain()
+            .map(|(key, (value, _))| (key, value))
+            .collect::<Vec<_>>();
+        entries.extend(self.spilled.drain());
+        entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SpillingMap;
+
+    #[test]
+    fn spilling_map_rehydrates_spilled_keys() {
+        let mut map = SpillingMap::<u32, u64>::new(2);
+        for i in 0..100 {
+            map.update(i % 10, || 0, |sum| *sum += i as u64);
+        }
+        assert_eq!(map.spilled_len(), 8);
+
+        let mut entries = map.drain();
+        entries.sort();
+        assert_eq!(
+            entries,
+            (0..10)
+                .map(|k| (k, (0..100).filter(|i| i % 10 == k).map(u64::from).sum()))
+                .collect::<Vec<_>>()
+        );
+    }
+}
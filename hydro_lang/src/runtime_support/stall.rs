@@ -0,0 +1,105 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+/// An alert emitted by [`crate::Stream::stall_alert`] when a stream goes quiet or resumes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum StallAlert {
+    /// No element has flowed for longer than the threshold.
+    Stalled {
+        /// How long it had been since the last element when the stall was detected.
+        idle: Duration,
+    },
+    /// An element flowed again after a stall.
+    Recovered {
+        /// How long it had been since the element before it.
+        stalled_for: Duration,
+    },
+}
+
+/// The time the last element was seen, for [`crate::Stream::stall_alert`].
+pub struct StallDetector {
+    threshold: Duration,
+    last_element: Instant,
+    stalled: bool,
+}
+
+impl StallDetector {
+    /// Starts tracking from now, as if an element had just been seen.
+    pub fn new(threshold: Duration) -> Self {
+        StallDetector {
+            threshold,
+            last_element: Instant::now(),
+            stalled: false,
+        }
+    }
+
+    /// Handles an element (`true`) or a timer firing (`false`) at time `now`, returning an
+    /// alert if the stream just stalled or recovered. Each stall is reported once.
+    pub fn observe_at(&mut self, now: Instant, is_element: bool) -> Option<StallAlert> {
+        let idle = now.saturating_duration_since(self.last_element);
+        if is_element {
+            self.last_element = now;
+            if self.stalled {
+                self.stalled = false;
+                return Some(StallAlert::Recovered { stalled_for: idle });
+            }
+        } else if !self.stalled && idle > self.threshold {
+            self.stalled = true;
+            return Some(StallAlert::Stalled { idle });
+        }
+        None
+    }
+
+    /// Like [`StallDetector::observe_at`], at the current time.
+    pub fn observe(&mut self, is_element: bool) -> Option<StallAlert> {
+        self.observe_at(Instant::now(), is_element)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{StallAlert, StallDetector};
+
+    #[test]
+    fn reports_each_stall_once_then_recovery() {
+        let start = Instant::now();
+        let mut detector = StallDetector::new(Duration::from_secs(1));
+        detector.last_element = start;
+
+        assert_eq!(
+            detector.observe_at(start + Duration::from_millis(500), true),
+            None
+        );
+        assert_eq!(
+            detector.observe_at(start + Duration::from_secs(1), false),
+            None
+        );
+
+        let stalled = detector.observe_at(start + Duration::from_secs(2), false);
+        assert_eq!(
+            stalled,
+            Some(StallAlert::Stalled {
+                idle: Duration::from_millis(1500)
+            })
+        );
+        assert_eq!(
+            detector.observe_at(start + Duration::from_secs(3), false),
+            None
+        );
+
+        let recovered = detector.observe_at(start + Duration::from_secs(4), true);
+        assert_eq!(
+            recovered,
+            Some(StallAlert::Recovered {
+                stalled_for: Duration::from_millis(3500)
+            })
+        );
+        assert_eq!(
+            detector.observe_at(start + Duration::from_secs(4), true),
+            None
+        );
+    }
+}
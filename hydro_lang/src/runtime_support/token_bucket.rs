@@ -0,0 +1,72 @@
+use std::time::Instant;
+
+/// The state of a token bucket, for [`crate::Stream::rate_limit`].
+///
+/// The bucket holds up to `capacity` tokens and is refilled continuously at `rate_per_sec`
+/// tokens per second. Each forwarded element takes one token.
+pub struct TokenBucket {
+    rate_per_sec: f64,
+    capacity: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    /// Creates a full bucket.
+    pub fn new(rate_per_sec: f64, capacity: u32) -> Self {
+        TokenBucket {
+            rate_per_sec,
+            capacity: capacity as f64,
+            tokens: capacity as f64,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Refills the bucket for the time elapsed until `now`, then takes a token if one is
+    /// available, returning whether it did.
+    pub fn try_take_at(&mut self, now: Instant) -> bool {
+        let elapsed = now
+            .saturating_duration_since(self.last_refill)
+            .as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate_per_sec).min(self.capacity);
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Like [`TokenBucket::try_take_at`], at the current time.
+    pub fn try_take(&mut self) -> bool {
+        self.try_take_at(Instant::now())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::TokenBucket;
+
+    #[test]
+    fn allows_burst_then_refills() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(2.0, 3);
+        bucket.last_refill = start;
+
+        assert!((0..3).all(|_| bucket.try_take_at(start)));
+        assert!(!bucket.try_take_at(start));
+
+        // Half a second refills one token at 2 tokens per second.
+        assert!(bucket.try_take_at(start + Duration::from_millis(500)));
+        assert!(!bucket.try_take_at(start + Duration::from_millis(500)));
+
+        // A long pause only refills up to the capacity.
+        let later = start + Duration::from_secs(60);
+        assert!((0..3).all(|_| bucket.try_take_at(later)));
+        assert!(!bucket.try_take_at(later));
+    }
+}
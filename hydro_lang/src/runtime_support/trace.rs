@@ -0,0 +1,70 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::ops::Deref;
+
+use dfir_rs::tracing::{self, Span};
+use serde::{Deserialize, Serialize};
+
+/// The tracing context attached to each element by [`crate::Stream::with_trace`], which
+/// travels with the element when it is sent to another location.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TraceContext {
+    /// The id of the span the element was tagged in on the sending side.
+    pub span_id: u64,
+}
+
+impl TraceContext {
+    /// Captures the span that is currently entered. If there is none, or no subscriber is
+    /// assigning span ids, a fresh random id is used so that the element can still be
+    /// correlated across locations.
+    pub fn current() -> Self {
+        let span_id = Span::current()
+            .id()
+            .map(|id| id.into_u64())
+            .unwrap_or_else(|| RandomState::new().build_hasher().finish().max(1));
+        TraceContext { span_id }
+    }
+
+    /// Creates the span that continues this context on the receiving side, recording the
+    /// sender's span id as its `remote_span_id` field.
+    pub fn restore(&self) -> RemoteSpan {
+        RemoteSpan {
+            span: tracing::info_span!("hydro_remote", remote_span_id = self.span_id),
+        }
+    }
+}
+
+/// A `tracing` span continuing a [`TraceContext`] received from another location, created by
+/// [`crate::Stream::restore_trace`]. It dereferences to the underlying [`Span`].
+#[derive(Clone, Debug)]
+pub struct RemoteSpan {
+    span: Span,
+}
+
+impl RemoteSpan {
+    /// Returns the underlying span.
+    pub fn into_inner(self) -> Span {
+        self.span
+    }
+}
+
+impl Deref for RemoteSpan {
+    type Target = Span;
+
+    fn deref(&self) -> &Span {
+        &self.span
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TraceContext;
+
+    #[test]
+    fn fresh_contexts_are_distinct() {
+        let first = TraceContext::current();
+        let second = TraceContext::current();
+        assert_ne!(first, second);
+        assert_ne!(first.span_id, 0);
+    }
+}
@@ -0,0 +1,58 @@
+use dfir_rs::bytes::Bytes;
+use serde::de::DeserializeOwned;
+
+/// A message received by [`crate::Stream::send_bincode_versioned`], before it has been
+/// migrated to the receiver's current type.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RawBytes {
+    /// The wire version the sender tagged the message with.
+    pub version: u8,
+    /// The bincode-serialized payload, in the sender's wire format.
+    pub payload: Bytes,
+}
+
+impl RawBytes {
+    /// Deserializes the payload as a `T`, which should be the type the sender used for
+    /// [`RawBytes::version`].
+    pub fn decode<T: DeserializeOwned>(&self) -> T {
+        bincode::deserialize(&self.payload).unwrap_or_else(|e| {
+            panic!(
+                "failed to decode payload with wire version {}: {}",
+                self.version, e
+            )
+        })
+    }
+}
+
+/// Prefixes a serialized payload with its wire `version`.
+pub fn frame(version: u8, payload: Vec<u8>) -> Bytes {
+    let mut framed = Vec::with_capacity(payload.len() + 1);
+    framed.push(version);
+    framed.extend(payload);
+    framed.into()
+}
+
+/// Reverses [`frame`], splitting off the leading version byte.
+pub fn unframe(framed: &[u8]) -> RawBytes {
+    let (version, payload) = framed
+        .split_first()
+        .expect("versioned network payload is missing its version byte");
+    RawBytes {
+        version: *version,
+        payload: Bytes::copy_from_slice(payload),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{frame, unframe};
+
+    #[test]
+    fn roundtrips_version_and_payload() {
+        let payload = bincode::serialize(&(1u32, "hello".to_string())).unwrap();
+        let raw = unframe(&frame(3, payload));
+
+        assert_eq!(raw.version, 3);
+        assert_eq!(raw.decode::<(u32, String)>(), (1, "hello".to_string()));
+    }
+}
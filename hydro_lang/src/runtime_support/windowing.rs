@@ -0,0 +1,300 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use serde::{Deserialize, Serialize};
+
+/// Why a [`Pane`] was emitted by a windowed aggregation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum PaneStatus {
+    /// Fired before the window closed; more elements may still be added to the window.
+    Early,
+    /// Fired when the window closed.
+    OnTime,
+    /// Fired for an element that arrived after the window closed.
+    Late,
+}
+
+/// The aggregate of one key's elements in a window, as of the time it was fired.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Pane<A> {
+    /// The index of the window, which covers event ticks
+    /// `[window * window_ticks, (window + 1) * window_ticks)`.
+    pub window: u64,
+    pub status: PaneStatus,
+    pub value: A,
+}
+
+/// The per-key window aggregates for [`crate::windowing::Windowed::fold`], along with the
+/// trigger that decides when they are fired.
+pub struct WindowState<K, A> {
+    window_ticks: u64,
+    early_every: Option<usize>,
+    fire_late: bool,
+    allowed_lateness: u64,
+    /// Aggregates of windows that have not closed yet, along with the number of elements
+    /// added since the last early fire.
+    open: HashMap<(K, u64), (A, usize)>,
+    /// Aggregates of closed windows, kept until the allowed lateness has passed.
+    closed: HashMap<(K, u64), A>,
+}
+
+impl<K: Eq + Hash + Clone, A: Clone> WindowState<K, A> {
+    pub fn new(
+        window_ticks: u64,
+        early_every: Option<usize>,
+        fire_late: bool,
+        allowed_lateness: u64,
+    ) -> Self {
+        assert!(window_ticks > 0, "window_ticks must be at least 1");
+        WindowState {
+            window_ticks,
+            early_every,
+            fire_late,
+            allowed_lateness,
+            open: HashMap::new(),
+            closed: HashMap::new(),
+        }
+    }
+
+    fn window_end(&self, window: u64) -> u64 {
+        (window + 1) * self.window_ticks
+    }
+
+    /// Adds `value`, produced at `event_tick`, to the window for `key` during tick `now`.
+    ///
+    /// Returns an early pane if this element completes a batch for an early trigger, or a late
+    /// pane if the window has already closed and late firing is enabled.
+    pub fn insert<V>(
+        &mut self,
+        now: u64,
+        key: K,
+        event_tick: u64,
+        value: V,
+        init: impl Fn() -> A,
+        comb: impl Fn(&mut A, V),
+    ) -> Option<(K, Pane<A>)> {
+        let window = event_tick / self.window_ticks;
+        let end = self.window_end(window);
+
+        if end > now {
+            let (acc, since_fire) = self
+                .open
+                .entry((key.clone(), window))
+                .or_insert_with(|| (init(), 0));
+            comb(acc, value);
+            *since_fire += 1;
+
+            match self.early_every {
+                Some(n) if *since_fire >= n => {
+                    *since_fire = 0;
+                    Some((
+                        key,
+                        Pane {
+                            window,
+                            status: PaneStatus::Early,
+                            value: acc.clone(),
+                        },
+                    ))
+                }
+                _ => None,
+            }
+        } else if self.fire_late && end + self.allowed_lateness > now {
+            let acc = self
+                .closed
+                .entry((key.clone(), window))
+                .or_insert_with(&init);
+            comb(acc, value);
+            Some((
+                key,
+                Pane {
+                    window,
+                    status: PaneStatus::Late,
+                    value: acc.clone(),
+                },
+            ))
+        } else {
+            None
+        }
+    }
+
+    /// Called at the end of tick `now`, closing every window whose last tick is `now` (or
+    /// earlier) and returning their on-time panes.
+    pub fn advance(&mut self, now: u64) -> Vec<(K, Pane<A>)> {
+        let (window_ticks, allowed_lateness) = (self.window_ticks, self.allowed_lateness);
+        let accepts_late_next_tick =
+            |window: u64| (window + 1) * window_ticks + allowed_lateness > now + 1;
+
+        self.closed
+            .retain(|(_, window), _| accepts_late_next_tick(*window));
+
+        let closing = self
+            .open
+            .keys()
+            .filter(|(_, window)| (window + 1) * window_ticks <= now + 1)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        closing
+            .into_iter()
+            .map(|(key, window)| {
+                let (acc, _) = self.open.remove(&(key.clone(), window)).unwrap();
+                if self.fire_late && accepts_late_next_tick(window) {
+                    self.closed.insert((key.clone(), window), acc.clone());
+                }
+
+                (
+                    key,
+                    Pane {
+                        window,
+                        status: PaneStatus::OnTime,
+                        value: acc,
+                    },
+                )
+            })
+            .collect()
+    }
+}
+
+/// The per-key window aggregates for [`crate::Stream::aggregate_on_watermark`], which are held
+/// until the watermark passes the end of their window.
+pub struct WatermarkState<K, A> {
+    /// Aggregates of windows that have not fired yet, keyed by the key and window end.
+    open: HashMap<(K, u64), A>,
+    /// The highest watermark seen so far; every window ending at or before it has fired.
+    watermark: Option<u64>,
+}
+
+impl<K, A> Default for WatermarkState<K, A> {
+    fn default() -> Self {
+        WatermarkState {
+            open: HashMap::new(),
+            watermark: None,
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone, A> WatermarkState<K, A> {
+    /// Adds `value` to the window for `key` that ends at `window_end`.
+    ///
+    /// If that window has already fired, the element is late and is returned instead.
+    pub fn insert<V>(
+        &mut self,
+        key: K,
+        window_end: u64,
+        value: V,
+        init: impl Fn() -> A,
+        comb: impl Fn(&mut A, V),
+    ) -> Option<(K, V)> {
+        if self
+            .watermark
+            .is_some_and(|watermark| window_end <= watermark)
+        {
+            return Some((key, value));
+        }
+
+        let acc = self.open.entry((key, window_end)).or_insert_with(init);
+        comb(acc, value);
+        None
+    }
+
+    /// Advances the watermark to `watermark`, firing every window that ends at or before it,
+    /// in order of window end. A watermark lower than one already seen has no effect.
+    pub fn advance(&mut self, watermark: u64) -> Vec<(K, A)> {
+        let watermark = self.watermark.map_or(watermark, |prev| prev.max(watermark));
+        self.watermark = Some(watermark);
+
+        let mut firing = self
+            .open
+            .keys()
+            .filter(|(_, window_end)| *window_end <= watermark)
+            .cloned()
+            .collect::<Vec<_>>();
+        firing.sort_by_key(|(_, window_end)| *window_end);
+
+        firing
+            .into_iter()
+            .map(|(key, window_end)| {
+                let acc = self.open.remove(&(key.clone(), window_end)).unwrap();
+                (key, acc)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Pane, PaneStatus, WatermarkState, WindowState};
+
+    #[test]
+    fn window_state_fires_early_on_time_and_late() {
+        let mut state = WindowState::<&'static str, i32>::new(2, Some(2), true, 2);
+        let insert = |state: &mut WindowState<&'static str, i32>, now, event_tick, v| {
+            state.insert(now, "k", event_tick, v, || 0, |acc, v| *acc += v)
+        };
+
+        assert_eq!(insert(&mut state, 0, 0, 1), None);
+        assert_eq!(
+            insert(&mut state, 0, 0, 2),
+            Some((
+                "k",
+                Pane {
+                    window: 0,
+                    status: PaneStatus::Early,
+                    value: 3
+                }
+            ))
+        );
+        assert_eq!(state.advance(0), vec![]);
+
+        assert_eq!(insert(&mut state, 1, 1, 4), None);
+        assert_eq!(
+            state.advance(1),
+            vec![(
+                "k",
+                Pane {
+                    window: 0,
+                    status: PaneStatus::OnTime,
+                    value: 7
+                }
+            )]
+        );
+
+        assert_eq!(
+            insert(&mut state, 2, 1, 8),
+            Some((
+                "k",
+                Pane {
+                    window: 0,
+                    status: PaneStatus::Late,
+                    value: 15
+                }
+            ))
+        );
+        assert_eq!(state.advance(2), vec![]);
+
+        // Past the allowed lateness, late elements are dropped.
+        state.advance(3);
+        assert_eq!(insert(&mut state, 4, 1, 16), None);
+    }
+
+    #[test]
+    fn watermark_state_fires_and_routes_late() {
+        let mut state = WatermarkState::<&'static str, i32>::default();
+        let insert = |state: &mut WatermarkState<&'static str, i32>, window_end, v| {
+            state.insert("k", window_end, v, || 0, |acc, v| *acc += v)
+        };
+
+        assert_eq!(insert(&mut state, 10, 1), None);
+        assert_eq!(insert(&mut state, 20, 2), None);
+        assert_eq!(insert(&mut state, 10, 4), None);
+        assert_eq!(state.advance(9), vec![]);
+        assert_eq!(state.advance(10), vec![("k", 5)]);
+
+        // The window ending at 10 has fired, so its elements are now late.
+        assert_eq!(insert(&mut state, 10, 8), Some(("k", 8)));
+        // A watermark that moves backwards does not reopen windows.
+        state.advance(5);
+        assert_eq!(insert(&mut state, 10, 16), Some(("k", 16)));
+        assert_eq!(state.advance(20), vec![("k", 2)]);
+    }
+}
@@ -10,7 +10,7 @@ use crate::cycle::{
     CycleCollection, CycleCollectionWithInitial, CycleComplete, DeferTick, ForwardRefMarker,
     TickCycleMarker,
 };
-use crate::ir::{HydroLeaf, HydroNode, TeeNode};
+use crate::ir::{assert_valid_label, HydroLeaf, HydroNode, TeeNode};
 use crate::location::tick::{NoTimestamp, Timestamped};
 use crate::location::{check_matching_location, Location, LocationId, NoTick, Tick};
 use crate::{Bounded, Optional, Stream, Unbounded};
@@ -205,6 +205,23 @@ impl<'a, T, L: Location<'a>, B> Singleton<T, L, B> {
         )
     }
 
+    /// Labels the operator that produces this singleton, so that the generated DFIR uses
+    /// `name` in its ident, see [`Stream::named`].
+    ///
+    /// # Panics
+    /// Panics if `name` is not a valid Rust identifier.
+    pub fn named(self, name: &str) -> Singleton<T, L, B> {
+        assert_valid_label(name);
+
+        Singleton::new(
+            self.location,
+            HydroNode::Named {
+                name: name.to_string(),
+                input: Box::new(self.ir_node.into_inner()),
+            },
+        )
+    }
+
     pub fn flat_map_ordered<U, I: IntoIterator<Item = U>, F: Fn(T) -> I + 'a>(
         self,
         f: impl IntoQuotedMut<'a, F, L>,
@@ -390,6 +407,53 @@ impl<'a, T, L: Location<'a> + NoTick, B> Singleton<T, L, B> {
                 .drop_timestamp()
         }
     }
+
+    /// Like [`Singleton::sample_every`], emits the value of the singleton at most once per tick,
+    /// on the ticks in which `interval` elapses, such as to send periodic snapshots of a running
+    /// aggregate over the network.
+    ///
+    /// If `emit_unchanged` is `true`, every sample is emitted even if the value is the same as
+    /// the previous sample, which is useful as a heartbeat. Otherwise, a sample is only emitted
+    /// if it differs from the last one that was emitted.
+    ///
+    /// # Safety
+    /// The output stream is non-deterministic in which elements are sampled, since this
+    /// is controlled by a clock.
+    pub unsafe fn sample_into_stream(
+        self,
+        interval: impl QuotedWithContext<'a, std::time::Duration, L> + Copy + 'a,
+        emit_unchanged: bool,
+    ) -> Stream<T, L, Unbounded>
+    where
+        L: NoTimestamp,
+        T: Clone + PartialEq,
+    {
+        let samples = unsafe {
+            // SAFETY: source of intentional non-determinism
+            self.sample_every(interval)
+        };
+
+        if emit_unchanged {
+            samples
+        } else {
+            // Operator closures are recreated every tick, so the last emitted value is kept
+            // in scan state, which persists across ticks.
+            let init = q!(|| None).splice_fn0_ctx::<Option<T>>(&samples.location);
+            let acc = q!(|last_emitted: &mut Option<_>, v| {
+                if last_emitted.as_ref() == Some(&v) {
+                    None
+                } else {
+                    *last_emitted = Some(std::clone::Clone::clone(&v));
+                    Some(v)
+                }
+            })
+            .splice_fn2_borrow_mut_ctx::<Option<T>, T, Option<T>>(&samples.location);
+
+            samples
+                .scan_static::<Option<T>>(init.into(), acc.into())
+                .filter_map(q!(|v| v))
+        }
+    }
 }
 
 impl<'a, T, L: Location<'a>> Singleton<T, Tick<L>, Bounded> {
@@ -1,4 +1,6 @@
 use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
 use std::hash::Hash;
 use std::marker::PhantomData;
 use std::ops::Deref;
@@ -6,6 +8,8 @@ use std::rc::Rc;
 
 use dfir_rs::bytes::Bytes;
 use dfir_rs::futures;
+use dfir_rs::scheduled::ticks::TickInstant;
+pub use either::Either;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 use stageleft::{q, IntoQuotedMut, QuotedWithContext};
@@ -14,15 +18,30 @@ use tokio::time::Instant;
 
 use crate::builder::FLOW_USED_MESSAGE;
 use crate::cycle::{CycleCollection, CycleComplete, DeferTick, ForwardRefMarker, TickCycleMarker};
-use crate::ir::{DebugInstantiate, HydroLeaf, HydroNode, TeeNode};
+use crate::ir::{
+    assert_valid_label, DebugExpr, DebugInstantiate, HydroLeaf, HydroNode, HydroSource, TeeNode,
+};
 use crate::location::cluster::CLUSTER_SELF_ID;
 use crate::location::external_process::{ExternalBincodeStream, ExternalBytesPort};
 use crate::location::tick::{NoTimestamp, Timestamped};
 use crate::location::{
     check_matching_location, CanSend, ExternalProcess, Location, LocationId, NoTick, Tick,
 };
+pub use crate::runtime_support::bloom::BloomFilter;
+pub use crate::runtime_support::buffer::OverflowPolicy;
+pub use crate::runtime_support::compression::Lz4Level;
+use crate::runtime_support::durable::DurableOffset;
+pub use crate::runtime_support::gaps::GapReport;
+pub use crate::runtime_support::heartbeat::Heartbeat;
+pub use crate::runtime_support::hyperloglog::HyperLogLog;
+pub use crate::runtime_support::stall::StallAlert;
+pub use crate::runtime_support::trace::{RemoteSpan, TraceContext};
+pub use crate::runtime_support::versioned::RawBytes;
 use crate::staging_util::get_this_crate;
-use crate::{Bounded, Cluster, ClusterId, Optional, Process, Singleton, Unbounded};
+use crate::windowing::{Trigger, Windowed};
+use crate::{
+    Bounded, Cluster, ClusterId, Optional, Process, Singleton, Unbounded, RUNTIME_CONTEXT,
+};
 
 /// Marks the stream as being totally ordered, which means that there are
 /// no sources of non-determinism (other than intentional ones) that will
@@ -58,6 +77,15 @@ impl MinOrder<TotalOrder> for NoOrder {
     type Min = NoOrder;
 }
 
+/// Which value [`Stream::dedup_by_key`] keeps for each key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum KeepPolicy {
+    /// Keep the first value seen for the key, ignoring the rest.
+    First,
+    /// Keep the last value seen for the key, overwriting earlier ones.
+    Last,
+}
+
 /// An ordered sequence stream of elements of type `T`.
 ///
 /// Type Parameters:
@@ -68,7 +96,7 @@ impl MinOrder<TotalOrder> for NoOrder {
 /// - `Order`: the ordering of the stream, which is either [`TotalOrder`]
 ///   or [`NoOrder`] (default is [`TotalOrder`])
 pub struct Stream<T, L, B, Order = TotalOrder> {
-    location: L,
+    pub(crate) location: L,
     pub(crate) ir_node: RefCell<HydroNode>,
 
     _phantom: PhantomData<(T, L, B, Order)>,
@@ -88,6 +116,44 @@ impl<'a, T, L: Location<'a>, B, Order> Stream<T, L, B, Order> {
     fn location_kind(&self) -> LocationId {
         self.location.id()
     }
+
+    /// Applies a [`HydroNode::Scan`] whose state is kept across ticks, even when this
+    /// stream is inside a tick.
+    pub(crate) fn scan_static<U>(self, init: DebugExpr, acc: DebugExpr) -> Stream<U, L, B, Order> {
+        if L::is_top_level() {
+            Stream::new(
+                self.location,
+                HydroNode::Persist(Box::new(HydroNode::Scan {
+                    init,
+                    acc,
+                    input: Box::new(HydroNode::Persist(Box::new(HydroNode::Unpersist(
+                        Box::new(self.ir_node.into_inner()),
+                    )))),
+                })),
+            )
+        } else {
+            Stream::new(
+                self.location,
+                HydroNode::Scan {
+                    init,
+                    acc,
+                    input: Box::new(HydroNode::Persist(Box::new(self.ir_node.into_inner()))),
+                },
+            )
+        }
+    }
+
+    /// Pairs each element with its position in the stream, counting across ticks, whatever
+    /// the order of the stream.
+    fn enumerate_static(self) -> Stream<(usize, T), L, B, Order> {
+        let init: syn::Expr = parse_quote!(|| 0usize);
+        let acc: syn::Expr = parse_quote!(|count: &mut usize, v| {
+            let index = *count;
+            *count += 1;
+            (index, v)
+        });
+        self.scan_static::<(usize, T)>(init.into(), acc.into())
+    }
 }
 
 impl<'a, T, L: Location<'a>, Order> DeferTick for Stream<T, Tick<L>, Bounded, Order> {
@@ -243,6 +309,42 @@ impl<'a, T, L: Location<'a>, B, Order> Stream<T, L, B, Order> {
         )
     }
 
+    /// Like [`Stream::map`], but promises that `f` preserves everything that any later
+    /// [`Stream::filter`] on the result inspects, so that the filter keeps exactly the same
+    /// elements whether it runs before or after `f`. This lets the opt-in
+    /// [`crate::rewrites::filter_pushdown`] pass move such filters ahead of `f`, so that `f` is
+    /// only applied to the elements that are kept.
+    ///
+    /// Without that pass, this behaves exactly like [`Stream::map`].
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let balances = process.source_iter(q!(vec![(1, 100), (2, 250)]));
+    /// balances
+    ///     .map_preserving_filters(q!(|(id, cents)| (id, cents * 2)))
+    ///     .filter(q!(|(id, _)| *id == 2))
+    /// # }, |mut stream| async move {
+    /// // (2, 500)
+    /// # assert_eq!(stream.next().await.unwrap(), (2, 500));
+    /// # }));
+    /// ```
+    pub fn map_preserving_filters<F: Fn(T) -> T + 'a>(
+        self,
+        f: impl IntoQuotedMut<'a, F, L>,
+    ) -> Stream<T, L, B, Order> {
+        let f = f.splice_fn1_ctx(&self.location).into();
+        Stream::new(
+            self.location,
+            HydroNode::FilterTransparent(Box::new(HydroNode::Map {
+                f,
+                input: Box::new(self.ir_node.into_inner()),
+            })),
+        )
+    }
+
     /// Clone each element of the stream; akin to `map(q!(|d| d.clone()))`.
     ///
     /// # Example
@@ -432,6 +534,168 @@ impl<'a, T, L: Location<'a>, B, Order> Stream<T, L, B, Order> {
         )
     }
 
+    /// Splits the stream into `N` streams, routing each element to the output at index
+    /// `f(x) % N`. Each output preserves the relative order of its elements.
+    ///
+    /// Unlike filtering a cloned stream `N` times, `f` is evaluated only once per element.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let [evens, _odds] = process
+    ///     .source_iter(q!(vec![1, 2, 3, 4]))
+    ///     .partition_n::<2, _>(q!(|&x| x));
+    /// evens
+    /// # }, |mut stream| async move {
+    /// // 2, 4
+    /// # for w in vec![2, 4] {
+    /// #     assert_eq!(stream.next().await.unwrap(), w);
+    /// # }
+    /// # }));
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if `N` is zero.
+    pub fn partition_n<const N: usize, F: Fn(&T) -> usize + 'a>(
+        self,
+        f: impl IntoQuotedMut<'a, F, L>,
+    ) -> [Stream<T, L, B, Order>; N] {
+        let f = f.splice_fn1_borrow_ctx(&self.location).into();
+        self.partition_n_with::<N>(f)
+    }
+
+    /// Splits the stream into the elements for which `f` returns `true` and those for which it
+    /// returns `false`. Each output preserves the relative order of its elements.
+    ///
+    /// Unlike filtering a cloned stream twice, `f` is evaluated only once per element, and both
+    /// outputs share a single copy of the upstream computation.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let (evens, _odds) = process
+    ///     .source_iter(q!(vec![1, 2, 3, 4]))
+    ///     .partition(q!(|x| x % 2 == 0));
+    /// evens
+    /// # }, |mut stream| async move {
+    /// // 2, 4
+    /// # for w in vec![2, 4] {
+    /// #     assert_eq!(stream.next().await.unwrap(), w);
+    /// # }
+    /// # }));
+    /// ```
+    #[expect(clippy::type_complexity, reason = "pair of output streams")]
+    pub fn partition<F: Fn(&T) -> bool + 'a>(
+        self,
+        f: impl IntoQuotedMut<'a, F, L>,
+    ) -> (Stream<T, L, B, Order>, Stream<T, L, B, Order>) {
+        let f = f.splice_fn1_borrow_ctx(&self.location);
+        let route: syn::Expr = parse_quote!({
+            let f = #f;
+            move |x: &_| if f(x) { 0 } else { 1 }
+        });
+        let [matching, nonmatching] = self.partition_n_with::<2>(route.into());
+        (matching, nonmatching)
+    }
+
+    fn partition_n_with<const N: usize>(self, f: DebugExpr) -> [Stream<T, L, B, Order>; N] {
+        assert!(N > 0, "partition_n requires at least one output");
+
+        if N == 1 {
+            let mut this = Some(self);
+            return std::array::from_fn(|_| this.take().unwrap());
+        }
+
+        let partition = Rc::new(RefCell::new(HydroNode::Partition {
+            f,
+            num_outputs: N,
+            input: Box::new(self.ir_node.into_inner()),
+        }));
+
+        std::array::from_fn(|index| {
+            Stream::new(
+                self.location.clone(),
+                HydroNode::PartitionOutput {
+                    index,
+                    inner: TeeNode(partition.clone()),
+                },
+            )
+        })
+    }
+
+    /// Splits the stream into the elements for which `f` returns `true` and those for which it
+    /// returns `false`, along with a [`Singleton`] of `(match_count, nonmatch_count)`. Each
+    /// output preserves the relative order of its elements.
+    ///
+    /// `f` is evaluated only once per element, and its result is teed once to both the
+    /// partition and the counts.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// let numbers = process.source_iter(q!(vec![1, 2, 3, 4, 6]));
+    /// let batch = unsafe { numbers.timestamped(&tick).tick_batch() };
+    /// let (_evens, _odds, counts) = batch.bisect(q!(|x| x % 2 == 0));
+    /// counts.all_ticks().drop_timestamp()
+    /// # }, |mut stream| async move {
+    /// // (3, 2)
+    /// # assert_eq!(stream.next().await.unwrap(), (3, 2));
+    /// # }));
+    /// ```
+    #[expect(clippy::type_complexity, reason = "pair of output streams with counts")]
+    pub fn bisect<F: Fn(&T) -> bool + 'a>(
+        self,
+        f: impl IntoQuotedMut<'a, F, L>,
+    ) -> (
+        Stream<T, L, B, Order>,
+        Stream<T, L, B, Order>,
+        Singleton<(usize, usize), L, B>,
+    )
+    where
+        T: Clone,
+        Order: MinOrder<NoOrder, Min = NoOrder>,
+    {
+        let f = f.splice_fn1_borrow_ctx(&self.location);
+        let tag: syn::Expr = parse_quote!({
+            let f = #f;
+            move |x| (f(&x), x)
+        });
+        let tagged = Stream::<(bool, T), L, B, Order>::new(
+            self.location.clone(),
+            HydroNode::Map {
+                f: tag.into(),
+                input: Box::new(self.ir_node.into_inner()),
+            },
+        );
+
+        let counts = tagged.clone().fold_commutative(
+            q!(|| (0usize, 0usize)),
+            q!(|counts, (matched, _)| {
+                if matched {
+                    counts.0 += 1;
+                } else {
+                    counts.1 += 1;
+                }
+            }),
+        );
+
+        let route: syn::Expr =
+            parse_quote!(|(matched, _): &(bool, _)| if *matched { 0 } else { 1 });
+        let [matching, nonmatching] = tagged.partition_n_with::<2>(route.into());
+        (
+            matching.map(q!(|(_, x)| x)),
+            nonmatching.map(q!(|(_, x)| x)),
+            counts,
+        )
+    }
+
     /// An operator that both filters and maps. It yields only the items for which the supplied closure `f` returns `Some(value)`.
     ///
     /// # Example
@@ -515,6 +779,84 @@ impl<'a, T, L: Location<'a>, B, Order> Stream<T, L, B, Order> {
         self.continue_if(other.into_stream().count().filter(q!(|c| *c == 0)))
     }
 
+    /// Builds a [`BloomFilter`] of the elements of the stream, for probing with
+    /// [`Stream::filter_in_bloom`]. The filter uses [`BloomFilter::default`] dimensions.
+    ///
+    /// The input stream can have any ordering guarantee, since inserting into the filter is
+    /// commutative.
+    pub fn build_bloom(self) -> Singleton<BloomFilter, L, B>
+    where
+        T: Hash,
+    {
+        let root = get_this_crate();
+        let init: syn::Expr = parse_quote!(|| <#root::runtime_support::bloom::BloomFilter as ::std::default::Default>::default());
+        let acc: syn::Expr = parse_quote!(
+            |filter: &mut #root::runtime_support::bloom::BloomFilter, item| {
+                filter.insert(&item);
+            }
+        );
+
+        let mut core = HydroNode::Fold {
+            init: init.into(),
+            acc: acc.into(),
+            input: Box::new(self.ir_node.into_inner()),
+        };
+
+        if L::is_top_level() {
+            core = HydroNode::Persist(Box::new(core));
+        }
+
+        Singleton::new(self.location, core)
+    }
+
+    /// Keeps only the elements that `filter` reports as possibly present, such as a filter
+    /// built by [`Stream::build_bloom`]. No element that was inserted into the filter is
+    /// dropped, but some that were not may be kept, in exchange for the filter taking far
+    /// less memory than the set it summarizes.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// let allowed = unsafe {
+    ///     process
+    ///         .source_iter(q!(vec![1, 3]))
+    ///         .timestamped(&tick)
+    ///         .tick_batch()
+    /// };
+    /// let batch = unsafe {
+    ///     process
+    ///         .source_iter(q!(vec![1, 2, 3]))
+    ///         .timestamped(&tick)
+    ///         .tick_batch()
+    /// };
+    /// batch
+    ///     .filter_in_bloom(allowed.build_bloom())
+    ///     .all_ticks()
+    ///     .drop_timestamp()
+    /// # }, |mut stream| async move {
+    /// // 1, 3
+    /// # assert_eq!(stream.next().await.unwrap(), 1);
+    /// # assert_eq!(stream.next().await.unwrap(), 3);
+    /// # }));
+    /// ```
+    pub fn filter_in_bloom(
+        self,
+        filter: Singleton<BloomFilter, L, Bounded>,
+    ) -> Stream<T, L, B, Order>
+    where
+        T: Hash,
+    {
+        self.cross_singleton(filter)
+            .filter_map(q!(|(item, filter)| if filter.contains(&item) {
+                Some(item)
+            } else {
+                None
+            }))
+    }
+
     /// Forms the cross-product (Cartesian product, cross-join) of the items in the 2 input streams, returning all
     /// tupled pairs.
     pub fn cross_product<O>(self, other: Stream<O, L, B, Order>) -> Stream<(T, O), L, B, Order>
@@ -545,73 +887,287 @@ impl<'a, T, L: Location<'a>, B, Order> Stream<T, L, B, Order> {
         )
     }
 
-    /// Outputs everything in this stream that is *not* contained in the `other` stream.
+    /// Passes every element through unchanged, but panics if the same element is ever
+    /// seen twice (across all ticks). This is useful for checking the invariant that a
+    /// pipeline emits distinct elements.
     ///
-    /// The `other` stream must be [`Bounded`], since this function will wait until
-    /// all its elements are available before producing any output.
-    pub fn filter_not_in<O2>(self, other: Stream<T, L, Bounded, O2>) -> Stream<T, L, Bounded, Order>
+    /// The check is only performed when the generated program is compiled with
+    /// `debug_assertions`; in release builds this is a no-op.
+    ///
+    /// # Panics
+    /// In debug builds, panics when an element equal to an earlier one is observed,
+    /// naming the duplicate element.
+    pub fn assert_unique(self) -> Stream<T, L, B, Order>
     where
-        T: Eq + Hash,
+        T: Eq + Hash + Clone + Debug,
     {
-        check_matching_location(&self.location, &other.location);
+        let init =
+            q!(|| std::collections::HashSet::new()).splice_fn0_ctx::<HashSet<T>>(&self.location);
+        let acc = q!(|seen: &mut std::collections::HashSet<_>, v| {
+            debug_assert!(
+                seen.insert(std::clone::Clone::clone(&v)),
+                "assert_unique: duplicate element {:?}",
+                v
+            );
+            v
+        })
+        .splice_fn2_borrow_mut_ctx::<HashSet<T>, T, T>(&self.location);
+
+        self.scan_static(init.into(), acc.into())
+    }
 
-        Stream::new(
-            self.location,
-            HydroNode::Difference(
-                Box::new(self.ir_node.into_inner()),
-                Box::new(other.ir_node.into_inner()),
-            ),
-        )
+    /// Applies `f` to each element like [`Stream::map`], but spreads the elements of each tick
+    /// across a pool of up to `parallelism` threads within this location. This is a hint for
+    /// CPU-heavy transforms, so cheap transforms are better off with [`Stream::map`].
+    ///
+    /// The elements of each tick are gathered with a `fold::<'tick>` before any of them is
+    /// processed, and the location waits for all of their results before moving on. So only
+    /// elements of the same tick are processed in parallel, all of them are held in memory at
+    /// once, and nothing else runs on this location while they are processed. The threads are
+    /// kept between ticks, so `f` and the elements must be `'static`.
+    ///
+    /// The results are emitted in the order they are produced on each thread, so the output is
+    /// [`NoOrder`]. Use [`Stream::map_parallel_ordered`] to keep the input order instead.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// let numbers = process.source_iter(q!(vec![1, 2, 3, 4]));
+    /// let batch = unsafe { numbers.timestamped(&tick).tick_batch() };
+    /// batch
+    ///     .map_parallel(q!(|x| x * x), 2)
+    ///     .fold_commutative(q!(|| 0), q!(|acc, x| *acc += x))
+    ///     .all_ticks()
+    ///     .drop_timestamp()
+    /// # }, |mut stream| async move {
+    /// // 30
+    /// # assert_eq!(stream.next().await.unwrap(), 30);
+    /// # }));
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if `parallelism` is zero.
+    pub fn map_parallel<U, F: Fn(T) -> U + Send + Sync + 'static>(
+        self,
+        f: impl IntoQuotedMut<'a, F, L>,
+        parallelism: usize,
+    ) -> Stream<U, L, B, NoOrder>
+    where
+        T: Send + 'static,
+        U: Send + 'static,
+    {
+        let f = f.splice_fn1_ctx(&self.location).into();
+        self.map_parallel_inner(f, parallelism, false)
     }
 
-    /// An operator which allows you to "inspect" each element of a stream without
-    /// modifying it. The closure `f` is called on a reference to each item. This is
-    /// mainly useful for debugging, and should not be used to generate side-effects.
-    pub fn inspect<F: Fn(&T) + 'a>(
+    /// Like [`Stream::map_parallel`], but emits the results in the order of the input. This
+    /// holds back each result until those of all earlier elements in the tick are ready.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// process
+    ///     .source_iter(q!(vec![1, 2, 3, 4]))
+    ///     .map_parallel_ordered(q!(|x| x * x), 2)
+    /// # }, |mut stream| async move {
+    /// // 1, 4, 9, 16
+    /// # for w in vec![1, 4, 9, 16] {
+    /// #     assert_eq!(stream.next().await.unwrap(), w);
+    /// # }
+    /// # }));
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if `parallelism` is zero.
+    pub fn map_parallel_ordered<U, F: Fn(T) -> U + Send + Sync + 'static>(
         self,
         f: impl IntoQuotedMut<'a, F, L>,
-    ) -> Stream<T, L, B, Order> {
-        let f = f.splice_fn1_borrow_ctx(&self.location).into();
+        parallelism: usize,
+    ) -> Stream<U, L, B, Order>
+    where
+        T: Send + 'static,
+        U: Send + 'static,
+    {
+        let f = f.splice_fn1_ctx(&self.location).into();
+        self.map_parallel_inner(f, parallelism, true)
+    }
+
+    fn map_parallel_inner<U, Order2>(
+        self,
+        f: DebugExpr,
+        parallelism: usize,
+        ordered: bool,
+    ) -> Stream<U, L, B, Order2> {
+        assert!(
+            parallelism > 0,
+            "map_parallel parallelism must be at least 1"
+        );
 
         if L::is_top_level() {
             Stream::new(
                 self.location,
-                HydroNode::Persist(Box::new(HydroNode::Inspect {
+                HydroNode::Persist(Box::new(HydroNode::MapParallel {
                     f,
+                    parallelism,
+                    ordered,
                     input: Box::new(HydroNode::Unpersist(Box::new(self.ir_node.into_inner()))),
                 })),
             )
         } else {
             Stream::new(
                 self.location,
-                HydroNode::Inspect {
+                HydroNode::MapParallel {
                     f,
+                    parallelism,
+                    ordered,
                     input: Box::new(self.ir_node.into_inner()),
                 },
             )
         }
     }
 
-    /// Explicitly "casts" the stream to a type with a different ordering
-    /// guarantee. Useful in unsafe code where the ordering cannot be proven
-    /// by the type-system.
+    /// Forwards elements at a rate of at most `rate_per_sec` per second on average, while
+    /// allowing bursts of up to `burst` elements at once. Elements that arrive when the limit
+    /// is exhausted are dropped.
     ///
-    /// # Safety
-    /// This function is used as an escape hatch, and any mistakes in the
-    /// provided ordering guarantee will propagate into the guarantees
-    /// for the rest of the program.
-    pub unsafe fn assume_ordering<O>(self) -> Stream<T, L, B, O> {
-        Stream::new(self.location, self.ir_node.into_inner())
-    }
-}
-
-impl<'a, T, L: Location<'a>, B, Order> Stream<T, L, B, Order>
-where
-    Order: MinOrder<NoOrder, Min = NoOrder>,
-{
-    /// Combines elements of the stream into a [`Singleton`], by starting with an intitial value,
-    /// generated by the `init` closure, and then applying the `comb` closure to each element in the stream.
-    /// Unlike iterators, `comb` takes the accumulator by `&mut` reference, so that it can be modified in place.
+    /// This is a token bucket: the bucket starts with `burst` tokens and is refilled at
+    /// `rate_per_sec` tokens per second, up to `burst`. Each forwarded element takes a token.
+    /// The bucket is kept across ticks, even when this stream is inside a tick.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let burst = process.source_iter(q!(vec![1, 2, 3, 4, 5]));
+    /// unsafe { burst.rate_limit(0.001, 3) }
+    /// # }, |mut stream| async move {
+    /// // 1, 2, 3
+    /// # for w in vec![1, 2, 3] {
+    /// #     assert_eq!(stream.next().await.unwrap(), w);
+    /// # }
+    /// # assert!(tokio::time::timeout(std::time::Duration::from_secs(1), stream.next()).await.is_err());
+    /// # }));
+    /// ```
+    ///
+    /// # Safety
+    /// Whether an element is forwarded depends on the wall-clock time at which it is
+    /// processed, which is non-deterministic.
+    ///
+    /// # Panics
+    /// Panics if `rate_per_sec` is not positive or `burst` is zero.
+    pub unsafe fn rate_limit(self, rate_per_sec: f64, burst: u32) -> Stream<T, L, B, Order> {
+        assert!(
+            rate_per_sec > 0.0,
+            "rate_limit rate_per_sec must be positive"
+        );
+        assert!(burst > 0, "rate_limit burst must be at least 1");
+
+        let root = get_this_crate();
+        let init: syn::Expr = parse_quote!(|| {
+            #root::runtime_support::token_bucket::TokenBucket::new(#rate_per_sec, #burst)
+        });
+        let acc: syn::Expr = parse_quote!(
+            |bucket: &mut #root::runtime_support::token_bucket::TokenBucket, item| {
+                bucket.try_take().then_some(item)
+            }
+        );
+
+        self.scan_static::<Option<T>>(init.into(), acc.into())
+            .filter_map(q!(|forwarded| forwarded))
+    }
+
+    /// Outputs everything in this stream that is *not* contained in the `other` stream.
+    ///
+    /// The `other` stream must be [`Bounded`], since this function will wait until
+    /// all its elements are available before producing any output.
+    pub fn filter_not_in<O2>(self, other: Stream<T, L, Bounded, O2>) -> Stream<T, L, Bounded, Order>
+    where
+        T: Eq + Hash,
+    {
+        check_matching_location(&self.location, &other.location);
+
+        Stream::new(
+            self.location,
+            HydroNode::Difference(
+                Box::new(self.ir_node.into_inner()),
+                Box::new(other.ir_node.into_inner()),
+            ),
+        )
+    }
+
+    /// An operator which allows you to "inspect" each element of a stream without
+    /// modifying it. The closure `f` is called on a reference to each item. This is
+    /// mainly useful for debugging, and should not be used to generate side-effects.
+    pub fn inspect<F: Fn(&T) + 'a>(
+        self,
+        f: impl IntoQuotedMut<'a, F, L>,
+    ) -> Stream<T, L, B, Order> {
+        let f = f.splice_fn1_borrow_ctx(&self.location).into();
+
+        if L::is_top_level() {
+            Stream::new(
+                self.location,
+                HydroNode::Persist(Box::new(HydroNode::Inspect {
+                    f,
+                    input: Box::new(HydroNode::Unpersist(Box::new(self.ir_node.into_inner()))),
+                })),
+            )
+        } else {
+            Stream::new(
+                self.location,
+                HydroNode::Inspect {
+                    f,
+                    input: Box::new(self.ir_node.into_inner()),
+                },
+            )
+        }
+    }
+
+    /// Explicitly "casts" the stream to a type with a different ordering
+    /// guarantee. Useful in unsafe code where the ordering cannot be proven
+    /// by the type-system.
+    ///
+    /// # Safety
+    /// This function is used as an escape hatch, and any mistakes in the
+    /// provided ordering guarantee will propagate into the guarantees
+    /// for the rest of the program.
+    pub unsafe fn assume_ordering<O>(self) -> Stream<T, L, B, O> {
+        Stream::new(self.location, self.ir_node.into_inner())
+    }
+
+    /// Labels the operator that produces this stream, so that the generated DFIR
+    /// uses `name` in place of `stream` in its ident (e.g. `parse_42` instead of
+    /// `stream_42`). This has no effect on the semantics of the program, but makes
+    /// the generated code and diagnostics easier to read.
+    ///
+    /// # Panics
+    /// Panics if `name` is not a valid Rust identifier.
+    pub fn named(self, name: &str) -> Stream<T, L, B, Order> {
+        assert_valid_label(name);
+
+        Stream::new(
+            self.location,
+            HydroNode::Named {
+                name: name.to_string(),
+                input: Box::new(self.ir_node.into_inner()),
+            },
+        )
+    }
+}
+
+impl<'a, T, L: Location<'a>, B, Order> Stream<T, L, B, Order>
+where
+    Order: MinOrder<NoOrder, Min = NoOrder>,
+{
+    /// Combines elements of the stream into a [`Singleton`], by starting with an intitial value,
+    /// generated by the `init` closure, and then applying the `comb` closure to each element in the stream.
+    /// Unlike iterators, `comb` takes the accumulator by `&mut` reference, so that it can be modified in place.
     ///
     /// The `comb` closure must be **commutative**, as the order of input items is not guaranteed.
     ///
@@ -770,6 +1326,84 @@ where
         Optional::new(self.location, core)
     }
 
+    /// Computes the maximum element in the stream as an [`Optional`], where elements are
+    /// compared by the value of the `key` function. When several elements have equally large
+    /// keys, the one that arrived first is kept. The [`Optional`] will be empty until the first
+    /// element in the input arrives.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// let numbers = process.source_iter(q!(vec![1i32, -4, 3]));
+    /// let batch = unsafe { numbers.timestamped(&tick).tick_batch() };
+    /// batch.max_by(q!(|x| x.abs())).all_ticks().drop_timestamp()
+    /// # }, |mut stream| async move {
+    /// // -4
+    /// # assert_eq!(stream.next().await.unwrap(), -4);
+    /// # }));
+    /// ```
+    pub fn max_by<K: Ord, F: Fn(&T) -> K + 'a>(
+        self,
+        key: impl IntoQuotedMut<'a, F, L> + Copy,
+    ) -> Optional<T, L, B> {
+        let key = key.splice_fn1_borrow_ctx(&self.location);
+        self.reduce_by_ordering(key, parse_quote!(::std::cmp::Ordering::Greater))
+    }
+
+    /// Computes the minimum element in the stream as an [`Optional`], where elements are
+    /// compared by the value of the `key` function. When several elements have equally small
+    /// keys, the one that arrived first is kept. The [`Optional`] will be empty until the first
+    /// element in the input arrives.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// let numbers = process.source_iter(q!(vec![-4i32, 1, 3]));
+    /// let batch = unsafe { numbers.timestamped(&tick).tick_batch() };
+    /// batch.min_by(q!(|x| x.abs())).all_ticks().drop_timestamp()
+    /// # }, |mut stream| async move {
+    /// // 1
+    /// # assert_eq!(stream.next().await.unwrap(), 1);
+    /// # }));
+    /// ```
+    pub fn min_by<K: Ord, F: Fn(&T) -> K + 'a>(
+        self,
+        key: impl IntoQuotedMut<'a, F, L> + Copy,
+    ) -> Optional<T, L, B> {
+        let key = key.splice_fn1_borrow_ctx(&self.location);
+        self.reduce_by_ordering(key, parse_quote!(::std::cmp::Ordering::Less))
+    }
+
+    /// Reduces the stream to a single element, replacing the current one with each new
+    /// element whose key compared to the current one's is `replace_when`.
+    fn reduce_by_ordering(self, key: syn::Expr, replace_when: syn::Expr) -> Optional<T, L, B> {
+        let wrapped: syn::Expr = parse_quote!({
+            let key_fn = #key;
+            move |curr, new| {
+                if ::std::cmp::Ord::cmp(&key_fn(&new), &key_fn(&*curr)) == #replace_when {
+                    *curr = new;
+                }
+            }
+        });
+
+        let mut core = HydroNode::Reduce {
+            f: wrapped.into(),
+            input: Box::new(self.ir_node.into_inner()),
+        };
+
+        if L::is_top_level() {
+            core = HydroNode::Persist(Box::new(core));
+        }
+
+        Optional::new(self.location, core)
+    }
+
     /// Computes the minimum element in the stream as an [`Optional`], which
     /// will be empty until the first element in the input arrives.
     ///
@@ -800,6 +1434,10 @@ where
 
     /// Computes the number of elements in the stream as a [`Singleton`].
     ///
+    /// Inside a tick, this counts the elements of the current batch, or of all batches so far if
+    /// the stream was [`Stream::persist`]ed. Outside a tick, it counts all the elements received
+    /// so far, so on an [`Unbounded`] stream the count grows as more elements arrive.
+    ///
     /// # Example
     /// ```rust
     /// # use hydro_lang::*;
@@ -817,6 +1455,68 @@ where
     pub fn count(self) -> Singleton<usize, L, B> {
         self.fold_commutative(q!(|| 0usize), q!(|count, _| *count += 1))
     }
+
+    /// Estimates the number of distinct elements in the stream as a [`Singleton`], using a
+    /// [`HyperLogLog`] sketch instead of keeping every element. The sketch has `2^precision`
+    /// one-byte registers, and its estimates have a relative standard error of about
+    /// `1.04 / sqrt(2^precision)`, so a `precision` of 14 takes 16 KiB for an error of about 0.8%.
+    ///
+    /// Like [`Stream::count`], inside a tick the estimate covers the current batch, or all
+    /// batches so far if the stream was [`Stream::persist`]ed, and outside a tick it covers
+    /// all the elements received so far.
+    ///
+    /// # Panics
+    /// Panics if `precision` is not between [`HyperLogLog::MIN_PRECISION`] and
+    /// [`HyperLogLog::MAX_PRECISION`].
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// let numbers = process.source_iter(q!(vec![1, 2, 2, 3, 3, 3]));
+    /// let batch = unsafe { numbers.timestamped(&tick).tick_batch() };
+    /// batch.approx_distinct_count(10).all_ticks().drop_timestamp()
+    /// # }, |mut stream| async move {
+    /// // 3
+    /// # assert_eq!(stream.next().await.unwrap(), 3);
+    /// # }));
+    /// ```
+    pub fn approx_distinct_count(self, precision: u8) -> Singleton<u64, L, B>
+    where
+        T: Hash,
+    {
+        assert!(
+            (HyperLogLog::MIN_PRECISION..=HyperLogLog::MAX_PRECISION).contains(&precision),
+            "HyperLogLog precision must be between {} and {}, got {}",
+            HyperLogLog::MIN_PRECISION,
+            HyperLogLog::MAX_PRECISION,
+            precision
+        );
+
+        let root = get_this_crate();
+        let init: syn::Expr = parse_quote!(
+            || #root::runtime_support::hyperloglog::HyperLogLog::new(#precision)
+        );
+        let acc: syn::Expr = parse_quote!(
+            |sketch: &mut #root::runtime_support::hyperloglog::HyperLogLog, item| {
+                sketch.insert(&item);
+            }
+        );
+
+        let mut core = HydroNode::Fold {
+            init: init.into(),
+            acc: acc.into(),
+            input: Box::new(self.ir_node.into_inner()),
+        };
+
+        if L::is_top_level() {
+            core = HydroNode::Persist(Box::new(core));
+        }
+
+        Singleton::<HyperLogLog, L, B>::new(self.location, core).map(q!(|sketch| sketch.estimate()))
+    }
 }
 
 impl<'a, T, L: Location<'a>, B> Stream<T, L, B, TotalOrder> {
@@ -840,71 +1540,360 @@ impl<'a, T, L: Location<'a>, B> Stream<T, L, B, TotalOrder> {
         }
     }
 
-    /// Computes the first element in the stream as an [`Optional`], which
-    /// will be empty until the first element in the input arrives.
-    ///
-    /// This requires the stream to have a [`TotalOrder`] guarantee, otherwise
-    /// re-ordering of elements may cause the first element to change.
+    /// Like [`Stream::enumerate`], but the index wraps around to 0 after reaching
+    /// `modulus - 1`, as with fixed-width sequence numbers. The index is kept across ticks, even
+    /// when this stream is inside a tick.
     ///
     /// # Example
     /// ```rust
     /// # use hydro_lang::*;
     /// # use dfir_rs::futures::StreamExt;
     /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
-    /// let tick = process.tick();
-    /// let numbers = process.source_iter(q!(vec![1, 2, 3, 4]));
-    /// let batch = unsafe { numbers.timestamped(&tick).tick_batch() };
-    /// batch.first().all_ticks().drop_timestamp()
+    /// process
+    ///     .source_iter(q!(vec![10, 20, 30, 40, 50]))
+    ///     .enumerate_mod(3)
     /// # }, |mut stream| async move {
-    /// // 1
-    /// # assert_eq!(stream.next().await.unwrap(), 1);
+    /// // (0, 10), (1, 20), (2, 30), (0, 40), (1, 50)
+    /// # for w in vec![(0, 10), (1, 20), (2, 30), (0, 40), (1, 50)] {
+    /// #     assert_eq!(stream.next().await.unwrap(), w);
+    /// # }
     /// # }));
     /// ```
-    pub fn first(self) -> Optional<T, L, B> {
-        Optional::new(self.location, self.ir_node.into_inner())
+    ///
+    /// # Panics
+    /// Panics if `modulus` is zero.
+    pub fn enumerate_mod(self, modulus: u64) -> Stream<(u64, T), L, B, TotalOrder> {
+        assert!(modulus > 0, "enumerate_mod modulus must be at least 1");
+
+        if L::is_top_level() {
+            Stream::new(
+                self.location,
+                HydroNode::Persist(Box::new(HydroNode::EnumerateMod {
+                    modulus,
+                    input: Box::new(HydroNode::Unpersist(Box::new(self.ir_node.into_inner()))),
+                })),
+            )
+        } else {
+            Stream::new(
+                self.location,
+                HydroNode::EnumerateMod {
+                    modulus,
+                    input: Box::new(self.ir_node.into_inner()),
+                },
+            )
+        }
     }
 
-    /// Computes the last element in the stream as an [`Optional`], which
-    /// will be empty until an element in the input arrives.
+    /// Downsamples the stream, passing through only every `stride`-th element: the first
+    /// element, then the `stride`-th, `2 * stride`-th, and so on. To sample by wall-clock time
+    /// instead, use [`Stream::sample_every`].
     ///
-    /// This requires the stream to have a [`TotalOrder`] guarantee, otherwise
-    /// re-ordering of elements may cause the last element to change.
+    /// The count of elements is kept across ticks, even when this stream is inside a tick, so
+    /// which elements are sampled does not depend on how they are batched. On an [`Unbounded`]
+    /// stream, the count runs over the entire stream, so the output is unbounded as well but
+    /// grows `stride` times slower.
     ///
     /// # Example
     /// ```rust
     /// # use hydro_lang::*;
     /// # use dfir_rs::futures::StreamExt;
     /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
-    /// let tick = process.tick();
-    /// let numbers = process.source_iter(q!(vec![1, 2, 3, 4]));
-    /// let batch = unsafe { numbers.timestamped(&tick).tick_batch() };
-    /// batch.last().all_ticks().drop_timestamp()
+    /// process.source_iter(q!(0..10)).sample_every_nth(4)
     /// # }, |mut stream| async move {
-    /// // 4
-    /// # assert_eq!(stream.next().await.unwrap(), 4);
+    /// // 0, 4, 8
+    /// # for w in vec![0, 4, 8] {
+    /// #     assert_eq!(stream.next().await.unwrap(), w);
+    /// # }
     /// # }));
     /// ```
-    pub fn last(self) -> Optional<T, L, B> {
-        self.reduce(q!(|curr, new| *curr = new))
+    ///
+    /// # Panics
+    /// Panics if `stride` is zero.
+    pub fn sample_every_nth(self, stride: usize) -> Stream<T, L, B, TotalOrder> {
+        assert!(stride > 0, "sample_every_nth stride must be at least 1");
+
+        if L::is_top_level() {
+            Stream::new(
+                self.location,
+                HydroNode::Persist(Box::new(HydroNode::SampleEvery {
+                    stride,
+                    input: Box::new(HydroNode::Unpersist(Box::new(self.ir_node.into_inner()))),
+                })),
+            )
+        } else {
+            Stream::new(
+                self.location,
+                HydroNode::SampleEvery {
+                    stride,
+                    input: Box::new(self.ir_node.into_inner()),
+                },
+            )
+        }
     }
 
-    /// Combines elements of the stream into a [`Singleton`], by starting with an intitial value,
-    /// generated by the `init` closure, and then applying the `comb` closure to each element in the stream.
-    /// Unlike iterators, `comb` takes the accumulator by `&mut` reference, so that it can be modified in place.
+    /// Threads an accumulator, created by `init`, through the stream, emitting the output of
+    /// `acc` for each element. Unlike [`Stream::fold`], which only produces the final value,
+    /// this emits one output per input, such as each running total of a sum.
     ///
-    /// The input stream must have a [`TotalOrder`] guarantee, which means that the `comb` closure is allowed
-    /// to depend on the order of elements in the stream.
+    /// At the top level, the accumulator is kept across ticks. Inside a tick, it is reset at
+    /// the start of every tick.
     ///
     /// # Example
     /// ```rust
     /// # use hydro_lang::*;
     /// # use dfir_rs::futures::StreamExt;
     /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
-    /// let tick = process.tick();
-    /// let words = process.source_iter(q!(vec!["HELLO", "WORLD"]));
-    /// let batch = unsafe { words.timestamped(&tick).tick_batch() };
-    /// batch
-    ///     .fold(q!(|| String::new()), q!(|acc, x| acc.push_str(x)))
+    /// process.source_iter(q!(vec![1, 2, 3, 4])).scan(
+    ///     q!(|| 0),
+    ///     q!(|sum, x| {
+    ///         *sum += x;
+    ///         *sum
+    ///     }),
+    /// )
+    /// # }, |mut stream| async move {
+    /// // 1, 3, 6, 10
+    /// # for w in vec![1, 3, 6, 10] {
+    /// #     assert_eq!(stream.next().await.unwrap(), w);
+    /// # }
+    /// # }));
+    /// ```
+    pub fn scan<A, U, I: Fn() -> A + 'a, F: Fn(&mut A, T) -> U + 'a>(
+        self,
+        init: impl IntoQuotedMut<'a, I, L>,
+        acc: impl IntoQuotedMut<'a, F, L>,
+    ) -> Stream<U, L, B, TotalOrder> {
+        let init = init.splice_fn0_ctx(&self.location).into();
+        let acc = acc.splice_fn2_borrow_mut_ctx(&self.location).into();
+
+        if L::is_top_level() {
+            self.scan_static(init, acc)
+        } else {
+            Stream::new(
+                self.location,
+                HydroNode::Scan {
+                    init,
+                    acc,
+                    input: Box::new(self.ir_node.into_inner()),
+                },
+            )
+        }
+    }
+
+    /// Pairs up the elements of this stream with those of `other` by position, so the `i`-th
+    /// output is the `i`-th element of each stream. Once one stream runs out, the remaining
+    /// elements of the other are not emitted.
+    ///
+    /// At the top level, an element waits across ticks for its partner in the other stream.
+    /// Inside a tick, elements are only paired with others in the same tick.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let letters = process.source_iter(q!(vec!['a', 'b', 'c']));
+    /// process.source_iter(q!(vec![1, 2, 3, 4])).zip(letters)
+    /// # }, |mut stream| async move {
+    /// // (1, 'a'), (2, 'b'), (3, 'c')
+    /// # for w in vec![(1, 'a'), (2, 'b'), (3, 'c')] {
+    /// #     assert_eq!(stream.next().await.unwrap(), w);
+    /// # }
+    /// # }));
+    /// ```
+    pub fn zip<U>(self, other: Stream<U, L, B, TotalOrder>) -> Stream<(T, U), L, B, TotalOrder> {
+        check_matching_location(&self.location, &other.location);
+
+        if L::is_top_level() {
+            Stream::new(
+                self.location,
+                HydroNode::Persist(Box::new(HydroNode::Zip(
+                    Box::new(HydroNode::Persist(Box::new(HydroNode::Unpersist(
+                        Box::new(self.ir_node.into_inner()),
+                    )))),
+                    Box::new(HydroNode::Persist(Box::new(HydroNode::Unpersist(
+                        Box::new(other.ir_node.into_inner()),
+                    )))),
+                ))),
+            )
+        } else {
+            Stream::new(
+                self.location,
+                HydroNode::Zip(
+                    Box::new(self.ir_node.into_inner()),
+                    Box::new(other.ir_node.into_inner()),
+                ),
+            )
+        }
+    }
+
+    /// Splits the stream into two, sending each element to the first output with probability
+    /// `p` and to the second otherwise, as for traffic splitting in experiments. Each output
+    /// preserves the relative order of its elements.
+    ///
+    /// The choice for each element is a hash of `seed` and the element's position in the
+    /// stream, so the same seed always splits the same stream the same way.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let (_treatment, control) = process
+    ///     .source_iter(q!(vec![1, 2, 3, 4]))
+    ///     .split_ratio(0.0, 42);
+    /// // with `p = 0.0`, every element goes to the second output
+    /// control
+    /// # }, |mut stream| async move {
+    /// // 1, 2, 3, 4
+    /// # for w in vec![1, 2, 3, 4] {
+    /// #     assert_eq!(stream.next().await.unwrap(), w);
+    /// # }
+    /// # }));
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if `p` is not between 0 and 1.
+    #[expect(clippy::type_complexity, reason = "pair of output streams")]
+    pub fn split_ratio(
+        self,
+        p: f64,
+        seed: u64,
+    ) -> (Stream<T, L, B, TotalOrder>, Stream<T, L, B, TotalOrder>)
+    where
+        T: Clone,
+    {
+        assert!(
+            (0.0..=1.0).contains(&p),
+            "split_ratio p must be between 0 and 1"
+        );
+
+        let root = get_this_crate();
+        let route: syn::Expr = parse_quote!(|(index, element): (u64, _)| {
+            if #root::runtime_support::ratio::assigns_first(#seed, index, #p) {
+                ::std::result::Result::Ok(element)
+            } else {
+                ::std::result::Result::Err(element)
+            }
+        });
+
+        let location = self.location.clone();
+        // Unlike `enumerate`, this index is kept across ticks, so that a stream inside a tick
+        // is not split the same way in every tick.
+        let indexed = self.enumerate_mod(u64::MAX);
+        Stream::<Result<T, T>, L, B, TotalOrder>::new(
+            location,
+            HydroNode::Map {
+                f: route.into(),
+                input: Box::new(indexed.ir_node.into_inner()),
+            },
+        )
+        .split_ok_err()
+    }
+
+    /// Emits each distinct element only the first time it appears, suppressing it every
+    /// time it appears after that. Unlike [`Stream::unique`], the set of elements seen so far
+    /// is kept across ticks even when this stream is inside a tick, so an element seen in an
+    /// earlier tick is never emitted again.
+    ///
+    /// Elements are emitted in the order of their first occurrence.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// process.source_iter(q!(vec![3, 1, 3, 2, 1, 4])).emit_new()
+    /// # }, |mut stream| async move {
+    /// // 3, 1, 2, 4
+    /// # for w in vec![3, 1, 2, 4] {
+    /// #     assert_eq!(stream.next().await.unwrap(), w);
+    /// # }
+    /// # }));
+    /// ```
+    pub fn emit_new(self) -> Stream<T, L, B, TotalOrder>
+    where
+        T: Eq + Hash + Clone,
+    {
+        let init =
+            q!(|| std::collections::HashSet::new()).splice_fn0_ctx::<HashSet<T>>(&self.location);
+        let acc = q!(|seen: &mut std::collections::HashSet<_>, v| {
+            if seen.insert(std::clone::Clone::clone(&v)) {
+                Some(v)
+            } else {
+                None
+            }
+        })
+        .splice_fn2_borrow_mut_ctx::<HashSet<T>, T, Option<T>>(&self.location);
+
+        self.scan_static::<Option<T>>(init.into(), acc.into())
+            .filter_map(q!(|v| v))
+    }
+
+    /// Computes the first element in the stream as an [`Optional`], which
+    /// will be empty until the first element in the input arrives.
+    ///
+    /// This requires the stream to have a [`TotalOrder`] guarantee, otherwise
+    /// re-ordering of elements may cause the first element to change.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// let numbers = process.source_iter(q!(vec![1, 2, 3, 4]));
+    /// let batch = unsafe { numbers.timestamped(&tick).tick_batch() };
+    /// batch.first().all_ticks().drop_timestamp()
+    /// # }, |mut stream| async move {
+    /// // 1
+    /// # assert_eq!(stream.next().await.unwrap(), 1);
+    /// # }));
+    /// ```
+    pub fn first(self) -> Optional<T, L, B> {
+        Optional::new(self.location, self.ir_node.into_inner())
+    }
+
+    /// Computes the last element in the stream as an [`Optional`], which
+    /// will be empty until an element in the input arrives.
+    ///
+    /// This requires the stream to have a [`TotalOrder`] guarantee, otherwise
+    /// re-ordering of elements may cause the last element to change.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// let numbers = process.source_iter(q!(vec![1, 2, 3, 4]));
+    /// let batch = unsafe { numbers.timestamped(&tick).tick_batch() };
+    /// batch.last().all_ticks().drop_timestamp()
+    /// # }, |mut stream| async move {
+    /// // 4
+    /// # assert_eq!(stream.next().await.unwrap(), 4);
+    /// # }));
+    /// ```
+    pub fn last(self) -> Optional<T, L, B> {
+        self.reduce(q!(|curr, new| *curr = new))
+    }
+
+    /// Combines elements of the stream into a [`Singleton`], by starting with an intitial value,
+    /// generated by the `init` closure, and then applying the `comb` closure to each element in the stream.
+    /// Unlike iterators, `comb` takes the accumulator by `&mut` reference, so that it can be modified in place.
+    ///
+    /// The input stream must have a [`TotalOrder`] guarantee, which means that the `comb` closure is allowed
+    /// to depend on the order of elements in the stream.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// let words = process.source_iter(q!(vec!["HELLO", "WORLD"]));
+    /// let batch = unsafe { words.timestamped(&tick).tick_batch() };
+    /// batch
+    ///     .fold(q!(|| String::new()), q!(|acc, x| acc.push_str(x)))
     ///     .all_ticks()
     ///     .drop_timestamp()
     /// # }, |mut stream| async move {
@@ -977,267 +1966,437 @@ impl<'a, T, L: Location<'a>, B> Stream<T, L, B, TotalOrder> {
 
         Optional::new(self.location, core)
     }
-}
 
-impl<'a, T, L: Location<'a> + NoTick + NoTimestamp, O> Stream<T, L, Unbounded, O> {
-    /// Produces a new stream that interleaves the elements of the two input streams.
-    /// The result has [`NoOrder`] because the order of interleaving is not guaranteed.
+    /// Computes an aggregate over a sliding window of the last `window` elements, emitting the
+    /// aggregate for each window once the first `window` elements have arrived.
     ///
-    /// Currently, both input streams must be [`Unbounded`]. When the streams are
-    /// [`Bounded`], you can use [`Stream::chain`] instead.
+    /// Rather than recomputing each window from scratch, the aggregate is updated incrementally:
+    /// `add` folds in the element entering the window and `sub` removes the element leaving it,
+    /// so `sub` must undo the effect of `add` (as with sums or counts). Each step takes constant
+    /// time regardless of the window size.
+    ///
+    /// At the top level, windows span across ticks. Inside a tick, the window is reset at the
+    /// start of each tick.
     ///
     /// # Example
     /// ```rust
     /// # use hydro_lang::*;
     /// # use dfir_rs::futures::StreamExt;
     /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
-    /// let numbers = process.source_iter(q!(vec![1, 2, 3, 4]));
-    /// numbers.clone().map(q!(|x| x + 1)).union(numbers)
+    /// process.source_iter(q!(vec![1, 2, 3, 4, 5])).sliding_reduce(
+    ///     3,
+    ///     q!(|sum, x| *sum += x),
+    ///     q!(|sum, x| *sum -= x),
+    /// )
     /// # }, |mut stream| async move {
-    /// // 2, 3, 4, 5, and 1, 2, 3, 4 interleaved in unknown order
-    /// # for w in vec![2, 3, 4, 5, 1, 2, 3, 4] {
+    /// // 6, 9, 12
+    /// # for w in vec![6, 9, 12] {
     /// #     assert_eq!(stream.next().await.unwrap(), w);
     /// # }
     /// # }));
     /// ```
-    pub fn union<O2>(self, other: Stream<T, L, Unbounded, O2>) -> Stream<T, L, Unbounded, NoOrder> {
-        let tick = self.location.tick();
-        unsafe {
-            // SAFETY: Because the outputs are unordered,
-            // we can interleave batches from both streams.
-            self.timestamped(&tick)
-                .tick_batch()
-                .assume_ordering::<NoOrder>()
-                .chain(
-                    other
-                        .timestamped(&tick)
-                        .tick_batch()
-                        .assume_ordering::<NoOrder>(),
-                )
-                .all_ticks()
-                .drop_timestamp()
-                .assume_ordering()
+    ///
+    /// # Panics
+    /// Panics if `window` is zero.
+    pub fn sliding_reduce<F1: Fn(&mut T, T) + 'a, F2: Fn(&mut T, T) + 'a>(
+        self,
+        window: usize,
+        add: impl IntoQuotedMut<'a, F1, L>,
+        sub: impl IntoQuotedMut<'a, F2, L>,
+    ) -> Stream<T, L, B, TotalOrder>
+    where
+        T: Clone,
+    {
+        assert!(window > 0, "sliding_reduce window must be at least 1");
+
+        let add = add.splice_fn2_borrow_mut_ctx(&self.location).into();
+        let sub = sub.splice_fn2_borrow_mut_ctx(&self.location).into();
+
+        if L::is_top_level() {
+            Stream::new(
+                self.location,
+                HydroNode::Persist(Box::new(HydroNode::SlidingReduce {
+                    window,
+                    add,
+                    sub,
+                    input: Box::new(HydroNode::Persist(Box::new(HydroNode::Unpersist(
+                        Box::new(self.ir_node.into_inner()),
+                    )))),
+                })),
+            )
+        } else {
+            Stream::new(
+                self.location,
+                HydroNode::SlidingReduce {
+                    window,
+                    add,
+                    sub,
+                    input: Box::new(self.ir_node.into_inner()),
+                },
+            )
         }
     }
-}
-
-impl<'a, T, L: Location<'a>, Order> Stream<T, L, Bounded, Order> {
-    /// Produces a new stream that emits the input elements in sorted order.
+    /// Runs a state machine over the stream. Starting from the state created by `init`, `f` is
+    /// called with a mutable reference to the state and each element, and may update the state
+    /// and emit any number of outputs, which are emitted in order.
     ///
-    /// The input stream can have any ordering guarantee, but the output stream
-    /// will have a [`TotalOrder`] guarantee. This operator will block until all
-    /// elements in the input stream are available, so it requires the input stream
-    /// to be [`Bounded`].
+    /// This is more general than a scan, which emits exactly one output per element. The state is
+    /// kept across ticks, even when this stream is inside a tick; use
+    /// [`Stream::state_machine_per_tick`] to reset it at the start of each tick.
     ///
     /// # Example
     /// ```rust
     /// # use hydro_lang::*;
     /// # use dfir_rs::futures::StreamExt;
     /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
-    /// let tick = process.tick();
-    /// let numbers = process.source_iter(q!(vec![4, 2, 3, 1]));
-    /// let batch = unsafe { numbers.timestamped(&tick).tick_batch() };
-    /// batch.sort().all_ticks().drop_timestamp()
+    /// // Emits a running count of elements, along with a marker every time it reaches 3.
+    /// process.source_iter(q!(vec![1, 1, 1, 1])).state_machine(
+    ///     q!(|| 0),
+    ///     q!(|count: &mut i32, x: i32| {
+    ///         *count += x;
+    ///         if *count % 3 == 0 {
+    ///             vec![*count, -1]
+    ///         } else {
+    ///             vec![*count]
+    ///         }
+    ///     }),
+    /// )
     /// # }, |mut stream| async move {
-    /// // 1, 2, 3, 4
-    /// # for w in (1..5) {
+    /// // 1, 2, 3, -1, 4
+    /// # for w in vec![1, 2, 3, -1, 4] {
     /// #     assert_eq!(stream.next().await.unwrap(), w);
     /// # }
     /// # }));
     /// ```
-    pub fn sort(self) -> Stream<T, L, Bounded, TotalOrder>
-    where
-        T: Ord,
-    {
+    pub fn state_machine<S, U, I: Fn() -> S + 'a, F: Fn(&mut S, T) -> Vec<U> + 'a>(
+        self,
+        init: impl IntoQuotedMut<'a, I, L>,
+        f: impl IntoQuotedMut<'a, F, L>,
+    ) -> Stream<U, L, B, TotalOrder> {
+        let init = init.splice_fn0_ctx(&self.location).into();
+        let f = f.splice_fn2_borrow_mut_ctx(&self.location).into();
+
+        if L::is_top_level() {
+            Stream::new(
+                self.location,
+                HydroNode::Persist(Box::new(HydroNode::StateMachine {
+                    init,
+                    f,
+                    input: Box::new(HydroNode::Persist(Box::new(HydroNode::Unpersist(
+                        Box::new(self.ir_node.into_inner()),
+                    )))),
+                })),
+            )
+        } else {
+            Stream::new(
+                self.location,
+                HydroNode::StateMachine {
+                    init,
+                    f,
+                    input: Box::new(HydroNode::Persist(Box::new(self.ir_node.into_inner()))),
+                },
+            )
+        }
+    }
+}
+
+impl<'a, T, L: Location<'a>> Stream<T, Tick<L>, Bounded, TotalOrder> {
+    /// Like [`Stream::state_machine`], but the state is reset to `init()` at the start of
+    /// each tick.
+    pub fn state_machine_per_tick<S, U, I: Fn() -> S + 'a, F: Fn(&mut S, T) -> Vec<U> + 'a>(
+        self,
+        init: impl IntoQuotedMut<'a, I, Tick<L>>,
+        f: impl IntoQuotedMut<'a, F, Tick<L>>,
+    ) -> Stream<U, Tick<L>, Bounded, TotalOrder> {
+        let init = init.splice_fn0_ctx(&self.location).into();
+        let f = f.splice_fn2_borrow_mut_ctx(&self.location).into();
+
         Stream::new(
             self.location,
-            HydroNode::Sort(Box::new(self.ir_node.into_inner())),
+            HydroNode::StateMachine {
+                init,
+                f,
+                input: Box::new(self.ir_node.into_inner()),
+            },
         )
     }
 
-    /// Produces a new stream that first emits the elements of the `self` stream,
-    /// and then emits the elements of the `other` stream. The output stream has
-    /// a [`TotalOrder`] guarantee if and only if both input streams have a
-    /// [`TotalOrder`] guarantee.
+    /// Aggregates the stream into sessions, which end when the whole stream has been idle
+    /// (received no elements) for `gap` consecutive ticks. Each session's elements are folded
+    /// together, starting from `init` and applying `comb` to each element, and the aggregate is
+    /// emitted at the end of the `gap`-th idle tick.
     ///
-    /// Currently, both input streams must be [`Bounded`]. This operator will block
-    /// on the first stream until all its elements are available. In a future version,
-    /// we will relax the requirement on the `other` stream.
+    /// This is an unkeyed session window; the time of the last activity is tracked for the
+    /// stream as a whole. A session that is still active is not emitted.
+    ///
+    /// # Panics
+    /// Panics if `gap` is zero.
+    pub fn global_session<A, I: Fn() -> A + 'a, F: Fn(&mut A, T) + 'a>(
+        self,
+        gap: usize,
+        init: impl IntoQuotedMut<'a, I, Tick<L>>,
+        comb: impl IntoQuotedMut<'a, F, Tick<L>>,
+    ) -> Stream<A, Tick<L>, Bounded, TotalOrder>
+    where
+        L: NoTick,
+    {
+        assert!(gap > 0, "global_session gap must be at least 1");
+
+        let init = init.splice_fn0_ctx(&self.location);
+        let comb = comb.splice_fn2_borrow_mut_ctx(&self.location);
+
+        let gap = gap as u64;
+        let scan_init: syn::Expr = parse_quote!(|| ::std::option::Option::None);
+        // The state is the current session's aggregate and the last tick it received an element.
+        // `None` marks the end of the tick's elements, at which point the session may be closed.
+        let scan_acc: syn::Expr = parse_quote!({
+            let init_fn = #init;
+            let comb_fn = #comb;
+            // The operator's closure is rebuilt whenever it runs, so this is the current tick.
+            let now = context.current_tick().0;
+            move |session: &mut ::std::option::Option<(_, u64)>, item: ::std::option::Option<_>| {
+                match item {
+                    ::std::option::Option::Some(v) => {
+                        let (acc, last_active) = session.get_or_insert_with(|| (init_fn(), now));
+                        comb_fn(acc, v);
+                        *last_active = now;
+                        ::std::option::Option::None
+                    }
+                    ::std::option::Option::None => match session {
+                        ::std::option::Option::Some((_, last_active)) if now >= *last_active + #gap => {
+                            session.take().map(|(acc, _)| acc)
+                        }
+                        _ => ::std::option::Option::None,
+                    },
+                }
+            }
+        });
+
+        let end_of_tick = self
+            .location
+            .clone()
+            .singleton(q!(()))
+            .into_stream()
+            .map(q!(|_| None));
+        self.map(q!(|v| Some(v)))
+            .chain(end_of_tick)
+            .scan_static::<Option<A>>(scan_init.into(), scan_acc.into())
+            .filter_map(q!(|closed| closed))
+    }
+
+    /// Passes the stream through a buffer that holds at most `capacity` elements, which are
+    /// released at the end of each tick. This decouples a producer from a consumer that can
+    /// only take `capacity` elements per tick.
+    ///
+    /// When more than `capacity` elements arrive in a tick, `policy` decides what happens to
+    /// the excess: [`OverflowPolicy::DropNewest`] drops the elements that arrived last,
+    /// [`OverflowPolicy::DropOldest`] drops the elements that arrived first, and
+    /// [`OverflowPolicy::Block`] holds them back and releases them, in order, in later ticks.
     ///
     /// # Example
     /// ```rust
     /// # use hydro_lang::*;
+    /// # use hydro_lang::stream::OverflowPolicy;
     /// # use dfir_rs::futures::StreamExt;
     /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
     /// let tick = process.tick();
     /// let numbers = process.source_iter(q!(vec![1, 2, 3, 4]));
     /// let batch = unsafe { numbers.timestamped(&tick).tick_batch() };
     /// batch
-    ///     .clone()
-    ///     .map(q!(|x| x + 1))
-    ///     .chain(batch)
+    ///     .bounded_buffer(2, OverflowPolicy::DropOldest)
     ///     .all_ticks()
     ///     .drop_timestamp()
     /// # }, |mut stream| async move {
-    /// // 2, 3, 4, 5, 1, 2, 3, 4
-    /// # for w in vec![2, 3, 4, 5, 1, 2, 3, 4] {
+    /// // 3, 4
+    /// # for w in vec![3, 4] {
     /// #     assert_eq!(stream.next().await.unwrap(), w);
     /// # }
     /// # }));
     /// ```
-    pub fn chain<O2>(self, other: Stream<T, L, Bounded, O2>) -> Stream<T, L, Bounded, Order::Min>
+    ///
+    /// # Panics
+    /// Panics if `capacity` is zero.
+    pub fn bounded_buffer(
+        self,
+        capacity: usize,
+        policy: OverflowPolicy,
+    ) -> Stream<T, Tick<L>, Bounded, TotalOrder>
     where
-        Order: MinOrder<O2>,
+        L: NoTick,
     {
-        check_matching_location(&self.location, &other.location);
+        assert!(capacity > 0, "bounded_buffer capacity must be at least 1");
 
-        Stream::new(
-            self.location,
-            HydroNode::Chain(
-                Box::new(self.ir_node.into_inner()),
-                Box::new(other.ir_node.into_inner()),
-            ),
-        )
-    }
-}
+        let root = get_this_crate();
+        let policy: syn::Expr = match policy {
+            OverflowPolicy::DropNewest => {
+                parse_quote!(#root::runtime_support::buffer::OverflowPolicy::DropNewest)
+            }
+            OverflowPolicy::DropOldest => {
+                parse_quote!(#root::runtime_support::buffer::OverflowPolicy::DropOldest)
+            }
+            OverflowPolicy::Block => {
+                parse_quote!(#root::runtime_support::buffer::OverflowPolicy::Block)
+            }
+        };
 
-impl<'a, K, V1, L: Location<'a>, B, Order> Stream<(K, V1), L, B, Order> {
-    /// Given two streams of pairs `(K, V1)` and `(K, V2)`, produces a new stream of nested pairs `(K, (V1, V2))`
-    /// by equi-joining the two streams on the key attribute `K`.
-    pub fn join<V2, O2>(self, n: Stream<(K, V2), L, B, O2>) -> Stream<(K, (V1, V2)), L, B, NoOrder>
-    where
-        K: Eq + Hash,
-    {
-        check_matching_location(&self.location, &n.location);
+        let scan_init: syn::Expr = parse_quote!(|| {
+            #root::runtime_support::buffer::BoundedBuffer::new(#capacity, #policy)
+        });
+        // `None` marks the end of the tick's elements, at which point the buffer is drained.
+        let scan_acc: syn::Expr = parse_quote!(
+            |buffer: &mut #root::runtime_support::buffer::BoundedBuffer<_>,
+             item: ::std::option::Option<_>| {
+                match item {
+                    ::std::option::Option::Some(v) => {
+                        buffer.push(v);
+                        ::std::vec::Vec::new()
+                    }
+                    ::std::option::Option::None => buffer.drain(),
+                }
+            }
+        );
 
-        Stream::new(
-            self.location,
-            HydroNode::Join(
-                Box::new(self.ir_node.into_inner()),
-                Box::new(n.ir_node.into_inner()),
-            ),
-        )
+        let end_of_tick = self
+            .location
+            .clone()
+            .singleton(q!(()))
+            .into_stream()
+            .map(q!(|_| None));
+        self.map(q!(|v| Some(v)))
+            .chain(end_of_tick)
+            .scan_static::<Vec<T>>(scan_init.into(), scan_acc.into())
+            .flatten_ordered()
     }
 
-    /// Given two streams of pairs `(K, V1)` and `(K, V2)`,
-    /// computes the anti-join of the items in the input -- i.e. returns
-    /// unique items in the first input that do not have a matching key
-    /// in the second input.
-    pub fn anti_join<O2>(self, n: Stream<K, L, Bounded, O2>) -> Stream<(K, V1), L, B, Order>
+    /// Converts the elements of each tick into a single Apache Arrow
+    /// [`RecordBatch`](crate::arrow::RecordBatch), with one row per element in order, for
+    /// feeding Hydro output into Arrow-based tools. Ticks with no elements do not produce a
+    /// batch.
+    ///
+    /// # Panics
+    /// Panics if the columns produced by
+    /// [`ArrowSerialize::columns`](crate::arrow::ArrowSerialize::columns) do not match its
+    /// schema.
+    #[cfg(feature = "arrow")]
+    #[expect(
+        clippy::wrong_self_convention,
+        reason = "consumes the stream like every other operator"
+    )]
+    pub fn to_arrow(self) -> Stream<crate::arrow::RecordBatch, Tick<L>, Bounded, TotalOrder>
     where
-        K: Eq + Hash,
+        T: crate::arrow::ArrowSerialize + Clone,
     {
-        check_matching_location(&self.location, &n.location);
+        let root = get_this_crate();
+        let to_batch: syn::Expr = parse_quote!(|rows: ::std::vec::Vec<_>| {
+            #root::arrow::ArrowSerialize::to_record_batch(&rows)
+                .expect("`ArrowSerialize` columns do not match the schema")
+        });
 
+        let rows = self
+            .fold(q!(|| Vec::new()), q!(|rows, x| rows.push(x)))
+            .filter(q!(|rows| !rows.is_empty()))
+            .into_stream();
         Stream::new(
-            self.location,
-            HydroNode::AntiJoin(
-                Box::new(self.ir_node.into_inner()),
-                Box::new(n.ir_node.into_inner()),
-            ),
+            rows.location.clone(),
+            HydroNode::Map {
+                f: to_batch.into(),
+                input: Box::new(rows.ir_node.into_inner()),
+            },
         )
     }
 }
 
-impl<'a, K: Eq + Hash, V, L: Location<'a>> Stream<(K, V), Tick<L>, Bounded> {
-    /// A special case of [`Stream::fold`], in the spirit of SQL's GROUP BY and aggregation constructs. The input
-    /// tuples are partitioned into groups by the first element ("keys"), and for each group the values
-    /// in the second element are accumulated via the `comb` closure.
-    ///
-    /// The input stream must have a [`TotalOrder`] guarantee, which means that the `comb` closure is allowed
-    /// to depend on the order of elements in the stream.
+impl<'a, T, L: Location<'a> + NoTick + NoTimestamp, O> Stream<T, L, Unbounded, O> {
+    /// Produces a new stream that interleaves the elements of the two input streams.
+    /// The result has [`NoOrder`] because the order of interleaving is not guaranteed.
     ///
-    /// If the input and output value types are the same and do not require initialization then use
-    /// [`Stream::reduce_keyed`].
+    /// Currently, both input streams must be [`Unbounded`]. When the streams are
+    /// [`Bounded`], you can use [`Stream::chain`] instead.
     ///
     /// # Example
     /// ```rust
     /// # use hydro_lang::*;
     /// # use dfir_rs::futures::StreamExt;
     /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
-    /// let tick = process.tick();
-    /// let numbers = process.source_iter(q!(vec![(1, 2), (2, 3), (1, 3), (2, 4)]));
-    /// let batch = unsafe { numbers.timestamped(&tick).tick_batch() };
-    /// batch
-    ///     .fold_keyed(q!(|| 0), q!(|acc, x| *acc += x))
-    ///     .all_ticks()
-    ///     .drop_timestamp()
+    /// let numbers = process.source_iter(q!(vec![1, 2, 3, 4]));
+    /// numbers.clone().map(q!(|x| x + 1)).union(numbers)
     /// # }, |mut stream| async move {
-    /// // (1, 5), (2, 7)
-    /// # assert_eq!(stream.next().await.unwrap(), (1, 5));
-    /// # assert_eq!(stream.next().await.unwrap(), (2, 7));
+    /// // 2, 3, 4, 5, and 1, 2, 3, 4 interleaved in unknown order
+    /// # for w in vec![2, 3, 4, 5, 1, 2, 3, 4] {
+    /// #     assert_eq!(stream.next().await.unwrap(), w);
+    /// # }
     /// # }));
     /// ```
-    pub fn fold_keyed<A, I: Fn() -> A + 'a, F: Fn(&mut A, V) + 'a>(
-        self,
-        init: impl IntoQuotedMut<'a, I, Tick<L>>,
-        comb: impl IntoQuotedMut<'a, F, Tick<L>>,
-    ) -> Stream<(K, A), Tick<L>, Bounded> {
-        let init = init.splice_fn0_ctx(&self.location).into();
-        let comb = comb.splice_fn2_borrow_mut_ctx(&self.location).into();
-
-        Stream::new(
-            self.location,
-            HydroNode::FoldKeyed {
-                init,
-                acc: comb,
-                input: Box::new(self.ir_node.into_inner()),
-            },
-        )
+    pub fn union<O2>(self, other: Stream<T, L, Unbounded, O2>) -> Stream<T, L, Unbounded, NoOrder> {
+        let tick = self.location.tick();
+        unsafe {
+            // SAFETY: Because the outputs are unordered,
+            // we can interleave batches from both streams.
+            self.timestamped(&tick)
+                .tick_batch()
+                .assume_ordering::<NoOrder>()
+                .chain(
+                    other
+                        .timestamped(&tick)
+                        .tick_batch()
+                        .assume_ordering::<NoOrder>(),
+                )
+                .all_ticks()
+                .drop_timestamp()
+                .assume_ordering()
+        }
     }
+}
 
-    /// A special case of [`Stream::reduce`], in the spirit of SQL's GROUP BY and aggregation constructs. The input
-    /// tuples are partitioned into groups by the first element ("keys"), and for each group the values
-    /// in the second element are accumulated via the `comb` closure.
-    ///
-    /// The input stream must have a [`TotalOrder`] guarantee, which means that the `comb` closure is allowed
-    /// to depend on the order of elements in the stream.
-    ///
-    /// If you need the accumulated value to have a different type than the input, use [`Stream::fold_keyed`].
+impl<'a, T, L: Location<'a>, Order> Stream<T, L, Unbounded, Order> {
+    /// Groups consecutive elements into `Vec`s of at most `size` elements, such as to feed a
+    /// downstream that processes elements in bulk. Because the stream is [`Unbounded`], batches
+    /// never span ticks: the elements that arrived in each tick are batched, and whatever is left
+    /// over at the end of the tick is flushed as a smaller batch.
     ///
     /// # Example
     /// ```rust
     /// # use hydro_lang::*;
     /// # use dfir_rs::futures::StreamExt;
     /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
-    /// let tick = process.tick();
-    /// let numbers = process.source_iter(q!(vec![(1, 2), (2, 3), (1, 3), (2, 4)]));
-    /// let batch = unsafe { numbers.timestamped(&tick).tick_batch() };
-    /// batch
-    ///     .reduce_keyed(q!(|acc, x| *acc += x))
-    ///     .all_ticks()
-    ///     .drop_timestamp()
+    /// process.source_iter(q!(vec![1, 2, 3, 4, 5])).batch(2)
     /// # }, |mut stream| async move {
-    /// // (1, 5), (2, 7)
-    /// # assert_eq!(stream.next().await.unwrap(), (1, 5));
-    /// # assert_eq!(stream.next().await.unwrap(), (2, 7));
+    /// // [1, 2], [3, 4], [5]
+    /// # for w in vec![vec![1, 2], vec![3, 4], vec![5]] {
+    /// #     assert_eq!(stream.next().await.unwrap(), w);
+    /// # }
     /// # }));
     /// ```
-    pub fn reduce_keyed<F: Fn(&mut V, V) + 'a>(
-        self,
-        comb: impl IntoQuotedMut<'a, F, Tick<L>>,
-    ) -> Stream<(K, V), Tick<L>, Bounded> {
-        let f = comb.splice_fn2_borrow_mut_ctx(&self.location).into();
+    ///
+    /// # Panics
+    /// Panics if `size` is zero.
+    pub fn batch(self, size: usize) -> Stream<Vec<T>, L, Unbounded, Order> {
+        assert!(size > 0, "batch size must be at least 1");
 
-        Stream::new(
-            self.location,
-            HydroNode::ReduceKeyed {
-                f,
-                input: Box::new(self.ir_node.into_inner()),
-            },
-        )
+        if L::is_top_level() {
+            Stream::new(
+                self.location,
+                HydroNode::Persist(Box::new(HydroNode::Batch {
+                    size,
+                    input: Box::new(HydroNode::Unpersist(Box::new(self.ir_node.into_inner()))),
+                })),
+            )
+        } else {
+            Stream::new(
+                self.location,
+                HydroNode::Batch {
+                    size,
+                    input: Box::new(self.ir_node.into_inner()),
+                },
+            )
+        }
     }
 }
 
-impl<'a, K: Eq + Hash, V, L: Location<'a>, Order> Stream<(K, V), Tick<L>, Bounded, Order> {
-    /// A special case of [`Stream::fold_commutative`], in the spirit of SQL's GROUP BY and aggregation constructs. The input
-    /// tuples are partitioned into groups by the first element ("keys"), and for each group the values
-    /// in the second element are accumulated via the `comb` closure.
-    ///
-    /// The `comb` closure must be **commutative**, as the order of input items is not guaranteed.
-    ///
-    /// If the input and output value types are the same and do not require initialization then use
-    /// [`Stream::reduce_keyed_commutative`].
+impl<'a, T, L: Location<'a>, Order> Stream<T, L, Bounded, Order> {
+    /// Groups consecutive elements into `Vec`s of at most `size` elements, such as to feed a
+    /// downstream that processes elements in bulk. Because the stream is [`Bounded`], all of it
+    /// is batched together, ending with its partial batch (if any).
     ///
     /// # Example
     /// ```rust
@@ -1245,49 +2404,39 @@ impl<'a, K: Eq + Hash, V, L: Location<'a>, Order> Stream<(K, V), Tick<L>, Bounde
     /// # use dfir_rs::futures::StreamExt;
     /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
     /// let tick = process.tick();
-    /// let numbers = process.source_iter(q!(vec![(1, 2), (2, 3), (1, 3), (2, 4)]));
+    /// let numbers = process.source_iter(q!(vec![1, 2, 3, 4, 5]));
     /// let batch = unsafe { numbers.timestamped(&tick).tick_batch() };
-    /// batch
-    ///     .fold_keyed_commutative(q!(|| 0), q!(|acc, x| *acc += x))
-    ///     .all_ticks()
-    ///     .drop_timestamp()
+    /// batch.batch(2).all_ticks().drop_timestamp()
     /// # }, |mut stream| async move {
-    /// // (1, 5), (2, 7)
-    /// # assert_eq!(stream.next().await.unwrap(), (1, 5));
-    /// # assert_eq!(stream.next().await.unwrap(), (2, 7));
+    /// // [1, 2], [3, 4], [5]
+    /// # for w in vec![vec![1, 2], vec![3, 4], vec![5]] {
+    /// #     assert_eq!(stream.next().await.unwrap(), w);
+    /// # }
     /// # }));
     /// ```
-    pub fn fold_keyed_commutative<A, I: Fn() -> A + 'a, F: Fn(&mut A, V) + 'a>(
-        self,
-        init: impl IntoQuotedMut<'a, I, Tick<L>>,
-        comb: impl IntoQuotedMut<'a, F, Tick<L>>,
-    ) -> Stream<(K, A), Tick<L>, Bounded, Order> {
-        let init = init.splice_fn0_ctx(&self.location).into();
-        let comb = comb.splice_fn2_borrow_mut_ctx(&self.location).into();
+    ///
+    /// # Panics
+    /// Panics if `size` is zero.
+    pub fn batch(self, size: usize) -> Stream<Vec<T>, L, Bounded, Order> {
+        assert!(size > 0, "batch size must be at least 1");
 
+        // Each tick, the input holds the entire bounded stream (at the top level, its persisted
+        // contents), so the batches are over the whole stream rather than each tick's elements.
         Stream::new(
             self.location,
-            HydroNode::FoldKeyed {
-                init,
-                acc: comb,
+            HydroNode::Batch {
+                size,
                 input: Box::new(self.ir_node.into_inner()),
             },
         )
     }
 
-    /// Given a stream of pairs `(K, V)`, produces a new stream of unique keys `K`.
-    pub fn keys(self) -> Stream<K, Tick<L>, Bounded, Order> {
-        self.fold_keyed_commutative(q!(|| ()), q!(|_, _| {}))
-            .map(q!(|(k, _)| k))
-    }
-
-    /// A special case of [`Stream::reduce_commutative`], in the spirit of SQL's GROUP BY and aggregation constructs. The input
-    /// tuples are partitioned into groups by the first element ("keys"), and for each group the values
-    /// in the second element are accumulated via the `comb` closure.
-    ///
-    /// The `comb` closure must be **commutative**, as the order of input items is not guaranteed.
+    /// Produces a new stream that emits the input elements in sorted order.
     ///
-    /// If you need the accumulated value to have a different type than the input, use [`Stream::fold_keyed_commutative`].
+    /// The input stream can have any ordering guarantee, but the output stream
+    /// will have a [`TotalOrder`] guarantee. This operator will block until all
+    /// elements in the input stream are available, so it requires the input stream
+    /// to be [`Bounded`].
     ///
     /// # Example
     /// ```rust
@@ -1295,657 +2444,7378 @@ impl<'a, K: Eq + Hash, V, L: Location<'a>, Order> Stream<(K, V), Tick<L>, Bounde
     /// # use dfir_rs::futures::StreamExt;
     /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
     /// let tick = process.tick();
-    /// let numbers = process.source_iter(q!(vec![(1, 2), (2, 3), (1, 3), (2, 4)]));
+    /// let numbers = process.source_iter(q!(vec![4, 2, 3, 1]));
     /// let batch = unsafe { numbers.timestamped(&tick).tick_batch() };
-    /// batch
-    ///     .reduce_keyed_commutative(q!(|acc, x| *acc += x))
-    ///     .all_ticks()
-    ///     .drop_timestamp()
+    /// batch.sort().all_ticks().drop_timestamp()
     /// # }, |mut stream| async move {
-    /// // (1, 5), (2, 7)
-    /// # assert_eq!(stream.next().await.unwrap(), (1, 5));
-    /// # assert_eq!(stream.next().await.unwrap(), (2, 7));
+    /// // 1, 2, 3, 4
+    /// # for w in (1..5) {
+    /// #     assert_eq!(stream.next().await.unwrap(), w);
+    /// # }
     /// # }));
     /// ```
-    pub fn reduce_keyed_commutative<F: Fn(&mut V, V) + 'a>(
-        self,
-        comb: impl IntoQuotedMut<'a, F, Tick<L>>,
-    ) -> Stream<(K, V), Tick<L>, Bounded, Order> {
-        let f = comb.splice_fn2_borrow_mut_ctx(&self.location).into();
-
+    pub fn sort(self) -> Stream<T, L, Bounded, TotalOrder>
+    where
+        T: Ord,
+    {
         Stream::new(
             self.location,
-            HydroNode::ReduceKeyed {
-                f,
-                input: Box::new(self.ir_node.into_inner()),
-            },
+            HydroNode::Sort(Box::new(self.ir_node.into_inner())),
         )
     }
-}
 
-impl<'a, T, L: Location<'a> + NoTick, B, Order> Stream<T, Timestamped<L>, B, Order> {
-    /// Given a tick, returns a stream corresponding to a batch of elements for that tick.
-    /// These batches are guaranteed to be contiguous across ticks and preserve the order
-    /// of the input.
+    /// Produces a new stream that first emits the elements of the `self` stream,
+    /// and then emits the elements of the `other` stream. The output stream has
+    /// a [`TotalOrder`] guarantee if and only if both input streams have a
+    /// [`TotalOrder`] guarantee.
     ///
-    /// # Safety
-    /// The batch boundaries are non-deterministic and may change across executions.
-    pub unsafe fn tick_batch(self) -> Stream<T, Tick<L>, Bounded, Order> {
-        Stream::new(
-            self.location.tick,
-            HydroNode::Unpersist(Box::new(self.ir_node.into_inner())),
-        )
-    }
-
-    pub fn drop_timestamp(self) -> Stream<T, L, B, Order> {
-        Stream::new(self.location.tick.l, self.ir_node.into_inner())
-    }
-
-    pub fn timestamp_source(&self) -> Tick<L> {
-        self.location.tick.clone()
-    }
-}
+    /// Currently, both input streams must be [`Bounded`]. This operator will block
+    /// on the first stream until all its elements are available. In a future version,
+    /// we will relax the requirement on the `other` stream.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// let numbers = process.source_iter(q!(vec![1, 2, 3, 4]));
+    /// let batch = unsafe { numbers.timestamped(&tick).tick_batch() };
+    /// batch
+    ///     .clone()
+    ///     .map(q!(|x| x + 1))
+    ///     .chain(batch)
+    ///     .all_ticks()
+    ///     .drop_timestamp()
+    /// # }, |mut stream| async move {
+    /// // 2, 3, 4, 5, 1, 2, 3, 4
+    /// # for w in vec![2, 3, 4, 5, 1, 2, 3, 4] {
+    /// #     assert_eq!(stream.next().await.unwrap(), w);
+    /// # }
+    /// # }));
+    /// ```
+    pub fn chain<O2>(self, other: Stream<T, L, Bounded, O2>) -> Stream<T, L, Bounded, Order::Min>
+    where
+        Order: MinOrder<O2>,
+    {
+        check_matching_location(&self.location, &other.location);
 
-impl<'a, T, L: Location<'a> + NoTick + NoTimestamp, B, Order> Stream<T, L, B, Order> {
-    pub fn timestamped(self, tick: &Tick<L>) -> Stream<T, Timestamped<L>, B, Order> {
         Stream::new(
-            Timestamped { tick: tick.clone() },
-            self.ir_node.into_inner(),
+            self.location,
+            HydroNode::Chain(
+                Box::new(self.ir_node.into_inner()),
+                Box::new(other.ir_node.into_inner()),
+            ),
         )
     }
 
-    /// Given a time interval, returns a stream corresponding to samples taken from the
-    /// stream roughly at that interval. The output will have elements in the same order
-    /// as the input, but with arbitrary elements skipped between samples. There is also
-    /// no guarantee on the exact timing of the samples.
+    /// Converts a stream that is expected to contain at most one element into an [`Optional`],
+    /// which holds that element if there is one.
     ///
-    /// # Safety
-    /// The output stream is non-deterministic in which elements are sampled, since this
-    /// is controlled by a clock.
-    pub unsafe fn sample_every(
-        self,
-        interval: impl QuotedWithContext<'a, std::time::Duration, L> + Copy + 'a,
-    ) -> Stream<T, L, Unbounded, Order> {
-        let samples = unsafe {
-            // SAFETY: source of intentional non-determinism
-            self.location.source_interval(interval)
+    /// This is useful for catching cardinality bugs in pipelines that should produce zero or
+    /// one result. The check is only performed when the generated program is compiled with
+    /// `debug_assertions`; in release builds the first element is kept.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// let numbers = process.source_iter(q!(vec![1, 2, 3, 4]));
+    /// let batch = unsafe { numbers.timestamped(&tick).tick_batch() };
+    /// batch
+    ///     .filter(q!(|x| *x == 3))
+    ///     .into_optional()
+    ///     .all_ticks()
+    ///     .drop_timestamp()
+    /// # }, |mut stream| async move {
+    /// // 3
+    /// # assert_eq!(stream.next().await.unwrap(), 3);
+    /// # }));
+    /// ```
+    ///
+    /// # Panics
+    /// In debug builds, panics if the stream contains more than one element.
+    pub fn into_optional(self) -> Optional<T, L, Bounded> {
+        let f = q!(|_: &mut _, _| {
+            debug_assert!(
+                false,
+                "into_optional: stream contains more than one element"
+            );
+        })
+        .splice_fn2_borrow_mut_ctx::<T, T, ()>(&self.location)
+        .into();
+        let mut core = HydroNode::Reduce {
+            f,
+            input: Box::new(self.ir_node.into_inner()),
         };
 
-        let tick = self.location.tick();
-        unsafe {
-            // SAFETY: source of intentional non-determinism
-            self.timestamped(&tick)
-                .tick_batch()
-                .continue_if(samples.timestamped(&tick).tick_batch().first())
-                .all_ticks()
-                .drop_timestamp()
+        if L::is_top_level() {
+            core = HydroNode::Persist(Box::new(core));
         }
+
+        Optional::new(self.location, core)
     }
 
-    /// Given a timeout duration, returns an [`Optional`]  which will have a value if the
-    /// stream has not emitted a value since that duration.
+    /// Collects the distinct elements of the stream into a [`HashSet`], held in a [`Singleton`].
+    /// This is equivalent to [`Stream::unique`] followed by collecting the elements, but keeps a
+    /// single set as its state.
     ///
-    /// # Safety
-    /// Timeout relies on non-deterministic sampling of the stream, so depending on when
-    /// samples take place, timeouts may be non-deterministically generated or missed,
-    /// and the notification of the timeout may be delayed as well. There is also no
-    /// guarantee on how long the [`Optional`] will have a value after the timeout is
-    /// detected based on when the next sample is taken.
-    pub unsafe fn timeout(
-        self,
-        duration: impl QuotedWithContext<'a, std::time::Duration, Tick<L>> + Copy + 'a,
-    ) -> Optional<(), L, Unbounded>
+    /// The input stream can have any ordering guarantee, since the set does not depend on the
+    /// order of its elements. The stream must be [`Bounded`] so that the set is complete.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// let numbers = process.source_iter(q!(vec![1, 1, 2, 3, 3]));
+    /// let batch = unsafe { numbers.timestamped(&tick).tick_batch() };
+    /// batch.collect_set().all_ticks().drop_timestamp()
+    /// # }, |mut stream| async move {
+    /// // {1, 2, 3}
+    /// # assert_eq!(stream.next().await.unwrap(), std::collections::HashSet::from([1, 2, 3]));
+    /// # }));
+    /// ```
+    pub fn collect_set(self) -> Singleton<HashSet<T>, L, Bounded>
     where
-        Order: MinOrder<NoOrder, Min = NoOrder>,
+        T: Eq + Hash,
     {
-        let tick = self.location.tick();
+        let init =
+            q!(|| std::collections::HashSet::new()).splice_fn0_ctx::<HashSet<T>>(&self.location);
+        let acc = q!(|set: &mut std::collections::HashSet<_>, v| {
+            set.insert(v);
+        })
+        .splice_fn2_borrow_mut_ctx::<HashSet<T>, T, ()>(&self.location);
 
-        let latest_received = self.fold_commutative(
-            q!(|| None),
-            q!(|latest, _| {
-                // Note: May want to check received ballot against our own?
-                *latest = Some(Instant::now());
-            }),
-        );
+        let mut core = HydroNode::Fold {
+            init: init.into(),
+            acc: acc.into(),
+            input: Box::new(self.ir_node.into_inner()),
+        };
 
-        unsafe {
-            // SAFETY: Non-deterministic delay in detecting a timeout is expected.
-            latest_received.timestamped(&tick).latest_tick()
+        if L::is_top_level() {
+            core = HydroNode::Persist(Box::new(core));
         }
-        .filter_map(q!(move |latest_received| {
-            if let Some(latest_received) = latest_received {
-                if Instant::now().duration_since(latest_received) > duration {
-                    Some(())
-                } else {
-                    None
-                }
-            } else {
-                Some(())
-            }
-        }))
-        .latest()
-        .drop_timestamp()
-    }
-}
 
-impl<'a, T, L: Location<'a> + NoTick, B, Order> Stream<T, L, B, Order> {
-    pub fn for_each<F: Fn(T) + 'a>(self, f: impl IntoQuotedMut<'a, F, L>) {
-        let f = f.splice_fn1_ctx(&self.location).into();
-        self.location
-            .flow_state()
-            .borrow_mut()
-            .leaves
-            .as_mut()
-            .expect(FLOW_USED_MESSAGE)
-            .push(HydroLeaf::ForEach {
-                input: Box::new(HydroNode::Unpersist(Box::new(self.ir_node.into_inner()))),
-                f,
-            });
+        Singleton::new(self.location, core)
     }
+}
 
-    pub fn dest_sink<S: Unpin + futures::Sink<T> + 'a>(
+impl<'a, T, L: Location<'a>> Stream<T, L, Bounded, TotalOrder> {
+    /// Forms the cross-product of the two input streams, like [`Stream::cross_product`], but
+    /// pairs each element with its position in its input stream (as in [`Stream::enumerate`]).
+    ///
+    /// This is useful for building index pairs, such as the cells of a matrix whose rows and
+    /// columns come from the two inputs.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// let rows = unsafe { process.source_iter(q!(vec!['a', 'b'])).timestamped(&tick).tick_batch() };
+    /// let cols = unsafe { process.source_iter(q!(vec!['x', 'y'])).timestamped(&tick).tick_batch() };
+    /// rows.cross_indexed(cols).all_ticks().drop_timestamp()
+    /// # }, |mut stream| async move {
+    /// // ((0, 'a'), (0, 'x')), ((0, 'a'), (1, 'y')), ((1, 'b'), (0, 'x')), ((1, 'b'), (1, 'y')) in any order
+    /// # let mut results = Vec::new();
+    /// # for _ in 0..4 {
+    /// #     results.push(stream.next().await.unwrap());
+    /// # }
+    /// # results.sort();
+    /// # assert_eq!(results, vec![((0, 'a'), (0, 'x')), ((0, 'a'), (1, 'y')), ((1, 'b'), (0, 'x')), ((1, 'b'), (1, 'y'))]);
+    /// # }));
+    /// ```
+    #[expect(clippy::type_complexity, reason = "indexed pairs")]
+    pub fn cross_indexed<O>(
         self,
-        sink: impl QuotedWithContext<'a, S, L>,
-    ) {
-        self.location
-            .flow_state()
-            .borrow_mut()
-            .leaves
-            .as_mut()
-            .expect(FLOW_USED_MESSAGE)
-            .push(HydroLeaf::DestSink {
-                sink: sink.splice_typed_ctx(&self.location).into(),
-                input: Box::new(self.ir_node.into_inner()),
-            });
+        other: Stream<O, L, Bounded, TotalOrder>,
+    ) -> Stream<((usize, T), (usize, O)), L, Bounded, TotalOrder>
+    where
+        T: Clone,
+        O: Clone,
+    {
+        self.enumerate().cross_product(other.enumerate())
     }
 }
 
-impl<'a, T, L: Location<'a>, Order> Stream<T, Tick<L>, Bounded, Order> {
-    pub fn all_ticks(self) -> Stream<T, Timestamped<L>, Unbounded, Order> {
-        Stream::new(
-            Timestamped {
-                tick: self.location.clone(),
-            },
-            HydroNode::Persist(Box::new(self.ir_node.into_inner())),
-        )
-    }
-
-    pub fn persist(self) -> Stream<T, Tick<L>, Bounded, Order>
+impl<'a, T, E, L: Location<'a>, B, Order> Stream<Result<T, E>, L, B, Order> {
+    /// Splits a stream of [`Result`]s into two streams, the first containing the
+    /// values inside `Ok` and the second the values inside `Err`. The input is
+    /// only teed once, and each output preserves the relative order of its elements.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let (oks, _errs) = process
+    ///     .source_iter(q!(vec!["1", "hello", "2"]))
+    ///     .map(q!(|s| s.parse::<usize>().map_err(|e| e.to_string())))
+    ///     .split_ok_err();
+    /// oks
+    /// # }, |mut stream| async move {
+    /// // 1, 2
+    /// # for w in (1..3) {
+    /// #     assert_eq!(stream.next().await.unwrap(), w);
+    /// # }
+    /// # }));
+    /// ```
+    #[expect(clippy::type_complexity, reason = "pair of output streams")]
+    pub fn split_ok_err(self) -> (Stream<T, L, B, Order>, Stream<E, L, B, Order>)
     where
         T: Clone,
+        E: Clone,
     {
-        Stream::new(
-            self.location,
-            HydroNode::Persist(Box::new(self.ir_node.into_inner())),
-        )
+        let oks = self.clone().filter_map(q!(|r| r.ok()));
+        let errs = self.filter_map(q!(|r| r.err()));
+        (oks, errs)
     }
+}
 
-    pub fn defer_tick(self) -> Stream<T, Tick<L>, Bounded, Order> {
-        Stream::new(
-            self.location,
-            HydroNode::DeferTick(Box::new(self.ir_node.into_inner())),
+impl<'a, T: Serialize, L: Location<'a>, B, Order> Stream<T, L, B, Order> {
+    /// Pairs each element with a checksum of its serialized form, so that corruption can be
+    /// detected with [`Stream::verify_checksum`] after the elements are sent to another
+    /// location, such as with [`Stream::send_bincode`].
+    ///
+    /// The checksum is computed over the element's bincode serialization, using a hash that is
+    /// stable across processes.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// process
+    ///     .source_iter(q!(vec![1, 2, 3]))
+    ///     .with_checksum()
+    ///     .verify_checksum()
+    /// # }, |mut stream| async move {
+    /// // 1, 2, 3
+    /// # for w in vec![1, 2, 3] {
+    /// #     assert_eq!(stream.next().await.unwrap(), w);
+    /// # }
+    /// # }));
+    /// ```
+    pub fn with_checksum(self) -> Stream<(u64, T), L, B, Order> {
+        let root = get_this_crate();
+        let f: syn::Expr = parse_quote!(|v| (#root::runtime_support::checksum::checksum(&v), v));
+
+        Stream::new(
+            self.location,
+            HydroNode::Map {
+                f: f.into(),
+                input: Box::new(self.ir_node.into_inner()),
+            },
         )
     }
+}
+
+impl<'a, T: Serialize, L: Location<'a>, B, Order> Stream<(u64, T), L, B, Order> {
+    /// Checks the checksums attached by [`Stream::with_checksum`], passing through the elements
+    /// whose serialized form still matches their checksum. Elements that do not match are
+    /// dropped, and each one is reported on stderr.
+    pub fn verify_checksum(self) -> Stream<T, L, B, Order> {
+        let root = get_this_crate();
+        let f: syn::Expr = parse_quote!(|(checksum, v)| {
+            #root::runtime_support::checksum::verify(checksum, v)
+        });
 
-    pub fn delta(self) -> Stream<T, Tick<L>, Bounded, Order> {
         Stream::new(
             self.location,
-            HydroNode::Delta(Box::new(self.ir_node.into_inner())),
+            HydroNode::FilterMap {
+                f: f.into(),
+                input: Box::new(self.ir_node.into_inner()),
+            },
         )
     }
 }
 
-fn serialize_bincode<T: Serialize>(is_demux: bool) -> syn::Expr {
-    let root = get_this_crate();
+impl<'a, T, L: Location<'a>, B> Stream<(u64, T), L, B, TotalOrder> {
+    /// Detects lost messages in a stream of sequence-numbered `(seq, data)` pairs from a single
+    /// source. Returns the data, passed through in order, and a stream of [`GapReport`]s, one
+    /// for each run of sequence numbers that was skipped.
+    ///
+    /// The highest sequence number seen so far is kept across ticks. The first sequence number
+    /// received is taken as the start of the sequence, and messages with a lower sequence
+    /// number than expected (duplicates, or late arrivals after a gap was reported) are passed
+    /// through without a report.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let (_data, gaps) = process
+    ///     .source_iter(q!(vec![(0, 'a'), (1, 'b'), (4, 'c'), (5, 'd')]))
+    ///     .detect_gaps();
+    /// gaps.map(q!(|gap| (gap.from, gap.to)))
+    /// # }, |mut stream| async move {
+    /// // (2, 3)
+    /// # assert_eq!(stream.next().await.unwrap(), (2, 3));
+    /// # }));
+    /// ```
+    #[expect(clippy::type_complexity, reason = "pair of output streams")]
+    pub fn detect_gaps(
+        self,
+    ) -> (
+        Stream<T, L, B, TotalOrder>,
+        Stream<GapReport, L, B, TotalOrder>,
+    )
+    where
+        T: Clone,
+    {
+        let root = get_this_crate();
+        let init: syn::Expr =
+            parse_quote!(|| #root::runtime_support::gaps::SequenceTracker::default());
+        let acc: syn::Expr = parse_quote!(
+            |tracker: &mut #root::runtime_support::gaps::SequenceTracker, (seq, v): (u64, _)| {
+                match tracker.observe(seq) {
+                    Some(gap) => vec![Err(gap), Ok(v)],
+                    None => vec![Ok(v)],
+                }
+            }
+        );
 
-    let t_type: syn::Type = stageleft::quote_type::<T>();
+        self.scan_static::<Vec<Result<T, GapReport>>>(init.into(), acc.into())
+            .flatten_ordered()
+            .split_ok_err()
+    }
+}
 
-    if is_demux {
-        parse_quote! {
-            |(id, data): (#root::ClusterId<_>, #t_type)| {
-                (id.raw_id, #root::runtime_support::bincode::serialize::<#t_type>(&data).unwrap().into())
-            }
-        }
-    } else {
-        parse_quote! {
-            |data| {
-                #root::runtime_support::bincode::serialize::<#t_type>(&data).unwrap().into()
+impl<'a, T, L: Location<'a>, B, Order> Stream<(u64, T), L, B, Order> {
+    /// Releases the data of sequence-numbered `(seq, data)` pairs in contiguous sequence order,
+    /// starting at `start_seq`, however the pairs were ordered on arrival. Elements that arrive
+    /// ahead of a missing sequence number are buffered until it arrives, so a gap blocks
+    /// everything after it. Elements whose sequence number was already released, or is below
+    /// `start_seq`, are dropped.
+    ///
+    /// The buffer and the next expected sequence number are kept across ticks, even when this
+    /// stream is inside a tick.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// process
+    ///     .source_iter(q!(vec![(2, 'c'), (0, 'a'), (4, 'e'), (1, 'b')]))
+    ///     .reorder(0)
+    /// # }, |mut stream| async move {
+    /// // 'a', 'b', 'c' ('e' waits for sequence number 3)
+    /// # for w in vec!['a', 'b', 'c'] {
+    /// #     assert_eq!(stream.next().await.unwrap(), w);
+    /// # }
+    /// # assert!(tokio::time::timeout(std::time::Duration::from_secs(1), stream.next()).await.is_err());
+    /// # }));
+    /// ```
+    pub fn reorder(self, start_seq: u64) -> Stream<T, L, B, TotalOrder> {
+        let root = get_this_crate();
+        let init: syn::Expr = parse_quote!(|| {
+            #root::runtime_support::reliable::ReorderBuffer::starting_at(#start_seq)
+        });
+        let acc: syn::Expr = parse_quote!(
+            |buffer: &mut #root::runtime_support::reliable::ReorderBuffer<_>, (seq, v): (u64, _)| {
+                buffer.receive(seq, v)
             }
-        }
+        );
+
+        let released = self
+            .scan_static::<Vec<T>>(init.into(), acc.into())
+            .flatten_ordered();
+
+        // Elements are released in sequence order, whatever the order of the input.
+        Stream::new(released.location, released.ir_node.into_inner())
     }
 }
 
-pub(super) fn deserialize_bincode<T: DeserializeOwned>(tagged: Option<syn::Type>) -> syn::Expr {
-    let root = get_this_crate();
-
-    let t_type: syn::Type = stageleft::quote_type::<T>();
+impl<'a, T, L: Location<'a>, B, Order> Stream<T, L, B, Order> {
+    /// Pairs each element with a [`TraceContext`] identifying the `tracing` span it was
+    /// produced in, so that the span can be continued with [`Stream::restore_trace`] after the
+    /// elements are sent to another location, such as with [`Stream::send_bincode`].
+    ///
+    /// If no span is entered, or no subscriber is installed, each element gets a fresh random
+    /// span id instead, which still lets it be correlated across locations.
+    pub fn with_trace(self) -> Stream<(TraceContext, T), L, B, Order> {
+        let root = get_this_crate();
+        let f: syn::Expr = parse_quote!(|v| {
+            (#root::runtime_support::trace::TraceContext::current(), v)
+        });
 
-    if let Some(c_type) = tagged {
-        parse_quote! {
-            |res| {
-                let (id, b) = res.unwrap();
-                (#root::ClusterId::<#c_type>::from_raw(id), #root::runtime_support::bincode::deserialize::<#t_type>(&b).unwrap())
-            }
-        }
-    } else {
-        parse_quote! {
-            |res| {
-                #root::runtime_support::bincode::deserialize::<#t_type>(&res.unwrap()).unwrap()
-            }
-        }
+        Stream::new(
+            self.location,
+            HydroNode::Map {
+                f: f.into(),
+                input: Box::new(self.ir_node.into_inner()),
+            },
+        )
     }
 }
 
-impl<'a, T, C1, B, Order> Stream<T, Cluster<'a, C1>, B, Order> {
-    pub fn decouple_cluster<C2: 'a, Tag>(
-        self,
-        other: &Cluster<'a, C2>,
-    ) -> Stream<T, Cluster<'a, C2>, Unbounded, Order>
-    where
-        Cluster<'a, C1>: Location<'a, Root = Cluster<'a, C1>>,
-        Cluster<'a, C1>:
-            CanSend<'a, Cluster<'a, C2>, In<T> = (ClusterId<C2>, T), Out<T> = (Tag, T)>,
-        T: Clone + Serialize + DeserializeOwned,
-        Order:
-            MinOrder<<Cluster<'a, C1> as CanSend<'a, Cluster<'a, C2>>>::OutStrongestOrder<Order>>,
-    {
-        let sent = self
-            .map(q!(move |b| (
-                ClusterId::from_raw(CLUSTER_SELF_ID.raw_id),
-                b.clone()
-            )))
-            .send_bincode_interleaved(other);
+impl<'a, T, L: Location<'a>, B, Order> Stream<(TraceContext, T), L, B, Order> {
+    /// Continues the spans captured by [`Stream::with_trace`], pairing each element with a
+    /// `hydro_remote` span that records the sender's span id as its `remote_span_id` field.
+    /// Work done inside the span, such as with
+    /// [`Span::in_scope`](dfir_rs::tracing::Span::in_scope), is then linked to the sending side
+    /// by trace collectors.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// process
+    ///     .source_iter(q!(vec![1, 2, 3]))
+    ///     .with_trace()
+    ///     .restore_trace()
+    ///     .map(q!(|(span, x)| span.in_scope(|| x * 2)))
+    /// # }, |mut stream| async move {
+    /// // 2, 4, 6
+    /// # for w in vec![2, 4, 6] {
+    /// #     assert_eq!(stream.next().await.unwrap(), w);
+    /// # }
+    /// # }));
+    /// ```
+    pub fn restore_trace(self) -> Stream<(RemoteSpan, T), L, B, Order> {
+        let root = get_this_crate();
+        let f: syn::Expr = parse_quote!(
+            |(context, v): (#root::runtime_support::trace::TraceContext, _)| (context.restore(), v)
+        );
 
-        unsafe {
-            // SAFETY: this is safe because we are mapping clusters 1:1
-            sent.assume_ordering()
-        }
+        Stream::new(
+            self.location,
+            HydroNode::Map {
+                f: f.into(),
+                input: Box::new(self.ir_node.into_inner()),
+            },
+        )
     }
 }
 
-impl<'a, T, L: Location<'a> + NoTick, B, Order> Stream<T, L, B, Order> {
-    pub fn decouple_process<P2>(
-        self,
-        other: &Process<'a, P2>,
-    ) -> Stream<T, Process<'a, P2>, Unbounded, Order>
-    where
-        L::Root: CanSend<'a, Process<'a, P2>, In<T> = T, Out<T> = T>,
-        T: Clone + Serialize + DeserializeOwned,
-        Order: MinOrder<
-            <L::Root as CanSend<'a, Process<'a, P2>>>::OutStrongestOrder<Order>,
-            Min = Order,
-        >,
-    {
-        self.send_bincode::<Process<'a, P2>, T>(other)
+impl<'a, T, L: Location<'a>, B, Order> Stream<(TickInstant, T), L, B, Order> {
+    /// Flags elements that arrive after their deadline, given as the last tick in which they
+    /// were expected. Each `(deadline, data)` pair is turned into `(late, data)`, where `late`
+    /// is `true` if the current tick (from [`crate::RUNTIME_CONTEXT`]) is past the deadline.
+    /// This is useful for tracking tardiness, such as for SLA monitoring.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// // everything arrives in the first tick (tick 0)
+    /// process
+    ///     .source_iter(q!(vec![
+    ///         (dfir_rs::scheduled::ticks::TickInstant(0), 'a'),
+    ///         (dfir_rs::scheduled::ticks::TickInstant(5), 'b'),
+    ///     ]))
+    ///     .flag_late()
+    /// # }, |mut stream| async move {
+    /// // (false, 'a'), (false, 'b')
+    /// # for w in vec![(false, 'a'), (false, 'b')] {
+    /// #     assert_eq!(stream.next().await.unwrap(), w);
+    /// # }
+    /// # }));
+    /// ```
+    pub fn flag_late(self) -> Stream<(bool, T), L, B, Order> {
+        self.map(q!(|(deadline, v)| (
+            RUNTIME_CONTEXT.current_tick() > deadline,
+            v
+        )))
     }
+}
 
-    pub fn send_bincode<L2: Location<'a>, CoreType>(
+impl<'a, K, V1, L: Location<'a>, B, Order> Stream<(K, V1), L, B, Order> {
+    /// Transforms the value of each `(key, value)` pair with `f`, leaving the key unchanged.
+    /// This is equivalent to `map(q!(|(k, v)| (k, f(v))))`, but makes it clear that the keys
+    /// are untouched.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let pairs = process.source_iter(q!(vec![(1, 2), (2, 3)]));
+    /// pairs.map_values(q!(|v| v * 10))
+    /// # }, |mut stream| async move {
+    /// // (1, 20), (2, 30)
+    /// # for w in vec![(1, 20), (2, 30)] {
+    /// #     assert_eq!(stream.next().await.unwrap(), w);
+    /// # }
+    /// # }));
+    /// ```
+    pub fn map_values<W, F: Fn(V1) -> W + 'a>(
         self,
-        other: &L2,
-    ) -> Stream<<L::Root as CanSend<'a, L2>>::Out<CoreType>, L2, Unbounded, Order::Min>
-    where
-        L::Root: CanSend<'a, L2, In<CoreType> = T>,
-        CoreType: Serialize + DeserializeOwned,
-        Order: MinOrder<<L::Root as CanSend<'a, L2>>::OutStrongestOrder<Order>>,
-    {
-        let serialize_pipeline = Some(serialize_bincode::<CoreType>(L::Root::is_demux()));
-
-        let deserialize_pipeline = Some(deserialize_bincode::<CoreType>(L::Root::tagged_type()));
+        f: impl IntoQuotedMut<'a, F, L>,
+    ) -> Stream<(K, W), L, B, Order> {
+        let f = f.splice_fn1_ctx::<V1, W>(&self.location);
+        let map_f: syn::Expr = parse_quote!({
+            let f = #f;
+            move |(k, v)| (k, f(v))
+        });
 
         Stream::new(
-            other.clone(),
-            HydroNode::Network {
-                from_location: self.location.root().id(),
-                from_key: None,
-                to_location: other.id(),
-                to_key: None,
-                serialize_fn: serialize_pipeline.map(|e| e.into()),
-                instantiate_fn: DebugInstantiate::Building(),
-                deserialize_fn: deserialize_pipeline.map(|e| e.into()),
+            self.location,
+            HydroNode::Map {
+                f: map_f.into(),
                 input: Box::new(self.ir_node.into_inner()),
             },
         )
     }
 
-    pub fn send_bincode_external<L2: 'a, CoreType>(
-        self,
-        other: &ExternalProcess<L2>,
-    ) -> ExternalBincodeStream<L::Out<CoreType>>
+    /// Given two streams of pairs `(K, V1)` and `(K, V2)`, produces a new stream of nested pairs `(K, (V1, V2))`
+    /// by equi-joining the two streams on the key attribute `K`.
+    pub fn join<V2, O2>(self, n: Stream<(K, V2), L, B, O2>) -> Stream<(K, (V1, V2)), L, B, NoOrder>
     where
-        L: CanSend<'a, ExternalProcess<'a, L2>, In<CoreType> = T, Out<CoreType> = CoreType>,
-        CoreType: Serialize + DeserializeOwned,
-        // for now, we restirct Out<CoreType> to be CoreType, which means no tagged cluster -> external
+        K: Eq + Hash,
     {
-        let serialize_pipeline = Some(serialize_bincode::<CoreType>(L::is_demux()));
-
-        let mut flow_state_borrow = self.location.flow_state().borrow_mut();
-
-        let external_key = flow_state_borrow.next_external_out;
-        flow_state_borrow.next_external_out += 1;
+        check_matching_location(&self.location, &n.location);
 
-        let leaves = flow_state_borrow.leaves.as_mut().expect("Attempted to add a leaf to a flow that has already been finalized. No leaves can be added after the flow has been compiled()");
+        Stream::new(
+            self.location,
+            HydroNode::Join(
+                Box::new(self.ir_node.into_inner()),
+                Box::new(n.ir_node.into_inner()),
+            ),
+        )
+    }
 
-        let dummy_f: syn::Expr = syn::parse_quote!(());
+    /// Given two streams of pairs `(K, V1)` and `(K, V2)`,
+    /// computes the anti-join of the items in the input -- i.e. returns
+    /// unique items in the first input that do not have a matching key
+    /// in the second input.
+    pub fn anti_join<O2>(self, n: Stream<K, L, Bounded, O2>) -> Stream<(K, V1), L, B, Order>
+    where
+        K: Eq + Hash,
+    {
+        check_matching_location(&self.location, &n.location);
 
-        leaves.push(HydroLeaf::ForEach {
-            f: dummy_f.into(),
-            input: Box::new(HydroNode::Network {
-                from_location: self.location.root().id(),
-                from_key: None,
-                to_location: other.id(),
-                to_key: Some(external_key),
-                serialize_fn: serialize_pipeline.map(|e| e.into()),
-                instantiate_fn: DebugInstantiate::Building(),
-                deserialize_fn: None,
+        Stream::new(
+            self.location,
+            HydroNode::AntiJoin(
+                Box::new(self.ir_node.into_inner()),
+                Box::new(n.ir_node.into_inner()),
+            ),
+        )
+    }
+
+    /// Forwards at most one element per key in each `period`, dropping any other elements
+    /// for that key that arrive before the period has elapsed. Each key is throttled
+    /// independently, so a hot key cannot starve the others.
+    ///
+    /// The last time an element was forwarded is tracked for each key. Entries older than
+    /// `period` have no effect on throttling and are periodically evicted, so the state is
+    /// bounded by the number of keys active within a single period.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let burst = process.source_iter(q!(vec![(1, 'a'), (1, 'b'), (2, 'c'), (1, 'd'), (2, 'e')]));
+    /// unsafe { burst.throttle_by_key(q!(std::time::Duration::from_secs(3600))) }
+    /// # }, |mut stream| async move {
+    /// // (1, 'a'), (2, 'c')
+    /// # assert_eq!(stream.next().await.unwrap(), (1, 'a'));
+    /// # assert_eq!(stream.next().await.unwrap(), (2, 'c'));
+    /// # assert!(tokio::time::timeout(std::time::Duration::from_secs(1), stream.next()).await.is_err());
+    /// # }));
+    /// ```
+    ///
+    /// # Safety
+    /// Whether an element is forwarded depends on the wall-clock time at which it is
+    /// processed, which is non-deterministic.
+    pub unsafe fn throttle_by_key(
+        self,
+        period: impl QuotedWithContext<'a, std::time::Duration, L> + Copy + 'a,
+    ) -> Stream<(K, V1), L, B, Order>
+    where
+        K: Eq + Hash + Clone,
+    {
+        // (last forwarded time per key, size at which to next evict expired keys)
+        let init = q!(|| (std::collections::HashMap::<_, Instant>::new(), 1024))
+            .splice_fn0_ctx::<(HashMap<K, Instant>, usize)>(&self.location)
+            .into();
+        let acc = q!(move |(last_forwarded, evict_at): &mut (
+            std::collections::HashMap<_, Instant>,
+            usize
+        ),
+                           (k, v): (_, _)| {
+            let now = Instant::now();
+
+            if last_forwarded.len() >= *evict_at {
+                last_forwarded.retain(|_, t| now.duration_since(*t) < period);
+                *evict_at = std::cmp::max(1024, last_forwarded.len() * 2);
+            }
+
+            match last_forwarded.get(&k) {
+                Some(t) if now.duration_since(*t) < period => None,
+                _ => {
+                    last_forwarded.insert(std::clone::Clone::clone(&k), now);
+                    Some((k, v))
+                }
+            }
+        })
+        .splice_fn2_borrow_mut_ctx::<(HashMap<K, Instant>, usize), (K, V1), Option<(K, V1)>>(
+            &self.location,
+        )
+        .into();
+
+        self.scan_static::<Option<(K, V1)>>(init, acc)
+            .filter_map(q!(|forwarded| forwarded))
+    }
+
+    /// Like [`Stream::partition_n`], but routes each element by its key, so that every element
+    /// with the same key goes to the same output. This allows stateful operators on each output
+    /// to run in parallel, each holding the state for its own keys.
+    ///
+    /// Keys are assigned with a stable consistent hash, so a key lands on the same output in
+    /// every tick and every run, and changing `N` to `N + 1` only moves keys to the new output.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let [partition] = process
+    ///     .source_iter(q!(vec![(1, 10), (2, 20), (1, 30)]))
+    ///     .stable_partition::<1>();
+    /// partition
+    /// # }, |mut stream| async move {
+    /// // (1, 10), (2, 20), (1, 30)
+    /// # for w in vec![(1, 10), (2, 20), (1, 30)] {
+    /// #     assert_eq!(stream.next().await.unwrap(), w);
+    /// # }
+    /// # }));
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if `N` is zero.
+    pub fn stable_partition<const N: usize>(self) -> [Stream<(K, V1), L, B, Order>; N]
+    where
+        K: Hash,
+    {
+        let root = get_this_crate();
+        let f: syn::Expr = parse_quote!(|(k, _): &(_, _)| {
+            #root::runtime_support::partition::stable_partition(k, #N)
+        });
+        self.partition_n_with::<N>(f.into())
+    }
+}
+
+impl<'a, K: Eq + Hash, V, L: Location<'a>> Stream<(K, V), Tick<L>, Bounded> {
+    /// A special case of [`Stream::fold`], in the spirit of SQL's GROUP BY and aggregation constructs. The input
+    /// tuples are partitioned into groups by the first element ("keys"), and for each group the values
+    /// in the second element are accumulated via the `comb` closure.
+    ///
+    /// The input stream must have a [`TotalOrder`] guarantee, which means that the `comb` closure is allowed
+    /// to depend on the order of elements in the stream.
+    ///
+    /// If the input and output value types are the same and do not require initialization then use
+    /// [`Stream::reduce_keyed`].
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// let numbers = process.source_iter(q!(vec![(1, 2), (2, 3), (1, 3), (2, 4)]));
+    /// let batch = unsafe { numbers.timestamped(&tick).tick_batch() };
+    /// batch
+    ///     .fold_keyed(q!(|| 0), q!(|acc, x| *acc += x))
+    ///     .all_ticks()
+    ///     .drop_timestamp()
+    /// # }, |mut stream| async move {
+    /// // (1, 5), (2, 7)
+    /// # assert_eq!(stream.next().await.unwrap(), (1, 5));
+    /// # assert_eq!(stream.next().await.unwrap(), (2, 7));
+    /// # }));
+    /// ```
+    pub fn fold_keyed<A, I: Fn() -> A + 'a, F: Fn(&mut A, V) + 'a>(
+        self,
+        init: impl IntoQuotedMut<'a, I, Tick<L>>,
+        comb: impl IntoQuotedMut<'a, F, Tick<L>>,
+    ) -> Stream<(K, A), Tick<L>, Bounded> {
+        let init = init.splice_fn0_ctx(&self.location).into();
+        let comb = comb.splice_fn2_borrow_mut_ctx(&self.location).into();
+
+        Stream::new(
+            self.location,
+            HydroNode::FoldKeyed {
+                init,
+                acc: comb,
                 input: Box::new(self.ir_node.into_inner()),
-            }),
+            },
+        )
+    }
+
+    /// Like [`Stream::fold_keyed`], but emits an aggregate for every key in `keys` each tick,
+    /// even keys that received no values, whose aggregate is just `init()`. This produces a
+    /// complete per-tick snapshot over a known set of keys.
+    ///
+    /// Keys that receive values but are not in `keys` are still emitted.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// let numbers = process.source_iter(q!(vec![(1, 2), (3, 4), (1, 3)]));
+    /// let batch = unsafe { numbers.timestamped(&tick).tick_batch() };
+    /// batch
+    ///     .fold_keyed_dense(
+    ///         tick.singleton(q!(vec![1, 2, 3])),
+    ///         q!(|| 0),
+    ///         q!(|acc, x| *acc += x),
+    ///     )
+    ///     .all_ticks()
+    ///     .drop_timestamp()
+    /// # }, |mut stream| async move {
+    /// // (1, 5), (2, 0), (3, 4) in any order
+    /// # let mut results = Vec::new();
+    /// # for _ in 0..3 {
+    /// #     results.push(stream.next().await.unwrap());
+    /// # }
+    /// # results.sort();
+    /// # assert_eq!(results, vec![(1, 5), (2, 0), (3, 4)]);
+    /// # }));
+    /// ```
+    pub fn fold_keyed_dense<A, I: Fn() -> A + 'a, F: Fn(&mut A, V) + 'a>(
+        self,
+        keys: Singleton<Vec<K>, Tick<L>, Bounded>,
+        init: impl IntoQuotedMut<'a, I, Tick<L>>,
+        comb: impl IntoQuotedMut<'a, F, Tick<L>>,
+    ) -> Stream<(K, A), Tick<L>, Bounded> {
+        let init = init.splice_fn0_ctx(&self.location).into();
+        let comb = comb.splice_fn2_borrow_mut_ctx(&self.location);
+        // Keys from the universe are folded in with no value, so they are initialized but
+        // otherwise left untouched.
+        let acc: syn::Expr = parse_quote!({
+            let comb_fn = #comb;
+            move |acc: &mut _, v: ::std::option::Option<_>| {
+                if let ::std::option::Option::Some(v) = v {
+                    comb_fn(acc, v);
+                }
+            }
         });
 
-        ExternalBincodeStream {
-            process_id: other.id,
-            port_id: external_key,
-            _phantom: PhantomData,
-        }
+        let universe = keys.into_stream().flatten_ordered().map(q!(|k| (k, None)));
+        let input = self.map(q!(|(k, v)| (k, Some(v)))).chain(universe);
+
+        Stream::new(
+            input.location,
+            HydroNode::FoldKeyed {
+                init,
+                acc: acc.into(),
+                input: Box::new(input.ir_node.into_inner()),
+            },
+        )
     }
 
-    pub fn send_bytes<L2: Location<'a>>(
+    /// Like [`Stream::fold_keyed`], but also returns a stream reporting the number of distinct
+    /// keys in the output, emitted once every `every` ticks. This is useful for monitoring the
+    /// growth of keyed state, such as a fold over a [`Stream::persist`]ed input.
+    ///
+    /// The report is emitted on ticks whose number is a multiple of `every`, and is 0 if
+    /// there are no keys in that tick.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// let numbers = process.source_iter(q!(vec![(1, 2), (2, 3), (1, 3)]));
+    /// let batch = unsafe { numbers.timestamped(&tick).tick_batch() };
+    /// let (_sums, cardinality) = batch.with_cardinality_report(1, q!(|| 0), q!(|acc, x| *acc += x));
+    /// cardinality.all_ticks().drop_timestamp()
+    /// # }, |mut stream| async move {
+    /// // 2
+    /// # assert_eq!(stream.next().await.unwrap(), 2);
+    /// # }));
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if `every` is zero.
+    #[expect(clippy::type_complexity, reason = "pair of output streams")]
+    pub fn with_cardinality_report<A, I: Fn() -> A + 'a, F: Fn(&mut A, V) + 'a>(
         self,
-        other: &L2,
-    ) -> Stream<<L::Root as CanSend<'a, L2>>::Out<Bytes>, L2, Unbounded, Order::Min>
+        every: usize,
+        init: impl IntoQuotedMut<'a, I, Tick<L>>,
+        comb: impl IntoQuotedMut<'a, F, Tick<L>>,
+    ) -> (
+        Stream<(K, A), Tick<L>, Bounded>,
+        Stream<usize, Tick<L>, Bounded>,
+    )
     where
-        L::Root: CanSend<'a, L2, In<Bytes> = T>,
-        Order: MinOrder<<L::Root as CanSend<'a, L2>>::OutStrongestOrder<Order>>,
+        K: Clone,
+        A: Clone,
     {
-        let root = get_this_crate();
+        assert!(
+            every > 0,
+            "with_cardinality_report every must be at least 1"
+        );
+
+        let folded = self.fold_keyed(init, comb);
+        let every = every as u64;
+        let report = folded
+            .clone()
+            .count()
+            .filter(q!(move |_| RUNTIME_CONTEXT.current_tick().0 % every == 0))
+            .into_stream();
+
+        (folded, report)
+    }
+
+    /// Like [`Stream::fold_keyed`], but guarantees that the values for each key are passed to `comb`
+    /// in the order they appear in the input, which makes it suitable for order-sensitive aggregations
+    /// such as string concatenation. The output emits each key once, in the order in which the key
+    /// first appeared in the input.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// let words = process.source_iter(q!(vec![(2, "c"), (1, "a"), (2, "d"), (1, "b")]));
+    /// let batch = unsafe { words.timestamped(&tick).tick_batch() };
+    /// batch
+    ///     .fold_keyed_ordered(q!(|| String::new()), q!(|acc, x| acc.push_str(x)))
+    ///     .all_ticks()
+    ///     .drop_timestamp()
+    /// # }, |mut stream| async move {
+    /// // (2, "cd"), (1, "ab")
+    /// # assert_eq!(stream.next().await.unwrap(), (2, "cd".to_string()));
+    /// # assert_eq!(stream.next().await.unwrap(), (1, "ab".to_string()));
+    /// # }));
+    /// ```
+    pub fn fold_keyed_ordered<A, I: Fn() -> A + 'a, F: Fn(&mut A, V) + 'a>(
+        self,
+        init: impl IntoQuotedMut<'a, I, Tick<L>>,
+        comb: impl IntoQuotedMut<'a, F, Tick<L>>,
+    ) -> Stream<(K, A), Tick<L>, Bounded>
+    where
+        K: Clone,
+    {
+        let init = init.splice_fn0_ctx(&self.location);
+        let comb = comb.splice_fn2_borrow_mut_ctx(&self.location);
+
+        // Groups are kept in a `Vec` in order of first appearance, with an index from
+        // each key to its group, and every value is folded in as it arrives.
+        let wrapped_init: syn::Expr =
+            parse_quote!(|| (::std::collections::HashMap::new(), ::std::vec::Vec::new()));
+        let wrapped_comb: syn::Expr = parse_quote!({
+            let init_fn = #init;
+            let comb_fn = #comb;
+            move |acc: &mut (::std::collections::HashMap<_, usize>, ::std::vec::Vec<(_, _)>), (k, v)| {
+                let (indices, groups) = acc;
+                let idx = *indices.entry(::std::clone::Clone::clone(&k)).or_insert_with(|| {
+                    groups.push((k, init_fn()));
+                    groups.len() - 1
+                });
+                comb_fn(&mut groups[idx].1, v);
+            }
+        });
+        let flatten_groups: syn::Expr = parse_quote!(|(_, groups)| groups);
+
         Stream::new(
-            other.clone(),
-            HydroNode::Network {
-                from_location: self.location.root().id(),
-                from_key: None,
-                to_location: other.id(),
-                to_key: None,
-                serialize_fn: None,
-                instantiate_fn: DebugInstantiate::Building(),
-                deserialize_fn: if let Some(c_type) = L::Root::tagged_type() {
-                    let expr: syn::Expr = parse_quote!(|(id, b)| (#root::ClusterId<#c_type>::from_raw(id), b.unwrap().freeze()));
-                    Some(expr.into())
-                } else {
-                    let expr: syn::Expr = parse_quote!(|b| b.unwrap().freeze());
-                    Some(expr.into())
-                },
-                input: Box::new(self.ir_node.into_inner()),
+            self.location,
+            HydroNode::FlatMap {
+                f: flatten_groups.into(),
+                input: Box::new(HydroNode::Fold {
+                    init: wrapped_init.into(),
+                    acc: wrapped_comb.into(),
+                    input: Box::new(self.ir_node.into_inner()),
+                }),
             },
         )
     }
 
-    pub fn send_bytes_external<L2: 'a>(self, other: &ExternalProcess<L2>) -> ExternalBytesPort
+    /// Like [`Stream::fold_keyed`], but keeps at most `mem_limit` accumulators in memory. When the
+    /// limit is exceeded, the least recently updated accumulators are serialized to a temporary
+    /// file, and they are read back in whenever their key appears again. This is intended for
+    /// aggregations over large batches with many distinct keys.
+    ///
+    /// Only the accumulators are spilled; the keys stay in memory. The output emits every key
+    /// once, in no particular order.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// let numbers = process.source_iter(q!(vec![(1, 2), (2, 3), (1, 3), (2, 4)]));
+    /// let batch = unsafe { numbers.timestamped(&tick).tick_batch() };
+    /// batch
+    ///     .fold_keyed_spilling(q!(|| 0), q!(|acc, x| *acc += x), 1)
+    ///     .all_ticks()
+    ///     .drop_timestamp()
+    /// # }, |mut stream| async move {
+    /// // (1, 5), (2, 7) in any order
+    /// # let mut results = vec![stream.next().await.unwrap(), stream.next().await.unwrap()];
+    /// # results.sort();
+    /// # assert_eq!(results, vec![(1, 5), (2, 7)]);
+    /// # }));
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if `mem_limit` is zero.
+    pub fn fold_keyed_spilling<A, I: Fn() -> A + 'a, F: Fn(&mut A, V) + 'a>(
+        self,
+        init: impl IntoQuotedMut<'a, I, Tick<L>>,
+        comb: impl IntoQuotedMut<'a, F, Tick<L>>,
+        mem_limit: usize,
+    ) -> Stream<(K, A), Tick<L>, Bounded, NoOrder>
     where
-        L::Root: CanSend<'a, ExternalProcess<'a, L2>, In<Bytes> = T, Out<Bytes> = Bytes>,
+        K: Clone,
+        A: Serialize + DeserializeOwned,
     {
-        let mut flow_state_borrow = self.location.flow_state().borrow_mut();
-        let external_key = flow_state_borrow.next_external_out;
-        flow_state_borrow.next_external_out += 1;
+        assert!(
+            mem_limit > 0,
+            "fold_keyed_spilling mem_limit must be at least 1"
+        );
+
+        let init = init.splice_fn0_ctx(&self.location);
+        let comb = comb.splice_fn2_borrow_mut_ctx(&self.location);
+
+        // `fold` clones its accumulator on output, so the map is shared through an `Rc` and
+        // drained when the aggregate is emitted.
+        let root = get_this_crate();
+        let wrapped_init: syn::Expr = parse_quote!(|| ::std::rc::Rc::new(::std::cell::RefCell::new(
+            #root::runtime_support::spill::SpillingMap::new(#mem_limit)
+        )));
+        let wrapped_comb: syn::Expr = parse_quote!({
+            let init_fn = #init;
+            let comb_fn = #comb;
+            move |acc: &mut ::std::rc::Rc<::std::cell::RefCell<#root::runtime_support::spill::SpillingMap<_, _>>>, (k, v)| {
+                acc.borrow_mut().update(k, &init_fn, |a| comb_fn(a, v));
+            }
+        });
+        let flatten_entries: syn::Expr = parse_quote!(|acc| acc.borrow_mut().drain());
+
+        Stream::new(
+            self.location,
+            HydroNode::FlatMap {
+                f: flatten_entries.into(),
+                input: Box::new(HydroNode::Fold {
+                    init: wrapped_init.into(),
+                    acc: wrapped_comb.into(),
+                    input: Box::new(self.ir_node.into_inner()),
+                }),
+            },
+        )
+    }
+
+    /// A special case of [`Stream::reduce`], in the spirit of SQL's GROUP BY and aggregation constructs. The input
+    /// tuples are partitioned into groups by the first element ("keys"), and for each group the values
+    /// in the second element are accumulated via the `comb` closure.
+    ///
+    /// The input stream must have a [`TotalOrder`] guarantee, which means that the `comb` closure is allowed
+    /// to depend on the order of elements in the stream.
+    ///
+    /// If you need the accumulated value to have a different type than the input, use [`Stream::fold_keyed`].
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// let numbers = process.source_iter(q!(vec![(1, 2), (2, 3), (1, 3), (2, 4)]));
+    /// let batch = unsafe { numbers.timestamped(&tick).tick_batch() };
+    /// batch
+    ///     .reduce_keyed(q!(|acc, x| *acc += x))
+    ///     .all_ticks()
+    ///     .drop_timestamp()
+    /// # }, |mut stream| async move {
+    /// // (1, 5), (2, 7)
+    /// # assert_eq!(stream.next().await.unwrap(), (1, 5));
+    /// # assert_eq!(stream.next().await.unwrap(), (2, 7));
+    /// # }));
+    /// ```
+    pub fn reduce_keyed<F: Fn(&mut V, V) + 'a>(
+        self,
+        comb: impl IntoQuotedMut<'a, F, Tick<L>>,
+    ) -> Stream<(K, V), Tick<L>, Bounded> {
+        let f = comb.splice_fn2_borrow_mut_ctx(&self.location).into();
+
+        Stream::new(
+            self.location,
+            HydroNode::ReduceKeyed {
+                f,
+                input: Box::new(self.ir_node.into_inner()),
+            },
+        )
+    }
+
+    /// Keeps a single value for each key, either the first or the last one in the stream as
+    /// chosen by `keep`. This relies on the order of the values for each key, so it is only
+    /// available on [`TotalOrder`] streams; on a [`NoOrder`] stream, which value is first is
+    /// not deterministic.
+    ///
+    /// Like [`Stream::fold_keyed`], the values are grouped within the tick, unless the stream is
+    /// [`Stream::persist`]ed, in which case the value is kept across ticks: the first value
+    /// ever seen for the key, or the latest one.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use hydro_lang::stream::KeepPolicy;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// let numbers = process.source_iter(q!(vec![(1, 2), (2, 3), (1, 3), (2, 4)]));
+    /// let batch = unsafe { numbers.timestamped(&tick).tick_batch() };
+    /// batch
+    ///     .dedup_by_key(KeepPolicy::First)
+    ///     .all_ticks()
+    ///     .drop_timestamp()
+    /// # }, |mut stream| async move {
+    /// // (1, 2), (2, 3)
+    /// # assert_eq!(stream.next().await.unwrap(), (1, 2));
+    /// # assert_eq!(stream.next().await.unwrap(), (2, 3));
+    /// # }));
+    /// ```
+    pub fn dedup_by_key(self, keep: KeepPolicy) -> Stream<(K, V), Tick<L>, Bounded> {
+        let kept = match keep {
+            KeepPolicy::First => self.fold_keyed(
+                q!(|| None),
+                q!(|kept, v| {
+                    if kept.is_none() {
+                        *kept = Some(v);
+                    }
+                }),
+            ),
+            KeepPolicy::Last => self.fold_keyed(q!(|| None), q!(|kept, v| *kept = Some(v))),
+        };
+        kept.map(q!(|(k, v)| (k, v.unwrap())))
+    }
+
+    /// Merges two streams of keyed updates into a view of the latest value for each key, which
+    /// is emitted in full every tick.
+    ///
+    /// An update from a later tick always replaces an earlier value, regardless of which stream
+    /// it came from. If both streams update the same key in the same tick, the last update from
+    /// `self` wins.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// let primary = process.source_iter(q!(vec![(1, 100)]));
+    /// let secondary = process.source_iter(q!(vec![(1, 200), (2, 200)]));
+    /// unsafe {
+    ///     primary
+    ///         .timestamped(&tick)
+    ///         .tick_batch()
+    ///         .merge_latest(secondary.timestamped(&tick).tick_batch())
+    /// }
+    /// .all_ticks()
+    /// .drop_timestamp()
+    /// # }, |mut stream| async move {
+    /// // (1, 100), (2, 200) in any order
+    /// # let mut results = vec![stream.next().await.unwrap(), stream.next().await.unwrap()];
+    /// # results.sort();
+    /// # assert_eq!(results, vec![(1, 100), (2, 200)]);
+    /// # }));
+    /// ```
+    pub fn merge_latest(
+        self,
+        other: Stream<(K, V), Tick<L>, Bounded>,
+    ) -> Stream<(K, V), Tick<L>, Bounded>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        // `self` is chained last so that its updates win ties within the tick.
+        other
+            .chain(self)
+            .reduce_keyed(q!(|curr, new| *curr = new))
+            .persist()
+            .reduce_keyed(q!(|curr, new| *curr = new))
+    }
+}
+
+impl<'a, K: Eq + Hash + Clone, V: Clone, L: Location<'a>> Stream<(Vec<K>, V), Tick<L>, Bounded> {
+    /// Like [`Stream::fold_keyed`], but each key is a path in a hierarchy (such as
+    /// `["US", "CA", "SF"]`), and values are aggregated at every level of the hierarchy. A value
+    /// contributes to the aggregate of each non-empty prefix of its path, so the output has one
+    /// aggregate per distinct prefix.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// let sales = process.source_iter(q!(vec![(vec![1, 10], 2), (vec![1, 11], 3)]));
+    /// unsafe { sales.timestamped(&tick).tick_batch() }
+    ///     .rollup(q!(|| 0), q!(|total, x| *total += x))
+    ///     .all_ticks()
+    ///     .drop_timestamp()
+    /// # }, |mut stream| async move {
+    /// // ([1], 5), ([1, 10], 2), ([1, 11], 3)
+    /// # let mut results = Vec::new();
+    /// # for _ in 0..3 {
+    /// #     results.push(stream.next().await.unwrap());
+    /// # }
+    /// # results.sort();
+    /// # assert_eq!(results, vec![(vec![1], 5), (vec![1, 10], 2), (vec![1, 11], 3)]);
+    /// # }));
+    /// ```
+    pub fn rollup<A, I: Fn() -> A + 'a, F: Fn(&mut A, V) + 'a>(
+        self,
+        init: impl IntoQuotedMut<'a, I, Tick<L>>,
+        acc: impl IntoQuotedMut<'a, F, Tick<L>>,
+    ) -> Stream<(Vec<K>, A), Tick<L>, Bounded> {
+        self.flat_map_ordered(q!(|(path, v)| {
+            (1..=path.len())
+                .map(|len| (path[..len].to_vec(), std::clone::Clone::clone(&v)))
+                .collect::<Vec<_>>()
+        }))
+        .fold_keyed(init, acc)
+    }
+}
+
+impl<'a, K: Eq + Hash + Clone, V, L: Location<'a> + NoTick>
+    Stream<(K, (u64, V)), Tick<L>, Bounded>
+{
+    /// Splits a keyed stream into tumbling windows of `window_ticks` ticks, to be aggregated
+    /// with [`Windowed::fold`]. Each value is paired with its event tick, usually the tick in
+    /// which it was produced at this location (from [`crate::RUNTIME_CONTEXT`]), which decides
+    /// its window.
+    ///
+    /// By default, a pane is fired for each key when its window closes. Use [`Windowed::trigger`]
+    /// to also fire early panes, or late panes for elements whose window has already closed.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use hydro_lang::windowing::{PaneStatus, Trigger};
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// let keys = process.source_iter(q!(vec![1, 2, 1]));
+    /// let batch = unsafe { keys.timestamped(&tick).tick_batch() };
+    /// batch
+    ///     .map(q!(|k| (k, (RUNTIME_CONTEXT.current_tick().0, ()))))
+    ///     .windowed(1)
+    ///     .trigger(Trigger::EveryN(2))
+    ///     .fold(q!(|| 0), q!(|count, _| *count += 1))
+    ///     .map(q!(|(k, pane)| (k, pane.status, pane.value)))
+    ///     .all_ticks()
+    ///     .drop_timestamp()
+    /// # }, |mut stream| async move {
+    /// // (1, Early, 2), then (1, OnTime, 2) and (2, OnTime, 1) in any order
+    /// # assert_eq!(stream.next().await.unwrap(), (1, PaneStatus::Early, 2));
+    /// # let mut on_time = vec![stream.next().await.unwrap(), stream.next().await.unwrap()];
+    /// # on_time.sort();
+    /// # assert_eq!(on_time, vec![(1, PaneStatus::OnTime, 2), (2, PaneStatus::OnTime, 1)]);
+    /// # }));
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if `window_ticks` is zero.
+    pub fn windowed(self, window_ticks: u64) -> Windowed<K, V, L> {
+        assert!(window_ticks > 0, "windowed window_ticks must be at least 1");
+
+        Windowed {
+            tick: self.location.clone(),
+            input: self,
+            window_ticks,
+            trigger: Trigger::AtClose,
+            allowed_lateness: window_ticks,
+        }
+    }
+}
+
+impl<'a, K: Eq + Hash + Clone, V, L: Location<'a> + NoTick> Stream<(K, V), Tick<L>, Bounded> {
+    /// Aggregates each key's elements into event-time windows, holding each window's aggregate
+    /// until the `watermark` passes the end of the window. `window_fn` gives the end of the
+    /// window an element belongs to, and the elements of each window are folded together,
+    /// starting from `init` and applying `comb` to each element.
+    ///
+    /// Returns the fired `(key, aggregate)` pairs, emitted in the tick in which the watermark
+    /// reaches the end of their window, and the late elements, which belong to a window that
+    /// fired in an earlier tick. Elements in the same tick as the watermark that fires their
+    /// window are not late. The watermark never moves backwards; a lower watermark than one
+    /// already seen is ignored.
+    #[expect(clippy::type_complexity, reason = "pair of output streams")]
+    pub fn aggregate_on_watermark<
+        A,
+        W: Fn(&V) -> u64 + 'a,
+        I: Fn() -> A + 'a,
+        F: Fn(&mut A, V) + 'a,
+    >(
+        self,
+        watermark: Singleton<u64, Tick<L>, Bounded>,
+        window_fn: impl IntoQuotedMut<'a, W, Tick<L>>,
+        init: impl IntoQuotedMut<'a, I, Tick<L>>,
+        comb: impl IntoQuotedMut<'a, F, Tick<L>>,
+    ) -> (
+        Stream<(K, A), Tick<L>, Bounded>,
+        Stream<(K, V), Tick<L>, Bounded>,
+    )
+    where
+        A: Clone,
+        V: Clone,
+    {
+        let window_fn = window_fn.splice_fn1_borrow_ctx(&self.location);
+        let init = init.splice_fn0_ctx(&self.location);
+        let comb = comb.splice_fn2_borrow_mut_ctx(&self.location);
+
+        let root = get_this_crate();
+        let scan_init: syn::Expr =
+            parse_quote!(|| #root::runtime_support::windowing::WatermarkState::default());
+        // The watermark follows the tick's elements, so that windows are fired after they
+        // have been added.
+        let scan_acc: syn::Expr = parse_quote!({
+            let window_fn = #window_fn;
+            let init_fn = #init;
+            let comb_fn = #comb;
+            move |state: &mut #root::runtime_support::windowing::WatermarkState<_, _>,
+                  item: ::std::result::Result<(_, _), u64>| {
+                match item {
+                    ::std::result::Result::Ok((k, v)) => {
+                        let window_end = window_fn(&v);
+                        state
+                            .insert(k, window_end, v, &init_fn, &comb_fn)
+                            .map(::std::result::Result::Err)
+                            .into_iter()
+                            .collect::<::std::vec::Vec<_>>()
+                    }
+                    ::std::result::Result::Err(watermark) => state
+                        .advance(watermark)
+                        .into_iter()
+                        .map(::std::result::Result::Ok)
+                        .collect(),
+                }
+            }
+        });
+
+        self.map(q!(|kv| Ok(kv)))
+            .chain(watermark.into_stream().map(q!(|w| Err(w))))
+            .scan_static::<Vec<Result<(K, A), (K, V)>>>(scan_init.into(), scan_acc.into())
+            .flatten_ordered()
+            .split_ok_err()
+    }
+}
+
+impl<'a, K: Eq + Hash, V, L: Location<'a>, Order> Stream<(K, V), Tick<L>, Bounded, Order> {
+    /// A special case of [`Stream::fold_commutative`], in the spirit of SQL's GROUP BY and aggregation constructs. The input
+    /// tuples are partitioned into groups by the first element ("keys"), and for each group the values
+    /// in the second element are accumulated via the `comb` closure.
+    ///
+    /// The `comb` closure must be **commutative**, as the order of input items is not guaranteed.
+    ///
+    /// If the input and output value types are the same and do not require initialization then use
+    /// [`Stream::reduce_keyed_commutative`].
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// let numbers = process.source_iter(q!(vec![(1, 2), (2, 3), (1, 3), (2, 4)]));
+    /// let batch = unsafe { numbers.timestamped(&tick).tick_batch() };
+    /// batch
+    ///     .fold_keyed_commutative(q!(|| 0), q!(|acc, x| *acc += x))
+    ///     .all_ticks()
+    ///     .drop_timestamp()
+    /// # }, |mut stream| async move {
+    /// // (1, 5), (2, 7)
+    /// # assert_eq!(stream.next().await.unwrap(), (1, 5));
+    /// # assert_eq!(stream.next().await.unwrap(), (2, 7));
+    /// # }));
+    /// ```
+    pub fn fold_keyed_commutative<A, I: Fn() -> A + 'a, F: Fn(&mut A, V) + 'a>(
+        self,
+        init: impl IntoQuotedMut<'a, I, Tick<L>>,
+        comb: impl IntoQuotedMut<'a, F, Tick<L>>,
+    ) -> Stream<(K, A), Tick<L>, Bounded, Order> {
+        let init = init.splice_fn0_ctx(&self.location).into();
+        let comb = comb.splice_fn2_borrow_mut_ctx(&self.location).into();
+
+        Stream::new(
+            self.location,
+            HydroNode::FoldKeyed {
+                init,
+                acc: comb,
+                input: Box::new(self.ir_node.into_inner()),
+            },
+        )
+    }
+
+    /// Given a stream of pairs `(K, V)`, produces a new stream of unique keys `K`.
+    pub fn keys(self) -> Stream<K, Tick<L>, Bounded, Order> {
+        self.fold_keyed_commutative(q!(|| ()), q!(|_, _| {}))
+            .map(q!(|(k, _)| k))
+    }
+
+    /// A special case of [`Stream::reduce_commutative`], in the spirit of SQL's GROUP BY and aggregation constructs. The input
+    /// tuples are partitioned into groups by the first element ("keys"), and for each group the values
+    /// in the second element are accumulated via the `comb` closure.
+    ///
+    /// The `comb` closure must be **commutative**, as the order of input items is not guaranteed.
+    ///
+    /// If you need the accumulated value to have a different type than the input, use [`Stream::fold_keyed_commutative`].
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// let numbers = process.source_iter(q!(vec![(1, 2), (2, 3), (1, 3), (2, 4)]));
+    /// let batch = unsafe { numbers.timestamped(&tick).tick_batch() };
+    /// batch
+    ///     .reduce_keyed_commutative(q!(|acc, x| *acc += x))
+    ///     .all_ticks()
+    ///     .drop_timestamp()
+    /// # }, |mut stream| async move {
+    /// // (1, 5), (2, 7)
+    /// # assert_eq!(stream.next().await.unwrap(), (1, 5));
+    /// # assert_eq!(stream.next().await.unwrap(), (2, 7));
+    /// # }));
+    /// ```
+    pub fn reduce_keyed_commutative<F: Fn(&mut V, V) + 'a>(
+        self,
+        comb: impl IntoQuotedMut<'a, F, Tick<L>>,
+    ) -> Stream<(K, V), Tick<L>, Bounded, Order> {
+        let f = comb.splice_fn2_borrow_mut_ctx(&self.location).into();
+
+        Stream::new(
+            self.location,
+            HydroNode::ReduceKeyed {
+                f,
+                input: Box::new(self.ir_node.into_inner()),
+            },
+        )
+    }
+
+    /// Treats each tick's batch as a snapshot of a keyed map and emits only what changed
+    /// since the previous tick's snapshot: `(k, Some(v))` for each key that was inserted or
+    /// whose value was updated, and `(k, None)` for each key that was deleted (that is,
+    /// missing from this tick's batch). Keys whose value is unchanged are not emitted.
+    ///
+    /// The previous snapshot is kept across ticks. Each key should appear at most once per
+    /// batch; otherwise, which of its values is compared depends on the order of the batch.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// let entries = process.source_iter(q!(vec![(1, 'a'), (2, 'b')]));
+    /// let batch = unsafe { entries.timestamped(&tick).tick_batch() };
+    /// batch.changelog().all_ticks().drop_timestamp()
+    /// # }, |mut stream| async move {
+    /// // (1, Some('a')), (2, Some('b')), in some order
+    /// # let mut first = vec![stream.next().await.unwrap(), stream.next().await.unwrap()];
+    /// # first.sort();
+    /// # assert_eq!(first, vec![(1, Some('a')), (2, Some('b'))]);
+    /// # }));
+    /// ```
+    pub fn changelog(self) -> Stream<(K, Option<V>), Tick<L>, Bounded, NoOrder>
+    where
+        L: NoTick,
+        K: Clone,
+        V: PartialEq + Clone,
+    {
+        let root = get_this_crate();
+        let init: syn::Expr =
+            parse_quote!(|| #root::runtime_support::changelog::SnapshotDiff::default());
+        // `None` marks the end of the tick's snapshot, at which point the changes are emitted.
+        let acc: syn::Expr = parse_quote!(
+            |diff: &mut #root::runtime_support::changelog::SnapshotDiff<_, _>,
+             item: ::std::option::Option<(_, _)>| {
+                match item {
+                    ::std::option::Option::Some((key, value)) => {
+                        diff.insert(key, value);
+                        ::std::vec::Vec::new()
+                    }
+                    ::std::option::Option::None => diff.end_tick(),
+                }
+            }
+        );
+
+        let tick = self.location.clone();
+        let entries = self.map(q!(|entry| Some(entry)));
+        // The snapshot is a map, so only the end-of-tick marker has to come last, which
+        // chaining guarantees.
+        let entries =
+            Stream::<_, _, Bounded, NoOrder>::new(entries.location, entries.ir_node.into_inner());
+        let end_of_tick: Stream<Option<(K, V)>, Tick<L>, Bounded> =
+            tick.singleton(q!(())).into_stream().map(q!(|_| None));
+
+        entries
+            .chain(end_of_tick)
+            .scan_static::<Vec<(K, Option<V>)>>(init.into(), acc.into())
+            .flatten_unordered()
+    }
+}
+
+impl<'a, T, L: Location<'a> + NoTick, B, Order> Stream<T, Timestamped<L>, B, Order> {
+    /// Given a tick, returns a stream corresponding to a batch of elements for that tick.
+    /// These batches are guaranteed to be contiguous across ticks and preserve the order
+    /// of the input.
+    ///
+    /// # Safety
+    /// The batch boundaries are non-deterministic and may change across executions.
+    pub unsafe fn tick_batch(self) -> Stream<T, Tick<L>, Bounded, Order> {
+        Stream::new(
+            self.location.tick,
+            HydroNode::Unpersist(Box::new(self.ir_node.into_inner())),
+        )
+    }
+
+    pub fn drop_timestamp(self) -> Stream<T, L, B, Order> {
+        Stream::new(self.location.tick.l, self.ir_node.into_inner())
+    }
+
+    pub fn timestamp_source(&self) -> Tick<L> {
+        self.location.tick.clone()
+    }
+}
+
+impl<'a, T, L: Location<'a> + NoTick + NoTimestamp, B, Order> Stream<T, L, B, Order> {
+    pub fn timestamped(self, tick: &Tick<L>) -> Stream<T, Timestamped<L>, B, Order> {
+        Stream::new(
+            Timestamped { tick: tick.clone() },
+            self.ir_node.into_inner(),
+        )
+    }
+
+    /// Given a time interval, returns a stream corresponding to samples taken from the
+    /// stream roughly at that interval. The output will have elements in the same order
+    /// as the input, but with arbitrary elements skipped between samples. There is also
+    /// no guarantee on the exact timing of the samples.
+    ///
+    /// # Safety
+    /// The output stream is non-deterministic in which elements are sampled, since this
+    /// is controlled by a clock.
+    pub unsafe fn sample_every(
+        self,
+        interval: impl QuotedWithContext<'a, std::time::Duration, L> + Copy + 'a,
+    ) -> Stream<T, L, Unbounded, Order> {
+        let samples = unsafe {
+            // SAFETY: source of intentional non-determinism
+            self.location.source_interval(interval)
+        };
+
+        let tick = self.location.tick();
+        unsafe {
+            // SAFETY: source of intentional non-determinism
+            self.timestamped(&tick)
+                .tick_batch()
+                .continue_if(samples.timestamped(&tick).tick_batch().first())
+                .all_ticks()
+                .drop_timestamp()
+        }
+    }
+
+    /// Given a timeout duration, returns an [`Optional`]  which will have a value if the
+    /// stream has not emitted a value since that duration.
+    ///
+    /// # Safety
+    /// Timeout relies on non-deterministic sampling of the stream, so depending on when
+    /// samples take place, timeouts may be non-deterministically generated or missed,
+    /// and the notification of the timeout may be delayed as well. There is also no
+    /// guarantee on how long the [`Optional`] will have a value after the timeout is
+    /// detected based on when the next sample is taken.
+    pub unsafe fn timeout(
+        self,
+        duration: impl QuotedWithContext<'a, std::time::Duration, Tick<L>> + Copy + 'a,
+    ) -> Optional<(), L, Unbounded>
+    where
+        Order: MinOrder<NoOrder, Min = NoOrder>,
+    {
+        let tick = self.location.tick();
+
+        let latest_received = self.fold_commutative(
+            q!(|| None),
+            q!(|latest, _| {
+                // Note: May want to check received ballot against our own?
+                *latest = Some(Instant::now());
+            }),
+        );
+
+        unsafe {
+            // SAFETY: Non-deterministic delay in detecting a timeout is expected.
+            latest_received.timestamped(&tick).latest_tick()
+        }
+        .filter_map(q!(move |latest_received| {
+            if let Some(latest_received) = latest_received {
+                if Instant::now().duration_since(latest_received) > duration {
+                    Some(())
+                } else {
+                    None
+                }
+            } else {
+                Some(())
+            }
+        }))
+        .latest()
+        .drop_timestamp()
+    }
+}
+
+impl<'a, T, L: Location<'a> + NoTick + NoTimestamp, Order> Stream<T, L, Unbounded, Order> {
+    /// Interleaves a [`Heartbeat`] into the stream roughly every `period`, whether or not any
+    /// data is flowing, so that a downstream consumer can tell an idle stream apart from a
+    /// failed one. Data elements are wrapped in [`Either::Left`] and heartbeats are
+    /// [`Either::Right`].
+    ///
+    /// # Safety
+    /// Heartbeats are generated by an OS timer, so where they fall relative to the data is
+    /// non-deterministic.
+    pub unsafe fn with_heartbeats(
+        self,
+        period: impl QuotedWithContext<'a, std::time::Duration, L> + Copy + 'a,
+    ) -> Stream<Either<T, Heartbeat>, L, Unbounded, NoOrder> {
+        let root = get_this_crate();
+        let heartbeats = unsafe {
+            // SAFETY: source of intentional non-determinism
+            self.location.source_interval(period)
+        };
+
+        let data_f: syn::Expr = parse_quote!(#root::runtime_support::heartbeat::Either::Left);
+        let heartbeat_f: syn::Expr = parse_quote!(|_| {
+            #root::runtime_support::heartbeat::Either::Right(
+                #root::runtime_support::heartbeat::Heartbeat,
+            )
+        });
+
+        let data = Stream::<_, L, Unbounded, Order>::new(
+            self.location.clone(),
+            HydroNode::Map {
+                f: data_f.into(),
+                input: Box::new(self.ir_node.into_inner()),
+            },
+        );
+        let heartbeats = Stream::<_, L, Unbounded, TotalOrder>::new(
+            heartbeats.location.clone(),
+            HydroNode::Map {
+                f: heartbeat_f.into(),
+                input: Box::new(heartbeats.ir_node.into_inner()),
+            },
+        );
+        data.union(heartbeats)
+    }
+
+    /// Passes the stream through unchanged, and alongside it emits a [`StallAlert::Stalled`]
+    /// when no element has flowed for longer than `threshold`, followed by a
+    /// [`StallAlert::Recovered`] once elements flow again. Each stall is reported once.
+    ///
+    /// An internal timer checks for stalls every half `threshold`, so a stall is detected
+    /// roughly between `threshold` and 1.5 times `threshold` after the last element.
+    ///
+    /// # Safety
+    /// Stalls are detected with an OS timer, so whether and when they are reported is
+    /// non-deterministic.
+    #[expect(clippy::type_complexity, reason = "pair of output streams")]
+    pub unsafe fn stall_alert(
+        self,
+        threshold: std::time::Duration,
+    ) -> (
+        Stream<T, L, Unbounded, Order>,
+        Stream<StallAlert, L, Unbounded, TotalOrder>,
+    )
+    where
+        T: Clone,
+    {
+        let root = get_this_crate();
+        let check_nanos = (threshold / 2).as_nanos().max(1) as u64;
+        let checks = unsafe {
+            // SAFETY: source of intentional non-determinism
+            self.location
+                .source_interval(q!(std::time::Duration::from_nanos(check_nanos)))
+        };
+
+        let threshold_nanos = threshold.as_nanos() as u64;
+        let init: syn::Expr = parse_quote!(|| {
+            #root::runtime_support::stall::StallDetector::new(
+                ::std::time::Duration::from_nanos(#threshold_nanos),
+            )
+        });
+        let acc: syn::Expr = parse_quote!(
+            |detector: &mut #root::runtime_support::stall::StallDetector, is_element| {
+                detector.observe(is_element)
+            }
+        );
+
+        let events = self
+            .clone()
+            .map(q!(|_| true))
+            .union(checks.map(q!(|_| false)));
+        let alerts = events.scan_static::<Option<StallAlert>>(init.into(), acc.into());
+
+        // Alerts are produced in order by the single scan, whatever the order of its input.
+        let alerts = Stream::<Option<StallAlert>, L, Unbounded, TotalOrder>::new(
+            alerts.location,
+            alerts.ir_node.into_inner(),
+        )
+        .filter_map(q!(|alert| alert));
+
+        (self, alerts)
+    }
+}
+
+impl<'a, T, L: Location<'a> + NoTick + NoTimestamp> Stream<T, L, Unbounded, TotalOrder> {
+    /// Aggregates elements over tumbling windows of `duration` wall-clock time, emitting the
+    /// aggregate of each window once it ends. Each window starts from `init()` and folds in
+    /// its elements with `acc`. Windows are independent of ticks; an internal timer firing
+    /// every `duration` detects when they close, even if no elements arrive.
+    ///
+    /// If `emit_empty` is set, windows without any elements emit `init()`; otherwise they are
+    /// skipped.
+    ///
+    /// # Safety
+    /// Which window an element falls into depends on when it arrives, and windows are only
+    /// closed when the timer fires or an element arrives, so the output is non-deterministic.
+    pub unsafe fn processing_window<A, I: Fn() -> A + 'a, F: Fn(&mut A, T) + 'a>(
+        self,
+        duration: impl QuotedWithContext<'a, std::time::Duration, L> + Copy + 'a,
+        init: impl IntoQuotedMut<'a, I, L>,
+        acc: impl IntoQuotedMut<'a, F, L>,
+        emit_empty: bool,
+    ) -> Stream<A, L, Unbounded, TotalOrder> {
+        let root = get_this_crate();
+        let timer = unsafe {
+            // SAFETY: source of intentional non-determinism
+            self.location.source_interval(duration)
+        };
+
+        let duration = duration.splice_untyped_ctx(&self.location);
+        let init = init.splice_fn0_ctx::<A>(&self.location);
+        let acc = acc.splice_fn2_borrow_mut_ctx::<A, T, ()>(&self.location);
+
+        let scan_init: syn::Expr = parse_quote!(|| {
+            #root::runtime_support::processing_window::ProcessingWindow::new(#duration, #emit_empty)
+        });
+        let scan_acc: syn::Expr = parse_quote!({
+            let init = #init;
+            let acc = #acc;
+            move |window: &mut #root::runtime_support::processing_window::ProcessingWindow<_>, item| {
+                window.observe(item, &init, &acc)
+            }
+        });
+
+        let events = self
+            .map(q!(|item| Some(item)))
+            .union(timer.map(q!(|_| None)));
+        let closed = events.scan_static::<Vec<A>>(scan_init.into(), scan_acc.into());
+
+        // Windows are closed in order by the single scan, whatever the order of its input.
+        Stream::<Vec<A>, L, Unbounded, TotalOrder>::new(
+            closed.location,
+            closed.ir_node.into_inner(),
+        )
+        .flat_map_ordered(q!(|windows| windows))
+    }
+
+    /// Like [`Stream::fold`], but starts the accumulator from the value of the `seed`
+    /// singleton instead of a constant, for when the starting value is itself derived from
+    /// other data (such as a prior checkpoint).
+    ///
+    /// The output is not available until `seed` is; elements that arrive before then are
+    /// held and folded in once it is. If `seed` changes, the output is recomputed from the
+    /// new value over all elements received so far.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let seed = process
+    ///     .source_iter(q!(vec![100]))
+    ///     .fold(q!(|| 0), q!(|acc, x| *acc += x));
+    /// let sum = process
+    ///     .source_iter(q!(vec![1, 2, 3]))
+    ///     .fold_seeded(seed, q!(|acc, x| *acc += x));
+    /// unsafe { sum.sample_eager() }
+    /// # }, |mut stream| async move {
+    /// // 106
+    /// # assert_eq!(stream.next().await.unwrap(), 106);
+    /// # }));
+    /// ```
+    pub fn fold_seeded<A, F: Fn(&mut A, T) + 'a>(
+        self,
+        seed: Singleton<A, L, Unbounded>,
+        comb: impl IntoQuotedMut<'a, F, L>,
+    ) -> Singleton<A, L, Unbounded>
+    where
+        T: Clone,
+    {
+        let location = self.location.clone();
+        let tick = location.tick();
+        let comb = comb.splice_fn2_borrow_mut_ctx(&self.location);
+
+        // Each tick, the latest seed is folded with every element received so far, so the
+        // result converges regardless of how the inputs are batched.
+        let seed_batch = unsafe {
+            // SAFETY: the result is recomputed from scratch each tick
+            seed.timestamped(&tick).latest_tick()
+        }
+        .into_stream()
+        .map(q!(|seed| Err(seed)));
+        let elements = unsafe {
+            // SAFETY: see above
+            self.timestamped(&tick).tick_batch()
+        }
+        .persist()
+        .map(q!(|element| Ok(element)));
+
+        let init: syn::Expr = parse_quote!(|| ::std::option::Option::None);
+        let acc: syn::Expr = parse_quote!({
+            let comb = #comb;
+            move |acc: &mut ::std::option::Option<_>, item: ::std::result::Result<_, _>| {
+                match item {
+                    ::std::result::Result::Err(seed) => *acc = ::std::option::Option::Some(seed),
+                    ::std::result::Result::Ok(element) => {
+                        if let ::std::option::Option::Some(acc) = acc {
+                            comb(acc, element);
+                        }
+                    }
+                }
+            }
+        });
+
+        // The seed is chained first, so it is in place before any element is folded in.
+        let folded = Singleton::<Option<A>, Tick<L>, Bounded>::new(
+            tick,
+            HydroNode::Fold {
+                init: init.into(),
+                acc: acc.into(),
+                input: Box::new(seed_batch.chain(elements).ir_node.into_inner()),
+            },
+        );
+
+        Singleton::new(
+            location,
+            folded
+                .filter_map(q!(|acc| acc))
+                .latest()
+                .drop_timestamp()
+                .ir_node
+                .into_inner(),
+        )
+    }
+}
+
+impl<'a, T, L: Location<'a> + NoTick, B, Order> Stream<T, L, B, Order> {
+    pub fn for_each<F: Fn(T) + 'a>(self, f: impl IntoQuotedMut<'a, F, L>) {
+        let f = f.splice_fn1_ctx(&self.location).into();
+        self.location
+            .flow_state()
+            .borrow_mut()
+            .leaves
+            .as_mut()
+            .expect(FLOW_USED_MESSAGE)
+            .push(HydroLeaf::ForEach {
+                input: Box::new(HydroNode::Unpersist(Box::new(self.ir_node.into_inner()))),
+                f,
+            });
+    }
+
+    pub fn dest_sink<S: Unpin + futures::Sink<T> + 'a>(
+        self,
+        sink: impl QuotedWithContext<'a, S, L>,
+    ) {
+        self.location
+            .flow_state()
+            .borrow_mut()
+            .leaves
+            .as_mut()
+            .expect(FLOW_USED_MESSAGE)
+            .push(HydroLeaf::DestSink {
+                sink: sink.splice_typed_ctx(&self.location).into(),
+                input: Box::new(self.ir_node.into_inner()),
+            });
+    }
+}
+
+impl<'a, L: Location<'a> + NoTick, B> Stream<DurableOffset, L, B, TotalOrder> {
+    /// Commits each offset produced by [`Location::source_durable`], marking the corresponding
+    /// record (and all earlier records) as processed.
+    ///
+    /// The stream must be totally ordered so that the committed offset only moves forward.
+    /// Offsets are synced to disk on a blocking thread, so committing does not stall the
+    /// runtime, but an offset may not be committed yet when the records after it are processed.
+    pub fn commit_offset(self) {
+        let root = get_this_crate();
+        let sink: syn::Expr = parse_quote!(#root::runtime_support::durable::commit_sink());
+
+        self.location
+            .flow_state()
+            .borrow_mut()
+            .leaves
+            .as_mut()
+            .expect(FLOW_USED_MESSAGE)
+            .push(HydroLeaf::DestSink {
+                sink: sink.into(),
+                input: Box::new(self.ir_node.into_inner()),
+            });
+    }
+}
+
+impl<'a, T, L: Location<'a>, Order> Stream<T, Tick<L>, Bounded, Order> {
+    pub fn all_ticks(self) -> Stream<T, Timestamped<L>, Unbounded, Order> {
+        Stream::new(
+            Timestamped {
+                tick: self.location.clone(),
+            },
+            HydroNode::Persist(Box::new(self.ir_node.into_inner())),
+        )
+    }
+
+    pub fn persist(self) -> Stream<T, Tick<L>, Bounded, Order>
+    where
+        T: Clone,
+    {
+        Stream::new(
+            self.location,
+            HydroNode::Persist(Box::new(self.ir_node.into_inner())),
+        )
+    }
+
+    pub fn defer_tick(self) -> Stream<T, Tick<L>, Bounded, Order> {
+        Stream::new(
+            self.location,
+            HydroNode::DeferTick(Box::new(self.ir_node.into_inner())),
+        )
+    }
+
+    pub fn delta(self) -> Stream<T, Tick<L>, Bounded, Order> {
+        Stream::new(
+            self.location,
+            HydroNode::Delta(Box::new(self.ir_node.into_inner())),
+        )
+    }
+
+    /// Drops every element in the first `n` ticks, passing elements through only once the
+    /// current tick (from [`crate::RUNTIME_CONTEXT`]) is at least `n`. This is useful for
+    /// suppressing output during a warmup period, such as while caches fill.
+    ///
+    /// Unlike skipping a number of elements, this is based on the tick count, so ticks with no
+    /// elements still count towards the warmup.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// let numbers = process.source_iter(q!(vec![1, 2, 3, 4]));
+    /// let batch = unsafe { numbers.timestamped(&tick).tick_batch() };
+    /// // all the elements arrive in the first tick, so they are all dropped
+    /// batch.skip_ticks(1).all_ticks().drop_timestamp()
+    /// # }, |mut stream| async move {
+    /// # assert!(tokio::time::timeout(std::time::Duration::from_secs(1), stream.next()).await.is_err());
+    /// # }));
+    /// ```
+    pub fn skip_ticks(self, n: usize) -> Stream<T, Tick<L>, Bounded, Order> {
+        let n = n as u64;
+        self.filter(q!(move |_| RUNTIME_CONTEXT.current_tick().0 >= n))
+    }
+
+    /// Transforms the whole batch of elements in each tick at once, rather than element by
+    /// element as in [`Stream::map`]. The elements of the tick are collected into a [`Vec`]
+    /// (in stream order) and passed to `f`, and the elements it returns are emitted in order.
+    ///
+    /// This allows batch-level optimizations, such as vectorized arithmetic, that are not
+    /// possible one element at a time. `f` is not called in ticks with no elements.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// let numbers = process.source_iter(q!(vec![1, 2, 3, 4]));
+    /// let batch = unsafe { numbers.timestamped(&tick).tick_batch() };
+    /// batch
+    ///     .map_batch(q!(|batch: Vec<i32>| {
+    ///         let total: i32 = batch.iter().sum();
+    ///         batch.into_iter().map(|x| x * 100 / total).collect()
+    ///     }))
+    ///     .all_ticks()
+    ///     .drop_timestamp()
+    /// # }, |mut stream| async move {
+    /// // 10, 20, 30, 40
+    /// # for w in vec![10, 20, 30, 40] {
+    /// #     assert_eq!(stream.next().await.unwrap(), w);
+    /// # }
+    /// # }));
+    /// ```
+    pub fn map_batch<U, F: Fn(Vec<T>) -> Vec<U> + 'a>(
+        self,
+        f: impl IntoQuotedMut<'a, F, Tick<L>>,
+    ) -> Stream<U, Tick<L>, Bounded, Order> {
+        let f = f.splice_fn1_ctx(&self.location);
+
+        // `fold` clones its accumulator on output, so the batch is shared through an `Rc` and
+        // taken out when it is emitted.
+        let init: syn::Expr =
+            parse_quote!(|| ::std::rc::Rc::new(::std::cell::RefCell::new(::std::vec::Vec::new())));
+        let push: syn::Expr = parse_quote!(|acc: &mut ::std::rc::Rc<
+            ::std::cell::RefCell<::std::vec::Vec<_>>,
+        >,
+                                            x| {
+            acc.borrow_mut().push(x);
+        });
+        let apply: syn::Expr = parse_quote!({
+            let f = #f;
+            move |acc: ::std::rc::Rc<::std::cell::RefCell<::std::vec::Vec<_>>>| {
+                let batch = acc.take();
+                if batch.is_empty() {
+                    ::std::vec::Vec::new()
+                } else {
+                    f(batch)
+                }
+            }
+        });
+
+        Stream::new(
+            self.location,
+            HydroNode::FlatMap {
+                f: apply.into(),
+                input: Box::new(HydroNode::Fold {
+                    init: init.into(),
+                    acc: push.into(),
+                    input: Box::new(self.ir_node.into_inner()),
+                }),
+            },
+        )
+    }
+
+    /// Like [`Stream::cross_product`], but fails if the product of the two inputs
+    /// would contain more than `max` elements in a tick.
+    ///
+    /// If the sizes of both inputs are known when the flow is built (for example, they
+    /// come from `source_iter` over a literal array, `vec!`, or range), this check happens
+    /// eagerly and the flow fails to compile with an error naming the sizes. Otherwise, the
+    /// sizes are counted in each tick and the program panics at runtime if the cap is exceeded.
+    pub fn cross_product_capped<O>(
+        self,
+        other: Stream<O, Tick<L>, Bounded, Order>,
+        max: usize,
+    ) -> Stream<(T, O), Tick<L>, Bounded, Order>
+    where
+        T: Clone,
+        O: Clone,
+        Order: MinOrder<NoOrder, Min = NoOrder>,
+    {
+        let left_len = static_len(&self.ir_node.borrow());
+        let right_len = static_len(&other.ir_node.borrow());
+        if let (Some(left_len), Some(right_len)) = (left_len, right_len) {
+            if left_len.saturating_mul(right_len) <= max {
+                return self.cross_product(other);
+            }
+
+            self.location
+                .flow_state()
+                .borrow_mut()
+                .build_errors
+                .push(format!(
+                    "cross_product_capped: product of {} x {} elements exceeds the cap of {}",
+                    left_len, right_len, max
+                ));
+        }
+
+        let left_count = self.clone().count();
+        let right_count = other.clone().count();
+        let within_cap = left_count.zip(right_count).map(q!(move |(l, r)| {
+            if l.saturating_mul(r) > max {
+                panic!(
+                    "cross_product_capped: product of {} x {} elements exceeds the cap of {}",
+                    l, r, max
+                );
+            }
+        }));
+
+        self.cross_product(other)
+            .cross_singleton(within_cap)
+            .map(q!(|(pair, _)| pair))
+    }
+
+    /// Pairs every element of this stream with every element of `other` in each tick, like
+    /// [`Stream::cross_product`]. If `max_pairs` is set, the join instead fails loudly when a
+    /// tick would produce more than that many pairs, as described in
+    /// [`Stream::cross_product_capped`], rather than running out of memory on unexpectedly
+    /// large inputs.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use dfir_rs::futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// let sizes = unsafe {
+    ///     process
+    ///         .source_iter(q!(vec!['S', 'L']))
+    ///         .timestamped(&tick)
+    ///         .tick_batch()
+    /// };
+    /// let counts = unsafe {
+    ///     process
+    ///         .source_iter(q!(vec![1]))
+    ///         .timestamped(&tick)
+    ///         .tick_batch()
+    /// };
+    /// sizes
+    ///     .cross_join(counts, Some(10))
+    ///     .all_ticks()
+    ///     .drop_timestamp()
+    /// # }, |mut stream| async move {
+    /// // ('S', 1), ('L', 1)
+    /// # assert_eq!(stream.next().await.unwrap(), ('S', 1));
+    /// # assert_eq!(stream.next().await.unwrap(), ('L', 1));
+    /// # }));
+    /// ```
+    pub fn cross_join<O>(
+        self,
+        other: Stream<O, Tick<L>, Bounded, Order>,
+        max_pairs: Option<usize>,
+    ) -> Stream<(T, O), Tick<L>, Bounded, Order>
+    where
+        T: Clone,
+        O: Clone,
+        Order: MinOrder<NoOrder, Min = NoOrder>,
+    {
+        match max_pairs {
+            Some(max) => self.cross_product_capped(other, max),
+            None => self.cross_product(other),
+        }
+    }
+}
+
+/// Returns the number of elements `node` will produce, if it is a source over a
+/// literal collection whose size is known while building the flow.
+fn static_len(node: &HydroNode) -> Option<usize> {
+    match node {
+        HydroNode::Persist(inner) | HydroNode::Unpersist(inner) => static_len(inner),
+        HydroNode::Tee { inner } => static_len(&inner.0.borrow()),
+        HydroNode::Source {
+            source: HydroSource::Iter(expr),
+            ..
+        } => {
+            let mut expr: &syn::Expr = expr;
+            // staged expressions are wrapped in a block that imports the staged context
+            while let syn::Expr::Block(block) = expr {
+                match block.block.stmts.last() {
+                    Some(syn::Stmt::Expr(inner, None)) => expr = inner,
+                    _ => return None,
+                }
+            }
+
+            match expr {
+                syn::Expr::Array(array) => Some(array.elems.len()),
+                syn::Expr::Macro(mac) if mac.mac.path.is_ident("vec") => mac
+                    .mac
+                    .parse_body_with(
+                        syn::punctuated::Punctuated::<syn::Expr, syn::Token![,]>::parse_terminated,
+                    )
+                    .ok()
+                    .map(|elems| elems.len()),
+                syn::Expr::Range(range) => {
+                    let bound = |e: &Option<Box<syn::Expr>>| match e.as_deref() {
+                        Some(syn::Expr::Lit(syn::ExprLit {
+                            lit: syn::Lit::Int(int),
+                            ..
+                        })) => int.base10_parse::<usize>().ok(),
+                        _ => None,
+                    };
+                    let start = bound(&range.start)?;
+                    let end = bound(&range.end)?;
+                    match range.limits {
+                        syn::RangeLimits::HalfOpen(_) => Some(end.saturating_sub(start)),
+                        syn::RangeLimits::Closed(_) => Some((end + 1).saturating_sub(start)),
+                    }
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn serialize_bincode<T: Serialize>(is_demux: bool) -> syn::Expr {
+    let root = get_this_crate();
+
+    let t_type: syn::Type = stageleft::quote_type::<T>();
+
+    if is_demux {
+        parse_quote! {
+            |(id, data): (#root::ClusterId<_>, #t_type)| {
+                (id.raw_id, #root::runtime_support::bincode::serialize::<#t_type>(&data).unwrap().into())
+            }
+        }
+    } else {
+        parse_quote! {
+            |data| {
+                #root::runtime_support::bincode::serialize::<#t_type>(&data).unwrap().into()
+            }
+        }
+    }
+}
+
+pub(super) fn deserialize_bincode<T: DeserializeOwned>(tagged: Option<syn::Type>) -> syn::Expr {
+    let root = get_this_crate();
+
+    let t_type: syn::Type = stageleft::quote_type::<T>();
+
+    if let Some(c_type) = tagged {
+        parse_quote! {
+            |res| {
+                let (id, b) = res.unwrap();
+                (#root::ClusterId::<#c_type>::from_raw(id), #root::runtime_support::bincode::deserialize::<#t_type>(&b).unwrap())
+            }
+        }
+    } else {
+        parse_quote! {
+            |res| {
+                #root::runtime_support::bincode::deserialize::<#t_type>(&res.unwrap()).unwrap()
+            }
+        }
+    }
+}
+
+fn serialize_bincode_adaptive<T: Serialize>(is_demux: bool, min_bytes: usize) -> syn::Expr {
+    let root = get_this_crate();
+
+    let t_type: syn::Type = stageleft::quote_type::<T>();
+
+    if is_demux {
+        parse_quote! {
+            |(id, data): (#root::ClusterId<_>, #t_type)| {
+                let payload = #root::runtime_support::bincode::serialize::<#t_type>(&data).unwrap();
+                (id.raw_id, #root::runtime_support::compression::compress_above(payload, #min_bytes))
+            }
+        }
+    } else {
+        parse_quote! {
+            |data| {
+                let payload = #root::runtime_support::bincode::serialize::<#t_type>(&data).unwrap();
+                #root::runtime_support::compression::compress_above(payload, #min_bytes)
+            }
+        }
+    }
+}
+
+fn deserialize_bincode_adaptive<T: DeserializeOwned>(tagged: Option<syn::Type>) -> syn::Expr {
+    let root = get_this_crate();
+
+    let t_type: syn::Type = stageleft::quote_type::<T>();
+
+    if let Some(c_type) = tagged {
+        parse_quote! {
+            |res| {
+                let (id, b) = res.unwrap();
+                let payload = #root::runtime_support::compression::decompress_above(&b);
+                (#root::ClusterId::<#c_type>::from_raw(id), #root::runtime_support::bincode::deserialize::<#t_type>(&payload).unwrap())
+            }
+        }
+    } else {
+        parse_quote! {
+            |res| {
+                let b = res.unwrap();
+                let payload = #root::runtime_support::compression::decompress_above(&b);
+                #root::runtime_support::bincode::deserialize::<#t_type>(&payload).unwrap()
+            }
+        }
+    }
+}
+
+fn serialize_bincode_compressed<T: Serialize>(is_demux: bool, level: Lz4Level) -> syn::Expr {
+    let root = get_this_crate();
+
+    let t_type: syn::Type = stageleft::quote_type::<T>();
+
+    let level: syn::Expr = match level {
+        Lz4Level::Fast => parse_quote!(#root::runtime_support::compression::Lz4Level::Fast),
+        Lz4Level::High => parse_quote!(#root::runtime_support::compression::Lz4Level::High),
+    };
+
+    if is_demux {
+        parse_quote! {
+            |(id, data): (#root::ClusterId<_>, #t_type)| {
+                let payload = #root::runtime_support::bincode::serialize::<#t_type>(&data).unwrap();
+                (id.raw_id, #root::runtime_support::compression::compress_frame(#level, &payload))
+            }
+        }
+    } else {
+        parse_quote! {
+            |data| {
+                let payload = #root::runtime_support::bincode::serialize::<#t_type>(&data).unwrap();
+                #root::runtime_support::compression::compress_frame(#level, &payload)
+            }
+        }
+    }
+}
+
+fn deserialize_bincode_compressed<T: DeserializeOwned>(tagged: Option<syn::Type>) -> syn::Expr {
+    let root = get_this_crate();
+
+    let t_type: syn::Type = stageleft::quote_type::<T>();
+
+    if let Some(c_type) = tagged {
+        parse_quote! {
+            |res| {
+                let (id, b) = res.unwrap();
+                let payload = #root::runtime_support::compression::decompress_frame(&b);
+                (#root::ClusterId::<#c_type>::from_raw(id), #root::runtime_support::bincode::deserialize::<#t_type>(&payload).unwrap())
+            }
+        }
+    } else {
+        parse_quote! {
+            |res| {
+                let b = res.unwrap();
+                let payload = #root::runtime_support::compression::decompress_frame(&b);
+                #root::runtime_support::bincode::deserialize::<#t_type>(&payload).unwrap()
+            }
+        }
+    }
+}
+
+fn serialize_bincode_versioned<T: Serialize>(is_demux: bool, version: u8) -> syn::Expr {
+    let root = get_this_crate();
+
+    let t_type: syn::Type = stageleft::quote_type::<T>();
+
+    if is_demux {
+        parse_quote! {
+            |(id, data): (#root::ClusterId<_>, #t_type)| {
+                let payload = #root::runtime_support::bincode::serialize::<#t_type>(&data).unwrap();
+                (id.raw_id, #root::runtime_support::versioned::frame(#version, payload))
+            }
+        }
+    } else {
+        parse_quote! {
+            |data| {
+                let payload = #root::runtime_support::bincode::serialize::<#t_type>(&data).unwrap();
+                #root::runtime_support::versioned::frame(#version, payload)
+            }
+        }
+    }
+}
+
+fn deserialize_bincode_versioned(tagged: Option<syn::Type>, migrate: syn::Expr) -> syn::Expr {
+    let root = get_this_crate();
+
+    if let Some(c_type) = tagged {
+        parse_quote! {
+            |res| {
+                let (id, b) = res.unwrap();
+                (#root::ClusterId::<#c_type>::from_raw(id), (#migrate)(#root::runtime_support::versioned::unframe(&b)))
+            }
+        }
+    } else {
+        parse_quote! {
+            |res| {
+                let b = res.unwrap();
+                (#migrate)(#root::runtime_support::versioned::unframe(&b))
+            }
+        }
+    }
+}
+
+fn serialize_bincode_batch<T: Serialize>(is_demux: bool) -> syn::Expr {
+    let root = get_this_crate();
+
+    let t_type: syn::Type = stageleft::quote_type::<T>();
+
+    if is_demux {
+        parse_quote! {
+            |batch: ::std::vec::Vec<(#root::ClusterId<_>, #t_type)>| {
+                let mut per_member = ::std::collections::BTreeMap::<u32, ::std::vec::Vec<#t_type>>::new();
+                for (id, data) in batch {
+                    per_member.entry(id.raw_id).or_default().push(data);
+                }
+
+                per_member
+                    .into_iter()
+                    .map(|(id, data)| (id, #root::runtime_support::bincode::serialize::<::std::vec::Vec<#t_type>>(&data).unwrap().into()))
+                    .collect::<::std::vec::Vec<_>>()
+            }
+        }
+    } else {
+        parse_quote! {
+            |batch: ::std::vec::Vec<#t_type>| {
+                vec![#root::runtime_support::bincode::serialize::<::std::vec::Vec<#t_type>>(&batch).unwrap().into()]
+            }
+        }
+    }
+}
+
+fn deserialize_bincode_batch<T: DeserializeOwned>(tagged: Option<syn::Type>) -> syn::Expr {
+    let root = get_this_crate();
+
+    let t_type: syn::Type = stageleft::quote_type::<T>();
+
+    if let Some(c_type) = tagged {
+        parse_quote! {
+            |res| {
+                let (id, b) = res.unwrap();
+                let id = #root::ClusterId::<#c_type>::from_raw(id);
+                #root::runtime_support::bincode::deserialize::<::std::vec::Vec<#t_type>>(&b).unwrap().into_iter().map(move |data| (id, data))
+            }
+        }
+    } else {
+        parse_quote! {
+            |res| {
+                #root::runtime_support::bincode::deserialize::<::std::vec::Vec<#t_type>>(&res.unwrap()).unwrap()
+            }
+        }
+    }
+}
+
+impl<'a, T, C1, B, Order> Stream<T, Cluster<'a, C1>, B, Order> {
+    pub fn decouple_cluster<C2: 'a, Tag>(
+        self,
+        other: &Cluster<'a, C2>,
+    ) -> Stream<T, Cluster<'a, C2>, Unbounded, Order>
+    where
+        Cluster<'a, C1>: Location<'a, Root = Cluster<'a, C1>>,
+        Cluster<'a, C1>:
+            CanSend<'a, Cluster<'a, C2>, In<T> = (ClusterId<C2>, T), Out<T> = (Tag, T)>,
+        T: Clone + Serialize + DeserializeOwned,
+        Order:
+            MinOrder<<Cluster<'a, C1> as CanSend<'a, Cluster<'a, C2>>>::OutStrongestOrder<Order>>,
+    {
+        let sent = self
+            .map(q!(move |b| (
+                ClusterId::from_raw(CLUSTER_SELF_ID.raw_id),
+                b.clone()
+            )))
+            .send_bincode_interleaved(other);
+
+        unsafe {
+            // SAFETY: this is safe because we are mapping clusters 1:1
+            sent.assume_ordering()
+        }
+    }
+
+    /// Counts the elements processed across all members of the cluster, producing a running
+    /// total on the `coordinator` process.
+    ///
+    /// Each member counts its elements in every tick and sends the non-zero counts to the
+    /// coordinator, which sums them. The total is eventually consistent: it may lag behind
+    /// the members while counts are in flight, but it never over-counts.
+    pub fn global_count<P>(
+        self,
+        coordinator: &Process<'a, P>,
+    ) -> Singleton<usize, Process<'a, P>, Unbounded>
+    where
+        Order: MinOrder<NoOrder, Min = NoOrder>,
+    {
+        let tick = self.location.tick();
+        let member_counts = unsafe {
+            // SAFETY: how elements are batched into ticks does not affect the total
+            self.timestamped(&tick).tick_batch()
+        }
+        .count()
+        .filter(q!(|count| *count > 0))
+        .all_ticks()
+        .drop_timestamp();
+
+        member_counts
+            .send_bincode(coordinator)
+            .map(q!(|(_, count)| count))
+            .fold_commutative(q!(|| 0), q!(|total, count| *total += count))
+    }
+
+    /// Sends the elements of this stream to the members of the cluster `other` in round-robin
+    /// order, regardless of their contents. Unlike [`Stream::send_partitioned`], this spreads
+    /// the load evenly even when keys are skewed.
+    ///
+    /// The round-robin position is kept across ticks. Each member of this cluster starts at
+    /// the member of `other` with the same index (modulo the size of `other`), so the first
+    /// elements of different senders go to different members.
+    pub fn send_balanced<C2: 'a>(
+        self,
+        other: &Cluster<'a, C2>,
+    ) -> Stream<(ClusterId<C1>, T), Cluster<'a, C2>, Unbounded, NoOrder>
+    where
+        Cluster<'a, C1>: Location<'a, Root = Cluster<'a, C1>>,
+        T: Serialize + DeserializeOwned,
+        Order: MinOrder<NoOrder, Min = NoOrder>,
+    {
+        let ids = other.members();
+
+        self.enumerate_static()
+            .map(q!(move |(i, x)| (
+                ids[(i + CLUSTER_SELF_ID.raw_id as usize) % ids.len()],
+                x
+            )))
+            .send_bincode(other)
+    }
+
+    /// Sends each element of this stream to every member of the cluster `other`, such as to
+    /// replicate configuration. Each received element is tagged with the member that sent it.
+    ///
+    /// When `other` is this same cluster, each member only sends to itself if `include_self` is
+    /// set. When `other` is a different cluster, `include_self` has no effect.
+    pub fn broadcast<C2: 'a>(
+        self,
+        other: &Cluster<'a, C2>,
+        include_self: bool,
+    ) -> Stream<(ClusterId<C1>, T), Cluster<'a, C2>, Unbounded, NoOrder>
+    where
+        Cluster<'a, C1>: Location<'a, Root = Cluster<'a, C1>>,
+        T: Clone + Serialize + DeserializeOwned,
+        Order: MinOrder<NoOrder, Min = NoOrder>,
+    {
+        let ids = other.members();
+        let exclude_self = !include_self && self.location.id() == other.id();
+
+        let copies = self.flat_map_ordered(q!(|b| ids.iter().map(move |id| (
+            ::std::clone::Clone::clone(id),
+            ::std::clone::Clone::clone(&b)
+        ))));
+        let copies = if exclude_self {
+            copies.filter(q!(move |(id, _)| id.raw_id != CLUSTER_SELF_ID.raw_id))
+        } else {
+            copies
+        };
+
+        copies.send_bincode(other)
+    }
+}
+
+impl<'a, T, P, B, Order> Stream<T, Process<'a, P>, B, Order> {
+    /// Sends the elements of this stream to the members of the cluster `other` in round-robin
+    /// order, regardless of their contents. Unlike [`Stream::send_partitioned`], this spreads
+    /// the load evenly even when keys are skewed.
+    ///
+    /// The round-robin position is kept across ticks, starting at the first member of `other`.
+    /// Unlike [`Stream::round_robin_bincode`], this does not require the stream to be ordered.
+    pub fn send_balanced<C2: 'a>(
+        self,
+        other: &Cluster<'a, C2>,
+    ) -> Stream<T, Cluster<'a, C2>, Unbounded, Order>
+    where
+        T: Serialize + DeserializeOwned,
+        Order: MinOrder<Order, Min = Order>,
+    {
+        let ids = other.members();
+
+        self.enumerate_static()
+            .map(q!(|(i, x)| (ids[i % ids.len()], x)))
+            .send_bincode(other)
+    }
+
+    /// Sends each element of this stream to every member of the cluster `other`, such as to
+    /// replicate configuration. This is shorthand for [`Stream::broadcast_bincode`].
+    pub fn broadcast<C2: 'a>(
+        self,
+        other: &Cluster<'a, C2>,
+    ) -> Stream<T, Cluster<'a, C2>, Unbounded, Order>
+    where
+        T: Clone + Serialize + DeserializeOwned,
+        Order: MinOrder<Order, Min = Order>,
+    {
+        self.broadcast_bincode(other)
+    }
+}
+
+impl<'a, T, C: 'a, Order> Stream<T, Cluster<'a, C>, Unbounded, Order> {
+    /// Redistributes the elements across the members of the cluster, sending each element to
+    /// the member with the shallowest queue of elements it has not yet processed.
+    ///
+    /// Each member tracks how many elements it has sent to every member. Alongside the data,
+    /// members report back to each sender how many of its elements they have received, once
+    /// per tick in which they received any. A member whose ticks are slow (for example,
+    /// because its downstream processing is expensive) reports late, so its queue looks
+    /// deeper and it is sent fewer elements.
+    pub fn rebalance_by_load(self) -> Stream<T, Cluster<'a, C>, Unbounded, NoOrder>
+    where
+        T: Clone + Serialize + DeserializeOwned,
+    {
+        let cluster = self.location.clone();
+        let tick = cluster.tick();
+        let ids = cluster.members();
+
+        // The control plane: (member, number of our elements it has newly received).
+        let (acks_complete, acks) =
+            cluster
+                .forward_ref::<Stream<(ClusterId<C>, usize), Cluster<'a, C>, Unbounded, NoOrder>>();
+
+        // Acks are applied before routing the elements of the same tick.
+        let acks_batch = unsafe {
+            // SAFETY: acks only affect which member an element is routed to
+            acks.timestamped(&tick).tick_batch()
+        }
+        .map(q!(|ack| Err(ack)));
+        let elements_batch = unsafe {
+            // SAFETY: the output is unordered anyway, and batching only affects which member
+            // an element is routed to
+            self.assume_ordering::<NoOrder>()
+                .timestamped(&tick)
+                .tick_batch()
+        }
+        .map(q!(|element| Ok(element)));
+
+        // (elements sent to each member, elements acked by each member)
+        let init = q!(|| (
+            std::collections::HashMap::<_, usize>::new(),
+            std::collections::HashMap::<_, usize>::new()
+        ))
+        .splice_fn0_ctx::<(HashMap<ClusterId<C>, usize>, HashMap<ClusterId<C>, usize>)>(&tick)
+        .into();
+        let acc = q!(move |(sent, acked): &mut (
+            std::collections::HashMap<_, usize>,
+            std::collections::HashMap<_, usize>
+        ),
+                           item: Result<_, (_, usize)>| {
+            match item {
+                Err((member, count)) => {
+                    *acked.entry(member).or_default() += count;
+                    None
+                }
+                Ok(element) => {
+                    let depth = |member| {
+                        sent.get(member)
+                            .copied()
+                            .unwrap_or_default()
+                            .saturating_sub(acked.get(member).copied().unwrap_or_default())
+                    };
+                    let target = *ids.iter().min_by_key(|member| depth(*member)).unwrap();
+                    *sent.entry(target).or_default() += 1;
+                    Some((target, element))
+                }
+            }
+        })
+        .splice_fn2_borrow_mut_ctx::<
+            (HashMap<ClusterId<C>, usize>, HashMap<ClusterId<C>, usize>),
+            Result<T, (ClusterId<C>, usize)>,
+            Option<(ClusterId<C>, T)>,
+        >(&tick)
+        .into();
+
+        let received = acks_batch
+            .chain(elements_batch)
+            .scan_static::<Option<(ClusterId<C>, T)>>(init, acc)
+            .filter_map(q!(|routed| routed))
+            .all_ticks()
+            .drop_timestamp()
+            .send_bincode(&cluster);
+
+        let received_counts = unsafe {
+            // SAFETY: acks are only used as a load estimate
+            received
+                .clone()
+                .map(q!(|(sender, _)| (sender, ())))
+                .timestamped(&tick)
+                .tick_batch()
+        }
+        .fold_keyed_commutative(q!(|| 0), q!(|count, _| *count += 1));
+        acks_complete.complete(
+            received_counts
+                .all_ticks()
+                .drop_timestamp()
+                .send_bincode(&cluster),
+        );
+
+        received.map(q!(|(_, element)| element))
+    }
+
+    /// Pairs each element with the latest value of `other`, a singleton on a process.
+    ///
+    /// Rather than sending the value for every element, the process broadcasts the value to
+    /// every member of the cluster once each time it changes, and each member crosses its
+    /// elements with the latest value it has received. Elements that arrive at a member
+    /// before any value are held until the first one does.
+    ///
+    /// # Safety
+    /// Each element is paired with whichever value the member last received when the element
+    /// was processed, which depends on the timing of the broadcast.
+    pub unsafe fn cross_broadcast<S, P, B>(
+        self,
+        other: Singleton<S, Process<'a, P>, B>,
+    ) -> Stream<(T, S), Cluster<'a, C>, Unbounded, Order>
+    where
+        T: Clone,
+        S: Clone + Eq + Hash + Serialize + DeserializeOwned,
+    {
+        let cluster = self.location.clone();
+        let tick = cluster.tick();
+
+        let process_tick = other.location.tick();
+        let updates = unsafe {
+            // SAFETY: each member only pairs its elements with the latest value
+            other.timestamped(&process_tick).latest_tick()
+        }
+        .delta()
+        .all_ticks()
+        .drop_timestamp();
+        let latest = updates
+            .broadcast_bincode(&cluster)
+            .reduce(q!(|latest, value| *latest = value));
+        let latest = unsafe {
+            // SAFETY: see the method docs
+            latest.timestamped(&tick).latest_tick()
+        };
+
+        let (pending_complete, pending) =
+            tick.cycle::<Stream<T, Tick<Cluster<'a, C>>, Bounded, Order>>();
+        let batch = pending.chain(unsafe {
+            // SAFETY: see the method docs
+            self.timestamped(&tick).tick_batch()
+        });
+        pending_complete.complete_next_tick(batch.clone().continue_unless(latest.clone()));
+
+        batch.cross_singleton(latest).all_ticks().drop_timestamp()
+    }
+}
+
+impl<'a, K, V, C: 'a, Order> Stream<(K, V), Cluster<'a, C>, Unbounded, Order> {
+    /// Combines the values of each key across all members of the cluster, using the `comb`
+    /// closure, which must be **associative** and **commutative**. Each key is owned by one
+    /// member (chosen by hashing the key), and the output on that member is the running
+    /// aggregate of the key, emitted in every tick in which the aggregate changes.
+    ///
+    /// Values are pre-aggregated in two stages to minimize network traffic: each member first
+    /// reduces the values of each key it received in a tick, then sends only the per-key
+    /// partial aggregates to the owning member, which reduces them into the running aggregate.
+    pub fn reduce_keyed_commutative<F: Fn(&mut V, V) + 'a>(
+        self,
+        comb: impl IntoQuotedMut<'a, F, Tick<Cluster<'a, C>>>,
+    ) -> Stream<(K, V), Cluster<'a, C>, Unbounded, NoOrder>
+    where
+        K: Eq + Hash + Clone + Serialize + DeserializeOwned,
+        V: Eq + Hash + Clone + Serialize + DeserializeOwned,
+    {
+        let cluster = self.location.clone();
+        let tick = cluster.tick();
+        let ids = cluster.members();
+        let f: DebugExpr = comb.splice_fn2_borrow_mut_ctx(&tick).into();
+
+        let batch = unsafe {
+            // SAFETY: `comb` is associative and commutative, so batching does not affect the
+            // final aggregate
+            self.timestamped(&tick).tick_batch()
+        };
+        let partials: Stream<(K, V), Tick<Cluster<'a, C>>, Bounded, NoOrder> = Stream::new(
+            tick.clone(),
+            HydroNode::ReduceKeyed {
+                f: f.clone(),
+                input: Box::new(batch.ir_node.into_inner()),
+            },
+        );
+
+        let shuffled = partials
+            .map(q!(move |(k, v)| {
+                let mut hasher = std::hash::DefaultHasher::new();
+                std::hash::Hash::hash(&k, &mut hasher);
+                let owner = ids[(std::hash::Hasher::finish(&hasher) % ids.len() as u64) as usize];
+                (owner, (k, v))
+            }))
+            .all_ticks()
+            .drop_timestamp()
+            .send_bincode(&cluster)
+            .map(q!(|(_, partial)| partial));
+
+        let received = unsafe {
+            // SAFETY: see above
+            shuffled.timestamped(&tick).tick_batch()
+        }
+        .persist();
+        let totals: Stream<(K, V), Tick<Cluster<'a, C>>, Bounded, NoOrder> = Stream::new(
+            tick,
+            HydroNode::ReduceKeyed {
+                f,
+                input: Box::new(received.ir_node.into_inner()),
+            },
+        );
+
+        totals.delta().all_ticks().drop_timestamp()
+    }
+}
+
+impl<'a, T, L> Stream<T, Process<'a, L>, Unbounded, TotalOrder> {
+    /// Sends the elements of this stream to the process `other`, retransmitting any element
+    /// that has not been acknowledged every `retransmit_every`, so that elements lost in
+    /// transit are eventually delivered. The output on `other` contains every element exactly
+    /// once, in the order they were sent.
+    ///
+    /// Elements are numbered in order, and the receiver acknowledges, once per tick in which it
+    /// received any element, the sequence number up to which it has received every element.
+    /// The sender drops acknowledged elements from its retransmit buffer, so the buffer only
+    /// holds elements that may still need to be retransmitted.
+    pub fn reliable_send<L2>(
+        self,
+        other: &Process<'a, L2>,
+        retransmit_every: impl QuotedWithContext<'a, std::time::Duration, Process<'a, L>> + Copy + 'a,
+    ) -> Stream<T, Process<'a, L2>, Unbounded, TotalOrder>
+    where
+        T: Clone + Serialize + DeserializeOwned,
+    {
+        self.reliable_send_with_faults(other, retransmit_every, q!(|_| false))
+    }
+
+    /// Like [`Stream::reliable_send`], but transmissions for which `drop_fn` returns `true`,
+    /// given the element's sequence number and the attempt (counting from zero), are dropped
+    /// instead of being sent. This simulates a lossy network, for testing.
+    pub(crate) fn reliable_send_with_faults<L2, F: Fn(&(u64, usize)) -> bool + 'a>(
+        self,
+        other: &Process<'a, L2>,
+        retransmit_every: impl QuotedWithContext<'a, std::time::Duration, Process<'a, L>> + Copy + 'a,
+        drop_fn: impl IntoQuotedMut<'a, F, Tick<Process<'a, L>>>,
+    ) -> Stream<T, Process<'a, L2>, Unbounded, TotalOrder>
+    where
+        T: Clone + Serialize + DeserializeOwned,
+    {
+        let sender = self.location.clone();
+        let tick = sender.tick();
+        let root = get_this_crate();
+
+        let (acks_complete, acks) =
+            sender.forward_ref::<Stream<u64, Process<'a, L>, Unbounded, TotalOrder>>();
+
+        // Acks are applied before sending new elements or retransmitting in the same tick.
+        // `Err(Some(ack))` is an ack and `Err(None)` is a retransmit timeout.
+        let acks_batch = unsafe {
+            // SAFETY: acks only decide which elements are retransmitted, which the receiver
+            // deduplicates
+            acks.timestamped(&tick).tick_batch()
+        }
+        .map(q!(|ack| Err(Some(ack))));
+        let elements_batch = unsafe {
+            // SAFETY: batching does not affect the sequence numbers
+            self.enumerate()
+                .map(q!(|(seq, element)| (seq as u64, element)))
+                .timestamped(&tick)
+                .tick_batch()
+        }
+        .map(q!(|element| Ok(element)));
+        let timeouts = unsafe {
+            // SAFETY: see above
+            sender
+                .source_interval(retransmit_every)
+                .timestamped(&tick)
+                .tick_batch()
+        }
+        .first()
+        .into_stream()
+        .map(q!(|_| Err(None)));
+
+        let scan_init: syn::Expr =
+            parse_quote!(|| #root::runtime_support::reliable::RetransmitBuffer::default());
+        let scan_acc: syn::Expr = parse_quote!(
+            |buffer: &mut #root::runtime_support::reliable::RetransmitBuffer<_>,
+             item: ::std::result::Result<(u64, _), ::std::option::Option<u64>>| {
+                match item {
+                    ::std::result::Result::Ok((seq, element)) => vec![buffer.send(seq, element)],
+                    ::std::result::Result::Err(::std::option::Option::Some(ack)) => {
+                        buffer.ack(ack);
+                        ::std::vec::Vec::new()
+                    }
+                    ::std::result::Result::Err(::std::option::Option::None) => buffer.retransmit(),
+                }
+            }
+        );
+        let transmissions = acks_batch
+            .chain(elements_batch)
+            .chain(timeouts)
+            .scan_static::<Vec<(u64, usize, T)>>(scan_init.into(), scan_acc.into())
+            .flatten_ordered();
+
+        let drop_fn = drop_fn.splice_fn1_borrow_ctx::<(u64, usize), bool>(&tick);
+        let keep: syn::Expr = parse_quote!({
+            let drop_fn = #drop_fn;
+            move |(seq, attempt, _): &(u64, usize, _)| !drop_fn(&(*seq, *attempt))
+        });
+        let delivered: Stream<(u64, usize, T), Tick<Process<'a, L>>, Bounded, NoOrder> =
+            Stream::new(
+                tick,
+                HydroNode::Filter {
+                    f: keep.into(),
+                    input: Box::new(transmissions.ir_node.into_inner()),
+                },
+            );
+        let received = delivered
+            .map(q!(|(seq, _, element)| (seq, element)))
+            .all_ticks()
+            .drop_timestamp()
+            .send_bincode(other);
+
+        let receiver_tick = other.tick();
+        let scan_init: syn::Expr =
+            parse_quote!(|| #root::runtime_support::reliable::ReorderBuffer::default());
+        // `None` marks the end of the tick's elements, at which point an ack is sent.
+        let scan_acc: syn::Expr = parse_quote!(
+            |buffer: &mut #root::runtime_support::reliable::ReorderBuffer<_>,
+             item: ::std::option::Option<(u64, _)>| {
+                match item {
+                    ::std::option::Option::Some((seq, element)) => buffer
+                        .receive(seq, element)
+                        .into_iter()
+                        .map(::std::result::Result::Ok)
+                        .collect(),
+                    ::std::option::Option::None => buffer
+                        .end_tick()
+                        .map(::std::result::Result::Err)
+                        .into_iter()
+                        .collect::<::std::vec::Vec<_>>(),
+                }
+            }
+        );
+        let end_of_tick = receiver_tick
+            .singleton(q!(()))
+            .into_stream()
+            .map(q!(|_| None));
+        let (elements, new_acks) = unsafe {
+            // SAFETY: elements are released in sequence order, regardless of how they were
+            // batched or reordered in transit
+            received
+                .map(q!(|element| Some(element)))
+                .timestamped(&receiver_tick)
+                .tick_batch()
+                .assume_ordering::<TotalOrder>()
+        }
+        .chain(end_of_tick)
+        .scan_static::<Vec<Result<T, u64>>>(scan_init.into(), scan_acc.into())
+        .flatten_ordered()
+        .split_ok_err();
+
+        acks_complete.complete(new_acks.all_ticks().drop_timestamp().send_bincode(&sender));
+
+        elements.all_ticks().drop_timestamp()
+    }
+}
+
+impl<'a, T, L: Location<'a> + NoTick, B, Order> Stream<T, L, B, Order> {
+    pub fn decouple_process<P2>(
+        self,
+        other: &Process<'a, P2>,
+    ) -> Stream<T, Process<'a, P2>, Unbounded, Order>
+    where
+        L::Root: CanSend<'a, Process<'a, P2>, In<T> = T, Out<T> = T>,
+        T: Clone + Serialize + DeserializeOwned,
+        Order: MinOrder<
+            <L::Root as CanSend<'a, Process<'a, P2>>>::OutStrongestOrder<Order>,
+            Min = Order,
+        >,
+    {
+        self.send_bincode::<Process<'a, P2>, T>(other)
+    }
+
+    pub fn send_bincode<L2: Location<'a>, CoreType>(
+        self,
+        other: &L2,
+    ) -> Stream<<L::Root as CanSend<'a, L2>>::Out<CoreType>, L2, Unbounded, Order::Min>
+    where
+        L::Root: CanSend<'a, L2, In<CoreType> = T>,
+        CoreType: Serialize + DeserializeOwned,
+        Order: MinOrder<<L::Root as CanSend<'a, L2>>::OutStrongestOrder<Order>>,
+    {
+        let serialize_pipeline = Some(serialize_bincode::<CoreType>(L::Root::is_demux()));
+
+        let deserialize_pipeline = Some(deserialize_bincode::<CoreType>(L::Root::tagged_type()));
+
+        Stream::new(
+            other.clone(),
+            HydroNode::Network {
+                from_location: self.location.root().id(),
+                from_key: None,
+                to_location: other.id(),
+                to_key: None,
+                serialize_fn: serialize_pipeline.map(|e| e.into()),
+                instantiate_fn: DebugInstantiate::Building(),
+                deserialize_fn: deserialize_pipeline.map(|e| e.into()),
+                batching: None,
+                input: Box::new(self.ir_node.into_inner()),
+            },
+        )
+    }
+
+    /// Like [`Stream::send_bincode`], but compresses (with LZ4) each serialized message whose
+    /// size is at least `min_bytes`. Small messages are sent as-is, since they are typically
+    /// not worth the CPU cost of compressing.
+    ///
+    /// Each message is prefixed with a flag byte recording whether it was compressed, so the
+    /// receiver only decompresses messages that need it.
+    pub fn send_bincode_adaptive<L2: Location<'a>, CoreType>(
+        self,
+        other: &L2,
+        min_bytes: usize,
+    ) -> Stream<<L::Root as CanSend<'a, L2>>::Out<CoreType>, L2, Unbounded, Order::Min>
+    where
+        L::Root: CanSend<'a, L2, In<CoreType> = T>,
+        CoreType: Serialize + DeserializeOwned,
+        Order: MinOrder<<L::Root as CanSend<'a, L2>>::OutStrongestOrder<Order>>,
+    {
+        let serialize_pipeline = Some(serialize_bincode_adaptive::<CoreType>(
+            L::Root::is_demux(),
+            min_bytes,
+        ));
+
+        let deserialize_pipeline = Some(deserialize_bincode_adaptive::<CoreType>(
+            L::Root::tagged_type(),
+        ));
+
+        Stream::new(
+            other.clone(),
+            HydroNode::Network {
+                from_location: self.location.root().id(),
+                from_key: None,
+                to_location: other.id(),
+                to_key: None,
+                serialize_fn: serialize_pipeline.map(|e| e.into()),
+                instantiate_fn: DebugInstantiate::Building(),
+                deserialize_fn: deserialize_pipeline.map(|e| e.into()),
+                batching: None,
+                input: Box::new(self.ir_node.into_inner()),
+            },
+        )
+    }
+
+    /// Like [`Stream::send_bincode`], but compresses every serialized message into an LZ4
+    /// frame laid out according to `level`. Unlike [`Stream::send_bincode_adaptive`], all
+    /// messages are compressed, which suits streams of large, repetitive payloads.
+    pub fn send_bincode_compressed<L2: Location<'a>, CoreType>(
+        self,
+        other: &L2,
+        level: Lz4Level,
+    ) -> Stream<<L::Root as CanSend<'a, L2>>::Out<CoreType>, L2, Unbounded, Order::Min>
+    where
+        L::Root: CanSend<'a, L2, In<CoreType> = T>,
+        CoreType: Serialize + DeserializeOwned,
+        Order: MinOrder<<L::Root as CanSend<'a, L2>>::OutStrongestOrder<Order>>,
+    {
+        let serialize_pipeline = Some(serialize_bincode_compressed::<CoreType>(
+            L::Root::is_demux(),
+            level,
+        ));
+
+        let deserialize_pipeline = Some(deserialize_bincode_compressed::<CoreType>(
+            L::Root::tagged_type(),
+        ));
+
+        Stream::new(
+            other.clone(),
+            HydroNode::Network {
+                from_location: self.location.root().id(),
+                from_key: None,
+                to_location: other.id(),
+                to_key: None,
+                serialize_fn: serialize_pipeline.map(|e| e.into()),
+                instantiate_fn: DebugInstantiate::Building(),
+                deserialize_fn: deserialize_pipeline.map(|e| e.into()),
+                batching: None,
+                input: Box::new(self.ir_node.into_inner()),
+            },
+        )
+    }
+
+    /// Like [`Stream::send_bincode`], but tags each message with a wire `version` byte and
+    /// passes it to `migrate` on the receiver as a [`RawBytes`], which converts it into the
+    /// receiver's current type. This lets senders running different versions of a program,
+    /// such as during a rolling upgrade, send to the same receiver.
+    ///
+    /// Senders should bump `version` whenever the serialized type changes, and `migrate`
+    /// should handle every version that may still be in flight.
+    pub fn send_bincode_versioned<L2: Location<'a>, CoreType, U, F>(
+        self,
+        other: &L2,
+        version: u8,
+        migrate: impl IntoQuotedMut<'a, F, L2>,
+    ) -> Stream<<L::Root as CanSend<'a, L2>>::Out<U>, L2, Unbounded, Order::Min>
+    where
+        L::Root: CanSend<'a, L2, In<CoreType> = T>,
+        CoreType: Serialize,
+        F: Fn(RawBytes) -> U + 'a,
+        Order: MinOrder<<L::Root as CanSend<'a, L2>>::OutStrongestOrder<Order>>,
+    {
+        let migrate = migrate.splice_fn1_ctx(other);
+
+        let serialize_pipeline = Some(serialize_bincode_versioned::<CoreType>(
+            L::Root::is_demux(),
+            version,
+        ));
+
+        let deserialize_pipeline = Some(deserialize_bincode_versioned(
+            L::Root::tagged_type(),
+            migrate,
+        ));
+
+        Stream::new(
+            other.clone(),
+            HydroNode::Network {
+                from_location: self.location.root().id(),
+                from_key: None,
+                to_location: other.id(),
+                to_key: None,
+                serialize_fn: serialize_pipeline.map(|e| e.into()),
+                instantiate_fn: DebugInstantiate::Building(),
+                deserialize_fn: deserialize_pipeline.map(|e| e.into()),
+                batching: None,
+                input: Box::new(self.ir_node.into_inner()),
+            },
+        )
+    }
+
+    /// Like [`Stream::send_bincode`], but coalesces elements into batches which are each sent
+    /// as a single network message, which reduces per-message overhead for streams of many
+    /// small elements. A batch is sent once `max_batch` elements have accumulated, or once
+    /// `max_delay` has elapsed since its first element was buffered, whichever comes first.
+    /// The receiver unpacks each batch, so the output stream is the same as with
+    /// [`Stream::send_bincode`].
+    ///
+    /// When sending from a cluster, elements are batched separately for each destination.
+    ///
+    /// # Panics
+    /// Panics at runtime if `max_batch` is zero.
+    pub fn send_bincode_batched<L2: Location<'a>, CoreType>(
+        self,
+        other: &L2,
+        max_batch: usize,
+        max_delay: std::time::Duration,
+    ) -> Stream<<L::Root as CanSend<'a, L2>>::Out<CoreType>, L2, Unbounded, Order::Min>
+    where
+        L::Root: CanSend<'a, L2, In<CoreType> = T>,
+        CoreType: Serialize + DeserializeOwned,
+        Order: MinOrder<<L::Root as CanSend<'a, L2>>::OutStrongestOrder<Order>>,
+    {
+        let serialize_pipeline = Some(serialize_bincode_batch::<CoreType>(L::Root::is_demux()));
+
+        let deserialize_pipeline =
+            Some(deserialize_bincode_batch::<CoreType>(L::Root::tagged_type()));
+
+        Stream::new(
+            other.clone(),
+            HydroNode::Network {
+                from_location: self.location.root().id(),
+                from_key: None,
+                to_location: other.id(),
+                to_key: None,
+                serialize_fn: serialize_pipeline.map(|e| e.into()),
+                instantiate_fn: DebugInstantiate::Building(),
+                deserialize_fn: deserialize_pipeline.map(|e| e.into()),
+                batching: Some((max_batch, max_delay)),
+                input: Box::new(self.ir_node.into_inner()),
+            },
+        )
+    }
+
+    pub fn send_bincode_external<L2: 'a, CoreType>(
+        self,
+        other: &ExternalProcess<L2>,
+    ) -> ExternalBincodeStream<L::Out<CoreType>>
+    where
+        L: CanSend<'a, ExternalProcess<'a, L2>, In<CoreType> = T>,
+        CoreType: Serialize + DeserializeOwned,
+        L::Out<CoreType>: DeserializeOwned,
+    {
+        let serialize_pipeline = Some(serialize_bincode::<CoreType>(L::is_demux()));
+
+        let mut flow_state_borrow = self.location.flow_state().borrow_mut();
+
+        let external_key = flow_state_borrow.next_external_out;
+        flow_state_borrow.next_external_out += 1;
+
+        let leaves = flow_state_borrow.leaves.as_mut().expect("Attempted to add a leaf to a flow that has already been finalized. No leaves can be added after the flow has been compiled()");
+
+        let dummy_f: syn::Expr = syn::parse_quote!(());
+
+        leaves.push(HydroLeaf::ForEach {
+            f: dummy_f.into(),
+            input: Box::new(HydroNode::Network {
+                from_location: self.location.root().id(),
+                from_key: None,
+                to_location: other.id(),
+                to_key: Some(external_key),
+                serialize_fn: serialize_pipeline.map(|e| e.into()),
+                instantiate_fn: DebugInstantiate::Building(),
+                deserialize_fn: None,
+                batching: None,
+                input: Box::new(self.ir_node.into_inner()),
+            }),
+        });
+
+        ExternalBincodeStream {
+            process_id: other.id,
+            port_id: external_key,
+            _phantom: PhantomData,
+        }
+    }
+
+    pub fn send_bytes<L2: Location<'a>>(
+        self,
+        other: &L2,
+    ) -> Stream<<L::Root as CanSend<'a, L2>>::Out<Bytes>, L2, Unbounded, Order::Min>
+    where
+        L::Root: CanSend<'a, L2, In<Bytes> = T>,
+        Order: MinOrder<<L::Root as CanSend<'a, L2>>::OutStrongestOrder<Order>>,
+    {
+        let root = get_this_crate();
+        Stream::new(
+            other.clone(),
+            HydroNode::Network {
+                from_location: self.location.root().id(),
+                from_key: None,
+                to_location: other.id(),
+                to_key: None,
+                serialize_fn: None,
+                instantiate_fn: DebugInstantiate::Building(),
+                deserialize_fn: if let Some(c_type) = L::Root::tagged_type() {
+                    let expr: syn::Expr = parse_quote!(|(id, b)| (#root::ClusterId<#c_type>::from_raw(id), b.unwrap().freeze()));
+                    Some(expr.into())
+                } else {
+                    let expr: syn::Expr = parse_quote!(|b| b.unwrap().freeze());
+                    Some(expr.into())
+                },
+                batching: None,
+                input: Box::new(self.ir_node.into_inner()),
+            },
+        )
+    }
+
+    /// Sends raw bytes to `other` without serializing them, for payloads that are already
+    /// encoded (such as protobuf messages). Each payload is converted into [`Bytes`] and sent
+    /// as one length-delimited frame, and is received as [`Bytes`]. Use [`Stream::send_bytes`]
+    /// for payloads that are already [`Bytes`].
+    pub fn send_bytes_into<L2: Location<'a>, Payload: Into<Bytes>>(
+        self,
+        other: &L2,
+    ) -> Stream<<L::Root as CanSend<'a, L2>>::Out<Bytes>, L2, Unbounded, Order::Min>
+    where
+        L::Root: CanSend<'a, L2, In<Payload> = T>,
+        Order: MinOrder<<L::Root as CanSend<'a, L2>>::OutStrongestOrder<Order>>,
+    {
+        let root = get_this_crate();
+        let payload_type = stageleft::quote_type::<Payload>();
+        let into_bytes: syn::Expr = if L::Root::is_demux() {
+            parse_quote!(|(id, payload)| (id, #root::runtime_support::into_bytes::<#payload_type>(payload)))
+        } else {
+            parse_quote!(#root::runtime_support::into_bytes::<#payload_type>)
+        };
+
+        Stream::<<L::Root as CanSend<'a, L2>>::In<Bytes>, L, B, Order>::new(
+            self.location,
+            HydroNode::Map {
+                f: into_bytes.into(),
+                input: Box::new(self.ir_node.into_inner()),
+            },
+        )
+        .send_bytes(other)
+    }
+
+    pub fn send_bytes_external<L2: 'a>(self, other: &ExternalProcess<L2>) -> ExternalBytesPort
+    where
+        L::Root: CanSend<'a, ExternalProcess<'a, L2>, In<Bytes> = T, Out<Bytes> = Bytes>,
+    {
+        let mut flow_state_borrow = self.location.flow_state().borrow_mut();
+        let external_key = flow_state_borrow.next_external_out;
+        flow_state_borrow.next_external_out += 1;
+
+        let leaves = flow_state_borrow.leaves.as_mut().expect("Attempted to add a leaf to a flow that has already been finalized. No leaves can be added after the flow has been compiled()");
+
+        let dummy_f: syn::Expr = syn::parse_quote!(());
+
+        leaves.push(HydroLeaf::ForEach {
+            f: dummy_f.into(),
+            input: Box::new(HydroNode::Network {
+                from_location: self.location.root().id(),
+                from_key: None,
+                to_location: other.id(),
+                to_key: Some(external_key),
+                serialize_fn: None,
+                instantiate_fn: DebugInstantiate::Building(),
+                deserialize_fn: None,
+                batching: None,
+                input: Box::new(self.ir_node.into_inner()),
+            }),
+        });
+
+        ExternalBytesPort {
+            process_id: other.id,
+            port_id: external_key,
+        }
+    }
+
+    pub fn send_bincode_interleaved<L2: Location<'a>, Tag, CoreType>(
+        self,
+        other: &L2,
+    ) -> Stream<CoreType, L2, Unbounded, Order::Min>
+    where
+        L::Root: CanSend<'a, L2, In<CoreType> = T, Out<CoreType> = (Tag, CoreType)>,
+        CoreType: Serialize + DeserializeOwned,
+        Order: MinOrder<<L::Root as CanSend<'a, L2>>::OutStrongestOrder<Order>>,
+    {
+        self.send_bincode::<L2, CoreType>(other).map(q!(|(_, b)| b))
+    }
+
+    pub fn send_bytes_interleaved<L2: Location<'a>, Tag>(
+        self,
+        other: &L2,
+    ) -> Stream<Bytes, L2, Unbounded, Order::Min>
+    where
+        L::Root: CanSend<'a, L2, In<Bytes> = T, Out<Bytes> = (Tag, Bytes)>,
+        Order: MinOrder<<L::Root as CanSend<'a, L2>>::OutStrongestOrder<Order>>,
+    {
+        self.send_bytes::<L2>(other).map(q!(|(_, b)| b))
+    }
+
+    #[expect(clippy::type_complexity, reason = "ordering semantics for broadcast")]
+    pub fn broadcast_bincode<C2: 'a>(
+        self,
+        other: &Cluster<'a, C2>,
+    ) -> Stream<
+        <L::Root as CanSend<'a, Cluster<'a, C2>>>::Out<T>,
+        Cluster<'a, C2>,
+        Unbounded,
+        Order::Min,
+    >
+    where
+        L::Root: CanSend<'a, Cluster<'a, C2>, In<T> = (ClusterId<C2>, T)>,
+        T: Clone + Serialize + DeserializeOwned,
+        Order: MinOrder<<L::Root as CanSend<'a, Cluster<'a, C2>>>::OutStrongestOrder<Order>>,
+    {
+        let ids = other.members();
+
+        self.flat_map_ordered(q!(|b| ids.iter().map(move |id| (
+            ::std::clone::Clone::clone(id),
+            ::std::clone::Clone::clone(&b)
+        ))))
+        .send_bincode(other)
+    }
+
+    pub fn broadcast_bincode_interleaved<C2: 'a, Tag>(
+        self,
+        other: &Cluster<'a, C2>,
+    ) -> Stream<T, Cluster<'a, C2>, Unbounded, Order::Min>
+    where
+        L::Root: CanSend<'a, Cluster<'a, C2>, In<T> = (ClusterId<C2>, T), Out<T> = (Tag, T)> + 'a,
+        T: Clone + Serialize + DeserializeOwned,
+        Order: MinOrder<<L::Root as CanSend<'a, Cluster<'a, C2>>>::OutStrongestOrder<Order>>,
+    {
+        self.broadcast_bincode(other).map(q!(|(_, b)| b))
+    }
+
+    #[expect(clippy::type_complexity, reason = "ordering semantics for broadcast")]
+    pub fn broadcast_bytes<C2: 'a>(
+        self,
+        other: &Cluster<'a, C2>,
+    ) -> Stream<
+        <L::Root as CanSend<'a, Cluster<'a, C2>>>::Out<Bytes>,
+        Cluster<'a, C2>,
+        Unbounded,
+        Order::Min,
+    >
+    where
+        L::Root: CanSend<'a, Cluster<'a, C2>, In<Bytes> = (ClusterId<C2>, T)> + 'a,
+        T: Clone,
+        Order: MinOrder<<L::Root as CanSend<'a, Cluster<'a, C2>>>::OutStrongestOrder<Order>>,
+    {
+        let ids = other.members();
+
+        self.flat_map_ordered(q!(|b| ids.iter().map(move |id| (
+            ::std::clone::Clone::clone(id),
+            ::std::clone::Clone::clone(&b)
+        ))))
+        .send_bytes(other)
+    }
+
+    pub fn broadcast_bytes_interleaved<C2: 'a, Tag>(
+        self,
+        other: &Cluster<'a, C2>,
+    ) -> Stream<Bytes, Cluster<'a, C2>, Unbounded, Order::Min>
+    where
+        L::Root: CanSend<'a, Cluster<'a, C2>, In<Bytes> = (ClusterId<C2>, T), Out<Bytes> = (Tag, Bytes)>
+            + 'a,
+        T: Clone,
+        Order: MinOrder<<L::Root as CanSend<'a, Cluster<'a, C2>>>::OutStrongestOrder<Order>>,
+    {
+        self.broadcast_bytes(other).map(q!(|(_, b)| b))
+    }
+
+    /// Sends each element to the member of the cluster `other` chosen by hashing the key
+    /// extracted by `key_fn`, so that all elements with the same key are sent to the same
+    /// member.
+    ///
+    /// The hash is stable across processes built with the same toolchain, so every sender
+    /// agrees on the placement of a key. Keys are spread with jump consistent hashing over the
+    /// members of `other`, as in [`Stream::stable_partition`].
+    #[expect(clippy::type_complexity, reason = "ordering semantics for send")]
+    pub fn send_partitioned<C2: 'a, K: Hash, F: Fn(&T) -> K + 'a>(
+        self,
+        other: &Cluster<'a, C2>,
+        key_fn: impl IntoQuotedMut<'a, F, L>,
+    ) -> Stream<
+        <L::Root as CanSend<'a, Cluster<'a, C2>>>::Out<T>,
+        Cluster<'a, C2>,
+        Unbounded,
+        Order::Min,
+    >
+    where
+        L::Root: CanSend<'a, Cluster<'a, C2>, In<T> = (ClusterId<C2>, T)>,
+        T: Serialize + DeserializeOwned,
+        Order: MinOrder<<L::Root as CanSend<'a, Cluster<'a, C2>>>::OutStrongestOrder<Order>>,
+    {
+        let ids = other.members();
+        let key_fn = key_fn.splice_fn1_borrow_ctx(&self.location);
+        let f: syn::Expr = parse_quote!({
+            let key_fn = #key_fn;
+            move |x| (key_fn(&x), x)
+        });
+
+        let keyed: Stream<(K, T), L, B, Order> = Stream::new(
+            self.location,
+            HydroNode::Map {
+                f: f.into(),
+                input: Box::new(self.ir_node.into_inner()),
+            },
+        );
+
+        keyed
+            .map(q!(|(key, x)| (
+                ids[crate::runtime_support::partition::stable_partition(&key, ids.len())],
+                x
+            )))
+            .send_bincode(other)
+    }
+}
+
+impl<'a, K, T, L: Location<'a> + NoTick, B, Order> Stream<(K, T), L, B, Order> {
+    /// Sends the elements of this stream to the process `other`, where each element is
+    /// delivered at most once per idempotency key. Each element is paired with its key, and the
+    /// receiver drops any element whose key it has already seen, such as a duplicate sent by a
+    /// retransmit.
+    ///
+    /// To limit memory, the receiver only remembers the last `window` distinct keys it has
+    /// seen; a duplicate that arrives after more than `window` other keys is not detected.
+    ///
+    /// # Panics
+    /// Panics if `window` is zero.
+    pub fn send_bincode_exactly_once<P2>(
+        self,
+        other: &Process<'a, P2>,
+        window: usize,
+    ) -> Stream<T, Process<'a, P2>, Unbounded, Order>
+    where
+        L::Root: CanSend<'a, Process<'a, P2>, In<(K, T)> = (K, T), Out<(K, T)> = (K, T)>,
+        K: Eq + Hash + Clone + Serialize + DeserializeOwned,
+        T: Serialize + DeserializeOwned,
+        Order: MinOrder<
+            <L::Root as CanSend<'a, Process<'a, P2>>>::OutStrongestOrder<Order>,
+            Min = Order,
+        >,
+    {
+        assert!(window > 0, "exactly-once dedup window must be at least 1");
+
+        let root = get_this_crate();
+        let scan_init: syn::Expr =
+            parse_quote!(|| #root::runtime_support::dedup::DedupWindow::new(#window));
+        let scan_acc: syn::Expr = parse_quote!(
+            |seen: &mut #root::runtime_support::dedup::DedupWindow<_>, (key, element): (_, _)| {
+                if seen.insert(key) {
+                    ::std::option::Option::Some(element)
+                } else {
+                    ::std::option::Option::None
+                }
+            }
+        );
+
+        self.send_bincode::<Process<'a, P2>, (K, T)>(other)
+            .scan_static::<Option<T>>(scan_init.into(), scan_acc.into())
+            .filter_map(q!(|element| element))
+    }
+}
+
+#[expect(clippy::type_complexity, reason = "ordering semantics for round-robin")]
+impl<'a, T, L: Location<'a> + NoTick, B> Stream<T, L, B, TotalOrder> {
+    pub fn round_robin_bincode<C2: 'a>(
+        self,
+        other: &Cluster<'a, C2>,
+    ) -> Stream<
+        <L::Root as CanSend<'a, Cluster<'a, C2>>>::Out<T>,
+        Cluster<'a, C2>,
+        Unbounded,
+        <TotalOrder as MinOrder<
+            <L::Root as CanSend<'a, Cluster<'a, C2>>>::OutStrongestOrder<TotalOrder>,
+        >>::Min,
+    >
+    where
+        L::Root: CanSend<'a, Cluster<'a, C2>, In<T> = (ClusterId<C2>, T)>,
+        T: Clone + Serialize + DeserializeOwned,
+        TotalOrder:
+            MinOrder<<L::Root as CanSend<'a, Cluster<'a, C2>>>::OutStrongestOrder<TotalOrder>>,
+    {
+        let ids = other.members();
+
+        self.enumerate()
+            .map(q!(|(i, w)| (ids[i % ids.len()], w)))
+            .send_bincode(other)
+    }
+
+    pub fn round_robin_bincode_interleaved<C2: 'a, Tag>(
+        self,
+        other: &Cluster<'a, C2>,
+    ) -> Stream<
+        T,
+        Cluster<'a, C2>,
+        Unbounded,
+        <TotalOrder as MinOrder<
+            <L::Root as CanSend<'a, Cluster<'a, C2>>>::OutStrongestOrder<TotalOrder>,
+        >>::Min,
+    >
+    where
+        L::Root: CanSend<'a, Cluster<'a, C2>, In<T> = (ClusterId<C2>, T), Out<T> = (Tag, T)> + 'a,
+        T: Clone + Serialize + DeserializeOwned,
+        TotalOrder:
+            MinOrder<<L::Root as CanSend<'a, Cluster<'a, C2>>>::OutStrongestOrder<TotalOrder>>,
+    {
+        self.round_robin_bincode(other).map(q!(|(_, b)| b))
+    }
+
+    pub fn round_robin_bytes<C2: 'a>(
+        self,
+        other: &Cluster<'a, C2>,
+    ) -> Stream<
+        <L::Root as CanSend<'a, Cluster<'a, C2>>>::Out<Bytes>,
+        Cluster<'a, C2>,
+        Unbounded,
+        <TotalOrder as MinOrder<
+            <L::Root as CanSend<'a, Cluster<'a, C2>>>::OutStrongestOrder<TotalOrder>,
+        >>::Min,
+    >
+    where
+        L::Root: CanSend<'a, Cluster<'a, C2>, In<Bytes> = (ClusterId<C2>, T)> + 'a,
+        T: Clone,
+        TotalOrder:
+            MinOrder<<L::Root as CanSend<'a, Cluster<'a, C2>>>::OutStrongestOrder<TotalOrder>>,
+    {
+        let ids = other.members();
+
+        self.enumerate()
+            .map(q!(|(i, w)| (ids[i % ids.len()], w)))
+            .send_bytes(other)
+    }
+
+    pub fn round_robin_bytes_interleaved<C2: 'a, Tag>(
+        self,
+        other: &Cluster<'a, C2>,
+    ) -> Stream<
+        Bytes,
+        Cluster<'a, C2>,
+        Unbounded,
+        <TotalOrder as MinOrder<
+            <L::Root as CanSend<'a, Cluster<'a, C2>>>::OutStrongestOrder<TotalOrder>,
+        >>::Min,
+    >
+    where
+        L::Root: CanSend<'a, Cluster<'a, C2>, In<Bytes> = (ClusterId<C2>, T), Out<Bytes> = (Tag, Bytes)>
+            + 'a,
+        T: Clone,
+        TotalOrder:
+            MinOrder<<L::Root as CanSend<'a, Cluster<'a, C2>>>::OutStrongestOrder<TotalOrder>>,
+    {
+        self.round_robin_bytes(other).map(q!(|(_, b)| b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use dfir_rs::futures::StreamExt;
+    use hydro_deploy::Deployment;
+    use serde::{Deserialize, Deserializer, Serialize};
+    use stageleft::{q, RuntimeData};
+
+    use crate::builder::built::CompileError;
+    use crate::deploy::{DeployCrateWrapper, DeployRuntime, MultiGraph};
+    use crate::location::external_process::ExternalBincodeStream;
+    use crate::location::Location;
+    use crate::runtime_support::durable::{append_record, committed_offset, DurableLog};
+    use crate::stream::{
+        Either, GapReport, Heartbeat, KeepPolicy, Lz4Level, OverflowPolicy, RawBytes, StallAlert,
+    };
+    use crate::windowing::{PaneStatus, Trigger};
+    use crate::{
+        ClusterId, ExternalProcess, FlowBuilder, Process, CLUSTER_SELF_ID, RUNTIME_CONTEXT,
+    };
+
+    struct P1 {}
+    struct P2 {}
+
+    #[derive(Serialize, Deserialize, Debug)]
+    struct SendOverNetwork {
+        n: u32,
+    }
+
+    static COUNTED_VALUES_RECEIVED: AtomicUsize = AtomicUsize::new(0);
+
+    /// A value that records how many values this process had deserialized when it arrived.
+    #[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Hash, Debug)]
+    struct CountedValue {
+        n: u32,
+        #[serde(deserialize_with = "count_received")]
+        received: usize,
+    }
+
+    fn count_received<'de, D: Deserializer<'de>>(deserializer: D) -> Result<usize, D::Error> {
+        usize::deserialize(deserializer)?;
+        Ok(COUNTED_VALUES_RECEIVED.fetch_add(1, Ordering::Relaxed) + 1)
+    }
+
+    #[tokio::test]
+    async fn first_ten_distributed() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let first_node = flow.process::<P1>();
+        let second_node = flow.process::<P2>();
+        let external = flow.external_process::<P2>();
+
+        let numbers = first_node.source_iter(q!(0..10));
+        let out = numbers
+            .map(q!(|n| SendOverNetwork { n }))
+            .send_bincode(&second_node)
+            .collect_for_test(&external);
+
+        let nodes = flow
+            .with_process(&first_node, deployment.Localhost())
+            .with_process(&second_node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let collected = out.connect(&nodes).await;
+
+        deployment.start().await.unwrap();
+        deployment.stop().await.unwrap();
+
+        assert_eq!(
+            collected.await.iter().map(|v| v.n).collect::<Vec<_>>(),
+            (0..10).collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn send_bincode_adaptive_round_trip() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let first_node = flow.process::<P1>();
+        let second_node = flow.process::<P2>();
+        let external = flow.external_process::<P2>();
+
+        let out = first_node
+            .source_iter(q!(vec!["small".to_string(), "large".repeat(1000)]))
+            .send_bincode_adaptive(&second_node, 64)
+            .collect_for_test(&external);
+
+        let nodes = flow
+            .with_process(&first_node, deployment.Localhost())
+            .with_process(&second_node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let collected = out.connect(&nodes).await;
+
+        deployment.start().await.unwrap();
+        deployment.stop().await.unwrap();
+
+        assert_eq!(
+            collected.await,
+            vec!["small".to_string(), "large".repeat(1000)]
+        );
+    }
+
+    #[tokio::test]
+    async fn send_bincode_compressed_round_trip() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let first_node = flow.process::<P1>();
+        let second_node = flow.process::<P2>();
+        let external = flow.external_process::<P2>();
+
+        let out = first_node
+            .source_iter(q!(vec!["small".to_string(), "large".repeat(1000)]))
+            .send_bincode_compressed(&second_node, Lz4Level::High)
+            .collect_for_test(&external);
+
+        let nodes = flow
+            .with_process(&first_node, deployment.Localhost())
+            .with_process(&second_node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let collected = out.connect(&nodes).await;
+
+        deployment.start().await.unwrap();
+        deployment.stop().await.unwrap();
+
+        assert_eq!(
+            collected.await,
+            vec!["small".to_string(), "large".repeat(1000)]
+        );
+    }
+
+    #[tokio::test]
+    async fn send_bincode_batched_coalesces() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let first_node = flow.process::<P1>();
+        let second_node = flow.process::<P2>();
+        let external = flow.external_process::<P2>();
+
+        // Elements trickle in, so each batch is only sent once it is full.
+        let numbers = unsafe { first_node.source_interval(q!(Duration::from_millis(10))) }
+            .enumerate()
+            .map(q!(|(i, _)| i))
+            .filter(q!(|i| *i < 8));
+        let received = numbers.send_bincode_batched(&second_node, 4, Duration::from_secs(3600));
+
+        // All elements of a batch are delivered together, so they land in the same tick.
+        let tick = second_node.tick();
+        let per_tick_counts = unsafe { received.clone().timestamped(&tick).tick_batch() }
+            .count()
+            .filter(q!(|c| *c > 0))
+            .all_ticks()
+            .drop_timestamp();
+
+        let elements_port = received.send_bincode_external(&external);
+        let counts_port = per_tick_counts.send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&first_node, deployment.Localhost())
+            .with_process(&second_node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut elements_out = nodes.connect_source_bincode(elements_port).await;
+        let mut counts_out = nodes.connect_source_bincode(counts_port).await;
+
+        deployment.start().await.unwrap();
+
+        for i in 0..8 {
+            assert_eq!(elements_out.next().await.unwrap(), i);
+        }
+
+        let mut delivered = 0;
+        let mut ticks = 0;
+        while delivered < 8 {
+            let count = counts_out.next().await.unwrap();
+            assert_eq!(count % 4, 0);
+            delivered += count;
+            ticks += 1;
+        }
+        assert!(ticks < 8);
+    }
+
+    /// Sends every record of the durable log to an external process, and commits the offset of
+    /// each record below `commit_below`.
+    fn durable_consumer<'a>(
+        flow: &FlowBuilder<'a>,
+        log_path: &str,
+        offset_path: &str,
+        commit_below: u32,
+    ) -> (
+        Process<'a, P1>,
+        ExternalProcess<'a, P2>,
+        ExternalBincodeStream<u32>,
+    ) {
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+
+        let records = node.source_durable::<u32>(log_path, offset_path);
+        records
+            .clone()
+            .filter(q!(move |(_, record)| *record < commit_below))
+            .map(q!(|(offset, _)| offset))
+            .commit_offset();
+        let out_port = records
+            .map(q!(|(_, record)| record))
+            .send_bincode_external(&external);
+        (node, external, out_port)
+    }
+
+    #[tokio::test]
+    async fn source_durable_resumes_after_restart() {
+        let dir =
+            std::env::temp_dir().join(format!("hydro_source_durable_test_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let log_path = dir.join("log.bin");
+        let offset_path = dir.join("offset");
+        let (log_path, offset_path) = (log_path.to_str().unwrap(), offset_path.to_str().unwrap());
+
+        for record in 0..5u32 {
+            append_record(log_path, &record);
+        }
+        let offsets = DurableLog::<u32>::open(log_path, dir.join("uncommitted"))
+            .map(|(offset, _)| offset.offset())
+            .collect::<Vec<_>>();
+        let (third_offset, last_offset) = (offsets[2], offsets[4]);
+
+        // Process the whole log but only commit the first three records, then "crash".
+        let mut deployment = Deployment::new();
+        let flow = FlowBuilder::new();
+        let (node, external, out_port) = durable_consumer(&flow, log_path, offset_path, 3);
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+        deployment.deploy().await.unwrap();
+        let out = nodes.connect_source_bincode(out_port).await;
+        deployment.start().await.unwrap();
+
+        assert_eq!(out.take(5).collect::<Vec<_>>().await, vec![0, 1, 2, 3, 4]);
+        while committed_offset(offset_path) != third_offset {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        drop(nodes);
+        drop(deployment);
+
+        let mut deployment = Deployment::new();
+        let flow = FlowBuilder::new();
+        let (node, external, out_port) = durable_consumer(&flow, log_path, offset_path, 5);
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+        deployment.deploy().await.unwrap();
+        let out = nodes.connect_source_bincode(out_port).await;
+        deployment.start().await.unwrap();
+
+        assert_eq!(out.take(2).collect::<Vec<_>>().await, vec![3, 4]);
+        // Wait for the last commit, so that no offset is being written while the files are
+        // removed.
+        while committed_offset(offset_path) != last_offset {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        drop(nodes);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn send_bincode_versioned_migrates_old_format() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let old_node = flow.process::<P1>();
+        let new_node = flow.process::<P1>();
+        let receiver = flow.process::<P2>();
+        let external = flow.external_process::<P2>();
+
+        // Version 1 of the wire format only carried an id; version 2 added a name.
+        let from_old = old_node
+            .source_iter(q!(vec![1u32, 2]))
+            .send_bincode_versioned(
+                &receiver,
+                1,
+                q!(|raw: RawBytes| match raw.version {
+                    1 => (raw.decode::<u32>(), "unnamed".to_string()),
+                    2 => raw.decode::<(u32, String)>(),
+                    v => panic!("unknown wire version {}", v),
+                }),
+            );
+        let from_new = new_node
+            .source_iter(q!(vec![(3u32, "three".to_string())]))
+            .send_bincode_versioned(
+                &receiver,
+                2,
+                q!(|raw: RawBytes| match raw.version {
+                    1 => (raw.decode::<u32>(), "unnamed".to_string()),
+                    2 => raw.decode::<(u32, String)>(),
+                    v => panic!("unknown wire version {}", v),
+                }),
+            );
+
+        let out_port = from_old.union(from_new).send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&old_node, deployment.Localhost())
+            .with_process(&new_node, deployment.Localhost())
+            .with_process(&receiver, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        let mut received = Vec::new();
+        for _ in 0..3 {
+            received.push(out.next().await.unwrap());
+        }
+        received.sort();
+
+        assert_eq!(
+            received,
+            vec![
+                (1, "unnamed".to_string()),
+                (2, "unnamed".to_string()),
+                (3, "three".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn named_operator_ident() {
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+
+        node.source_iter(q!(vec!["1", "2", "3"]))
+            .map(q!(|s| s.parse::<u32>().unwrap()))
+            .named("parse")
+            .for_each(q!(|n| println!("{}", n)));
+
+        let compiled = flow
+            .finalize()
+            .with_default_optimize::<MultiGraph>()
+            .compile_no_network();
+
+        let graph = compiled.hydroflow_ir().values().next().unwrap();
+        let varnames = graph
+            .node_ids()
+            .filter_map(|id| graph.node_varname(id))
+            .map(|ident| ident.to_string())
+            .collect::<Vec<_>>();
+        assert!(
+            varnames.iter().any(|name| name.starts_with("parse_")),
+            "no labeled ident in {:?}",
+            varnames
+        );
+    }
+
+    #[test]
+    fn cross_product_capped_static() {
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let tick = node.tick();
+
+        let left = unsafe {
+            node.source_iter(q!(vec![1, 2, 3]))
+                .timestamped(&tick)
+                .tick_batch()
+        };
+        let right = unsafe { node.source_iter(q!(0..2)).timestamped(&tick).tick_batch() };
+
+        left.cross_product_capped(right, 5)
+            .all_ticks()
+            .for_each(q!(|_| {}));
+
+        let error = flow
+            .finalize()
+            .try_compile_no_network::<MultiGraph>()
+            .err()
+            .unwrap();
+        assert_eq!(
+            error,
+            CompileError::Build(vec![
+                "cross_product_capped: product of 3 x 2 elements exceeds the cap of 5".to_string()
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn cross_join_under_limit() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+        let tick = node.tick();
+
+        let left = unsafe {
+            node.source_iter(q!((0..3).collect::<Vec<_>>()))
+                .timestamped(&tick)
+                .tick_batch()
+        };
+        let right = unsafe {
+            node.source_iter(q!((0..2).collect::<Vec<_>>()))
+                .timestamped(&tick)
+                .tick_batch()
+        };
+        let out = left
+            .cross_join(right, Some(6))
+            .all_ticks()
+            .drop_timestamp()
+            .collect_for_test(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let collected = out.connect(&nodes).await;
+
+        deployment.start().await.unwrap();
+        deployment.stop().await.unwrap();
+
+        let mut pairs = collected.await;
+        pairs.sort();
+        assert_eq!(pairs, vec![(0, 0), (0, 1), (1, 0), (1, 1), (2, 0), (2, 1)]);
+    }
+
+    #[tokio::test]
+    async fn cross_join_trips_limit() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let tick = node.tick();
+
+        let left = unsafe {
+            node.source_iter(q!((0..3).collect::<Vec<_>>()))
+                .timestamped(&tick)
+                .tick_batch()
+        };
+        let right = unsafe {
+            node.source_iter(q!((0..2).collect::<Vec<_>>()))
+                .timestamped(&tick)
+                .tick_batch()
+        };
+        left.cross_join(right, Some(5))
+            .all_ticks()
+            .drop_timestamp()
+            .for_each(q!(|v| println!("{:?}", v)));
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut stderr = nodes.get_process(&node).stderr().await;
+
+        deployment.start().await.unwrap();
+
+        tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                let line = stderr.recv().await.unwrap();
+                if line.contains("cross_product_capped") {
+                    assert!(line.contains("product of 3 x 2 elements exceeds the cap of 5"));
+                    break;
+                }
+            }
+        })
+        .await
+        .expect("timed out waiting for the panic message");
+    }
+
+    #[tokio::test]
+    async fn cross_product_capped_runtime() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let tick = node.tick();
+
+        let left = unsafe {
+            node.source_iter(q!((0..3).collect::<Vec<_>>()))
+                .timestamped(&tick)
+                .tick_batch()
+        };
+        let right = unsafe {
+            node.source_iter(q!((0..2).collect::<Vec<_>>()))
+                .timestamped(&tick)
+                .tick_batch()
+        };
+        left.cross_product_capped(right, 5)
+            .all_ticks()
+            .drop_timestamp()
+            .for_each(q!(|v| println!("{:?}", v)));
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut stderr = nodes.get_process(&node).stderr().await;
+
+        deployment.start().await.unwrap();
+
+        tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                let line = stderr.recv().await.unwrap();
+                if line.contains("cross_product_capped") {
+                    assert!(line.contains("product of 3 x 2 elements exceeds the cap of 5"));
+                    break;
+                }
+            }
+        })
+        .await
+        .expect("timed out waiting for the panic message");
+    }
+
+    #[tokio::test]
+    async fn throttle_by_key_across_ticks() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+
+        // Each element arrives in its own tick.
+        let elements = unsafe { node.source_interval(q!(Duration::from_millis(10))) }
+            .enumerate()
+            .map(q!(|(i, _)| (i % 2, i)))
+            .filter(q!(|(_, i)| *i < 6));
+        let out_port = unsafe { elements.throttle_by_key(q!(Duration::from_secs(3600))) }
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        assert_eq!(out.next().await.unwrap(), (0, 0));
+        assert_eq!(out.next().await.unwrap(), (1, 1));
+        assert!(tokio::time::timeout(Duration::from_millis(500), out.next())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn assert_unique_panics_on_duplicate() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+
+        node.source_iter(q!(vec![1, 2, 3, 2]))
+            .assert_unique()
+            .for_each(q!(|v| println!("{}", v)));
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut stderr = nodes.get_process(&node).stderr().await;
+
+        deployment.start().await.unwrap();
+
+        tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                let line = stderr.recv().await.unwrap();
+                if line.contains("assert_unique") {
+                    assert!(line.contains("duplicate element 2"));
+                    break;
+                }
+            }
+        })
+        .await
+        .expect("timed out waiting for the panic message");
+    }
+
+    #[tokio::test]
+    async fn assert_unique_panics_on_duplicate_across_ticks() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+
+        // Each element arrives in its own tick, so the duplicate is in a later tick.
+        unsafe { node.source_interval(q!(Duration::from_millis(10))) }
+            .enumerate()
+            .map(q!(|(i, _)| i % 3))
+            .assert_unique()
+            .for_each(q!(|v| println!("{}", v)));
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut stderr = nodes.get_process(&node).stderr().await;
+
+        deployment.start().await.unwrap();
+
+        tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                let line = stderr.recv().await.unwrap();
+                if line.contains("assert_unique") {
+                    assert!(line.contains("duplicate element 0"));
+                    break;
+                }
+            }
+        })
+        .await
+        .expect("timed out waiting for the panic message");
+    }
+
+    #[tokio::test]
+    async fn sliding_reduce_sum() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+
+        // Each element arrives in its own tick, so windows must span ticks.
+        let out_port = unsafe { node.source_interval(q!(Duration::from_millis(10))) }
+            .enumerate()
+            .map(q!(|(i, _)| i))
+            .filter(q!(|i| *i < 6))
+            .sliding_reduce(3, q!(|sum, x| *sum += x), q!(|sum, x| *sum -= x))
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        for expected in [3, 6, 9, 12] {
+            assert_eq!(out.next().await.unwrap(), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn partition_n_by_mod() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+
+        let buckets = node.source_iter(q!(0..9)).partition_n::<3, _>(q!(|&x| x));
+        let ports = buckets.map(|bucket| bucket.send_bincode_external(&external));
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut outs = Vec::new();
+        for port in ports {
+            outs.push(nodes.connect_source_bincode(port).await);
+        }
+
+        deployment.start().await.unwrap();
+
+        for (bucket, out) in outs.iter_mut().enumerate() {
+            for i in 0..3 {
+                assert_eq!(out.next().await.unwrap(), bucket + 3 * i);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn partition_evens_and_odds() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+
+        let (evens, odds) = node.source_iter(q!(0..10)).partition(q!(|x| x % 2 == 0));
+        let evens_port = evens.send_bincode_external(&external);
+        let odds_port = odds.send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut evens_out = nodes.connect_source_bincode(evens_port).await;
+        let mut odds_out = nodes.connect_source_bincode(odds_port).await;
+
+        deployment.start().await.unwrap();
+
+        for i in 0..5 {
+            assert_eq!(evens_out.next().await.unwrap(), 2 * i);
+            assert_eq!(odds_out.next().await.unwrap(), 2 * i + 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn partition_evaluates_predicate_once_per_element() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+
+        // The predicate alternates on every call, so evaluating it more than once per element
+        // would send elements to both outputs or to neither.
+        let (pass, fail) = node.source_iter(q!(0..10)).partition(q!({
+            let calls = std::cell::Cell::new(0);
+            move |_: &i32| {
+                calls.set(calls.get() + 1);
+                calls.get() % 2 == 1
+            }
+        }));
+        let pass = pass.collect_for_test(&external);
+        let fail = fail.collect_for_test(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let pass = pass.connect(&nodes).await;
+        let fail = fail.connect(&nodes).await;
+
+        deployment.start().await.unwrap();
+        deployment.stop().await.unwrap();
+
+        assert_eq!(pass.await, vec![0, 2, 4, 6, 8]);
+        assert_eq!(fail.await, vec![1, 3, 5, 7, 9]);
+    }
+
+    #[tokio::test]
+    async fn merge_latest_prefers_latest_tick() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+        let tick = node.tick();
+
+        // Each event arrives in its own tick; both sides update key 0 in the last one.
+        let events = unsafe { node.source_interval(q!(Duration::from_millis(10))) }
+            .enumerate()
+            .map(q!(|(i, _)| i));
+        let left = events
+            .clone()
+            .filter(q!(|i| *i == 0 || *i == 2 || *i == 3))
+            .map(q!(|i| (0, format!("left{}", i))));
+        let right = events
+            .filter(q!(|i| *i == 1 || *i == 3))
+            .map(q!(|i| (0, format!("right{}", i))));
+
+        let out_port = unsafe {
+            left.timestamped(&tick)
+                .tick_batch()
+                .merge_latest(right.timestamped(&tick).tick_batch())
+        }
+        .all_ticks()
+        .drop_timestamp()
+        .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        // The full view is emitted every tick, so only look at changes.
+        let mut views: Vec<(usize, String)> = Vec::new();
+        while views.len() < 4 {
+            let view = out.next().await.unwrap();
+            if views.last() != Some(&view) {
+                views.push(view);
+            }
+        }
+
+        assert_eq!(
+            views,
+            vec![
+                (0, "left0".to_string()),
+                (0, "right1".to_string()),
+                (0, "left2".to_string()),
+                (0, "left3".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn fold_keyed_spilling_counts() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+        let tick = node.tick();
+
+        // 20 keys with only 2 accumulators in memory, so most keys are spilled and reloaded.
+        let counts = unsafe {
+            node.source_iter(q!((0..200).map(|i| (i % 20, i))))
+                .timestamped(&tick)
+                .tick_batch()
+        }
+        .fold_keyed_spilling(
+            q!(|| (0, 0)),
+            q!(|(count, sum), x| {
+                *count += 1;
+                *sum += x;
+            }),
+            2,
+        );
+        let out_port = counts
+            .all_ticks()
+            .drop_timestamp()
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        let mut results: Vec<(i32, (usize, i32))> = Vec::new();
+        for _ in 0..20 {
+            results.push(out.next().await.unwrap());
+        }
+        results.sort();
+
+        assert_eq!(
+            results,
+            (0..20)
+                .map(|k| (k, (10, (0..200).filter(|i| i % 20 == k).sum())))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn fold_keyed_ordered_emits_in_first_seen_order() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+        let tick = node.tick();
+
+        let words = unsafe {
+            node.source_iter(q!(vec![
+                (3, "x"),
+                (1, "a"),
+                (3, "y"),
+                (2, "p"),
+                (1, "b"),
+                (2, "q"),
+                (3, "z"),
+            ]))
+            .timestamped(&tick)
+            .tick_batch()
+        };
+        let out_port = words
+            .fold_keyed_ordered(q!(|| String::new()), q!(|acc, x| acc.push_str(x)))
+            .all_ticks()
+            .drop_timestamp()
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        let mut results: Vec<(i32, String)> = Vec::new();
+        for _ in 0..3 {
+            results.push(out.next().await.unwrap());
+        }
+
+        // Keys come out in the order they were first seen, not sorted or hashed.
+        assert_eq!(
+            results,
+            vec![
+                (3, "xyz".to_string()),
+                (1, "ab".to_string()),
+                (2, "pq".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn global_count_sums_members() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let coordinator = flow.process::<P1>();
+        let cluster = flow.cluster::<P2>();
+        let external = flow.external_process::<P2>();
+
+        // Member 0 processes 5 elements and member 1 processes 10.
+        let total = cluster
+            .source_iter(q!(0..(CLUSTER_SELF_ID.raw_id + 1) * 5))
+            .global_count(&coordinator);
+        let out_port = unsafe { total.sample_eager() }.send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&coordinator, deployment.Localhost())
+            .with_cluster(&cluster, (0..2).map(|_| deployment.Localhost()))
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        loop {
+            let total: usize = out.next().await.unwrap();
+            assert!(total <= 15);
+            if total == 15 {
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn reduce_keyed_commutative_cluster_ir() {
+        let flow = FlowBuilder::new();
+        let cluster = flow.cluster::<P2>();
+
+        cluster
+            .source_iter(q!(vec![(1, 1), (2, 2), (1, 3)]))
+            .reduce_keyed_commutative(q!(|total, x| *total += x))
+            .for_each(q!(|(k, total)| println!("{}: {}", k, total)));
+
+        // Each member reduces locally, then the partial aggregates are shuffled by key and
+        // reduced again on the owning member.
+        let built = flow.with_default_optimize::<DeployRuntime>();
+        insta::assert_debug_snapshot!(built.ir());
+        let _ = built.compile(&RuntimeData::new("FAKE"));
+    }
+
+    fn deep_flow(flow: &FlowBuilder) {
+        let node = flow.process::<P1>();
+        let tick = node.tick();
+
+        // Each `count` waits for its whole input, so starts a new stratum.
+        unsafe { node.source_iter(q!(0..10)).timestamped(&tick).tick_batch() }
+            .count()
+            .into_stream()
+            .count()
+            .into_stream()
+            .count()
+            .all_ticks()
+            .drop_timestamp()
+            .for_each(q!(|n| println!("{}", n)));
+    }
+
+    #[test]
+    fn with_max_strata_allows_shallow_graph() {
+        let flow = FlowBuilder::new().with_max_strata(10);
+        deep_flow(&flow);
+        let _ = flow
+            .with_default_optimize::<DeployRuntime>()
+            .compile(&RuntimeData::new("FAKE"));
+    }
+
+    #[test]
+    #[should_panic(expected = "exceeding the maximum of 1. Deepest chain: sg_")]
+    fn with_max_strata_rejects_deep_graph() {
+        let flow = FlowBuilder::new().with_max_strata(1);
+        deep_flow(&flow);
+        let _ = flow
+            .with_default_optimize::<DeployRuntime>()
+            .compile(&RuntimeData::new("FAKE"));
+    }
+
+    #[tokio::test]
+    async fn reduce_keyed_commutative_sums_across_members() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let collector = flow.process::<P1>();
+        let cluster = flow.cluster::<P2>();
+        let external = flow.external_process::<P2>();
+
+        // Both members produce the same elements, so each key's total is doubled.
+        let out_port = cluster
+            .source_iter(q!(vec![(1, 1), (2, 2), (1, 3), (3, 4)]))
+            .reduce_keyed_commutative(q!(|total, x| *total += x))
+            .send_bincode(&collector)
+            .map(q!(|(_, total)| total))
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&collector, deployment.Localhost())
+            .with_cluster(&cluster, (0..2).map(|_| deployment.Localhost()))
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        // The totals are updated as partial aggregates arrive from each member.
+        let expected = HashMap::from([(1, 8), (2, 4), (3, 8)]);
+        let mut totals = HashMap::new();
+        while totals != expected {
+            let (k, total): (i32, i32) = out.next().await.unwrap();
+            assert!(total <= expected[&k]);
+            totals.insert(k, total);
+        }
+    }
+
+    #[tokio::test]
+    async fn aggregate_on_watermark_fires_and_routes_late() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+        let tick = node.tick();
+
+        // Each tick receives one interval element, and the watermark advances by 5 per tick.
+        let steps = unsafe { node.source_interval(q!(Duration::from_millis(10))) }
+            .enumerate()
+            .map(q!(|(i, _)| i as u64));
+        let batch = unsafe { steps.timestamped(&tick).tick_batch() };
+        let watermark = batch
+            .clone()
+            .map(q!(|i| i * 5))
+            .max()
+            .unwrap_or(tick.singleton(q!(0)));
+        // (key, event time), in windows of 10 event time units.
+        let elements = batch.flat_map_ordered(q!(|i| match i {
+            0 => vec![(1, 3), (1, 5), (2, 12)],
+            3 => vec![(1, 7), (2, 14)],
+            _ => vec![],
+        }));
+
+        let (fired, late) = elements.aggregate_on_watermark(
+            watermark,
+            q!(|t: &u64| (t / 10 + 1) * 10),
+            q!(|| 0),
+            q!(|sum, t| *sum += t),
+        );
+        let out_port = fired
+            .map(q!(|kv| Ok(kv)))
+            .chain(late.map(q!(|kv| Err(kv))))
+            .all_ticks()
+            .drop_timestamp()
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        let mut results: Vec<Result<(i32, u64), (i32, u64)>> = Vec::new();
+        for _ in 0..3 {
+            results.push(out.next().await.unwrap());
+        }
+
+        // Key 1's window fires once the watermark reaches 10, after which its element at 7 is
+        // late; key 2's window fires once the watermark reaches 20.
+        assert_eq!(results, vec![Ok((1, 8)), Err((1, 7)), Ok((2, 26))]);
+    }
+
+    #[tokio::test]
+    async fn reliable_send_retransmits_dropped() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let sender = flow.process::<P1>();
+        let receiver = flow.process::<P2>();
+        let external = flow.external_process::<P2>();
+
+        // The first two transmissions of every third element are lost.
+        let out_port = sender
+            .source_iter(q!(0..10))
+            .reliable_send_with_faults(
+                &receiver,
+                q!(Duration::from_millis(50)),
+                q!(|(seq, attempt)| seq % 3 == 1 && *attempt < 2),
+            )
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&sender, deployment.Localhost())
+            .with_process(&receiver, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        let mut results: Vec<i32> = Vec::new();
+        for _ in 0..10 {
+            results.push(out.next().await.unwrap());
+        }
+        assert_eq!(results, (0..10).collect::<Vec<_>>());
+
+        // Retransmissions of elements that were already delivered are not released again.
+        assert!(tokio::time::timeout(Duration::from_millis(500), out.next())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn split_ratio_is_seeded() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+
+        let (first, second) = node.source_iter(q!(0..1000u64)).split_ratio(0.3, 7);
+        let out_port = first
+            .map(q!(|x| (true, x)))
+            .union(second.map(q!(|x| (false, x))))
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        let mut results: Vec<(bool, u64)> = Vec::new();
+        for _ in 0..1000 {
+            results.push(out.next().await.unwrap());
+        }
+
+        let first_count = results.iter().filter(|(first, _)| *first).count();
+        assert!((250..350).contains(&first_count), "{}", first_count);
+
+        // The split only depends on the seed and each element's position.
+        for (first, x) in results {
+            assert_eq!(
+                first,
+                crate::runtime_support::ratio::assigns_first(7, x, 0.3)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn send_bincode_exactly_once_drops_duplicates() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let sender = flow.process::<P1>();
+        let receiver = flow.process::<P2>();
+        let external = flow.external_process::<P2>();
+
+        // Every element is sent three times, as if it were retransmitted.
+        let out_port = sender
+            .source_iter(q!(0..10))
+            .flat_map_ordered(q!(|x| vec![(x, x * 10); 3]))
+            .send_bincode_exactly_once(&receiver, 100)
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&sender, deployment.Localhost())
+            .with_process(&receiver, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        let mut results: Vec<i32> = Vec::new();
+        for _ in 0..10 {
+            results.push(out.next().await.unwrap());
+        }
+        assert_eq!(results, (0..10).map(|x| x * 10).collect::<Vec<_>>());
+
+        assert!(tokio::time::timeout(Duration::from_millis(500), out.next())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn stable_partition_is_consistent_across_ticks() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+
+        // Each key recurs every five ticks.
+        let [p0, p1, p2] = unsafe { node.source_interval(q!(Duration::from_millis(10))) }
+            .enumerate()
+            .map(q!(|(i, _)| (i % 5, i)))
+            .stable_partition::<3>();
+        let out_port = p0
+            .map(q!(|(k, _)| (0, k)))
+            .union(p1.map(q!(|(k, _)| (1, k))))
+            .union(p2.map(q!(|(k, _)| (2, k))))
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        let mut assigned: HashMap<usize, usize> = HashMap::new();
+        for _ in 0..25 {
+            let (partition, key): (usize, usize) = out.next().await.unwrap();
+            assert_eq!(*assigned.entry(key).or_insert(partition), partition);
+            assert_eq!(
+                partition,
+                crate::runtime_support::partition::stable_partition(&key, 3)
+            );
+        }
+        assert_eq!(assigned.len(), 5);
+    }
+
+    #[tokio::test]
+    async fn with_heartbeats_while_idle() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+
+        // The data is all sent at startup, after which the stream is idle.
+        let out_port = unsafe {
+            node.source_iter(q!(0..3))
+                .with_heartbeats(q!(Duration::from_millis(50)))
+        }
+        .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        let mut data = Vec::new();
+        let mut idle_heartbeats = 0;
+        while idle_heartbeats < 3 {
+            match out.next().await.unwrap() {
+                Either::Left(x) => data.push(x),
+                Either::Right(Heartbeat) if data.len() == 3 => idle_heartbeats += 1,
+                Either::Right(Heartbeat) => {}
+            }
+        }
+        data.sort();
+        assert_eq!(data, vec![0, 1, 2]);
+    }
+
+    #[tokio::test]
+    async fn fold_seeded_starts_from_seed() {
+        use dfir_rs::futures::SinkExt;
+
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+
+        let seed = node
+            .source_iter(q!(vec![40, 60]))
+            .fold(q!(|| 0), q!(|acc, x| *acc += x));
+        let (in_port, elements) = external.source_external_bincode(&node);
+        let sum = elements.fold_seeded(seed, q!(|acc: &mut i32, x: i32| *acc += x));
+        let out_port = unsafe { sum.sample_eager() }.send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut in_port = nodes.connect_sink_bincode(in_port).await;
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        // Before any elements arrive, the output is just the seed.
+        assert_eq!(out.next().await.unwrap(), 100);
+
+        for x in 1..=5 {
+            in_port.send(x).await.unwrap();
+        }
+
+        loop {
+            let sum: i32 = out.next().await.unwrap();
+            assert!((100..=115).contains(&sum), "{}", sum);
+            if sum == 115 {
+                break;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn flag_late_after_deadline() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+
+        let on_time = node.source_iter(q!(vec![(dfir_rs::scheduled::ticks::TickInstant(0), 0)]));
+        // Fires well after the first tick, so its deadline has passed.
+        let late = unsafe {
+            node.source_interval_delayed(
+                q!(Duration::from_millis(100)),
+                q!(Duration::from_secs(3600)),
+            )
+        }
+        .map(q!(|_| (dfir_rs::scheduled::ticks::TickInstant(0), 1)));
+        let out_port = on_time
+            .union(late)
+            .flag_late()
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        let mut results: Vec<(bool, i32)> =
+            vec![out.next().await.unwrap(), out.next().await.unwrap()];
+        results.sort();
+        assert_eq!(results, vec![(false, 0), (true, 1)]);
+    }
+
+    #[tokio::test]
+    async fn ping_pong_measures_latency() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let pinger = flow.process::<P1>();
+        let echoer = flow.process::<P2>();
+        let external = flow.external_process::<P2>();
+
+        let out_port = pinger
+            .source_iter(q!(0..5))
+            .ping_pong(&echoer)
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&pinger, deployment.Localhost())
+            .with_process(&echoer, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        for _ in 0..5 {
+            let latency: Duration = out.next().await.unwrap();
+            assert!(latency > Duration::ZERO);
+            assert!(latency < Duration::from_secs(60), "{:?}", latency);
+        }
+
+        assert!(tokio::time::timeout(Duration::from_millis(500), out.next())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn bisect_by_evenness_with_counts() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+        let tick = node.tick();
+
+        let (evens, odds, counts) =
+            unsafe { node.source_iter(q!(0..10)).timestamped(&tick).tick_batch() }
+                .bisect(q!(|x| x % 2 == 0));
+        let out_port = evens
+            .map(q!(|x| (true, x)))
+            .chain(odds.map(q!(|x| (false, x))))
+            .all_ticks()
+            .drop_timestamp()
+            .send_bincode_external(&external);
+        let counts_port = counts
+            .all_ticks()
+            .drop_timestamp()
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+        let mut counts_out = nodes.connect_source_bincode(counts_port).await;
+
+        deployment.start().await.unwrap();
+
+        let mut results: Vec<(bool, i32)> = Vec::new();
+        for _ in 0..10 {
+            results.push(out.next().await.unwrap());
+        }
+        assert_eq!(
+            results,
+            [0, 2, 4, 6, 8]
+                .map(|x| (true, x))
+                .into_iter()
+                .chain([1, 3, 5, 7, 9].map(|x| (false, x)))
+                .collect::<Vec<_>>()
+        );
+
+        let counts: (usize, usize) = counts_out.next().await.unwrap();
+        assert_eq!(counts, (5, 5));
+    }
+
+    #[tokio::test]
+    async fn scan_emits_running_sums() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let first_node = flow.process::<P1>();
+        let second_node = flow.process::<P2>();
+        let external = flow.external_process::<P2>();
+
+        let out_port = first_node
+            .source_iter(q!(0..10))
+            .send_bincode(&second_node)
+            .scan(
+                q!(|| 0),
+                q!(|sum, x| {
+                    *sum += x;
+                    *sum
+                }),
+            )
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&first_node, deployment.Localhost())
+            .with_process(&second_node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        let mut sum = 0;
+        for i in 0..10 {
+            sum += i;
+            assert_eq!(out.next().await.unwrap(), sum);
+        }
+    }
+
+    #[tokio::test]
+    async fn zip_pairs_by_position() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let first_node = flow.process::<P1>();
+        let second_node = flow.process::<P2>();
+        let external = flow.external_process::<P2>();
+
+        // The right side arrives over the network, so elements must wait for their partners.
+        let left = second_node.source_iter(q!(0..5));
+        let right = first_node
+            .source_iter(q!(10..15))
+            .send_bincode(&second_node);
+
+        let out_port = left.zip(right).send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&first_node, deployment.Localhost())
+            .with_process(&second_node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        for i in 0..5 {
+            assert_eq!(out.next().await.unwrap(), (i, i + 10));
+        }
+    }
+
+    #[tokio::test]
+    async fn rollup_aggregates_every_level() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+
+        let tick = node.tick();
+        let populations = node.source_iter(q!(vec![
+            (vec!["CA", "SF"], 8),
+            (vec!["CA", "LA"], 39),
+            (vec!["NY", "NYC"], 88),
+            (vec!["CA", "SF"], 1),
+        ]));
+        let out_port = unsafe { populations.timestamped(&tick).tick_batch() }
+            .rollup(q!(|| 0), q!(|total, x| *total += x))
+            .map(q!(|(path, total)| (path.join("/"), total)))
+            .all_ticks()
+            .drop_timestamp()
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        let mut results = HashMap::new();
+        for _ in 0..5 {
+            let (path, total) = out.next().await.unwrap();
+            results.insert(path, total);
+        }
+
+        assert_eq!(
+            results,
+            HashMap::from([
+                ("CA".to_string(), 48),
+                ("CA/SF".to_string(), 9),
+                ("CA/LA".to_string(), 39),
+                ("NY".to_string(), 88),
+                ("NY/NYC".to_string(), 88),
+            ])
+        );
+        assert!(tokio::time::timeout(Duration::from_millis(500), out.next())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn batch_bounded_exact_multiple_and_remainder() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+
+        let tick = node.tick();
+        let exact_port = unsafe { node.source_iter(q!(0..6)).timestamped(&tick).tick_batch() }
+            .batch(3)
+            .all_ticks()
+            .drop_timestamp()
+            .send_bincode_external(&external);
+        let remainder_port = unsafe { node.source_iter(q!(0..7)).timestamped(&tick).tick_batch() }
+            .batch(3)
+            .all_ticks()
+            .drop_timestamp()
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut exact_out = nodes.connect_source_bincode(exact_port).await;
+        let mut remainder_out = nodes.connect_source_bincode(remainder_port).await;
+
+        deployment.start().await.unwrap();
+
+        assert_eq!(exact_out.next().await.unwrap(), vec![0, 1, 2]);
+        assert_eq!(exact_out.next().await.unwrap(), vec![3, 4, 5]);
+
+        assert_eq!(remainder_out.next().await.unwrap(), vec![0, 1, 2]);
+        assert_eq!(remainder_out.next().await.unwrap(), vec![3, 4, 5]);
+        assert_eq!(remainder_out.next().await.unwrap(), vec![6]);
+
+        assert!(
+            tokio::time::timeout(Duration::from_millis(500), exact_out.next())
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn batch_unbounded_flushes_each_tick() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+
+        // 0..4 arrive in the first tick and 4..8 in the second, which the interval wakes up.
+        unsafe { node.source_interval(q!(Duration::from_millis(10))) }.for_each(q!(|_| {}));
+        let tick = node.tick();
+        let first = unsafe { node.source_iter(q!(0..4)).timestamped(&tick).tick_batch() };
+        let second = first.clone().map(q!(|x| x + 4)).defer_tick();
+        let out_port = first
+            .chain(second)
+            .all_ticks()
+            .drop_timestamp()
+            .batch(3)
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        assert_eq!(out.next().await.unwrap(), vec![0, 1, 2]);
+        assert_eq!(out.next().await.unwrap(), vec![3]);
+        assert_eq!(out.next().await.unwrap(), vec![4, 5, 6]);
+        assert_eq!(out.next().await.unwrap(), vec![7]);
+
+        assert!(tokio::time::timeout(Duration::from_millis(500), out.next())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn rate_limit_allows_burst_then_enforces_rate() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+
+        // One element every 10ms, against a limit of one every 100ms after a burst of 3.
+        let elements = unsafe { node.source_interval(q!(Duration::from_millis(10))) }
+            .enumerate()
+            .map(q!(|(i, _)| i));
+        let out_port = unsafe { elements.rate_limit(10.0, 3) }.send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        for i in 0..3 {
+            assert_eq!(out.next().await.unwrap(), i);
+        }
+
+        let mut prev: usize = out.next().await.unwrap();
+        for _ in 0..3 {
+            let next: usize = out.next().await.unwrap();
+            assert!(next - prev >= 5, "forwarded {} soon after {}", next, prev);
+            prev = next;
+        }
+    }
+
+    #[tokio::test]
+    async fn source_external_bincode_into_cluster() {
+        use dfir_rs::futures::SinkExt;
+
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let collector = flow.process::<P1>();
+        let cluster = flow.cluster::<P2>();
+        let external = flow.external_process::<P2>();
+
+        let (in_port, input) = external.source_external_bincode::<_, u32>(&cluster);
+        let out_port = input
+            .map(q!(move |n| (CLUSTER_SELF_ID.raw_id, n)))
+            .send_bincode(&collector)
+            .map(q!(|(_, received)| received))
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&collector, deployment.Localhost())
+            .with_cluster(&cluster, (0..2).map(|_| deployment.Localhost()))
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut in_port = nodes.connect_sink_bincode_demux(in_port).await;
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        for (member, n) in [(0, 1), (1, 2), (0, 3)] {
+            in_port
+                .send((ClusterId::from_raw(member), n))
+                .await
+                .unwrap();
+        }
+
+        let mut received = std::collections::HashSet::new();
+        for _ in 0..3 {
+            let (member, n): (u32, u32) = out.next().await.unwrap();
+            received.insert((member, n));
+        }
+        assert_eq!(
+            received,
+            std::collections::HashSet::from([(0, 1), (1, 2), (0, 3)])
+        );
+    }
+
+    #[tokio::test]
+    async fn send_bincode_external_from_cluster() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let cluster = flow.cluster::<P1>();
+        let external = flow.external_process::<P2>();
+
+        let out_port = cluster
+            .source_iter(q!(0..2u32))
+            .map(q!(move |n| CLUSTER_SELF_ID.raw_id * 10 + n))
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_cluster(&cluster, (0..2).map(|_| deployment.Localhost()))
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode_tagged(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        let mut received = std::collections::HashSet::new();
+        for _ in 0..4 {
+            let (member, n) = out.next().await.unwrap();
+            received.insert((member.raw_id, n));
+        }
+        assert_eq!(
+            received,
+            std::collections::HashSet::from([(0, 0), (0, 1), (1, 10), (1, 11)])
+        );
+    }
+
+    #[tokio::test]
+    async fn processing_window_emits_per_window() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+
+        let numbers = node.source_iter(q!(0..10));
+        let with_empty = unsafe {
+            numbers.clone().processing_window(
+                q!(Duration::from_millis(100)),
+                q!(|| 0),
+                q!(|acc, x| *acc += x),
+                true,
+            )
+        }
+        .send_bincode_external(&external);
+        let without_empty = unsafe {
+            numbers.processing_window(
+                q!(Duration::from_millis(100)),
+                q!(|| 0),
+                q!(|acc, x| *acc += x),
+                false,
+            )
+        }
+        .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut with_empty = nodes.connect_source_bincode(with_empty).await;
+        let mut without_empty = nodes.connect_source_bincode(without_empty).await;
+
+        deployment.start().await.unwrap();
+
+        // Every element lands in the first window, and the windows after it are empty.
+        assert_eq!(with_empty.next().await.unwrap(), 45);
+        for _ in 0..3 {
+            assert_eq!(with_empty.next().await.unwrap(), 0);
+        }
+
+        assert_eq!(without_empty.next().await.unwrap(), 45);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(500), without_empty.next())
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn map_values_preserves_keys() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+
+        let out_port = node
+            .source_iter(q!(vec![(1, 1), (2, 5), (1, 7)]))
+            .map_values(q!(|v| v * 2))
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        for expected in [(1, 2), (2, 10), (1, 14)] {
+            assert_eq!(out.next().await.unwrap(), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn send_bytes_passes_through_blobs() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let first_node = flow.process::<P1>();
+        let second_node = flow.process::<P2>();
+        let external = flow.external_process::<P2>();
+
+        let out_port = first_node
+            .source_iter(q!(vec![vec![0u8, 255, 10], vec![], vec![42u8; 1000]]))
+            .send_bytes_into(&second_node)
+            .send_bytes_external(&external);
+
+        let nodes = flow
+            .with_process(&first_node, deployment.Localhost())
+            .with_process(&second_node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bytes(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        for expected in [vec![0u8, 255, 10], vec![], vec![42u8; 1000]] {
+            assert_eq!(out.next().await.unwrap(), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn stall_alert_reports_stall_then_recovery() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+
+        // Elements flow for about 100ms, pause for about 500ms, then resume.
+        let elements = unsafe { node.source_interval(q!(Duration::from_millis(10))) }
+            .enumerate()
+            .filter_map(q!(|(i, _)| if !(10..60).contains(&i) {
+                Some(i)
+            } else {
+                None
+            }));
+        let (data, alerts) = unsafe { elements.stall_alert(Duration::from_millis(200)) };
+        let data_port = data.send_bincode_external(&external);
+        let alerts_port = alerts.send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut data = nodes.connect_source_bincode(data_port).await;
+        let mut alerts = nodes.connect_source_bincode(alerts_port).await;
+
+        deployment.start().await.unwrap();
+
+        for expected in (0..10).chain(60..65) {
+            assert_eq!(data.next().await.unwrap(), expected);
+        }
+
+        match alerts.next().await.unwrap() {
+            StallAlert::Stalled { idle } => assert!(idle > Duration::from_millis(200)),
+            other => panic!("expected a stall, got {:?}", other),
+        }
+        match alerts.next().await.unwrap() {
+            StallAlert::Recovered { stalled_for } => {
+                assert!(stalled_for > Duration::from_millis(200))
+            }
+            other => panic!("expected a recovery, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn cross_broadcast_sends_once_per_member() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let cluster = flow.cluster::<P1>();
+        let leader = flow.process::<P2>();
+        let external = flow.external_process::<P2>();
+
+        // The leader keeps ticking, so a value re-sent every tick would be deserialized again.
+        let leader_ticks = unsafe { leader.source_interval(q!(Duration::from_millis(10))) }
+            .filter_map(q!(|_| None));
+        let value = leader
+            .source_iter(q!(vec![CountedValue { n: 7, received: 0 }]))
+            .union(leader_ticks)
+            .fold_commutative(
+                q!(|| CountedValue { n: 0, received: 0 }),
+                q!(|acc, v| *acc = v),
+            );
+        // Elements trickle in over many ticks, so a value re-sent per tick or per element
+        // would be deserialized more than once.
+        let numbers = unsafe { cluster.source_interval(q!(Duration::from_millis(10))) }
+            .enumerate()
+            .map(q!(|(i, _)| i as u32))
+            .filter(q!(|i| *i < 5));
+        let out_port = unsafe { numbers.cross_broadcast(value) }
+            .map(q!(|(n, v)| (n, v.n, v.received)))
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_cluster(&cluster, (0..2).map(|_| deployment.Localhost()))
+            .with_process(&leader, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode_tagged(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        let mut received = std::collections::HashSet::new();
+        for _ in 0..10 {
+            let (member, (n, value, deserialized)) = out.next().await.unwrap();
+            // Each member deserializes the value once, however many elements it has.
+            assert_eq!((value, deserialized), (7, 1));
+            received.insert((member.raw_id, n));
+        }
+        assert_eq!(
+            received,
+            (0..2u32)
+                .flat_map(|member| (0..5).map(move |n| (member, n)))
+                .collect()
+        );
+    }
+
+    #[tokio::test]
+    async fn sample_every_nth_counts_across_ticks() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+
+        // Elements trickle in, so they are spread across many ticks.
+        let numbers = unsafe { node.source_interval(q!(Duration::from_millis(10))) }
+            .enumerate()
+            .map(q!(|(i, _)| i))
+            .filter(q!(|i| *i < 10));
+        let tick = node.tick();
+        let out = unsafe { numbers.timestamped(&tick).tick_batch() }
+            .sample_every_nth(3)
+            .all_ticks()
+            .drop_timestamp()
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out).await;
+
+        deployment.start().await.unwrap();
+
+        for expected in [0, 3, 6, 9] {
+            assert_eq!(out.next().await.unwrap(), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn filter_in_bloom_has_no_false_negatives() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+
+        let tick = node.tick();
+        let evens = unsafe {
+            node.source_iter(q!((0..1000u32).map(|i| i * 2)))
+                .timestamped(&tick)
+                .tick_batch()
+        };
+        let numbers = unsafe {
+            node.source_iter(q!(0..2000u32))
+                .timestamped(&tick)
+                .tick_batch()
+        };
+        let out = numbers
+            .filter_in_bloom(evens.build_bloom())
+            .all_ticks()
+            .drop_timestamp()
+            .collect_for_test(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let collected = out.connect(&nodes).await;
+
+        deployment.start().await.unwrap();
+        deployment.stop().await.unwrap();
+
+        let kept = collected.await;
+        assert!((0..1000u32).all(|i| kept.contains(&(i * 2))));
+        // Odd numbers only get through as false positives, which should be rare.
+        assert!(kept.len() < 1100, "kept {} elements", kept.len());
+    }
+
+    #[tokio::test]
+    async fn extrema_by_handle_empty_and_single_element_batches() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+
+        let tick = node.tick();
+        let empty = unsafe {
+            node.source_iter(q!(Vec::<(u32, char)>::new()))
+                .timestamped(&tick)
+                .tick_batch()
+        };
+        let single = unsafe {
+            node.source_iter(q!(vec![(5u32, 'a')]))
+                .timestamped(&tick)
+                .tick_batch()
+        };
+        let tied = unsafe {
+            node.source_iter(q!(vec![(1u32, 'a'), (3, 'b'), (3, 'c'), (1, 'd')]))
+                .timestamped(&tick)
+                .tick_batch()
+        };
+
+        let by_number = q!(|x: &(u32, char)| x.0);
+        let out = empty
+            .clone()
+            .max_by(by_number)
+            .into_stream()
+            .map(q!(|x| ("empty_max".to_string(), x)))
+            .chain(
+                empty
+                    .min_by(by_number)
+                    .into_stream()
+                    .map(q!(|x| ("empty_min".to_string(), x))),
+            )
+            .chain(
+                single
+                    .clone()
+                    .max_by(by_number)
+                    .into_stream()
+                    .map(q!(|x| ("single_max".to_string(), x))),
+            )
+            .chain(
+                single
+                    .min_by(by_number)
+                    .into_stream()
+                    .map(q!(|x| ("single_min".to_string(), x))),
+            )
+            .chain(
+                tied.clone()
+                    .max_by(by_number)
+                    .into_stream()
+                    .map(q!(|x| ("tied_max".to_string(), x))),
+            )
+            .chain(
+                tied.min_by(by_number)
+                    .into_stream()
+                    .map(q!(|x| ("tied_min".to_string(), x))),
+            )
+            .all_ticks()
+            .drop_timestamp()
+            .collect_for_test(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let collected = out.connect(&nodes).await;
+
+        deployment.start().await.unwrap();
+        deployment.stop().await.unwrap();
+
+        let mut collected = collected.await;
+        collected.sort();
+        assert_eq!(
+            collected,
+            vec![
+                ("single_max".to_string(), (5, 'a')),
+                ("single_min".to_string(), (5, 'a')),
+                ("tied_max".to_string(), (3, 'b')),
+                ("tied_min".to_string(), (1, 'a')),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn optional_or_else_falls_back_when_empty() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+
+        let tick = node.tick();
+        let empty = unsafe {
+            node.source_iter(q!(Vec::<u32>::new()))
+                .timestamped(&tick)
+                .tick_batch()
+        }
+        .first();
+        let one = unsafe {
+            node.source_iter(q!(vec![1u32]))
+                .timestamped(&tick)
+                .tick_batch()
+        }
+        .first();
+        let two = unsafe {
+            node.source_iter(q!(vec![2u32]))
+                .timestamped(&tick)
+                .tick_batch()
+        }
+        .first();
+
+        let out = empty
+            .clone()
+            .or_else(two.clone())
+            .into_stream()
+            .map(q!(|x| ("fallback".to_string(), x)))
+            .chain(
+                one.or_else(two)
+                    .into_stream()
+                    .map(q!(|x| ("present".to_string(), x))),
+            )
+            .chain(
+                empty
+                    .clone()
+                    .or_else(empty.clone())
+                    .into_stream()
+                    .map(q!(|x| ("both_empty".to_string(), x))),
+            )
+            .chain(
+                empty
+                    .unwrap_or(tick.singleton(q!(3u32)))
+                    .into_stream()
+                    .map(q!(|x| ("unwrap_or".to_string(), x))),
+            )
+            .all_ticks()
+            .drop_timestamp()
+            .collect_for_test(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let collected = out.connect(&nodes).await;
+
+        deployment.start().await.unwrap();
+        deployment.stop().await.unwrap();
+
+        let mut collected = collected.await;
+        collected.sort();
+        assert_eq!(
+            collected,
+            vec![
+                ("fallback".to_string(), 2),
+                ("present".to_string(), 1),
+                ("unwrap_or".to_string(), 3),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn reorder_releases_in_sequence_and_blocks_on_gaps() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+
+        // Scrambled sequence numbers trickle in over many ticks, and 6 never arrives.
+        let scrambled = unsafe { node.source_interval(q!(Duration::from_millis(10))) }
+            .enumerate()
+            .filter(q!(|(i, _)| *i < 7))
+            .map(q!(|(i, _)| {
+                let seq = [2u64, 0, 3, 1, 7, 5, 4][i];
+                (seq, seq)
+            }));
+        let tick = node.tick();
+        let out = unsafe { scrambled.timestamped(&tick).tick_batch() }
+            .reorder(0)
+            .all_ticks()
+            .drop_timestamp()
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out).await;
+
+        deployment.start().await.unwrap();
+
+        for expected in 0..6u64 {
+            assert_eq!(out.next().await.unwrap(), expected);
+        }
+        // Sequence number 7 is stuck behind the missing 6.
+        assert!(tokio::time::timeout(Duration::from_millis(500), out.next())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn changelog_reports_updates_and_deletes() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+
+        // The state moves through three versions, one every 100ms. Every tick sees the latest
+        // version, so ticks that run in between see an unchanged snapshot.
+        let version = unsafe { node.source_interval(q!(Duration::from_millis(100))) }
+            .enumerate()
+            .map(q!(|(i, _)| i))
+            .filter(q!(|i| *i < 3))
+            .max();
+        let tick = node.tick();
+        let snapshot = unsafe { version.timestamped(&tick).latest_tick() }
+            .into_stream()
+            .flat_map_ordered(q!(|version| match version {
+                0 => vec![(1u32, 'a'), (2, 'b')],
+                1 => vec![(1, 'a'), (2, 'c'), (3, 'd')],
+                _ => vec![(2, 'c'), (3, 'd')],
+            }));
+        let out = snapshot
+            .changelog()
+            .all_ticks()
+            .drop_timestamp()
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out).await;
+
+        deployment.start().await.unwrap();
+
+        let mut inserts = vec![out.next().await.unwrap(), out.next().await.unwrap()];
+        inserts.sort();
+        assert_eq!(inserts, vec![(1, Some('a')), (2, Some('b'))]);
+
+        // Key 1 is unchanged, so only the update and the insert are reported.
+        let mut updates = vec![out.next().await.unwrap(), out.next().await.unwrap()];
+        updates.sort();
+        assert_eq!(updates, vec![(2, Some('c')), (3, Some('d'))]);
+
+        assert_eq!(out.next().await.unwrap(), (1, None));
+        assert!(tokio::time::timeout(Duration::from_millis(500), out.next())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn with_trace_preserves_span_id_across_network() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let first_node = flow.process::<P1>();
+        let second_node = flow.process::<P2>();
+        let external = flow.external_process::<P2>();
+
+        let traced = first_node.source_iter(q!(vec![1, 2, 3])).with_trace();
+        let sent = traced
+            .clone()
+            .map(q!(|(context, x)| (context.span_id, x)))
+            .collect_for_test(&external);
+        let received = traced
+            .send_bincode(&second_node)
+            .map(q!(|(context, x)| (context.span_id, x)))
+            .collect_for_test(&external);
+
+        let nodes = flow
+            .with_process(&first_node, deployment.Localhost())
+            .with_process(&second_node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let sent = sent.connect(&nodes).await;
+        let received = received.connect(&nodes).await;
+
+        deployment.start().await.unwrap();
+        deployment.stop().await.unwrap();
+
+        let sent = sent.await;
+        assert_eq!(
+            sent.iter().map(|(_, x)| *x).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        assert_eq!(received.await, sent);
+    }
+
+    #[tokio::test]
+    async fn send_partitioned_routes_keys_to_one_member() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let cluster = flow.cluster::<P2>();
+        let external = flow.external_process::<P2>();
+
+        let out_port = node
+            .source_iter(q!(
+                (0..3).flat_map(|round| (0..10).map(move |key| (key, round)))
+            ))
+            .send_partitioned(&cluster, q!(|(key, _)| *key))
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_cluster(&cluster, (0..3).map(|_| deployment.Localhost()))
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode_tagged(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        let mut placement = HashMap::new();
+        for _ in 0..30 {
+            let (member, (key, _)): (_, (u32, u32)) = out.next().await.unwrap();
+            assert_eq!(
+                *placement.entry(key).or_insert(member.raw_id),
+                member.raw_id
+            );
+        }
+        assert_eq!(placement.len(), 10);
+        assert!(
+            placement
+                .values()
+                .collect::<std::collections::HashSet<_>>()
+                .len()
+                > 1
+        );
+    }
+
+    #[tokio::test]
+    async fn send_balanced_spreads_evenly() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let cluster = flow.cluster::<P2>();
+        let external = flow.external_process::<P2>();
+
+        // Every element has the same key, which would all land on one member if hashed.
+        let out_port = node
+            .source_iter(q!((0..100).map(|i| (0, i))))
+            .send_balanced(&cluster)
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_cluster(&cluster, (0..4).map(|_| deployment.Localhost()))
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode_tagged(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        let mut counts = HashMap::new();
+        for _ in 0..100 {
+            let (member, _): (_, (u32, u32)) = out.next().await.unwrap();
+            *counts.entry(member.raw_id).or_insert(0) += 1;
+        }
+        assert_eq!(counts, HashMap::from([(0, 25), (1, 25), (2, 25), (3, 25)]));
+    }
+
+    #[tokio::test]
+    async fn send_balanced_offsets_each_sender() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let senders = flow.cluster::<P1>();
+        let receivers = flow.cluster::<P2>();
+        let external = flow.external_process::<P2>();
+
+        let out_port = senders
+            .source_iter(q!(vec![0]))
+            .send_balanced(&receivers)
+            .map(q!(|(sender, _)| sender.raw_id))
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_cluster(&senders, (0..2).map(|_| deployment.Localhost()))
+            .with_cluster(&receivers, (0..2).map(|_| deployment.Localhost()))
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode_tagged(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        // Each sender's first element goes to the receiver with its own index.
+        let mut received = HashMap::new();
+        for _ in 0..2 {
+            let (receiver, sender): (_, u32) = out.next().await.unwrap();
+            received.insert(sender, receiver.raw_id);
+        }
+        assert_eq!(received, HashMap::from([(0, 0), (1, 1)]));
+    }
+
+    #[tokio::test]
+    async fn broadcast_reaches_every_member() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let cluster = flow.cluster::<P2>();
+        let external = flow.external_process::<P2>();
+
+        let out_port = node
+            .source_iter(q!(vec![1, 2]))
+            .broadcast(&cluster)
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_cluster(&cluster, (0..3).map(|_| deployment.Localhost()))
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode_tagged(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        let mut received = HashMap::new();
+        for _ in 0..6 {
+            let (member, item): (_, u32) = out.next().await.unwrap();
+            received
+                .entry(member.raw_id)
+                .or_insert_with(Vec::new)
+                .push(item);
+        }
+        assert_eq!(
+            received,
+            HashMap::from([(0, vec![1, 2]), (1, vec![1, 2]), (2, vec![1, 2])])
+        );
+    }
+
+    #[tokio::test]
+    async fn broadcast_within_cluster_excludes_self() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let cluster = flow.cluster::<P1>();
+        let external = flow.external_process::<P2>();
+
+        let out_port = cluster
+            .source_iter(q!(vec![0]))
+            .broadcast(&cluster, false)
+            .map(q!(|(sender, _)| sender.raw_id))
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_cluster(&cluster, (0..3).map(|_| deployment.Localhost()))
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode_tagged(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        let mut received = std::collections::HashSet::new();
+        for _ in 0..6 {
+            let (receiver, sender): (_, u32) = out.next().await.unwrap();
+            assert_ne!(receiver.raw_id, sender);
+            received.insert((receiver.raw_id, sender));
+        }
+        assert_eq!(received.len(), 6);
+        assert!(tokio::time::timeout(Duration::from_millis(500), out.next())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn map_fusion_preserves_results() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+
+        let out = node
+            .source_iter(q!(0..10))
+            .map(q!(|n| n + 1))
+            .filter_map(q!(|n| if n % 2 == 0 { Some(n) } else { None }))
+            .map(q!(|n| n * 10))
+            .flat_map_ordered(q!(|n| vec![n, n + 1]))
+            .collect_for_test(&external);
+
+        let nodes = flow
+            .with_map_fusion()
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let collected = out.connect(&nodes).await;
+
+        deployment.start().await.unwrap();
+        deployment.stop().await.unwrap();
+
+        assert_eq!(
+            collected.await,
+            vec![20, 21, 40, 41, 60, 61, 80, 81, 100, 101]
+        );
+    }
+
+    #[tokio::test]
+    async fn dedup_by_key_keeps_first_per_key() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+        let tick = node.tick();
+
+        let out = unsafe {
+            node.source_iter(q!(vec![(1, 'a'), (2, 'b'), (1, 'c'), (3, 'd'), (2, 'e')]))
+                .timestamped(&tick)
+                .tick_batch()
+        }
+        .dedup_by_key(KeepPolicy::First)
+        .all_ticks()
+        .drop_timestamp()
+        .collect_for_test(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let collected = out.connect(&nodes).await;
+
+        deployment.start().await.unwrap();
+        deployment.stop().await.unwrap();
+
+        let mut kept = collected.await;
+        kept.sort();
+        assert_eq!(kept, vec![(1, 'a'), (2, 'b'), (3, 'd')]);
+    }
+
+    #[tokio::test]
+    async fn dedup_by_key_keeps_last_per_key() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+        let tick = node.tick();
+
+        let out = unsafe {
+            node.source_iter(q!(vec![(1, 'a'), (2, 'b'), (1, 'c'), (3, 'd'), (2, 'e')]))
+                .timestamped(&tick)
+                .tick_batch()
+        }
+        .dedup_by_key(KeepPolicy::Last)
+        .all_ticks()
+        .drop_timestamp()
+        .collect_for_test(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let collected = out.connect(&nodes).await;
+
+        deployment.start().await.unwrap();
+        deployment.stop().await.unwrap();
+
+        let mut kept = collected.await;
+        kept.sort();
+        assert_eq!(kept, vec![(1, 'c'), (2, 'e'), (3, 'd')]);
+    }
+
+    #[tokio::test]
+    async fn count_in_one_tick() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+        let tick = node.tick();
+
+        let out_port = unsafe { node.source_iter(q!(0..100)).timestamped(&tick).tick_batch() }
+            .count()
+            .all_ticks()
+            .drop_timestamp()
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        assert_eq!(out.next().await.unwrap(), 100usize);
+    }
+
+    #[tokio::test]
+    async fn count_persisted_across_ticks() {
+        use dfir_rs::futures::SinkExt;
+
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+        let tick = node.tick();
+
+        let (in_port, elements) = external.source_external_bincode::<_, u32>(&node);
+        let out_port = unsafe { elements.timestamped(&tick).tick_batch() }
+            .persist()
+            .count()
+            .all_ticks()
+            .drop_timestamp()
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut in_port = nodes.connect_sink_bincode(in_port).await;
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        for x in 0..3 {
+            in_port.send(x).await.unwrap();
+        }
+        loop {
+            let count: usize = out.next().await.unwrap();
+            assert!(count <= 3, "{}", count);
+            if count == 3 {
+                break;
+            }
+        }
+
+        // Later ticks keep counting the elements of earlier ones.
+        for x in 3..5 {
+            in_port.send(x).await.unwrap();
+        }
+        loop {
+            let count: usize = out.next().await.unwrap();
+            assert!((3..=5).contains(&count), "{}", count);
+            if count == 5 {
+                break;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn approx_distinct_count_within_error_bound() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+        let tick = node.tick();
+
+        // Every hundredth item repeats the one before it, leaving 99,000 distinct items.
+        let items = node.source_iter(q!((0..100_000u64).map(|i| if i % 100 == 99 {
+            i - 1
+        } else {
+            i
+        })));
+        let out_port = unsafe { items.timestamped(&tick).tick_batch() }
+            .approx_distinct_count(14)
+            .all_ticks()
+            .drop_timestamp()
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        // Three times the standard error of about 0.8% at this precision.
+        let estimate: u64 = out.next().await.unwrap();
+        assert!(estimate.abs_diff(99_000) <= 2_500, "{}", estimate);
+    }
+
+    #[tokio::test]
+    async fn sample_into_stream_emits_once_per_tick() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+
+        let sum = node
+            .source_iter(q!(vec![1, 2, 3]))
+            .fold(q!(|| 0), q!(|acc, x| *acc += x));
+        let out_port = unsafe { sum.sample_into_stream(q!(Duration::from_millis(10)), true) }
+            .map(q!(|v| (RUNTIME_CONTEXT.current_tick().0, v)))
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        let mut last_tick = None;
+        for _ in 0..5 {
+            let (tick, value): (u64, i32) = out.next().await.unwrap();
+            assert_eq!(value, 6);
+            assert!(last_tick < Some(tick), "{:?} then {}", last_tick, tick);
+            last_tick = Some(tick);
+        }
+    }
+
+    #[tokio::test]
+    async fn sample_into_stream_skips_unchanged_values() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+
+        let sum = node
+            .source_iter(q!(vec![1, 2, 3]))
+            .fold(q!(|| 0), q!(|acc, x| *acc += x));
+        let out_port = unsafe { sum.sample_into_stream(q!(Duration::from_millis(10)), false) }
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        assert_eq!(out.next().await.unwrap(), 6);
+        // The value never changes again, so no other sample is emitted.
+        assert!(tokio::time::timeout(Duration::from_millis(500), out.next())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn map_parallel_uses_multiple_threads() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+
+        let out_port = node
+            .source_iter(q!(0..64))
+            .map_parallel(
+                q!(|x: u64| {
+                    std::thread::sleep(std::time::Duration::from_millis(2));
+                    (x * x, format!("{:?}", std::thread::current().id()))
+                }),
+                4,
+            )
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        let mut results = Vec::new();
+        for _ in 0..64 {
+            results.push(out.next().await.unwrap());
+        }
+        let mut squares = results
+            .iter()
+            .map(|(square, _)| *square)
+            .collect::<Vec<_>>();
+        squares.sort();
+        assert_eq!(squares, (0..64).map(|x| x * x).collect::<Vec<_>>());
+
+        let threads = results
+            .into_iter()
+            .map(|(_, thread)| thread)
+            .collect::<std::collections::HashSet<_>>();
+        assert!(threads.len() > 1, "only ran on {:?}", threads);
+    }
+
+    #[tokio::test]
+    async fn map_parallel_reuses_threads_across_ticks() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+
+        // Each interval tick brings 8 elements, so every tick uses the pool.
+        let elements = unsafe { node.source_interval(q!(Duration::from_millis(10))) }
+            .enumerate()
+            .map(q!(|(i, _)| i))
+            .filter(q!(|i| *i < 5))
+            .flat_map_ordered(q!(|i| (0..8).map(move |j| i * 8 + j)));
+        let out_port = elements
+            .map_parallel(
+                q!(|_: usize| {
+                    std::thread::sleep(std::time::Duration::from_millis(1));
+                    format!("{:?}", std::thread::current().id())
+                }),
+                2,
+            )
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        let mut threads = std::collections::HashSet::new();
+        for _ in 0..40 {
+            threads.insert(out.next().await.unwrap());
+        }
+        // Thread ids are never reused, so a new pool each tick would show up as more threads.
+        assert_eq!(threads.len(), 2, "ran on {:?}", threads);
+    }
+
+    #[tokio::test]
+    async fn map_parallel_ordered_keeps_input_order() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+
+        let out_port = node
+            .source_iter(q!(0..64))
+            .map_parallel_ordered(
+                q!(|x: u64| {
+                    // Later elements finish first, so only reordering restores the input order.
+                    std::thread::sleep(std::time::Duration::from_micros(64 - x));
+                    x * 2
+                }),
+                4,
+            )
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        for w in (0..64).map(|x| x * 2) {
+            assert_eq!(out.next().await.unwrap(), w);
+        }
+    }
+
+    #[tokio::test]
+    async fn collect_set_deduplicates() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+        let tick = node.tick();
+
+        let out_port = unsafe {
+            node.source_iter(q!(vec![1, 1, 2, 3, 3]))
+                .timestamped(&tick)
+                .tick_batch()
+        }
+        .collect_set()
+        .all_ticks()
+        .drop_timestamp()
+        .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        let set: std::collections::HashSet<i32> = out.next().await.unwrap();
+        assert_eq!(set, std::collections::HashSet::from([1, 2, 3]));
+    }
+
+    #[tokio::test]
+    async fn windowed_fires_early_on_time_and_late() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+        let tick = node.tick();
+
+        // The first event adds three elements to a window, and the second adds an element whose
+        // event tick is long past, so its window has already closed.
+        let events = unsafe { node.source_interval(q!(Duration::from_millis(10))) }
+            .enumerate()
+            .filter_map(q!(|(i, _)| match i {
+                0 => Some(vec![("a", true), ("a", true), ("a", true)]),
+                1 => Some(vec![("b", false)]),
+                _ => None,
+            }))
+            .flatten_ordered()
+            .map(q!(|(key, on_time)| {
+                let event_tick = if on_time {
+                    RUNTIME_CONTEXT.current_tick().0
+                } else {
+                    0
+                };
+                (key.to_string(), (event_tick, ()))
+            }));
+
+        let out_port = unsafe { events.timestamped(&tick).tick_batch() }
+            .windowed(1)
+            .trigger(Trigger::EveryN(2).and_late())
+            .allowed_lateness(1_000_000)
+            .fold(q!(|| 0), q!(|count, _| *count += 1))
+            .map(q!(|(key, pane)| (key, pane.status, pane.value)))
+            .all_ticks()
+            .drop_timestamp()
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        let mut panes: Vec<(String, PaneStatus, usize)> = Vec::new();
+        for _ in 0..3 {
+            panes.push(out.next().await.unwrap());
+        }
+
+        assert_eq!(
+            panes,
+            vec![
+                ("a".to_string(), PaneStatus::Early, 2),
+                ("a".to_string(), PaneStatus::OnTime, 3),
+                ("b".to_string(), PaneStatus::Late, 1),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn map_batch_reverses_tick() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+        let tick = node.tick();
+
+        let out_port = unsafe { node.source_iter(q!(0..5)).timestamped(&tick).tick_batch() }
+            .map_batch(q!(|batch: Vec<i32>| batch.into_iter().rev().collect()))
+            .all_ticks()
+            .drop_timestamp()
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        let mut results: Vec<i32> = Vec::new();
+        for _ in 0..5 {
+            results.push(out.next().await.unwrap());
+        }
+
+        assert_eq!(results, vec![4, 3, 2, 1, 0]);
+    }
+
+    #[tokio::test]
+    async fn cross_indexed_pairs() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+        let tick = node.tick();
+
+        let rows = unsafe {
+            node.source_iter(q!(vec!['a', 'b', 'c']))
+                .timestamped(&tick)
+                .tick_batch()
+        };
+        let cols = unsafe {
+            node.source_iter(q!(vec![10, 20, 30]))
+                .timestamped(&tick)
+                .tick_batch()
+        };
+        let out_port = rows
+            .cross_indexed(cols)
+            .all_ticks()
+            .drop_timestamp()
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        let mut results: Vec<((usize, char), (usize, i32))> = Vec::new();
+        for _ in 0..9 {
+            results.push(out.next().await.unwrap());
+        }
+        results.sort();
+
+        let mut expected = Vec::new();
+        for (i, row) in ['a', 'b', 'c'].into_iter().enumerate() {
+            for (j, col) in [10, 20, 30].into_iter().enumerate() {
+                expected.push(((i, row), (j, col)));
+            }
+        }
+        assert_eq!(results, expected);
+    }
+
+    #[tokio::test]
+    async fn rebalance_by_load_avoids_slow_member() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let collector = flow.process::<P1>();
+        let cluster = flow.cluster::<P2>();
+        let external = flow.external_process::<P2>();
+
+        // Each member produces 100 elements over time, and member 0 is slow to process them.
+        let elements = unsafe { cluster.source_interval(q!(Duration::from_millis(5))) }
+            .enumerate()
+            .map(q!(|(i, _)| i))
+            .filter(q!(|i| *i < 100));
+        let out_port = elements
+            .rebalance_by_load()
+            .map(q!(move |i| {
+                if CLUSTER_SELF_ID.raw_id == 0 {
+                    std::thread::sleep(Duration::from_millis(20));
+                }
+                i
+            }))
+            .send_bincode(&collector)
+            .map(q!(|(member, _)| member.raw_id))
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&collector, deployment.Localhost())
+            .with_cluster(&cluster, (0..2).map(|_| deployment.Localhost()))
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        let mut processed_by = [0; 2];
+        for _ in 0..200 {
+            let member: u32 = out.next().await.unwrap();
+            processed_by[member as usize] += 1;
+        }
+
+        // Round-robin would give each member 100 elements.
+        assert!(
+            processed_by[0] < 200 / 3,
+            "slow member processed {:?}",
+            processed_by
+        );
+    }
+
+    #[tokio::test]
+    async fn into_optional_zero_or_one() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+        let tick = node.tick();
+
+        let numbers = unsafe {
+            node.source_iter(q!(vec![1, 2, 3]))
+                .timestamped(&tick)
+                .tick_batch()
+        };
+        let none = numbers
+            .clone()
+            .filter(q!(|x| *x > 10))
+            .into_optional()
+            .map(q!(|x| ("none", x)));
+        let one = numbers
+            .filter(q!(|x| *x == 2))
+            .into_optional()
+            .map(q!(|x| ("one", x)));
+        let out_port = none
+            .into_stream()
+            .chain(one.into_stream())
+            .all_ticks()
+            .drop_timestamp()
+            .map(q!(|(label, x)| (label.to_string(), x)))
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        let result: (String, i32) = out.next().await.unwrap();
+        assert_eq!(result, ("one".to_string(), 2));
+        assert!(tokio::time::timeout(Duration::from_secs(1), out.next())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn into_optional_panics_on_two() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let tick = node.tick();
+
+        unsafe {
+            node.source_iter(q!(vec![1, 2]))
+                .timestamped(&tick)
+                .tick_batch()
+        }
+        .into_optional()
+        .all_ticks()
+        .drop_timestamp()
+        .for_each(q!(|v| println!("{}", v)));
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut stderr = nodes.get_process(&node).stderr().await;
+
+        deployment.start().await.unwrap();
+
+        tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                let line = stderr.recv().await.unwrap();
+                if line.contains("into_optional") {
+                    assert!(line.contains("more than one element"));
+                    break;
+                }
+            }
+        })
+        .await
+        .expect("timed out waiting for the panic message");
+    }
+
+    #[tokio::test]
+    async fn emit_new_suppresses_repeats_across_ticks() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+        let tick = node.tick();
+
+        // Each value is repeated in a later tick, followed by values that are always new.
+        let values = unsafe { node.source_interval(q!(Duration::from_millis(10))) }
+            .enumerate()
+            .map(q!(|(i, _)| if i < 8 { i / 2 } else { 100 + i }));
+        let out_port = unsafe { values.timestamped(&tick).tick_batch() }
+            .emit_new()
+            .all_ticks()
+            .drop_timestamp()
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        let mut results: Vec<usize> = Vec::new();
+        for _ in 0..5 {
+            results.push(out.next().await.unwrap());
+        }
+
+        assert_eq!(results, vec![0, 1, 2, 3, 108]);
+    }
+
+    #[tokio::test]
+    async fn detect_gaps_reports_skipped_sequence() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+
+        let (data, gaps) = node
+            .source_iter(q!(vec![(0, 10), (1, 11), (2, 12), (5, 15), (6, 16)]))
+            .detect_gaps();
+        let data_port = data.send_bincode_external(&external);
+        let gaps_port = gaps.send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut data_out = nodes.connect_source_bincode(data_port).await;
+        let mut gaps_out = nodes.connect_source_bincode(gaps_port).await;
+
+        deployment.start().await.unwrap();
+
+        let mut data_results: Vec<i32> = Vec::new();
+        for _ in 0..5 {
+            data_results.push(data_out.next().await.unwrap());
+        }
+        assert_eq!(data_results, vec![10, 11, 12, 15, 16]);
+
+        assert_eq!(gaps_out.next().await.unwrap(), GapReport { from: 3, to: 4 });
+    }
+
+    #[tokio::test]
+    async fn fold_keyed_dense_emits_absent_keys() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+        let tick = node.tick();
+
+        let batch = unsafe {
+            node.source_iter(q!(vec![(1, 5), (1, 6), (3, 1)]))
+                .timestamped(&tick)
+                .tick_batch()
+        };
+        let out_port = batch
+            .fold_keyed_dense(
+                tick.singleton(q!(vec![1, 2, 3, 4])),
+                q!(|| 0),
+                q!(|acc, x| *acc += x),
+            )
+            .all_ticks()
+            .drop_timestamp()
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        let mut results: Vec<(i32, i32)> = Vec::new();
+        for _ in 0..4 {
+            results.push(out.next().await.unwrap());
+        }
+        results.sort();
+
+        assert_eq!(results, vec![(1, 11), (2, 0), (3, 1), (4, 0)]);
+    }
+
+    #[tokio::test]
+    async fn state_machine_counter_resets() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+        let tick = node.tick();
+
+        // Each element arrives in its own tick, so the count is carried across ticks.
+        let events = unsafe { node.source_interval(q!(Duration::from_millis(10))) }
+            .enumerate()
+            .map(q!(|(i, _)| if i % 4 == 3 { None } else { Some(i) }));
+        let out_port = unsafe { events.timestamped(&tick).tick_batch() }
+            .state_machine(
+                q!(|| 0),
+                q!(|count: &mut usize, event: Option<usize>| match event {
+                    Some(_) => {
+                        *count += 1;
+                        vec![]
+                    }
+                    None => {
+                        let total = *count;
+                        *count = 0;
+                        vec![total, 0]
+                    }
+                }),
+            )
+            .all_ticks()
+            .drop_timestamp()
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        let mut results: Vec<usize> = Vec::new();
+        for _ in 0..4 {
+            results.push(out.next().await.unwrap());
+        }
+
+        assert_eq!(results, vec![3, 0, 3, 0]);
+    }
+
+    #[tokio::test]
+    async fn with_cardinality_report_every_n_ticks() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+        let tick = node.tick();
+
+        // Every element has a new key, and the persisted state keeps growing.
+        let keys = unsafe { node.source_interval(q!(Duration::from_millis(10))) }
+            .enumerate()
+            .map(q!(|(i, _)| (i, 1)));
+        let (_counts, cardinality) = unsafe { keys.timestamped(&tick).tick_batch() }
+            .persist()
+            .with_cardinality_report(3, q!(|| 0), q!(|acc, x| *acc += x));
+        let out_port = cardinality
+            .map(q!(|c| (RUNTIME_CONTEXT.current_tick().0, c)))
+            .all_ticks()
+            .drop_timestamp()
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        let mut last: Option<(u64, usize)> = None;
+        for _ in 0..5 {
+            let (tick, count): (u64, usize) = out.next().await.unwrap();
+            assert_eq!(tick % 3, 0);
+            if let Some((last_tick, last_count)) = last {
+                assert!(tick > last_tick);
+                assert!(count >= last_count);
+            }
+            last = Some((tick, count));
+        }
+        assert!(last.unwrap().1 > 0);
+    }
+
+    #[tokio::test]
+    async fn skip_ticks_drops_warmup() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+        let tick = node.tick();
+
+        let elements = unsafe { node.source_interval(q!(Duration::from_millis(10))) };
+        let out_port = unsafe { elements.timestamped(&tick).tick_batch() }
+            .skip_ticks(5)
+            .map(q!(|_| RUNTIME_CONTEXT.current_tick().0))
+            .all_ticks()
+            .drop_timestamp()
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        for _ in 0..5 {
+            let tick: u64 = out.next().await.unwrap();
+            assert!(tick >= 5);
+        }
+    }
+
+    #[tokio::test]
+    async fn verify_checksum_flags_corrupted() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let sender = flow.process::<P1>();
+        let receiver = flow.process::<P2>();
+        let external = flow.external_process::<P2>();
+
+        // Simulate corruption in transit by changing an element after its checksum is computed.
+        let out_port = sender
+            .source_iter(q!(vec![1, 2, 3]))
+            .with_checksum()
+            .map(q!(|(sum, v)| if v == 2 { (sum, 20) } else { (sum, v) }))
+            .send_bincode(&receiver)
+            .verify_checksum()
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&sender, deployment.Localhost())
+            .with_process(&receiver, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+        let mut stderr = nodes.get_process(&receiver).stderr().await;
+
+        deployment.start().await.unwrap();
+
+        let mut results: Vec<i32> = Vec::new();
+        for _ in 0..2 {
+            results.push(out.next().await.unwrap());
+        }
+        assert_eq!(results, vec![1, 3]);
+
+        tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                let line = stderr.recv().await.unwrap();
+                if line.contains("verify_checksum") {
+                    assert!(line.contains("dropping element"));
+                    break;
+                }
+            }
+        })
+        .await
+        .expect("timed out waiting for the dropped element message");
+    }
+
+    #[tokio::test]
+    async fn global_session_closes_after_idle_gap() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+        let tick = node.tick();
+
+        // Each tick receives one interval element, which is turned into activity for the first
+        // two bursts of ticks and dropped in between and afterwards.
+        let active = unsafe { node.source_interval(q!(Duration::from_millis(10))) }
+            .enumerate()
+            .filter_map(q!(|(i, _)| if i < 3 || (10..14).contains(&i) {
+                Some(1)
+            } else {
+                None
+            }));
+        let out_port = unsafe { active.timestamped(&tick).tick_batch() }
+            .global_session(3, q!(|| 0), q!(|count, x| *count += x))
+            .all_ticks()
+            .drop_timestamp()
+            .send_bincode_external(&external);
 
-        let leaves = flow_state_borrow.leaves.as_mut().expect("Attempted to add a leaf to a flow that has already been finalized. No leaves can be added after the flow has been compiled()");
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
 
-        let dummy_f: syn::Expr = syn::parse_quote!(());
+        deployment.deploy().await.unwrap();
 
-        leaves.push(HydroLeaf::ForEach {
-            f: dummy_f.into(),
-            input: Box::new(HydroNode::Network {
-                from_location: self.location.root().id(),
-                from_key: None,
-                to_location: other.id(),
-                to_key: Some(external_key),
-                serialize_fn: None,
-                instantiate_fn: DebugInstantiate::Building(),
-                deserialize_fn: None,
-                input: Box::new(self.ir_node.into_inner()),
-            }),
-        });
+        let mut out = nodes.connect_source_bincode(out_port).await;
 
-        ExternalBytesPort {
-            process_id: other.id,
-            port_id: external_key,
+        deployment.start().await.unwrap();
+
+        let mut results: Vec<i32> = Vec::new();
+        for _ in 0..2 {
+            results.push(out.next().await.unwrap());
         }
-    }
 
-    pub fn send_bincode_interleaved<L2: Location<'a>, Tag, CoreType>(
-        self,
-        other: &L2,
-    ) -> Stream<CoreType, L2, Unbounded, Order::Min>
-    where
-        L::Root: CanSend<'a, L2, In<CoreType> = T, Out<CoreType> = (Tag, CoreType)>,
-        CoreType: Serialize + DeserializeOwned,
-        Order: MinOrder<<L::Root as CanSend<'a, L2>>::OutStrongestOrder<Order>>,
-    {
-        self.send_bincode::<L2, CoreType>(other).map(q!(|(_, b)| b))
+        assert_eq!(results, vec![3, 4]);
     }
 
-    pub fn send_bytes_interleaved<L2: Location<'a>, Tag>(
-        self,
-        other: &L2,
-    ) -> Stream<Bytes, L2, Unbounded, Order::Min>
-    where
-        L::Root: CanSend<'a, L2, In<Bytes> = T, Out<Bytes> = (Tag, Bytes)>,
-        Order: MinOrder<<L::Root as CanSend<'a, L2>>::OutStrongestOrder<Order>>,
-    {
-        self.send_bytes::<L2>(other).map(q!(|(_, b)| b))
-    }
+    async fn bounded_buffer_output(policy: OverflowPolicy) -> Vec<i32> {
+        let mut deployment = Deployment::new();
 
-    #[expect(clippy::type_complexity, reason = "ordering semantics for broadcast")]
-    pub fn broadcast_bincode<C2: 'a>(
-        self,
-        other: &Cluster<'a, C2>,
-    ) -> Stream<
-        <L::Root as CanSend<'a, Cluster<'a, C2>>>::Out<T>,
-        Cluster<'a, C2>,
-        Unbounded,
-        Order::Min,
-    >
-    where
-        L::Root: CanSend<'a, Cluster<'a, C2>, In<T> = (ClusterId<C2>, T)>,
-        T: Clone + Serialize + DeserializeOwned,
-        Order: MinOrder<<L::Root as CanSend<'a, Cluster<'a, C2>>>::OutStrongestOrder<Order>>,
-    {
-        let ids = other.members();
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+        let tick = node.tick();
+
+        // All five elements arrive in the first tick, overflowing the buffer of two. The interval
+        // keeps ticks running afterwards, so blocked elements can be released.
+        let numbers = unsafe { node.source_interval(q!(Duration::from_millis(10))) }
+            .enumerate()
+            .flat_map_ordered(q!(|(i, _)| if i == 0 {
+                vec![1, 2, 3, 4, 5]
+            } else {
+                vec![]
+            }));
+        let out_port = unsafe { numbers.timestamped(&tick).tick_batch() }
+            .bounded_buffer(2, policy)
+            .map(q!(|x| (RUNTIME_CONTEXT.current_tick().0, x)))
+            .all_ticks()
+            .drop_timestamp()
+            .send_bincode_external(&external);
 
-        self.flat_map_ordered(q!(|b| ids.iter().map(move |id| (
-            ::std::clone::Clone::clone(id),
-            ::std::clone::Clone::clone(&b)
-        ))))
-        .send_bincode(other)
-    }
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
 
-    pub fn broadcast_bincode_interleaved<C2: 'a, Tag>(
-        self,
-        other: &Cluster<'a, C2>,
-    ) -> Stream<T, Cluster<'a, C2>, Unbounded, Order::Min>
-    where
-        L::Root: CanSend<'a, Cluster<'a, C2>, In<T> = (ClusterId<C2>, T), Out<T> = (Tag, T)> + 'a,
-        T: Clone + Serialize + DeserializeOwned,
-        Order: MinOrder<<L::Root as CanSend<'a, Cluster<'a, C2>>>::OutStrongestOrder<Order>>,
-    {
-        self.broadcast_bincode(other).map(q!(|(_, b)| b))
-    }
+        deployment.deploy().await.unwrap();
 
-    #[expect(clippy::type_complexity, reason = "ordering semantics for broadcast")]
-    pub fn broadcast_bytes<C2: 'a>(
-        self,
-        other: &Cluster<'a, C2>,
-    ) -> Stream<
-        <L::Root as CanSend<'a, Cluster<'a, C2>>>::Out<Bytes>,
-        Cluster<'a, C2>,
-        Unbounded,
-        Order::Min,
-    >
-    where
-        L::Root: CanSend<'a, Cluster<'a, C2>, In<Bytes> = (ClusterId<C2>, T)> + 'a,
-        T: Clone,
-        Order: MinOrder<<L::Root as CanSend<'a, Cluster<'a, C2>>>::OutStrongestOrder<Order>>,
-    {
-        let ids = other.members();
+        let mut out = nodes.connect_source_bincode(out_port).await;
 
-        self.flat_map_ordered(q!(|b| ids.iter().map(move |id| (
-            ::std::clone::Clone::clone(id),
-            ::std::clone::Clone::clone(&b)
-        ))))
-        .send_bytes(other)
-    }
+        deployment.start().await.unwrap();
 
-    pub fn broadcast_bytes_interleaved<C2: 'a, Tag>(
-        self,
-        other: &Cluster<'a, C2>,
-    ) -> Stream<Bytes, Cluster<'a, C2>, Unbounded, Order::Min>
-    where
-        L::Root: CanSend<'a, Cluster<'a, C2>, In<Bytes> = (ClusterId<C2>, T), Out<Bytes> = (Tag, Bytes)>
-            + 'a,
-        T: Clone,
-        Order: MinOrder<<L::Root as CanSend<'a, Cluster<'a, C2>>>::OutStrongestOrder<Order>>,
-    {
-        self.broadcast_bytes(other).map(q!(|(_, b)| b))
-    }
-}
+        let expected = if policy == OverflowPolicy::Block {
+            5
+        } else {
+            2
+        };
+        let mut results: Vec<(u64, i32)> = Vec::new();
+        for _ in 0..expected {
+            results.push(out.next().await.unwrap());
+        }
 
-#[expect(clippy::type_complexity, reason = "ordering semantics for round-robin")]
-impl<'a, T, L: Location<'a> + NoTick, B> Stream<T, L, B, TotalOrder> {
-    pub fn round_robin_bincode<C2: 'a>(
-        self,
-        other: &Cluster<'a, C2>,
-    ) -> Stream<
-        <L::Root as CanSend<'a, Cluster<'a, C2>>>::Out<T>,
-        Cluster<'a, C2>,
-        Unbounded,
-        <TotalOrder as MinOrder<
-            <L::Root as CanSend<'a, Cluster<'a, C2>>>::OutStrongestOrder<TotalOrder>,
-        >>::Min,
-    >
-    where
-        L::Root: CanSend<'a, Cluster<'a, C2>, In<T> = (ClusterId<C2>, T)>,
-        T: Clone + Serialize + DeserializeOwned,
-        TotalOrder:
-            MinOrder<<L::Root as CanSend<'a, Cluster<'a, C2>>>::OutStrongestOrder<TotalOrder>>,
-    {
-        let ids = other.members();
+        // Every tick releases at most `capacity` elements.
+        for (tick, _) in &results {
+            assert!(results.iter().filter(|(t, _)| t == tick).count() <= 2);
+        }
 
-        self.enumerate()
-            .map(q!(|(i, w)| (ids[i % ids.len()], w)))
-            .send_bincode(other)
+        results.into_iter().map(|(_, x)| x).collect()
     }
 
-    pub fn round_robin_bincode_interleaved<C2: 'a, Tag>(
-        self,
-        other: &Cluster<'a, C2>,
-    ) -> Stream<
-        T,
-        Cluster<'a, C2>,
-        Unbounded,
-        <TotalOrder as MinOrder<
-            <L::Root as CanSend<'a, Cluster<'a, C2>>>::OutStrongestOrder<TotalOrder>,
-        >>::Min,
-    >
-    where
-        L::Root: CanSend<'a, Cluster<'a, C2>, In<T> = (ClusterId<C2>, T), Out<T> = (Tag, T)> + 'a,
-        T: Clone + Serialize + DeserializeOwned,
-        TotalOrder:
-            MinOrder<<L::Root as CanSend<'a, Cluster<'a, C2>>>::OutStrongestOrder<TotalOrder>>,
-    {
-        self.round_robin_bincode(other).map(q!(|(_, b)| b))
+    #[tokio::test]
+    async fn bounded_buffer_drop_newest() {
+        assert_eq!(
+            bounded_buffer_output(OverflowPolicy::DropNewest).await,
+            vec![1, 2]
+        );
     }
 
-    pub fn round_robin_bytes<C2: 'a>(
-        self,
-        other: &Cluster<'a, C2>,
-    ) -> Stream<
-        <L::Root as CanSend<'a, Cluster<'a, C2>>>::Out<Bytes>,
-        Cluster<'a, C2>,
-        Unbounded,
-        <TotalOrder as MinOrder<
-            <L::Root as CanSend<'a, Cluster<'a, C2>>>::OutStrongestOrder<TotalOrder>,
-        >>::Min,
-    >
-    where
-        L::Root: CanSend<'a, Cluster<'a, C2>, In<Bytes> = (ClusterId<C2>, T)> + 'a,
-        T: Clone,
-        TotalOrder:
-            MinOrder<<L::Root as CanSend<'a, Cluster<'a, C2>>>::OutStrongestOrder<TotalOrder>>,
-    {
-        let ids = other.members();
-
-        self.enumerate()
-            .map(q!(|(i, w)| (ids[i % ids.len()], w)))
-            .send_bytes(other)
+    #[tokio::test]
+    async fn bounded_buffer_drop_oldest() {
+        assert_eq!(
+            bounded_buffer_output(OverflowPolicy::DropOldest).await,
+            vec![4, 5]
+        );
     }
 
-    pub fn round_robin_bytes_interleaved<C2: 'a, Tag>(
-        self,
-        other: &Cluster<'a, C2>,
-    ) -> Stream<
-        Bytes,
-        Cluster<'a, C2>,
-        Unbounded,
-        <TotalOrder as MinOrder<
-            <L::Root as CanSend<'a, Cluster<'a, C2>>>::OutStrongestOrder<TotalOrder>,
-        >>::Min,
-    >
-    where
-        L::Root: CanSend<'a, Cluster<'a, C2>, In<Bytes> = (ClusterId<C2>, T), Out<Bytes> = (Tag, Bytes)>
-            + 'a,
-        T: Clone,
-        TotalOrder:
-            MinOrder<<L::Root as CanSend<'a, Cluster<'a, C2>>>::OutStrongestOrder<TotalOrder>>,
-    {
-        self.round_robin_bytes(other).map(q!(|(_, b)| b))
+    #[tokio::test]
+    async fn bounded_buffer_block() {
+        assert_eq!(
+            bounded_buffer_output(OverflowPolicy::Block).await,
+            vec![1, 2, 3, 4, 5]
+        );
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use dfir_rs::futures::StreamExt;
-    use hydro_deploy::Deployment;
-    use serde::{Deserialize, Serialize};
-    use stageleft::q;
+    #[tokio::test]
+    async fn enumerate_mod_wraps_across_ticks() {
+        let mut deployment = Deployment::new();
 
-    use crate::location::Location;
-    use crate::FlowBuilder;
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+        let tick = node.tick();
+
+        let elements = unsafe { node.source_interval(q!(Duration::from_millis(10))) };
+        let out_port = unsafe { elements.timestamped(&tick).tick_batch() }
+            .enumerate_mod(3)
+            .map(q!(|(i, _)| i))
+            .all_ticks()
+            .drop_timestamp()
+            .send_bincode_external(&external);
 
-    struct P1 {}
-    struct P2 {}
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
 
-    #[derive(Serialize, Deserialize, Debug)]
-    struct SendOverNetwork {
-        n: u32,
+        deployment.deploy().await.unwrap();
+
+        let mut out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        let mut results: Vec<u64> = Vec::new();
+        for _ in 0..7 {
+            results.push(out.next().await.unwrap());
+        }
+
+        assert_eq!(results, vec![0, 1, 2, 0, 1, 2, 0]);
     }
 
     #[tokio::test]
-    async fn first_ten_distributed() {
+    async fn split_ok_err_partitions() {
         let mut deployment = Deployment::new();
 
         let flow = FlowBuilder::new();
-        let first_node = flow.process::<P1>();
-        let second_node = flow.process::<P2>();
+        let node = flow.process::<P1>();
         let external = flow.external_process::<P2>();
 
-        let numbers = first_node.source_iter(q!(0..10));
-        let out_port = numbers
-            .map(q!(|n| SendOverNetwork { n }))
-            .send_bincode(&second_node)
-            .send_bincode_external(&external);
+        let (oks, errs) = node
+            .source_iter(q!(vec![
+                Ok(1),
+                Err("a".to_string()),
+                Ok(2),
+                Err("b".to_string()),
+                Ok(3)
+            ]))
+            .split_ok_err();
+        let oks_port = oks.send_bincode_external(&external);
+        let errs_port = errs.send_bincode_external(&external);
 
         let nodes = flow
-            .with_process(&first_node, deployment.Localhost())
-            .with_process(&second_node, deployment.Localhost())
+            .with_process(&node, deployment.Localhost())
             .with_external(&external, deployment.Localhost())
             .deploy(&mut deployment);
 
         deployment.deploy().await.unwrap();
 
-        let mut external_out = nodes.connect_source_bincode(out_port).await;
+        let mut oks_out = nodes.connect_source_bincode(oks_port).await;
+        let mut errs_out = nodes.connect_source_bincode(errs_port).await;
 
         deployment.start().await.unwrap();
 
-        for i in 0..10 {
-            assert_eq!(external_out.next().await.unwrap().n, i);
+        for i in 1..=3 {
+            assert_eq!(oks_out.next().await.unwrap(), i);
+        }
+        for e in ["a", "b"] {
+            assert_eq!(errs_out.next().await.unwrap(), e);
         }
     }
 }
@@ -1,10 +1,18 @@
 use std::future::Future;
 use std::pin::Pin;
+use std::time::Duration;
 
+use dfir_rs::futures::StreamExt;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
+use stageleft::q;
+use syn::parse_quote;
 
-use crate::{FlowBuilder, Process, Stream, Unbounded};
+use crate::builder::deploy::DeployResult;
+use crate::deploy::Deploy;
+use crate::location::external_process::ExternalBincodeStream;
+use crate::location::Location;
+use crate::{ExternalProcess, FlowBuilder, Process, Stream, TotalOrder, Unbounded};
 
 pub async fn stream_transform_test<
     'a,
@@ -33,3 +41,92 @@ pub async fn stream_transform_test<
 
     check(external_out).await;
 }
+
+/// A handle to the elements of a stream collected with [`Stream::collect_for_test`].
+pub struct CollectHandle<T: DeserializeOwned> {
+    port: ExternalBincodeStream<T>,
+}
+
+impl<T: Serialize + DeserializeOwned + 'static> CollectHandle<T> {
+    /// Connects to the deployed stream, which must happen before `deployment.start()`.
+    ///
+    /// The returned future resolves to every element sent by the stream, or to an empty
+    /// [`Vec`] if it sent none, once the sending process has exited, e.g. after
+    /// `deployment.stop()`.
+    pub async fn connect<'a, D: Deploy<'a>>(
+        self,
+        nodes: &DeployResult<'a, D>,
+    ) -> impl Future<Output = Vec<T>> {
+        nodes.connect_source_bincode(self.port).await.collect()
+    }
+}
+
+impl<'a, T: Serialize + DeserializeOwned, P, B, Order> Stream<T, Process<'a, P>, B, Order> {
+    /// Sends all elements of this stream to `external` so that a deploy test
+    /// can assert on them as a [`Vec`], rather than scraping the stdout of the process.
+    pub fn collect_for_test<E: 'a>(self, external: &ExternalProcess<'a, E>) -> CollectHandle<T> {
+        CollectHandle {
+            port: self.send_bincode_external(external),
+        }
+    }
+}
+
+impl<'a, T, P> Stream<T, Process<'a, P>, Unbounded, TotalOrder> {
+    /// Measures the round-trip latency to `other`, for benchmarking a network edge. Each
+    /// element of this stream sends a ping, which is stamped with the current time when it is
+    /// sent and echoed back by `other`. The returned stream has the elapsed time for each ping
+    /// once its echo returns, in the order the echoes arrive.
+    pub fn ping_pong<P2>(
+        self,
+        other: &Process<'a, P2>,
+    ) -> Stream<Duration, Process<'a, P>, Unbounded, TotalOrder> {
+        let sender = self.location.clone();
+        let tick = sender.tick();
+
+        let pings = unsafe {
+            // SAFETY: each ping is stamped in the tick it is sent
+            self.enumerate()
+                .map(q!(|(id, _)| id as u64))
+                .timestamped(&tick)
+                .tick_batch()
+        };
+        let echoes = pings
+            .clone()
+            .all_ticks()
+            .drop_timestamp()
+            .send_bincode(other)
+            .send_bincode(&sender);
+
+        let stamps = pings.map(q!(|id| Ok((id, std::time::Instant::now()))));
+        let returned = unsafe {
+            // SAFETY: latency is measured when the echo is processed
+            echoes.timestamped(&tick).tick_batch()
+        }
+        .map(q!(|id| Err(id)));
+
+        let scan_init: syn::Expr =
+            parse_quote!(|| { ::std::collections::HashMap::<u64, ::std::time::Instant>::new() });
+        let scan_acc: syn::Expr =
+            parse_quote!(
+                |sent: &mut ::std::collections::HashMap<u64, ::std::time::Instant>,
+                 item: ::std::result::Result<(u64, ::std::time::Instant), u64>| {
+                    match item {
+                        ::std::result::Result::Ok((id, stamp)) => {
+                            sent.insert(id, stamp);
+                            ::std::option::Option::None
+                        }
+                        ::std::result::Result::Err(id) => {
+                            sent.remove(&id).map(|stamp| stamp.elapsed())
+                        }
+                    }
+                }
+            );
+
+        stamps
+            .chain(returned)
+            .scan_static::<Option<Duration>>(scan_init.into(), scan_acc.into())
+            .filter_map(q!(|latency| latency))
+            .all_ticks()
+            .drop_timestamp()
+    }
+}
@@ -0,0 +1,124 @@
+use std::hash::Hash;
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use stageleft::{q, IntoQuotedMut, QuotedWithContext};
+use syn::parse_quote;
+
+use crate::location::{Location, NoTick, Tick};
+pub use crate::runtime_support::windowing::{Pane, PaneStatus};
+use crate::staging_util::get_this_crate;
+use crate::{Bounded, Stream};
+
+/// Decides when a [`Windowed`] aggregation fires panes.
+///
+/// Every trigger fires an [`PaneStatus::OnTime`] pane for each key when its window closes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Trigger {
+    /// Fires only when the window closes.
+    AtClose,
+    /// Also fires an [`PaneStatus::Early`] pane every `n` elements added to a key's window.
+    EveryN(usize),
+    /// Like the inner trigger, and also fires a [`PaneStatus::Late`] pane for each element that
+    /// arrives after its window closed (within the allowed lateness).
+    AndLate(Box<Trigger>),
+}
+
+impl Trigger {
+    /// Extends this trigger to also fire on late elements.
+    pub fn and_late(self) -> Trigger {
+        Trigger::AndLate(Box::new(self))
+    }
+
+    fn early_every(&self) -> Option<usize> {
+        match self {
+            Trigger::AtClose => None,
+            Trigger::EveryN(n) => Some(*n),
+            Trigger::AndLate(inner) => inner.early_every(),
+        }
+    }
+
+    fn fires_late(&self) -> bool {
+        matches!(self, Trigger::AndLate(_))
+    }
+}
+
+/// A keyed stream split into tumbling windows of event ticks, created by [`Stream::windowed`].
+pub struct Windowed<K, V, L> {
+    pub(crate) input: Stream<(K, (u64, V)), Tick<L>, Bounded>,
+    pub(crate) tick: Tick<L>,
+    pub(crate) window_ticks: u64,
+    pub(crate) trigger: Trigger,
+    pub(crate) allowed_lateness: u64,
+}
+
+impl<'a, K: Eq + Hash + Clone, V, L: Location<'a> + NoTick> Windowed<K, V, L> {
+    /// Sets when panes are fired. Defaults to [`Trigger::AtClose`].
+    pub fn trigger(self, trigger: Trigger) -> Self {
+        Windowed { trigger, ..self }
+    }
+
+    /// Sets how many ticks after a window closes its aggregate is kept for late elements.
+    /// Defaults to the length of one window. Has no effect unless the trigger fires late.
+    pub fn allowed_lateness(self, ticks: u64) -> Self {
+        Windowed {
+            allowed_lateness: ticks,
+            ..self
+        }
+    }
+
+    /// Aggregates each key's elements in each window, starting from `init` and applying `comb`
+    /// to each element, and emits a [`Pane`] with the aggregate whenever the trigger fires.
+    ///
+    /// Windows are closed at the end of the tick with the last event tick they cover, so the
+    /// on-time pane for a window is emitted in that tick.
+    pub fn fold<A: Clone, I: Fn() -> A + 'a, F: Fn(&mut A, V) + 'a>(
+        self,
+        init: impl IntoQuotedMut<'a, I, Tick<L>>,
+        comb: impl IntoQuotedMut<'a, F, Tick<L>>,
+    ) -> Stream<(K, Pane<A>), Tick<L>, Bounded> {
+        let init = init.splice_fn0_ctx(&self.tick);
+        let comb = comb.splice_fn2_borrow_mut_ctx(&self.tick);
+
+        let root = get_this_crate();
+        let window_ticks = self.window_ticks;
+        let early_every: TokenStream = match self.trigger.early_every() {
+            Some(n) => quote!(Some(#n)),
+            None => quote!(None),
+        };
+        let fire_late = self.trigger.fires_late();
+        let allowed_lateness = self.allowed_lateness;
+
+        let scan_init: syn::Expr = parse_quote!(|| {
+            #root::runtime_support::windowing::WindowState::new(
+                #window_ticks,
+                #early_every,
+                #fire_late,
+                #allowed_lateness,
+            )
+        });
+        // `None` marks the end of the tick's elements, at which point windows are closed.
+        let scan_acc: syn::Expr = parse_quote!({
+            let init_fn = #init;
+            let comb_fn = #comb;
+            // The operator's closure is rebuilt whenever it runs, so this is the current tick.
+            let now = context.current_tick().0;
+            move |state: &mut #root::runtime_support::windowing::WindowState<_, _>, item: ::std::option::Option<(_, (u64, _))>| {
+                match item {
+                    Some((k, (event_tick, v))) => state
+                        .insert(now, k, event_tick, v, &init_fn, &comb_fn)
+                        .into_iter()
+                        .collect::<::std::vec::Vec<_>>(),
+                    None => state.advance(now),
+                }
+            }
+        });
+
+        let end_of_tick = self.tick.singleton(q!(())).into_stream().map(q!(|_| None));
+        self.input
+            .map(q!(|item| Some(item)))
+            .chain(end_of_tick)
+            .scan_static::<Vec<(K, Pane<A>)>>(scan_init.into(), scan_acc.into())
+            .flatten_ordered()
+    }
+}
@@ -3,7 +3,7 @@ use location::external_process::ExternalBincodeSink;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize)]
-struct SendOverNetwork {
+pub struct SendOverNetwork {
     pub n: u32,
 }
 
@@ -18,20 +18,96 @@ pub fn first_ten_distributed<'a>(
     let (numbers_external_port, numbers_external) = external.source_external_bincode(process);
     numbers_external.for_each(q!(|n| println!("hi: {:?}", n)));
 
+    first_ten_received(process, second_process).for_each(q!(|n| println!("{}", n.n)));
+
+    numbers_external_port
+}
+
+pub fn first_ten_received<'a>(
+    process: &Process<'a, P1>,
+    second_process: &Process<'a, P2>,
+) -> Stream<SendOverNetwork, Process<'a, P2>, Unbounded> {
     let numbers = process.source_iter(q!(0..10));
     numbers
         .map(q!(|n| SendOverNetwork { n }))
         .send_bincode(second_process)
-        .for_each(q!(|n| println!("{}", n.n)));
-
-    numbers_external_port
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+
     use futures::SinkExt;
     use hydro_deploy::Deployment;
     use hydro_lang::deploy::DeployCrateWrapper;
+    use hydro_lang::rewrites::persist_pullup::persist_pullup;
+    use hydro_lang::Location;
+
+    #[test]
+    fn first_ten_distributed_operators() {
+        let deployment = Deployment::new();
+
+        let builder = hydro_lang::FlowBuilder::new();
+        let external = builder.external_process();
+        let p1 = builder.process();
+        let p2 = builder.process();
+        let _ = super::first_ten_distributed(&external, &p1, &p2);
+
+        let compiled = builder
+            .with_default_optimize()
+            .with_process(&p1, deployment.Localhost())
+            .with_process(&p2, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .compile(&());
+        let operators = compiled.operators_by_location();
+        let subgraphs = compiled.subgraph_count().unwrap();
+
+        let sender_operators = &operators[&p1.id().raw_id()];
+        assert!(sender_operators.iter().any(|op| op == "map"));
+        assert!(sender_operators.iter().any(|op| op == "dest_sink"));
+
+        let receiver_operators = &operators[&p2.id().raw_id()];
+        assert!(!receiver_operators.iter().any(|op| op == "dest_sink"));
+
+        assert!(subgraphs.keys().eq(operators.keys()));
+        assert!(subgraphs.values().all(|count| *count > 0));
+    }
+
+    #[test]
+    fn first_ten_distributed_ir_histogram() {
+        let builder = hydro_lang::FlowBuilder::new();
+        let external = builder.external_process();
+        let p1 = builder.process();
+        let p2 = builder.process();
+        let _ = super::first_ten_distributed(&external, &p1, &p2);
+
+        let mut built = builder.finalize();
+        assert_eq!(
+            built.ir_histogram(),
+            HashMap::from([
+                ("ForEach", 2),
+                ("Source", 2),
+                ("Persist", 2),
+                ("Unpersist", 2),
+                ("Map", 1),
+                ("Network", 2),
+            ])
+        );
+
+        // `persist_pullup` removes the `Persist` and `Unpersist` marker nodes.
+        let mut optimized = built.optimize_with(persist_pullup);
+        assert_eq!(
+            optimized.ir_histogram(),
+            HashMap::from([("ForEach", 2), ("Source", 2), ("Map", 1), ("Network", 2)])
+        );
+
+        let deployment = Deployment::new();
+        let _ = optimized
+            .with_process(&p1, deployment.Localhost())
+            .with_process(&p2, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .compile(&());
+    }
 
     #[tokio::test]
     async fn first_ten_distributed() {
@@ -75,4 +151,34 @@ mod tests {
             assert_eq!(second_node_stdout.recv().await.unwrap(), i.to_string());
         }
     }
+
+    #[tokio::test]
+    async fn first_ten_received() {
+        let mut deployment = Deployment::new();
+
+        let builder = hydro_lang::FlowBuilder::new();
+        let external = builder.external_process::<()>();
+        let p1 = builder.process();
+        let p2 = builder.process();
+        let received = super::first_ten_received(&p1, &p2).collect_for_test(&external);
+
+        let nodes = builder
+            .with_default_optimize()
+            .with_process(&p1, deployment.Localhost())
+            .with_process(&p2, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let received = received.connect(&nodes).await;
+
+        deployment.start().await.unwrap();
+        deployment.stop().await.unwrap();
+
+        assert_eq!(
+            received.await.iter().map(|n| n.n).collect::<Vec<_>>(),
+            (0..10).collect::<Vec<_>>()
+        );
+    }
 }
@@ -115,6 +115,7 @@ pub fn compile_dfir(
         no_references,
         op_short_text,
         op_text_no_imports: false,
+        distinct_handoffs: false,
     };
 
     let out = match syn::parse_str(&program) {
@@ -170,6 +171,7 @@ pub fn compile_datalog(
         no_references,
         op_short_text,
         op_text_no_imports: false,
+        distinct_handoffs: false,
     };
 
     let wrapped = format!("r#\"{}\"#", program);